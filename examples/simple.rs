@@ -1,20 +1,15 @@
 #![allow(clippy::needless_return)]
 
 use clap::{crate_version, App, Arg};
-#[cfg(feature = "abi-7-26")]
 use log::info;
 use log::LevelFilter;
 use log::{debug, warn};
 use rofuse::consts::FOPEN_DIRECT_IO;
-#[cfg(feature = "abi-7-26")]
-use rofuse::consts::FUSE_HANDLE_KILLPRIV;
-#[cfg(feature = "abi-7-31")]
-use rofuse::consts::FUSE_WRITE_KILL_PRIV;
 use rofuse::TimeOrNow::Now;
 use rofuse::{
-    Filesystem, KernelConfig, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
-    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr, Request, TimeOrNow,
-    FUSE_ROOT_ID,
+    Capabilities, Filesystem, KernelConfig, MountOption, ReplyAttr, ReplyCreate, ReplyData,
+    ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr,
+    Request, TimeOrNow, FUSE_ROOT_ID,
 };
 use serde::{Deserialize, Serialize};
 use std::cmp::min;
@@ -250,28 +245,12 @@ struct SimpleFS {
 }
 
 impl SimpleFS {
-    fn new(
-        data_dir: String,
-        direct_io: bool,
-        #[allow(unused_variables)] suid_support: bool,
-    ) -> SimpleFS {
-        #[cfg(feature = "abi-7-26")]
-        {
-            SimpleFS {
-                data_dir,
-                next_file_handle: AtomicU64::new(1),
-                direct_io,
-                suid_support,
-            }
-        }
-        #[cfg(not(feature = "abi-7-26"))]
-        {
-            SimpleFS {
-                data_dir,
-                next_file_handle: AtomicU64::new(1),
-                direct_io,
-                suid_support: false,
-            }
+    fn new(data_dir: String, direct_io: bool, suid_support: bool) -> SimpleFS {
+        SimpleFS {
+            data_dir,
+            next_file_handle: AtomicU64::new(1),
+            direct_io,
+            suid_support,
         }
     }
 
@@ -476,13 +455,10 @@ impl SimpleFS {
 }
 
 impl Filesystem for SimpleFS {
-    fn init(
-        &mut self,
-        _req: &Request,
-        #[allow(unused_variables)] config: &mut KernelConfig,
-    ) -> Result<(), c_int> {
-        #[cfg(feature = "abi-7-26")]
-        config.add_capabilities(FUSE_HANDLE_KILLPRIV).unwrap();
+    fn init(&mut self, _req: &Request, config: &mut KernelConfig) -> Result<(), c_int> {
+        config
+            .add_capabilities(Capabilities::HANDLE_KILLPRIV)
+            .unwrap();
 
         fs::create_dir_all(Path::new(&self.data_dir).join("inodes")).unwrap();
         fs::create_dir_all(Path::new(&self.data_dir).join("contents")).unwrap();
@@ -559,6 +535,7 @@ impl Filesystem for SimpleFS {
         _chgtime: Option<SystemTime>,
         _bkuptime: Option<SystemTime>,
         _flags: Option<u32>,
+        _kill_suidgid: bool,
         reply: ReplyAttr,
     ) {
         let mut attrs = match self.get_inode(inode) {
@@ -746,6 +723,7 @@ impl Filesystem for SimpleFS {
         mut mode: u32,
         _umask: u32,
         _rdev: u32,
+        _security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyEntry,
     ) {
         let file_type = mode & libc::S_IFMT as u32;
@@ -832,6 +810,7 @@ impl Filesystem for SimpleFS {
         name: &OsStr,
         mut mode: u32,
         _umask: u32,
+        _security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyEntry,
     ) {
         debug!("mkdir() called with {:?} {:?} {:o}", parent, name, mode);
@@ -1023,6 +1002,7 @@ impl Filesystem for SimpleFS {
         parent: u64,
         name: &OsStr,
         link: &Path,
+        _security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyEntry,
     ) {
         debug!("symlink() called with {:?} {:?} {:?}", parent, name, link);
@@ -1319,7 +1299,14 @@ impl Filesystem for SimpleFS {
         }
     }
 
-    fn open(&mut self, req: &Request, inode: u64, flags: i32, reply: ReplyOpen) {
+    fn open(
+        &mut self,
+        req: &Request,
+        inode: u64,
+        flags: i32,
+        _kill_suidgid: bool,
+        reply: ReplyOpen,
+    ) {
         debug!("open() called for {:?}", inode);
         let (access_mask, read, write) = match flags & libc::O_ACCMODE {
             libc::O_RDONLY => {
@@ -1413,6 +1400,7 @@ impl Filesystem for SimpleFS {
         _write_flags: u32,
         #[allow(unused_variables)] flags: i32,
         _lock_owner: Option<u64>,
+        _kill_suidgid: bool,
         reply: ReplyWrite,
     ) {
         debug!("write() called with {:?} size={:?}", inode, data.len());
@@ -1433,11 +1421,11 @@ impl Filesystem for SimpleFS {
             if data.len() + offset as usize > attrs.size as usize {
                 attrs.size = (data.len() + offset as usize) as u64;
             }
-            // #[cfg(feature = "abi-7-31")]
-            // if flags & FUSE_WRITE_KILL_PRIV as i32 != 0 {
+            // if kill_suidgid {
             //     clear_suid_sgid(&mut attrs);
             // }
-            // XXX: In theory we should only need to do this when WRITE_KILL_PRIV is set for 7.31+
+            // XXX: In theory we should only need to do this when KILL_SUIDGID is set with
+            // FUSE_HANDLE_KILLPRIV_V2
             // However, xfstests fail in that case
             clear_suid_sgid(&mut attrs);
             self.write_inode(&attrs);
@@ -1580,6 +1568,7 @@ impl Filesystem for SimpleFS {
         key: &OsStr,
         value: &[u8],
         _flags: i32,
+        _setxattr_flags: u32,
         _position: u32,
         reply: ReplyEmpty,
     ) {
@@ -1695,6 +1684,8 @@ impl Filesystem for SimpleFS {
         mut mode: u32,
         _umask: u32,
         flags: i32,
+        _kill_suidgid: bool,
+        _security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyCreate,
     ) {
         debug!("create() called with {:?} {:?}", parent, name);
@@ -2004,17 +1995,10 @@ fn main() {
 
     let mut options = vec![MountOption::FSName("fuser".to_string())];
 
-    #[cfg(feature = "abi-7-26")]
-    {
-        if matches.is_present("suid") {
-            info!("setuid bit support enabled");
-            options.push(MountOption::Suid);
-        } else {
-            options.push(MountOption::AutoUnmount);
-        }
-    }
-    #[cfg(not(feature = "abi-7-26"))]
-    {
+    if matches.is_present("suid") {
+        info!("setuid bit support enabled");
+        options.push(MountOption::Suid);
+    } else {
         options.push(MountOption::AutoUnmount);
     }
     if let Ok(enabled) = fuse_allow_other_enabled() {
@@ -0,0 +1,285 @@
+//! Capabilities of the FUSE connection
+//!
+//! The kernel offers capabilities in `FUSE_INIT` and the filesystem requests the ones it wants
+//! through [`KernelConfig`](crate::KernelConfig). The flags correspond to the `FUSE_*` init
+//! flags in [`consts`](crate::consts).
+
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub};
+
+use crate::ll::fuse_abi::consts::*;
+
+/// Set of capabilities of the FUSE connection
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u64);
+
+macro_rules! capabilities {
+    ($($(#[$attr:meta])* $name:ident = $flag:ident;)*) => {
+        impl Capabilities {
+            $(
+                $(#[$attr])*
+                pub const $name: Capabilities = Capabilities($flag);
+            )*
+        }
+
+        impl fmt::Debug for Capabilities {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut set = f.debug_set();
+                let mut rest = *self;
+                $(
+                    // Only the cfg attributes matter here
+                    #[allow(unused_doc_comments)]
+                    $(#[$attr])*
+                    {
+                        if self.contains(Capabilities::$name) {
+                            set.entry(&format_args!(stringify!($name)));
+                            rest.remove(Capabilities::$name);
+                        }
+                    }
+                )*
+                if !rest.is_empty() {
+                    set.entry(&format_args!("{:#x}", rest.0));
+                }
+                set.finish()
+            }
+        }
+    };
+}
+
+capabilities! {
+    /// Asynchronous read requests
+    ASYNC_READ = FUSE_ASYNC_READ;
+    /// Remote locking for POSIX file locks
+    POSIX_LOCKS = FUSE_POSIX_LOCKS;
+    /// Kernel sends file handle for fstat, etc...
+    FILE_OPS = FUSE_FILE_OPS;
+    /// Handles the `O_TRUNC` open flag in the filesystem
+    ATOMIC_O_TRUNC = FUSE_ATOMIC_O_TRUNC;
+    /// Filesystem handles lookups of "." and ".."
+    EXPORT_SUPPORT = FUSE_EXPORT_SUPPORT;
+    /// Filesystem can handle write size larger than 4kB
+    BIG_WRITES = FUSE_BIG_WRITES;
+    /// Don't apply umask to file mode on create operations
+    DONT_MASK = FUSE_DONT_MASK;
+    /// Kernel supports splice write on the device
+    #[cfg(not(target_os = "macos"))]
+    SPLICE_WRITE = FUSE_SPLICE_WRITE;
+    /// Kernel supports splice move on the device
+    #[cfg(not(target_os = "macos"))]
+    SPLICE_MOVE = FUSE_SPLICE_MOVE;
+    /// Kernel supports splice read on the device
+    #[cfg(not(target_os = "macos"))]
+    SPLICE_READ = FUSE_SPLICE_READ;
+    /// Remote locking for BSD style file locks
+    FLOCK_LOCKS = FUSE_FLOCK_LOCKS;
+    /// Kernel supports ioctl on directories
+    HAS_IOCTL_DIR = FUSE_HAS_IOCTL_DIR;
+    /// Automatically invalidate cached pages
+    AUTO_INVAL_DATA = FUSE_AUTO_INVAL_DATA;
+    /// Do READDIRPLUS (READDIR+LOOKUP in one)
+    DO_READDIRPLUS = FUSE_DO_READDIRPLUS;
+    /// Adaptive readdirplus
+    READDIRPLUS_AUTO = FUSE_READDIRPLUS_AUTO;
+    /// Asynchronous direct I/O submission
+    ASYNC_DIO = FUSE_ASYNC_DIO;
+    /// Use writeback cache for buffered writes
+    WRITEBACK_CACHE = FUSE_WRITEBACK_CACHE;
+    /// Kernel supports zero-message opens
+    NO_OPEN_SUPPORT = FUSE_NO_OPEN_SUPPORT;
+    /// Allow parallel lookups and readdir
+    PARALLEL_DIROPS = FUSE_PARALLEL_DIROPS;
+    /// Filesystem handles killing suid/sgid/cap on write/chown/trunc
+    HANDLE_KILLPRIV = FUSE_HANDLE_KILLPRIV;
+    /// Filesystem supports posix acls
+    POSIX_ACL = FUSE_POSIX_ACL;
+    /// Reading the device after abort returns `ECONNABORTED`
+    ABORT_ERROR = FUSE_ABORT_ERROR;
+    /// The max pages of requests are configured
+    MAX_PAGES = FUSE_MAX_PAGES;
+    /// Cache READLINK responses
+    CACHE_SYMLINKS = FUSE_CACHE_SYMLINKS;
+    /// Kernel supports zero-message opendir
+    NO_OPENDIR_SUPPORT = FUSE_NO_OPENDIR_SUPPORT;
+    /// Only invalidate cached pages on explicit request
+    EXPLICIT_INVAL_DATA = FUSE_EXPLICIT_INVAL_DATA;
+    /// The alignment of DAX mappings is configured
+    #[cfg(not(target_os = "macos"))]
+    MAP_ALIGNMENT = FUSE_MAP_ALIGNMENT;
+    /// Kernel supports auto-mounting directory submounts
+    #[cfg(not(target_os = "macos"))]
+    SUBMOUNTS = FUSE_SUBMOUNTS;
+    /// Filesystem kills suid/sgid/cap on write/chown/trunc, as requested by the kernel
+    #[cfg(not(target_os = "macos"))]
+    HANDLE_KILLPRIV_V2 = FUSE_HANDLE_KILLPRIV_V2;
+    /// Filesystem supports the extended setxattr request
+    #[cfg(not(target_os = "macos"))]
+    SETXATTR_EXT = FUSE_SETXATTR_EXT;
+    /// Extended init request, needed for all capabilities beyond the first 32
+    #[cfg(not(target_os = "macos"))]
+    INIT_EXT = FUSE_INIT_EXT;
+    /// Add security contexts to create, mkdir, symlink, and mknod
+    #[cfg(not(target_os = "macos"))]
+    SECURITY_CTX = FUSE_SECURITY_CTX;
+    /// Use per inode DAX
+    #[cfg(not(target_os = "macos"))]
+    HAS_INODE_DAX = FUSE_HAS_INODE_DAX;
+    /// Add supplementary group info to create, mkdir, symlink and mknod
+    #[cfg(not(target_os = "macos"))]
+    CREATE_SUPP_GROUP = FUSE_CREATE_SUPP_GROUP;
+    /// Kernel supports expiry-only entry invalidation
+    #[cfg(not(target_os = "macos"))]
+    HAS_EXPIRE_ONLY = FUSE_HAS_EXPIRE_ONLY;
+    /// Allow shared mmap in `FOPEN_DIRECT_IO` mode
+    #[cfg(not(target_os = "macos"))]
+    DIRECT_IO_ALLOW_MMAP = FUSE_DIRECT_IO_ALLOW_MMAP;
+    /// Passthrough mode for read/write io
+    #[cfg(not(target_os = "macos"))]
+    PASSTHROUGH = FUSE_PASSTHROUGH;
+    /// Explicitly disable export support
+    #[cfg(not(target_os = "macos"))]
+    NO_EXPORT_SUPPORT = FUSE_NO_EXPORT_SUPPORT;
+    /// Kernel supports resending pending requests
+    #[cfg(not(target_os = "macos"))]
+    HAS_RESEND = FUSE_HAS_RESEND;
+    /// Allow creation of idmapped mounts. Requires the `default_permissions` mount option,
+    /// and is not requested without it or if access is restricted with `allow_root` or
+    /// `auto_unmount`
+    #[cfg(not(target_os = "macos"))]
+    ALLOW_IDMAP = FUSE_ALLOW_IDMAP;
+    /// Pass requests through io_uring
+    #[cfg(not(target_os = "macos"))]
+    OVER_IO_URING = FUSE_OVER_IO_URING;
+    /// Kernel supports timing out requests
+    #[cfg(not(target_os = "macos"))]
+    REQUEST_TIMEOUT = FUSE_REQUEST_TIMEOUT;
+    /// Filesystem supports fallocate
+    #[cfg(target_os = "macos")]
+    ALLOCATE = FUSE_ALLOCATE;
+    /// Filesystem supports exchangedata
+    #[cfg(target_os = "macos")]
+    EXCHANGE_DATA = FUSE_EXCHANGE_DATA;
+    /// Filesystem is case insensitive
+    #[cfg(target_os = "macos")]
+    CASE_INSENSITIVE = FUSE_CASE_INSENSITIVE;
+    /// Filesystem supports volume renames
+    #[cfg(target_os = "macos")]
+    VOL_RENAME = FUSE_VOL_RENAME;
+    /// Filesystem supports extended times
+    #[cfg(target_os = "macos")]
+    XTIMES = FUSE_XTIMES;
+}
+
+impl Capabilities {
+    /// The empty set
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+
+    /// Create a set from the raw `FUSE_*` init flags
+    pub const fn from_bits(bits: u64) -> Self {
+        Capabilities(bits)
+    }
+
+    /// Returns the raw `FUSE_*` init flags
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if no capability is set
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if all capabilities of `other` are set
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if any capability of `other` is set
+    pub const fn intersects(self, other: Capabilities) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Adds the capabilities of `other`
+    pub fn insert(&mut self, other: Capabilities) {
+        self.0 |= other.0;
+    }
+
+    /// Removes the capabilities of `other`
+    pub fn remove(&mut self, other: Capabilities) {
+        self.0 &= !other.0;
+    }
+}
+
+impl From<u64> for Capabilities {
+    fn from(bits: u64) -> Self {
+        Capabilities(bits)
+    }
+}
+
+impl From<Capabilities> for u64 {
+    fn from(capabilities: Capabilities) -> Self {
+        capabilities.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, rhs: Capabilities) -> Capabilities {
+        Capabilities(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, rhs: Capabilities) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Capabilities;
+
+    fn bitand(self, rhs: Capabilities) -> Capabilities {
+        Capabilities(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for Capabilities {
+    fn bitand_assign(&mut self, rhs: Capabilities) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl Sub for Capabilities {
+    type Output = Capabilities;
+
+    fn sub(self, rhs: Capabilities) -> Capabilities {
+        Capabilities(self.0 & !rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Capabilities;
+
+    #[test]
+    fn set_operations() {
+        let mut caps = Capabilities::ASYNC_READ | Capabilities::POSIX_LOCKS;
+        assert!(caps.contains(Capabilities::ASYNC_READ));
+        assert!(!caps.contains(Capabilities::ASYNC_READ | Capabilities::FLOCK_LOCKS));
+        assert!(caps.intersects(Capabilities::ASYNC_READ | Capabilities::FLOCK_LOCKS));
+        caps.insert(Capabilities::FLOCK_LOCKS);
+        caps.remove(Capabilities::POSIX_LOCKS);
+        assert_eq!(caps, Capabilities::ASYNC_READ | Capabilities::FLOCK_LOCKS);
+        assert_eq!(caps - Capabilities::ASYNC_READ, Capabilities::FLOCK_LOCKS);
+        assert_eq!(caps.bits(), (1 << 0) | (1 << 10));
+        assert!((caps & Capabilities::DONT_MASK).is_empty());
+    }
+
+    #[test]
+    fn debug() {
+        let caps = Capabilities::ASYNC_READ | Capabilities::from_bits(1 << 63);
+        assert_eq!(format!("{:?}", caps), "{ASYNC_READ, 0x8000000000000000}");
+    }
+}
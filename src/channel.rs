@@ -1,8 +1,46 @@
-use std::{fs::File, io, os::unix::prelude::AsRawFd, sync::Arc};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, IoSlice},
+    os::unix::prelude::{AsRawFd, RawFd},
+    sync::Arc,
+};
 
 use libc::{c_int, c_void, size_t};
 
+#[cfg(target_os = "linux")]
+use crate::ll::fuse_abi as abi;
 use crate::reply::ReplySender;
+#[cfg(target_os = "linux")]
+use crate::uring::RingEntrySender;
+
+/// A bidirectional connection to the FUSE kernel driver, or to anything else that sends
+/// FUSE requests and receives their replies.
+///
+/// A [`Session`](crate::Session) receives requests from its transport one by one, and sends
+/// the replies through the transport's senders, which may be moved to other threads.
+/// [`Channel`] is the transport of a mounted FUSE device. Others can carry requests over a
+/// socket, an in-memory queue or a virtio queue.
+pub trait Transport: Send + 'static {
+    /// Sender for the replies to the received requests
+    type Sender: ReplySender + Clone + fmt::Debug;
+
+    /// Receives the next request into the given buffer and returns its size. Blocks until a
+    /// request is available. A request that doesn't fit into the buffer, which is large
+    /// enough for the largest write, must fail instead of being truncated.
+    ///
+    /// The session loop retries after `ENOENT`, `EINTR` and `EAGAIN`, and ends after
+    /// `ENODEV`, which tells it that the filesystem was unmounted.
+    fn receive(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Returns a sender for replies and notifications
+    fn sender(&self) -> Self::Sender;
+
+    /// Sends a reply or notification
+    fn send(&self, bufs: &[IoSlice<'_>]) -> io::Result<()> {
+        self.sender().send(bufs)
+    }
+}
 
 /// A raw communication channel to the FUSE kernel driver
 #[derive(Debug)]
@@ -38,18 +76,59 @@ impl Channel {
     pub fn sender(&self) -> ChannelSender {
         // Since write/writev syscalls are threadsafe, we can simply create
         // a sender by using the same file and use it in other threads.
-        ChannelSender(self.0.clone())
+        ChannelSender {
+            device: self.0.clone(),
+            #[cfg(target_os = "linux")]
+            ring_entry: None,
+        }
     }
 }
 
+impl Transport for Channel {
+    type Sender = ChannelSender;
+
+    fn receive(&self, buf: &mut [u8]) -> io::Result<usize> {
+        Channel::receive(self, buf)
+    }
+
+    fn sender(&self) -> ChannelSender {
+        Channel::sender(self)
+    }
+}
+
+/// Sender of replies to the FUSE kernel driver
 #[derive(Clone, Debug)]
-pub struct ChannelSender(Arc<File>);
+pub struct ChannelSender {
+    device: Arc<File>,
+    /// Ring entry to commit replies through, if the request was received through io_uring
+    #[cfg(target_os = "linux")]
+    ring_entry: Option<RingEntrySender>,
+}
+
+#[cfg(target_os = "linux")]
+impl ChannelSender {
+    /// Returns a sender that sends replies through the given io_uring ring entry
+    pub(crate) fn with_ring_entry(&self, ring_entry: RingEntrySender) -> ChannelSender {
+        ChannelSender {
+            device: self.device.clone(),
+            ring_entry: Some(ring_entry),
+        }
+    }
+
+    pub(crate) fn raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
 
 impl ReplySender for ChannelSender {
     fn send(&self, bufs: &[io::IoSlice<'_>]) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let Some(ring_entry) = &self.ring_entry {
+            return ring_entry.send(bufs);
+        }
         let rc = unsafe {
             libc::writev(
-                self.0.as_raw_fd(),
+                self.device.as_raw_fd(),
                 bufs.as_ptr() as *const libc::iovec,
                 bufs.len() as c_int,
             )
@@ -61,4 +140,41 @@ impl ReplySender for ChannelSender {
             Ok(())
         }
     }
+
+    #[cfg(target_os = "linux")]
+    fn open_backing(&self, fd: RawFd) -> io::Result<u32> {
+        let map = abi::fuse_backing_map {
+            fd,
+            flags: 0,
+            padding: 0,
+        };
+        let rc = unsafe {
+            libc::ioctl(
+                self.device.as_raw_fd(),
+                abi::FUSE_DEV_IOC_BACKING_OPEN as _,
+                &map as *const abi::fuse_backing_map,
+            )
+        };
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(rc as u32)
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn close_backing(&self, backing_id: u32) -> io::Result<()> {
+        let rc = unsafe {
+            libc::ioctl(
+                self.device.as_raw_fd(),
+                abi::FUSE_DEV_IOC_BACKING_CLOSE as _,
+                &backing_id as *const u32,
+            )
+        };
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,388 @@
+//! Character devices in userspace (CUSE)
+//!
+//! CUSE uses the FUSE protocol to implement a character device instead of a filesystem. A CUSE
+//! session opens `/dev/cuse`, announces the device name and number in reply to the `CUSE_INIT`
+//! request and then serves the file operations (open, read, write, ioctl, poll, ...) issued
+//! on the device node the kernel created for it.
+
+use libc::{c_int, EAGAIN, EINTR, ENODEV, ENOENT, ENOSYS};
+use log::{debug, info, warn};
+use std::fs::OpenOptions;
+use std::io;
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+use crate::channel::{Channel, ChannelSender};
+use crate::ll::reply::Notification;
+use crate::ll::{self, fuse_abi as abi};
+use crate::reply::ReplySender;
+use crate::request::Request;
+use crate::session::{aligned_sub_buf, BUFFER_SIZE, MAX_WRITE_SIZE};
+use crate::{ReplyData, ReplyEmpty, ReplyIoctl, ReplyOpen, ReplyPoll, ReplyWrite};
+
+/// Path of the CUSE kernel driver device
+const CUSE_DEVICE: &str = "/dev/cuse";
+
+/// Configuration of a CUSE character device
+#[derive(Debug)]
+pub struct CuseConfig {
+    name: String,
+    pub(crate) dev_major: u32,
+    pub(crate) dev_minor: u32,
+    pub(crate) max_read: u32,
+    pub(crate) max_write: u32,
+    flags: u32,
+}
+
+impl CuseConfig {
+    fn new(name: String, dev_major: u32, dev_minor: u32) -> Self {
+        Self {
+            name,
+            dev_major,
+            dev_minor,
+            max_read: MAX_WRITE_SIZE as u32,
+            // use a max write size that fits into the session's buffer
+            max_write: MAX_WRITE_SIZE as u32,
+            flags: 0,
+        }
+    }
+
+    /// Set the device number of the character device. A major number of 0 lets the
+    /// kernel pick a free one.
+    pub fn set_device_number(&mut self, major: u32, minor: u32) {
+        self.dev_major = major;
+        self.dev_minor = minor;
+    }
+
+    /// Set the maximum read size for a single request
+    ///
+    /// On success returns the previous value. On error returns the nearest value which will succeed
+    pub fn set_max_read(&mut self, value: u32) -> Result<u32, u32> {
+        if value == 0 {
+            return Err(1);
+        }
+        let previous = self.max_read;
+        self.max_read = value;
+        Ok(previous)
+    }
+
+    /// Set the maximum write size for a single request
+    ///
+    /// On success returns the previous value. On error returns the nearest value which will succeed
+    pub fn set_max_write(&mut self, value: u32) -> Result<u32, u32> {
+        if value == 0 {
+            return Err(1);
+        }
+        if value > MAX_WRITE_SIZE as u32 {
+            return Err(MAX_WRITE_SIZE as u32);
+        }
+        let previous = self.max_write;
+        self.max_write = value;
+        Ok(previous)
+    }
+
+    /// Enable or disable unrestricted ioctls. If enabled, the kernel passes any ioctl
+    /// to the device, which must then request the argument buffers with
+    /// [`ReplyIoctl::retry`](crate::ReplyIoctl::retry).
+    pub fn set_unrestricted_ioctl(&mut self, enabled: bool) {
+        if enabled {
+            self.flags |= abi::consts::CUSE_UNRESTRICTED_IOCTL;
+        } else {
+            self.flags &= !abi::consts::CUSE_UNRESTRICTED_IOCTL;
+        }
+    }
+
+    /// Flags requested from the kernel in the `CUSE_INIT` reply
+    pub(crate) fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Device info strings sent to the kernel after the `CUSE_INIT` reply. Each entry
+    /// is a `KEY=value` pair terminated by a NUL byte.
+    pub(crate) fn device_info(&self) -> Vec<u8> {
+        let mut info = format!("DEVNAME={}", self.name).into_bytes();
+        info.push(0);
+        info
+    }
+}
+
+/// Character device trait.
+///
+/// This trait must be implemented to provide a userspace character device via CUSE.
+/// These methods correspond to cuse_lowlevel_ops in libfuse. Reasonable default
+/// implementations are provided here to get a device that can be opened but does
+/// nothing else.
+#[allow(clippy::too_many_arguments)]
+pub trait CharDevice {
+    /// Initialize the device.
+    /// Called before any other device method. The device name and number are already
+    /// set in the config, but may be adjusted.
+    fn init(&mut self, _req: &Request<'_>, _config: &mut CuseConfig) -> Result<(), c_int> {
+        Ok(())
+    }
+
+    /// Clean up the device.
+    /// Called on session exit.
+    fn destroy(&mut self) {}
+
+    /// Open the device.
+    /// Open flags (with the exception of O_CREAT, O_EXCL, O_NOCTTY and O_TRUNC) are
+    /// available in flags. The device may store an arbitrary file handle in fh, and
+    /// use this in all other operations on the opened device.
+    fn open(&mut self, _req: &Request<'_>, _flags: i32, reply: ReplyOpen) {
+        reply.opened(0, 0);
+    }
+
+    /// Read data.
+    /// Since there is no file size, the returned data may be shorter than size. fh will
+    /// contain the value set by the open method.
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        flags: i32,
+        reply: ReplyData,
+    ) {
+        warn!(
+            "[Not Implemented] read(fh: {}, offset: {}, size: {}, flags: {:#x?})",
+            fh, offset, size, flags
+        );
+        reply.error(ENOSYS);
+    }
+
+    /// Write data.
+    /// fh will contain the value set by the open method.
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        write_flags: u32,
+        flags: i32,
+        reply: ReplyWrite,
+    ) {
+        debug!(
+            "[Not Implemented] write(fh: {}, offset: {}, data.len(): {}, \
+            write_flags: {:#x?}, flags: {:#x?})",
+            fh,
+            offset,
+            data.len(),
+            write_flags,
+            flags
+        );
+        reply.error(ENOSYS);
+    }
+
+    /// Flush method.
+    /// This is called on each close() of the opened device.
+    fn flush(&mut self, _req: &Request<'_>, fh: u64, lock_owner: u64, reply: ReplyEmpty) {
+        debug!(
+            "[Not Implemented] flush(fh: {}, lock_owner: {:?})",
+            fh, lock_owner
+        );
+        reply.error(ENOSYS);
+    }
+
+    /// Release an open device.
+    /// Release is called when there are no more references to an open device. For every
+    /// open call there will be exactly one release call.
+    fn release(&mut self, _req: &Request<'_>, _fh: u64, _flags: i32, reply: ReplyEmpty) {
+        reply.ok();
+    }
+
+    /// Synchronize device contents.
+    /// If the datasync parameter is true, then only the user data should be flushed.
+    fn fsync(&mut self, _req: &Request<'_>, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        debug!(
+            "[Not Implemented] fsync(fh: {}, datasync: {})",
+            fh, datasync
+        );
+        reply.error(ENOSYS);
+    }
+
+    /// control device
+    /// arg is the user space address of the ioctl argument. If unrestricted ioctls are
+    /// enabled in the config, it can be used to request the caller's buffers with
+    /// [`ReplyIoctl::retry`].
+    fn ioctl(
+        &mut self,
+        _req: &Request<'_>,
+        fh: u64,
+        flags: u32,
+        cmd: u32,
+        arg: u64,
+        in_data: &[u8],
+        out_size: u32,
+        reply: ReplyIoctl,
+    ) {
+        debug!(
+            "[Not Implemented] ioctl(fh: {}, flags: {}, cmd: {}, arg: {:#x}, \
+            in_data.len(): {}, out_size: {})",
+            fh,
+            flags,
+            cmd,
+            arg,
+            in_data.len(),
+            out_size,
+        );
+        reply.error(ENOSYS);
+    }
+
+    /// Poll for IO readiness events.
+    /// Reply with the events that are ready right now. If `FUSE_POLL_SCHEDULE_NOTIFY` is
+    /// set in flags, the device should keep kh and send a wakeup through a
+    /// [`PollNotifier`] once the readiness changes.
+    fn poll(
+        &mut self,
+        _req: &Request<'_>,
+        fh: u64,
+        kh: u64,
+        events: u32,
+        flags: u32,
+        reply: ReplyPoll,
+    ) {
+        debug!(
+            "[Not Implemented] poll(fh: {}, kh: {}, events: {:#x?}, flags: {:#x?})",
+            fh, kh, events, flags
+        );
+        reply.error(ENOSYS);
+    }
+}
+
+/// Sends poll wakeup notifications to the kernel. Can be cloned and sent to other threads.
+#[derive(Clone, Debug)]
+pub struct PollNotifier(ChannelSender);
+
+impl PollNotifier {
+    /// Wake up the poll waiters registered under the given kernel handle
+    pub fn wakeup(&self, kh: u64) -> io::Result<()> {
+        Notification::PollWakeup(kh).with_iovec(|iov| self.0.send(iov))
+    }
+}
+
+/// The CUSE session data structure
+#[derive(Debug)]
+pub struct CuseSession<D: CharDevice> {
+    /// Character device operation implementations
+    pub(crate) device: D,
+    /// Communication channel to the kernel driver
+    ch: Channel,
+    /// Device configuration, handed to the device on init
+    pub(crate) config: CuseConfig,
+    /// CUSE protocol minor version supported by the kernel
+    pub(crate) proto_minor: u32,
+    /// True if the device is initialized (init operation done)
+    pub(crate) initialized: bool,
+    /// True if the device was destroyed (destroy operation done)
+    pub(crate) destroyed: bool,
+}
+
+impl<D: CharDevice> CuseSession<D> {
+    /// Create a new session for a character device with the given name (as in `/dev/<name>`)
+    /// and device number. A major number of 0 lets the kernel pick a free one.
+    pub fn new(device: D, name: &str, dev_major: u32, dev_minor: u32) -> io::Result<Self> {
+        if name.is_empty()
+            || name.contains('\0')
+            || name.len() + "DEVNAME=".len() + 1 > abi::consts::CUSE_INIT_INFO_MAX
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid CUSE device name {:?}", name),
+            ));
+        }
+        info!("Opening {} for device {}", CUSE_DEVICE, name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(CUSE_DEVICE)?;
+        Ok(CuseSession {
+            device,
+            ch: Channel::new(Arc::new(file)),
+            config: CuseConfig::new(name.to_owned(), dev_major, dev_minor),
+            proto_minor: 0,
+            initialized: false,
+            destroyed: false,
+        })
+    }
+
+    /// Protocol settings for parsing requests, as negotiated in init
+    fn protocol(&self) -> ll::Protocol {
+        ll::Protocol {
+            minor: self.proto_minor,
+            flags: 0,
+        }
+    }
+
+    /// Returns a notifier that can be used to wake up pollers from other threads
+    pub fn notifier(&self) -> PollNotifier {
+        PollNotifier(self.ch.sender())
+    }
+
+    /// Run the session loop that receives kernel requests and dispatches them to method
+    /// calls into the device. The loop ends when the device channel is closed.
+    pub fn run(&mut self) -> io::Result<()> {
+        // Buffer for receiving requests from the kernel. Only one is allocated and
+        // it is reused immediately after dispatching to conserve memory and allocations.
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let buf = aligned_sub_buf(
+            buffer.deref_mut(),
+            std::mem::align_of::<abi::fuse_in_header>(),
+        );
+        loop {
+            match self.ch.receive(buf) {
+                Ok(size) => {
+                    match Request::new(self.ch.sender(), &buf[..size], self.protocol()) {
+                        // Dispatch request
+                        Some(req) => req.dispatch_cuse(self),
+                        // Quit loop on illegal request
+                        None => break,
+                    }
+                }
+                Err(err) => match err.raw_os_error() {
+                    // Operation interrupted. Accordingly to FUSE, this is safe to retry
+                    Some(ENOENT) => continue,
+                    // Interrupted system call, retry
+                    Some(EINTR) => continue,
+                    // Explicitly try again
+                    Some(EAGAIN) => continue,
+                    // Device was released, quit the loop
+                    Some(ENODEV) => break,
+                    // Unhandled error
+                    _ => return Err(err),
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<D: CharDevice> Drop for CuseSession<D> {
+    fn drop(&mut self) {
+        if !self.destroyed {
+            self.device.destroy();
+            self.destroyed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CuseConfig;
+
+    #[test]
+    fn device_info() {
+        let config = CuseConfig::new("foo".to_owned(), 0, 0);
+        assert_eq!(config.device_info(), b"DEVNAME=foo\0");
+    }
+
+    #[test]
+    fn max_write() {
+        let mut config = CuseConfig::new("foo".to_owned(), 0, 0);
+        assert_eq!(config.set_max_write(0), Err(1));
+        assert!(config.set_max_write(4096).is_ok());
+        assert_eq!(config.max_write, 4096);
+    }
+}
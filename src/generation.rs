@@ -0,0 +1,67 @@
+//! Inode generations
+//!
+//! With `FUSE_EXPORT_SUPPORT`, the kernel hands out file handles made of the inode number and
+//! its generation (e.g. to NFS clients) and later looks the inode up by them. A filesystem that
+//! reuses inode numbers must therefore give each use of an inode number a new generation, or
+//! a stale file handle would reach the new inode.
+
+use std::collections::HashMap;
+
+use libc::{c_int, ESTALE};
+
+/// Tracks the generation of inode numbers of a filesystem that reuses them. Call
+/// [`reuse`](Self::reuse) when an inode number is allocated again and reply with
+/// [`generation`](Self::generation), e.g. in [`ReplyEntry::entry`](crate::ReplyEntry::entry).
+/// Entries replied with [`ReplyEntry::entry_checked`](crate::ReplyEntry::entry_checked) are
+/// validated against the current generation, so that an outdated one fails with `ESTALE`.
+#[derive(Clone, Debug, Default)]
+pub struct InodeGenerations {
+    generations: HashMap<u64, u64>,
+}
+
+impl InodeGenerations {
+    /// Create a tracker where all inode numbers are in their first generation (0)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current generation of the given inode number
+    pub fn generation(&self, ino: u64) -> u64 {
+        self.generations.get(&ino).copied().unwrap_or(0)
+    }
+
+    /// Starts a new generation of the given inode number, which is about to be used for
+    /// another inode. Returns the new generation.
+    pub fn reuse(&mut self, ino: u64) -> u64 {
+        let generation = self.generations.entry(ino).or_insert(0);
+        *generation += 1;
+        *generation
+    }
+
+    /// Checks that the given generation is the current one of the inode number, before it is
+    /// replied to the kernel. Returns `ESTALE` if the inode number was reused since.
+    pub fn validate(&self, ino: u64, generation: u64) -> Result<(), c_int> {
+        if generation == self.generation(ino) {
+            Ok(())
+        } else {
+            Err(ESTALE)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::InodeGenerations;
+
+    #[test]
+    fn reuse() {
+        let mut generations = InodeGenerations::new();
+        assert_eq!(generations.generation(2), 0);
+        assert_eq!(generations.validate(2, 0), Ok(()));
+        assert_eq!(generations.reuse(2), 1);
+        assert_eq!(generations.reuse(2), 2);
+        assert_eq!(generations.validate(2, 1), Err(libc::ESTALE));
+        assert_eq!(generations.validate(2, 2), Ok(()));
+        assert_eq!(generations.generation(3), 0);
+    }
+}
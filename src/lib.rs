@@ -14,68 +14,86 @@ use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 use std::io;
 use std::path::Path;
-#[cfg(feature = "abi-7-23")]
 use std::time::Duration;
-use std::time::SystemTime;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{convert::AsRef, io::ErrorKind};
 
 use crate::ll::fuse_abi::consts::*;
 pub use crate::ll::fuse_abi::FUSE_ROOT_ID;
-pub use crate::ll::{fuse_abi::consts, TimeOrNow};
+pub use crate::ll::{fuse_abi::consts, op, IoctlIovec, TimeOrNow};
 use crate::mnt::mount_options::check_option_conflicts;
 use crate::session::MAX_WRITE_SIZE;
-#[cfg(feature = "abi-7-16")]
+pub use capabilities::Capabilities;
+pub use channel::Transport;
+pub use cuse::{CharDevice, CuseConfig, CuseSession, PollNotifier};
+pub use generation::InodeGenerations;
 pub use ll::fuse_abi::fuse_forget_one;
 pub use mnt::mount_options::MountOption;
+pub use operations::Operations;
+pub use remote::{Relay, SocketSender, SocketStream, SocketTransport};
+pub use reply::ReplyPoll;
+pub use reply::ReplySender;
 #[cfg(target_os = "macos")]
 pub use reply::ReplyXTimes;
 pub use reply::ReplyXattr;
 pub use reply::{Reply, ReplyAttr, ReplyData, ReplyEmpty, ReplyEntry, ReplyOpen};
 pub use reply::{
     ReplyBmap, ReplyCreate, ReplyDirectory, ReplyDirectoryPlus, ReplyIoctl, ReplyLock, ReplyLseek,
-    ReplyStatfs, ReplyWrite,
+    ReplyStatfs, ReplyStatx, ReplyWrite,
 };
 pub use request::Request;
 pub use session::{BackgroundSession, Session};
-#[cfg(feature = "abi-7-28")]
 use std::cmp::max;
-#[cfg(feature = "abi-7-13")]
 use std::cmp::min;
+pub use v2::FilesystemV2;
+#[cfg(target_os = "linux")]
+pub use virtio::{VirtioFsBackend, VirtioFsSender};
 
+mod capabilities;
 pub mod channel;
-mod ll;
+mod cuse;
+mod generation;
+pub mod ll;
 pub mod mnt;
+mod operations;
+mod readdirplus;
+mod remote;
 mod reply;
 mod request;
 mod session;
+pub mod testing;
+#[cfg(target_os = "linux")]
+mod uring;
+mod v2;
+#[cfg(target_os = "linux")]
+mod virtio;
 
-/// We generally support async reads
-#[cfg(all(not(target_os = "macos"), not(feature = "abi-7-10")))]
-const INIT_FLAGS: u32 = FUSE_ASYNC_READ;
-#[cfg(all(not(target_os = "macos"), feature = "abi-7-10"))]
-const INIT_FLAGS: u32 = FUSE_ASYNC_READ | FUSE_BIG_WRITES;
-// TODO: Add FUSE_EXPORT_SUPPORT
+/// We generally support async reads and big writes
+#[cfg(not(target_os = "macos"))]
+const INIT_FLAGS: u64 = FUSE_ASYNC_READ | FUSE_BIG_WRITES;
 
 /// On macOS, we additionally support case insensitiveness, volume renames and xtimes
 /// TODO: we should eventually let the filesystem implementation decide which flags to set
 #[cfg(target_os = "macos")]
-const INIT_FLAGS: u32 = FUSE_ASYNC_READ | FUSE_CASE_INSENSITIVE | FUSE_VOL_RENAME | FUSE_XTIMES;
-// TODO: Add FUSE_EXPORT_SUPPORT and FUSE_BIG_WRITES (requires ABI 7.10)
+const INIT_FLAGS: u64 = FUSE_ASYNC_READ | FUSE_CASE_INSENSITIVE | FUSE_VOL_RENAME | FUSE_XTIMES;
+// TODO: Add FUSE_BIG_WRITES (requires ABI 7.10)
 
-const fn default_init_flags(#[allow(unused_variables)] capabilities: u32) -> u32 {
-    #[cfg(not(feature = "abi-7-28"))]
-    {
-        INIT_FLAGS
+const fn default_init_flags(capabilities: u64) -> u64 {
+    let mut flags = INIT_FLAGS;
+    if capabilities & FUSE_MAX_PAGES != 0 {
+        flags |= FUSE_MAX_PAGES;
     }
-
-    #[cfg(feature = "abi-7-28")]
-    {
-        let mut flags = INIT_FLAGS;
-        if capabilities & FUSE_MAX_PAGES != 0 {
-            flags |= FUSE_MAX_PAGES;
-        }
-        flags
+    // Needed to pass any capability beyond the first 32 bits
+    #[cfg(not(target_os = "macos"))]
+    if capabilities & FUSE_INIT_EXT != 0 {
+        flags |= FUSE_INIT_EXT;
     }
+    // Lets the session tell an aborted connection from an unmount
+    #[cfg(not(target_os = "macos"))]
+    if capabilities & FUSE_ABORT_ERROR != 0 {
+        flags |= FUSE_ABORT_ERROR;
+    }
+    flags
 }
 
 /// File types
@@ -114,7 +132,7 @@ pub struct FileAttr {
     pub mtime: SystemTime,
     /// Time of last change
     pub ctime: SystemTime,
-    /// Time of creation (macOS only)
+    /// Time of creation (macOS only, see [`FileStatx`] for Linux)
     pub crtime: SystemTime,
     /// Kind of file (directory, file, pipe, etc)
     pub kind: FileType,
@@ -130,42 +148,83 @@ pub struct FileAttr {
     pub rdev: u32,
     /// Block size
     pub blksize: u32,
-    /// Flags (macOS only, see chflags(2))
+    /// Flags (macOS: see chflags(2), Linux: `FUSE_ATTR_SUBMOUNT` and `FUSE_ATTR_DAX`, only
+    /// supported with ABI >= 7.32, other bits are ignored)
     pub flags: u32,
 }
 
+/// Extended file attributes, as returned by statx(2)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serializable", derive(Serialize, Deserialize))]
+pub struct FileStatx {
+    /// Basic file attributes
+    pub attr: FileAttr,
+    /// Time of creation
+    pub btime: SystemTime,
+    /// Which attributes are valid (`STATX_*` bits, e.g. `STATX_BASIC_STATS | STATX_BTIME`)
+    pub mask: u32,
+    /// File attributes (`STATX_ATTR_*` flags)
+    pub attributes: u64,
+    /// `STATX_ATTR_*` flags that are supported by the filesystem
+    pub attributes_mask: u64,
+}
+
+impl From<FileAttr> for FileStatx {
+    /// Reports the basic attributes only: `crtime` isn't used as birth time, as it is
+    /// usually not set on Linux
+    fn from(attr: FileAttr) -> Self {
+        FileStatx {
+            attr,
+            btime: UNIX_EPOCH,
+            mask: STATX_BASIC_STATS,
+            attributes: 0,
+            attributes_mask: 0,
+        }
+    }
+}
+
 /// Configuration of the fuse kernel module connection
 #[derive(Debug)]
 pub struct KernelConfig {
-    capabilities: u32,
-    requested: u32,
+    capabilities: u64,
+    requested: u64,
     max_readahead: u32,
     max_max_readahead: u32,
-    #[cfg(feature = "abi-7-13")]
     max_background: u16,
-    #[cfg(feature = "abi-7-13")]
     congestion_threshold: Option<u16>,
     max_write: u32,
-    #[cfg(feature = "abi-7-23")]
     time_gran: Duration,
+    #[cfg(not(target_os = "macos"))]
+    map_alignment: u16,
+    #[cfg(not(target_os = "macos"))]
+    max_stack_depth: u32,
+    #[cfg(target_os = "linux")]
+    uring_queue_depth: u16,
+    #[cfg(not(target_os = "macos"))]
+    request_timeout: u16,
 }
 
 impl KernelConfig {
-    fn new(capabilities: u32, max_readahead: u32) -> Self {
+    fn new(capabilities: u64, max_readahead: u32) -> Self {
         Self {
             capabilities,
             requested: default_init_flags(capabilities),
             max_readahead,
             max_max_readahead: max_readahead,
-            #[cfg(feature = "abi-7-13")]
             max_background: 16,
-            #[cfg(feature = "abi-7-13")]
             congestion_threshold: None,
             // use a max write size that fits into the session's buffer
             max_write: MAX_WRITE_SIZE as u32,
             // 1ns means nano-second granularity.
-            #[cfg(feature = "abi-7-23")]
             time_gran: Duration::new(0, 1),
+            #[cfg(not(target_os = "macos"))]
+            map_alignment: 0,
+            #[cfg(not(target_os = "macos"))]
+            max_stack_depth: 0,
+            #[cfg(target_os = "linux")]
+            uring_queue_depth: 1,
+            #[cfg(not(target_os = "macos"))]
+            request_timeout: 0,
         }
     }
 
@@ -174,7 +233,6 @@ impl KernelConfig {
     /// Must be a power of 10 nanoseconds. i.e. 1s, 0.1s, 0.01s, 1ms, 0.1ms...etc
     ///
     /// On success returns the previous value. On error returns the nearest value which will succeed
-    #[cfg(feature = "abi-7-23")]
     pub fn set_time_granularity(&mut self, value: Duration) -> Result<Duration, Duration> {
         if value.as_nanos() == 0 {
             return Err(Duration::new(0, 1));
@@ -228,18 +286,102 @@ impl KernelConfig {
     /// Add a set of capabilities.
     ///
     /// On success returns Ok, else return bits of capabilities not supported when capabilities you provided are not all supported by kernel.
-    pub fn add_capabilities(&mut self, capabilities_to_add: u32) -> Result<(), u32> {
-        if capabilities_to_add & self.capabilities != capabilities_to_add {
-            return Err(capabilities_to_add - (capabilities_to_add & self.capabilities));
+    pub fn add_capabilities(
+        &mut self,
+        capabilities_to_add: impl Into<Capabilities>,
+    ) -> Result<(), Capabilities> {
+        let capabilities_to_add = capabilities_to_add.into();
+        if !self.offered().contains(capabilities_to_add) {
+            return Err(capabilities_to_add - self.offered());
         }
-        self.requested |= capabilities_to_add;
+        self.requested |= capabilities_to_add.bits();
         Ok(())
     }
 
+    /// Capabilities offered by the kernel
+    pub fn offered(&self) -> Capabilities {
+        Capabilities::from_bits(self.capabilities)
+    }
+
+    /// Capabilities that will be requested from the kernel. Includes the default ones and
+    /// those added with [`add_capabilities`](Self::add_capabilities), limited to the offered ones.
+    pub fn requested(&self) -> Capabilities {
+        Capabilities::from_bits(self.capabilities & self.requested)
+    }
+
+    /// Set the alignment of DAX mappings, in bytes. Must be a power of two. The kernel only
+    /// checks it if `FUSE_MAP_ALIGNMENT` was added to the capabilities.
+    ///
+    /// On success returns the previous value. On error returns the nearest value which will succeed
+    #[cfg(not(target_os = "macos"))]
+    pub fn set_map_alignment(&mut self, value: u64) -> Result<u64, u64> {
+        if value == 0 {
+            return Err(1);
+        }
+        if !value.is_power_of_two() {
+            return Err(value.checked_next_power_of_two().unwrap_or(1 << 63));
+        }
+        let previous = 1 << self.map_alignment;
+        self.map_alignment = value.trailing_zeros() as u16;
+        Ok(previous)
+    }
+
+    /// Set the maximum stacking depth of the backing files of passthrough files. Must be
+    /// at least 1 for `FUSE_PASSTHROUGH` to take effect; 1 means the backing files are not
+    /// on a stacking filesystem (like overlayfs or another FUSE filesystem).
+    ///
+    /// On success returns the previous value. On error returns the nearest value which will succeed
+    #[cfg(not(target_os = "macos"))]
+    pub fn set_max_stack_depth(&mut self, value: u32) -> Result<u32, u32> {
+        if value > ll::fuse_abi::FILESYSTEM_MAX_STACK_DEPTH {
+            return Err(ll::fuse_abi::FILESYSTEM_MAX_STACK_DEPTH);
+        }
+        let previous = self.max_stack_depth;
+        self.max_stack_depth = value;
+        Ok(previous)
+    }
+
+    /// Set the time after which the kernel aborts the connection if a request is not replied
+    /// to. Must be whole seconds. The kernel only applies it if `FUSE_REQUEST_TIMEOUT` was
+    /// added to the capabilities; [`Session::run`] then fails with an error of kind
+    /// [`ConnectionAborted`](ErrorKind::ConnectionAborted) once a request timed out.
+    ///
+    /// On success returns the previous value. On error returns the nearest value which will succeed
+    #[cfg(not(target_os = "macos"))]
+    pub fn set_request_timeout(&mut self, value: Duration) -> Result<Duration, Duration> {
+        if value < Duration::from_secs(1) {
+            return Err(Duration::from_secs(1));
+        }
+        if value > Duration::from_secs(u16::MAX as u64) {
+            return Err(Duration::from_secs(u16::MAX as u64));
+        }
+        if value.subsec_nanos() != 0 {
+            return Err(Duration::from_secs(value.as_secs()));
+        }
+        let previous = Duration::from_secs(self.request_timeout as u64);
+        self.request_timeout = value.as_secs() as u16;
+        Ok(previous)
+    }
+
+    /// Set the number of io_uring ring entries per queue. The kernel only passes requests
+    /// through io_uring if `FUSE_OVER_IO_URING` was added to the capabilities; there is a
+    /// queue per possible CPU, and every ring entry has buffers for a request of the maximum
+    /// write size.
+    ///
+    /// On success returns the previous value. On error returns the nearest value which will succeed
+    #[cfg(target_os = "linux")]
+    pub fn set_uring_queue_depth(&mut self, value: u16) -> Result<u16, u16> {
+        if value == 0 {
+            return Err(1);
+        }
+        let previous = self.uring_queue_depth;
+        self.uring_queue_depth = value;
+        Ok(previous)
+    }
+
     /// Set the maximum number of pending background requests. Such as readahead requests.
     ///
     /// On success returns the previous value. On error returns the nearest value which will succeed
-    #[cfg(feature = "abi-7-13")]
     pub fn set_max_background(&mut self, value: u16) -> Result<u16, u16> {
         if value == 0 {
             return Err(1);
@@ -253,7 +395,6 @@ impl KernelConfig {
     /// request queue congested. (it may then switch to sleeping instead of spin-waiting, for example)
     ///
     /// On success returns the previous value. On error returns the nearest value which will succeed
-    #[cfg(feature = "abi-7-13")]
     pub fn set_congestion_threshold(&mut self, value: u16) -> Result<u16, u16> {
         if value == 0 {
             return Err(1);
@@ -263,7 +404,6 @@ impl KernelConfig {
         Ok(previous)
     }
 
-    #[cfg(feature = "abi-7-13")]
     fn congestion_threshold(&self) -> u16 {
         match self.congestion_threshold {
             // Default to a threshold of 3/4 of the max background threads
@@ -272,10 +412,103 @@ impl KernelConfig {
         }
     }
 
-    #[cfg(feature = "abi-7-28")]
+    fn map_alignment(&self) -> u16 {
+        #[cfg(not(target_os = "macos"))]
+        return self.map_alignment;
+        #[cfg(target_os = "macos")]
+        0
+    }
+
+    fn max_stack_depth(&self) -> u32 {
+        #[cfg(not(target_os = "macos"))]
+        return self.max_stack_depth;
+        #[cfg(target_os = "macos")]
+        0
+    }
+
+    fn request_timeout(&self) -> u16 {
+        #[cfg(not(target_os = "macos"))]
+        return self.request_timeout;
+        #[cfg(target_os = "macos")]
+        0
+    }
+
     fn max_pages(&self) -> u16 {
         ((max(self.max_write, self.max_readahead) - 1) / page_size::get() as u32) as u16 + 1
     }
+
+    /// Stop requesting the capabilities that make the kernel send operations the filesystem
+    /// doesn't implement
+    fn restrict_to(&mut self, operations: Operations) {
+        let unsupported = self.requested() & operations.unsupported_capabilities();
+        if !unsupported.is_empty() {
+            debug!(
+                "Not requesting {:?}, the filesystem only implements {:?}",
+                unsupported, operations
+            );
+            self.requested &= !unsupported.bits();
+        }
+    }
+
+    /// Stop requesting `FUSE_ALLOW_IDMAP`, which the session can't serve for the given reason
+    #[cfg(not(target_os = "macos"))]
+    fn restrict_idmap(&mut self, reason: &str) {
+        if self.requested().contains(Capabilities::ALLOW_IDMAP) {
+            warn!("Not requesting FUSE_ALLOW_IDMAP, as {}", reason);
+            self.requested &= !FUSE_ALLOW_IDMAP;
+        }
+    }
+
+    fn negotiated(&self, proto_major: u32, proto_minor: u32) -> NegotiatedConfig {
+        NegotiatedConfig {
+            capabilities: self.requested(),
+            proto_major,
+            proto_minor,
+            max_readahead: self.max_readahead,
+            max_write: self.max_write,
+            max_pages: self.max_pages(),
+            max_background: self.max_background,
+            congestion_threshold: self.congestion_threshold(),
+            time_gran: self.time_gran,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn uring_config(&self) -> uring::UringConfig {
+        // The kernel sizes requests by the max write size and max pages, which default to
+        // 32 pages if not negotiated
+        let pages = max(self.max_pages() as usize, 32);
+        uring::UringConfig {
+            queue_depth: self.uring_queue_depth,
+            payload_size: max(
+                max(self.max_write as usize, pages * page_size::get()),
+                FUSE_MIN_READ_BUFFER,
+            ),
+        }
+    }
+}
+
+/// Settings of the FUSE connection agreed on in init. See [`Session::negotiated`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NegotiatedConfig {
+    /// Capabilities offered by the kernel and requested by the filesystem
+    pub capabilities: Capabilities,
+    /// FUSE protocol major version
+    pub proto_major: u32,
+    /// FUSE protocol minor version, the lower one of the kernel's and ours
+    pub proto_minor: u32,
+    /// Maximum readahead size
+    pub max_readahead: u32,
+    /// Maximum write size for a single request
+    pub max_write: u32,
+    /// Maximum number of pages of a single request
+    pub max_pages: u16,
+    /// Maximum number of pending background requests
+    pub max_background: u16,
+    /// Threshold of background requests at which the kernel considers the queue congested
+    pub congestion_threshold: u16,
+    /// Timestamp granularity
+    pub time_gran: Duration,
 }
 
 /// Filesystem trait.
@@ -297,7 +530,25 @@ pub trait Filesystem {
     /// Called on filesystem exit.
     fn destroy(&mut self) {}
 
+    /// Operations the filesystem implements. Capabilities that make the kernel send other
+    /// operations (like `FUSE_DO_READDIRPLUS` without readdirplus) are not requested in init,
+    /// and requests of other operations are replied to with `ENOSYS` without calling the
+    /// filesystem. Operations without a flag, like open, are always passed to the filesystem.
+    /// Called once when the session is created.
+    fn supported_ops(&self) -> Operations {
+        Operations::all()
+    }
+
     /// Look up a directory entry by name and get its attributes.
+    ///
+    /// If `FUSE_EXPORT_SUPPORT` was added to the capabilities (which is needed to export the
+    /// filesystem over NFS), the kernel also looks up "." to find an inode by its number
+    /// and ".." to find the parent of a directory, with the inode (or directory) as parent.
+    /// Both must reply the entry of the inode they refer to and count as a lookup of it;
+    /// lookups of "." fail with `ESTALE` if the inode is gone. As the kernel checks the
+    /// generation of the entry against that of the file handle it looks up, inode numbers
+    /// that are reused must get a new generation, e.g. with [`InodeGenerations`]. A
+    /// filesystem that can't support this should add `FUSE_NO_EXPORT_SUPPORT` instead.
     fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
         warn!(
             "[Not Implemented] lookup(parent: {:#x?}, name {:?})",
@@ -317,7 +568,6 @@ pub trait Filesystem {
 
     /// Like forget, but take multiple forget requests at once for performance. The default
     /// implementation will fallback to forget.
-    #[cfg(feature = "abi-7-16")]
     fn batch_forget(&mut self, req: &Request<'_>, nodes: &[fuse_forget_one]) {
         for node in nodes {
             self.forget(req, node.nodeid, node.nlookup);
@@ -330,7 +580,28 @@ pub trait Filesystem {
         reply.error(ENOSYS);
     }
 
+    /// Get extended file attributes, see statx(2).
+    /// Only called if the caller asked for more than getattr() provides, e.g. the birth
+    /// time. mask contains the `STATX_*` bits the caller is interested in, flags the
+    /// `AT_STATX_*` sync flags. fh is set if the caller passed an open file. If this
+    /// method is not implemented, getattr() is called and its attributes are replied
+    /// without birth time.
+    fn statx(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        _fh: Option<u64>,
+        _flags: u32,
+        _mask: u32,
+        reply: ReplyStatx,
+    ) {
+        self.getattr(req, ino, reply.getattr_fallback());
+    }
+
     /// Set file attributes.
+    /// kill_suidgid: with `FUSE_HANDLE_KILLPRIV_V2`, set if the size is changed by a caller
+    /// without `CAP_FSETID`; the setuid and setgid bits must then be cleared (the setgid
+    /// bit only if the file is group executable). The bits must also be cleared on chown.
     fn setattr(
         &mut self,
         _req: &Request<'_>,
@@ -347,12 +618,13 @@ pub trait Filesystem {
         _chgtime: Option<SystemTime>,
         _bkuptime: Option<SystemTime>,
         flags: Option<u32>,
+        kill_suidgid: bool,
         reply: ReplyAttr,
     ) {
         debug!(
             "[Not Implemented] setattr(ino: {:#x?}, mode: {:?}, uid: {:?}, \
-            gid: {:?}, size: {:?}, fh: {:?}, flags: {:?})",
-            ino, mode, uid, gid, size, fh, flags
+            gid: {:?}, size: {:?}, fh: {:?}, flags: {:?}, kill_suidgid: {})",
+            ino, mode, uid, gid, size, fh, flags, kill_suidgid
         );
         reply.error(ENOSYS);
     }
@@ -365,6 +637,10 @@ pub trait Filesystem {
 
     /// Create file node.
     /// Create a regular file, character device, block device, fifo or socket node.
+    /// security_ctx contains the security contexts (e.g. `security.selinux`) of the new
+    /// node as name/value pairs. It's only filled if `FUSE_SECURITY_CTX` was added to the
+    /// capabilities, and the filesystem should then store them as extended attributes
+    /// together with the node.
     fn mknod(
         &mut self,
         _req: &Request<'_>,
@@ -373,17 +649,19 @@ pub trait Filesystem {
         mode: u32,
         umask: u32,
         rdev: u32,
+        security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyEntry,
     ) {
         debug!(
             "[Not Implemented] mknod(parent: {:#x?}, name: {:?}, mode: {}, \
-            umask: {:#x?}, rdev: {})",
-            parent, name, mode, umask, rdev
+            umask: {:#x?}, rdev: {}, security_ctx: {:?})",
+            parent, name, mode, umask, rdev, security_ctx
         );
         reply.error(ENOSYS);
     }
 
     /// Create a directory.
+    /// See mknod() for security_ctx.
     fn mkdir(
         &mut self,
         _req: &Request<'_>,
@@ -391,11 +669,13 @@ pub trait Filesystem {
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyEntry,
     ) {
         debug!(
-            "[Not Implemented] mkdir(parent: {:#x?}, name: {:?}, mode: {}, umask: {:#x?})",
-            parent, name, mode, umask
+            "[Not Implemented] mkdir(parent: {:#x?}, name: {:?}, mode: {}, umask: {:#x?}, \
+            security_ctx: {:?})",
+            parent, name, mode, umask, security_ctx
         );
         reply.error(ENOSYS);
     }
@@ -419,17 +699,20 @@ pub trait Filesystem {
     }
 
     /// Create a symbolic link.
+    /// See mknod() for security_ctx.
     fn symlink(
         &mut self,
         _req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         link: &Path,
+        security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyEntry,
     ) {
         debug!(
-            "[Not Implemented] symlink(parent: {:#x?}, name: {:?}, link: {:?})",
-            parent, name, link,
+            "[Not Implemented] symlink(parent: {:#x?}, name: {:?}, link: {:?}, \
+            security_ctx: {:?})",
+            parent, name, link, security_ctx
         );
         reply.error(ENOSYS);
     }
@@ -454,6 +737,8 @@ pub trait Filesystem {
     }
 
     /// Create a hard link.
+    /// This is also how an unnamed file created by tmpfile() is given a name, when the
+    /// client calls `linkat(2)` on it. In that case the link count of ino is zero.
     fn link(
         &mut self,
         _req: &Request<'_>,
@@ -477,7 +762,17 @@ pub trait Filesystem {
     /// anything in fh. There are also some flags (direct_io, keep_cache) which the
     /// filesystem may set, to change the way the file is opened. See fuse_file_info
     /// structure in <fuse_common.h> for more details.
-    fn open(&mut self, _req: &Request<'_>, _ino: u64, _flags: i32, reply: ReplyOpen) {
+    /// kill_suidgid: with `FUSE_HANDLE_KILLPRIV_V2`, set if the file is opened with O_TRUNC
+    /// by a caller without `CAP_FSETID`; the setuid and setgid bits must then be cleared if
+    /// the file is executable.
+    fn open(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _flags: i32,
+        _kill_suidgid: bool,
+        reply: ReplyOpen,
+    ) {
         reply.opened(0, 0);
     }
 
@@ -522,6 +817,9 @@ pub trait Filesystem {
     /// is disabled
     /// flags: these are the file flags, such as O_SYNC. Only supported with ABI >= 7.9
     /// lock_owner: only supported with ABI >= 7.9
+    /// kill_suidgid: with `FUSE_HANDLE_KILLPRIV_V2`, set if the caller doesn't have
+    /// `CAP_FSETID`; the setuid and setgid bits must then be cleared (the setgid bit only
+    /// if the file is group executable)
     fn write(
         &mut self,
         _req: &Request<'_>,
@@ -532,18 +830,20 @@ pub trait Filesystem {
         write_flags: u32,
         flags: i32,
         lock_owner: Option<u64>,
+        kill_suidgid: bool,
         reply: ReplyWrite,
     ) {
         debug!(
             "[Not Implemented] write(ino: {:#x?}, fh: {}, offset: {}, data.len(): {}, \
-            write_flags: {:#x?}, flags: {:#x?}, lock_owner: {:?})",
+            write_flags: {:#x?}, flags: {:#x?}, lock_owner: {:?}, kill_suidgid: {})",
             ino,
             fh,
             offset,
             data.len(),
             write_flags,
             flags,
-            lock_owner
+            lock_owner,
+            kill_suidgid
         );
         reply.error(ENOSYS);
     }
@@ -633,20 +933,29 @@ pub trait Filesystem {
     /// Send a buffer filled using buffer.fill(), with size not exceeding the
     /// requested size. Send an empty buffer on end of stream. fh will contain the
     /// value set by the opendir method, or will be undefined if the opendir method
-    /// didn't set any value.
+    /// didn't set any value. Every entry other than "." and ".." counts as looked up,
+    /// like after lookup(). If this method is not implemented, the entries of readdir()
+    /// are looked up with lookup() instead; this waits for their replies.
     fn readdirplus(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         fh: u64,
         offset: i64,
         reply: ReplyDirectoryPlus,
     ) {
         debug!(
-            "[Not Implemented] readdirplus(ino: {:#x?}, fh: {}, offset: {})",
+            "[Not Implemented] readdirplus(ino: {:#x?}, fh: {}, offset: {}), \
+            falling back to readdir and lookup",
             ino, fh, offset
         );
-        reply.error(ENOSYS);
+        readdirplus::readdirplus(
+            self,
+            req,
+            reply,
+            |fs, listing| fs.readdir(req, ino, fh, offset, listing),
+            |fs, name, entry| fs.lookup(req, ino, name, entry),
+        );
     }
 
     /// Release an open directory.
@@ -689,6 +998,8 @@ pub trait Filesystem {
     }
 
     /// Set an extended attribute.
+    /// setxattr_flags: `FUSE_SETXATTR_*` flags, e.g. `FUSE_SETXATTR_ACL_KILL_SGID`. Only
+    /// supported with ABI >= 7.33 and if `FUSE_SETXATTR_EXT` was added to the capabilities.
     fn setxattr(
         &mut self,
         _req: &Request<'_>,
@@ -696,12 +1007,14 @@ pub trait Filesystem {
         name: &OsStr,
         _value: &[u8],
         flags: i32,
+        setxattr_flags: u32,
         position: u32,
         reply: ReplyEmpty,
     ) {
         debug!(
-            "[Not Implemented] setxattr(ino: {:#x?}, name: {:?}, flags: {:#x?}, position: {})",
-            ino, name, flags, position
+            "[Not Implemented] setxattr(ino: {:#x?}, name: {:?}, flags: {:#x?}, \
+            setxattr_flags: {:#x?}, position: {})",
+            ino, name, flags, setxattr_flags, position
         );
         reply.error(ENOSYS);
     }
@@ -764,7 +1077,8 @@ pub trait Filesystem {
     /// filesystem may set, to change the way the file is opened. See fuse_file_info
     /// structure in <fuse_common.h> for more details. If this method is not
     /// implemented or under Linux kernel versions earlier than 2.6.15, the mknod()
-    /// and open() methods will be called instead.
+    /// and open() methods will be called instead. See mknod() for security_ctx and
+    /// open() for kill_suidgid.
     fn create(
         &mut self,
         _req: &Request<'_>,
@@ -773,12 +1087,35 @@ pub trait Filesystem {
         mode: u32,
         umask: u32,
         flags: i32,
+        kill_suidgid: bool,
+        security_ctx: &[(&OsStr, &[u8])],
         reply: ReplyCreate,
     ) {
         debug!(
             "[Not Implemented] create(parent: {:#x?}, name: {:?}, mode: {}, umask: {:#x?}, \
+            flags: {:#x?}, kill_suidgid: {}, security_ctx: {:?})",
+            parent, name, mode, umask, flags, kill_suidgid, security_ctx
+        );
+        reply.error(ENOSYS);
+    }
+
+    /// Create and open an unnamed file in the directory parent (`O_TMPFILE`).
+    /// The reply is the same as for create(). The new inode has a link count of zero
+    /// and is removed once it's released, unless the client gives it a name through
+    /// link(). If this method is not implemented, `O_TMPFILE` fails with `EOPNOTSUPP`.
+    fn tmpfile(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        debug!(
+            "[Not Implemented] tmpfile(parent: {:#x?}, mode: {}, umask: {:#x?}, \
             flags: {:#x?})",
-            parent, name, mode, umask, flags
+            parent, mode, umask, flags
         );
         reply.error(ENOSYS);
     }
@@ -844,6 +1181,9 @@ pub trait Filesystem {
     }
 
     /// control device
+    /// arg is the user space address of the ioctl argument. Unrestricted ioctls
+    /// (`FUSE_IOCTL_UNRESTRICTED` in flags) can use it to request the caller's buffers
+    /// with [`ReplyIoctl::retry`].
     fn ioctl(
         &mut self,
         _req: &Request<'_>,
@@ -851,17 +1191,19 @@ pub trait Filesystem {
         fh: u64,
         flags: u32,
         cmd: u32,
+        arg: u64,
         in_data: &[u8],
         out_size: u32,
         reply: ReplyIoctl,
     ) {
         debug!(
-            "[Not Implemented] ioctl(ino: {:#x?}, fh: {}, flags: {}, cmd: {}, \
+            "[Not Implemented] ioctl(ino: {:#x?}, fh: {}, flags: {}, cmd: {}, arg: {:#x}, \
             in_data.len(): {}, out_size: {})",
             ino,
             fh,
             flags,
             cmd,
+            arg,
             in_data.len(),
             out_size,
         );
@@ -927,6 +1269,14 @@ pub trait Filesystem {
         reply.error(ENOSYS);
     }
 
+    /// Synchronize the whole filesystem.
+    /// Called on `syncfs(2)` and `sync(1)`. ino is the root inode of the mount. Filesystems
+    /// with write-back caching should flush all pending data before replying.
+    fn syncfs(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyEmpty) {
+        debug!("[Not Implemented] syncfs(ino: {:#x?})", ino);
+        reply.error(ENOSYS);
+    }
+
     /// macOS only: Rename the volume. Set fuse_init_out.flags during init to
     /// FUSE_VOL_RENAME to enable
     #[cfg(target_os = "macos")]
@@ -970,7 +1320,7 @@ pub trait Filesystem {
 /// Note that you need to lead each option with a separate `"-o"` string. See
 /// `examples/hello.rs`.
 #[deprecated(note = "use mount2() instead")]
-pub fn mount<FS: Filesystem, P: AsRef<Path>>(
+pub fn mount<FS: FilesystemV2, P: AsRef<Path>>(
     filesystem: FS,
     mountpoint: P,
     options: &[&OsStr],
@@ -983,7 +1333,7 @@ pub fn mount<FS: Filesystem, P: AsRef<Path>>(
 /// not return until the filesystem is unmounted.
 ///
 /// NOTE: This will eventually replace mount(), once the API is stable
-pub fn mount2<FS: Filesystem, P: AsRef<Path>>(
+pub fn mount2<FS: FilesystemV2, P: AsRef<Path>>(
     filesystem: FS,
     mountpoint: P,
     options: &[MountOption],
@@ -1002,7 +1352,7 @@ pub fn mount2<FS: Filesystem, P: AsRef<Path>>(
 ///
 /// This interface is inherently unsafe if the BackgroundSession is allowed to leak without being
 /// dropped. See rust-lang/rust#24292 for more details.
-pub fn spawn_mount<'a, FS: Filesystem + Send + 'static + 'a, P: AsRef<Path>>(
+pub fn spawn_mount<'a, FS: FilesystemV2 + Send + 'static + 'a, P: AsRef<Path>>(
     filesystem: FS,
     mountpoint: P,
     options: &[&OsStr],
@@ -3,10 +3,13 @@
 //! Helper to decompose a slice of binary data (incoming FUSE request) into multiple data
 //! structures (request arguments).
 
+use std::borrow::Cow;
 use std::ffi::OsStr;
+use std::mem;
 use std::os::unix::ffi::OsStrExt;
 
 /// An iterator that can be used to fetch typed arguments from a byte slice.
+#[derive(Debug)]
 pub struct ArgumentIterator<'a> {
     data: &'a [u8],
 }
@@ -22,6 +25,11 @@ impl<'a> ArgumentIterator<'a> {
         self.data.len()
     }
 
+    /// Returns true if there's no data left.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     /// Fetch a slice of all remaining bytes.
     pub fn fetch_all(&mut self) -> &'a [u8] {
         let bytes = self.data;
@@ -50,8 +58,35 @@ impl<'a> ArgumentIterator<'a> {
         }
     }
 
+    /// Fetch a typed argument of which only the first `size` bytes are present, as sent by
+    /// kernels with an older ABI. Missing fields are zeroed. Returns `None` if there's not
+    /// enough data left.
+    pub fn fetch_truncated<T>(&mut self, size: usize) -> Option<Cow<'a, T>>
+    where
+        T: zerocopy::FromBytes + zerocopy::AsBytes + Clone,
+    {
+        if size >= mem::size_of::<T>() {
+            return self.fetch().map(Cow::Borrowed);
+        }
+        if self.data.len() < size {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(size);
+        self.data = rest;
+        let mut arg = T::new_zeroed();
+        arg.as_bytes_mut()[..size].copy_from_slice(bytes);
+        Some(Cow::Owned(arg))
+    }
+
+    /// Fetch a copy of a typed argument that isn't necessarily aligned, e.g. because it
+    /// follows a string. Returns `None` if there's not enough data left.
+    pub fn fetch_unaligned<T: zerocopy::FromBytes>(&mut self) -> Option<T> {
+        let arg = T::read_from_prefix(self.data)?;
+        self.data = &self.data[mem::size_of::<T>()..];
+        Some(arg)
+    }
+
     /// Fetch a slice of typed of arguments. Returns `None` if there's not enough data left.
-    #[cfg(feature = "abi-7-16")]
     pub fn fetch_slice<T: zerocopy::FromBytes>(&mut self, count: usize) -> Option<&'a [T]> {
         match zerocopy::LayoutVerified::<_, [T]>::new_slice_from_prefix(self.data, count) {
             None => {
@@ -88,13 +123,13 @@ pub mod tests {
 
     use super::super::test::AlignedData;
     use super::*;
-    use zerocopy::FromBytes;
+    use zerocopy::{AsBytes, FromBytes};
 
     const TEST_DATA: AlignedData<[u8; 10]> =
         AlignedData([0x66, 0x6f, 0x6f, 0x00, 0x62, 0x61, 0x72, 0x00, 0x62, 0x61]);
 
     #[repr(C)]
-    #[derive(FromBytes)]
+    #[derive(Clone, AsBytes, FromBytes)]
     struct TestArgument {
         p1: u8,
         p2: u8,
@@ -146,6 +181,36 @@ pub mod tests {
         assert_eq!(arg, [0x62, 0x61]);
     }
 
+    #[test]
+    fn truncated_argument() {
+        let mut it = ArgumentIterator::new(TEST_DATA.deref());
+        let arg: Cow<'_, TestArgument> = it.fetch_truncated(2).unwrap();
+        assert_eq!(arg.p1, 0x66);
+        assert_eq!(arg.p2, 0x6f);
+        assert_eq!(arg.p3, 0);
+        let arg: Cow<'_, TestArgument> = it.fetch_truncated(4).unwrap();
+        assert_eq!(arg.p1, 0x6f);
+        assert_eq!(arg.p3, 0x6162);
+        assert_eq!(it.len(), 4);
+        let mut it = ArgumentIterator::new(&TEST_DATA[..1]);
+        let arg: Option<Cow<'_, TestArgument>> = it.fetch_truncated(2);
+        assert!(arg.is_none());
+        assert_eq!(it.len(), 1);
+    }
+
+    #[test]
+    fn unaligned_argument() {
+        let mut it = ArgumentIterator::new(TEST_DATA.deref());
+        it.fetch_str().unwrap();
+        let arg: TestArgument = it.fetch_unaligned().unwrap();
+        assert_eq!(arg.p1, 0x62);
+        assert_eq!(arg.p2, 0x61);
+        assert_eq!(arg.p3, 0x0072);
+        let arg: Option<TestArgument> = it.fetch_unaligned();
+        assert!(arg.is_none());
+        assert_eq!(it.len(), 2);
+    }
+
     #[test]
     fn out_of_data() {
         let mut it = ArgumentIterator::new(TEST_DATA.deref());
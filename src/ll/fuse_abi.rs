@@ -5,82 +5,74 @@
 //! interface is versioned and capabilities are exchanged during the initialization (mounting)
 //! of a filesystem.
 //!
-//! OSXFUSE (macOS): https://github.com/osxfuse/fuse/blob/master/include/fuse_kernel.h
+//! OSXFUSE (macOS): <https://github.com/osxfuse/fuse/blob/master/include/fuse_kernel.h>
 //! - supports ABI 7.8 in OSXFUSE 2.x
 //! - supports ABI 7.19 since OSXFUSE 3.0.0
 //!
-//! libfuse (Linux/BSD): https://github.com/libfuse/libfuse/blob/master/include/fuse_kernel.h
+//! libfuse (Linux/BSD): <https://github.com/libfuse/libfuse/blob/master/include/fuse_kernel.h>
 //! - supports ABI 7.8 since FUSE 2.6.0
 //! - supports ABI 7.12 since FUSE 2.8.0
 //! - supports ABI 7.18 since FUSE 2.9.0
 //! - supports ABI 7.19 since FUSE 2.9.1
 //! - supports ABI 7.26 since FUSE 3.0.0
+//! - supports ABI 7.36 since FUSE 3.11.0
+//! - supports ABI 7.40 since FUSE 3.17.0
 //!
-//! Items without a version annotation are valid with ABI 7.8 and later
+//! The structs follow the layout of the newest supported ABI. Kernels with an older ABI send
+//! and expect shorter versions of some of them, see the `FUSE_COMPAT_*_SIZE` constants.
 
 #![warn(missing_debug_implementations)]
 #![allow(missing_docs)]
 
-#[cfg(feature = "abi-7-9")]
 use crate::consts::{FATTR_ATIME_NOW, FATTR_MTIME_NOW};
 use std::convert::TryFrom;
+use std::mem;
 use zerocopy::{AsBytes, FromBytes};
 
 pub const FUSE_KERNEL_VERSION: u32 = 7;
 
-#[cfg(not(feature = "abi-7-9"))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 8;
-#[cfg(all(feature = "abi-7-9", not(feature = "abi-7-10")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 9;
-#[cfg(all(feature = "abi-7-10", not(feature = "abi-7-11")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 10;
-#[cfg(all(feature = "abi-7-11", not(feature = "abi-7-12")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 11;
-#[cfg(all(feature = "abi-7-12", not(feature = "abi-7-13")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 12;
-#[cfg(all(feature = "abi-7-13", not(feature = "abi-7-14")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 13;
-#[cfg(all(feature = "abi-7-14", not(feature = "abi-7-15")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 14;
-#[cfg(all(feature = "abi-7-15", not(feature = "abi-7-16")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 15;
-#[cfg(all(feature = "abi-7-16", not(feature = "abi-7-17")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 16;
-#[cfg(all(feature = "abi-7-17", not(feature = "abi-7-18")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 17;
-#[cfg(all(feature = "abi-7-18", not(feature = "abi-7-19")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 18;
-#[cfg(all(feature = "abi-7-19", not(feature = "abi-7-20")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 19;
-#[cfg(all(feature = "abi-7-20", not(feature = "abi-7-21")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 20;
-#[cfg(all(feature = "abi-7-21", not(feature = "abi-7-22")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 21;
-#[cfg(all(feature = "abi-7-22", not(feature = "abi-7-23")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 22;
-#[cfg(all(feature = "abi-7-23", not(feature = "abi-7-24")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 23;
-#[cfg(all(feature = "abi-7-24", not(feature = "abi-7-25")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 24;
-#[cfg(all(feature = "abi-7-25", not(feature = "abi-7-26")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 25;
-#[cfg(all(feature = "abi-7-26", not(feature = "abi-7-27")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 26;
-#[cfg(all(feature = "abi-7-27", not(feature = "abi-7-28")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 27;
-#[cfg(all(feature = "abi-7-28", not(feature = "abi-7-29")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 28;
-#[cfg(all(feature = "abi-7-29", not(feature = "abi-7-30")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 29;
-#[cfg(all(feature = "abi-7-30", not(feature = "abi-7-31")))]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 30;
-#[cfg(feature = "abi-7-31")]
-pub const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
+pub const FUSE_KERNEL_MINOR_VERSION: u32 = 43;
+
+// Sizes of structs as used by kernels before the ABI version in which they were extended
+pub const FUSE_COMPAT_ENTRY_OUT_SIZE: usize = mem::size_of::<fuse_entry_out>() - 8; // 7.9
+pub const FUSE_COMPAT_ATTR_OUT_SIZE: usize = mem::size_of::<fuse_attr_out>() - 8; // 7.9
+pub const FUSE_COMPAT_READ_IN_SIZE: usize = 24; // 7.9
+pub const FUSE_COMPAT_WRITE_IN_SIZE: usize = 24; // 7.9
+pub const FUSE_COMPAT_LK_IN_SIZE: usize = 40; // 7.9
+pub const FUSE_COMPAT_MKNOD_IN_SIZE: usize = 8; // 7.12
+pub const FUSE_COMPAT_CREATE_IN_SIZE: usize = 8; // 7.12
+pub const FUSE_COMPAT_INIT_OUT_SIZE: usize = 8; // 7.5
+pub const FUSE_COMPAT_22_INIT_OUT_SIZE: usize = 24; // 7.23
+pub const FUSE_COMPAT_INIT_IN_SIZE: usize = 16; // 7.36
 
 pub const FUSE_ROOT_ID: u64 = 1;
 
+// Unique ids of requests: the kernel counts in steps of two and sets the lowest bit in the id
+// of an interrupt, which is otherwise the id of the interrupted request
+pub const FUSE_INT_REQ_BIT: u64 = 1 << 0;
+pub const FUSE_REQ_ID_STEP: u64 = 1 << 1;
+
+// Ioctls on the FUSE device
+#[cfg(target_os = "linux")]
+pub const FUSE_DEV_IOC_BACKING_OPEN: u32 = 0x4010_e501; // _IOW(229, 1, struct fuse_backing_map)
+#[cfg(target_os = "linux")]
+pub const FUSE_DEV_IOC_BACKING_CLOSE: u32 = 0x4004_e502; // _IOW(229, 2, uint32_t)
+
+/// Maximum stacking depth of the backing files of passthrough files
+pub const FILESYSTEM_MAX_STACK_DEPTH: u32 = 2;
+
+// Commands of FUSE-over-io_uring (IORING_OP_URING_CMD on the FUSE device)
+#[cfg(target_os = "linux")]
+pub const FUSE_IO_URING_CMD_REGISTER: u32 = 1; // register a ring entry
+#[cfg(target_os = "linux")]
+pub const FUSE_IO_URING_CMD_COMMIT_AND_FETCH: u32 = 2; // commit a reply and fetch the next request
+
+// Sizes of the areas in the header buffer of a ring entry
+pub const FUSE_URING_IN_OUT_HEADER_SZ: usize = 128;
+pub const FUSE_URING_OP_IN_OUT_SZ: usize = 128;
+
 #[repr(C)]
-#[derive(Debug, AsBytes, Clone, Copy)]
+#[derive(Debug, AsBytes, FromBytes, Clone, Copy)]
 pub struct fuse_attr {
     pub ino: u64,
     pub size: u64,
@@ -108,14 +100,15 @@ pub struct fuse_attr {
     pub rdev: u32,
     #[cfg(target_os = "macos")]
     pub flags: u32, // see chflags(2)
-    #[cfg(feature = "abi-7-9")]
     pub blksize: u32,
-    #[cfg(feature = "abi-7-9")]
+    #[cfg(target_os = "macos")]
     pub padding: u32,
+    #[cfg(not(target_os = "macos"))]
+    pub flags: u32, // FUSE_ATTR_* flags
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_kstatfs {
     pub blocks: u64,  // Total blocks (in units of frsize)
     pub bfree: u64,   // Free blocks
@@ -130,7 +123,7 @@ pub struct fuse_kstatfs {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_file_lock {
     pub start: u64,
     pub end: u64,
@@ -148,14 +141,11 @@ pub mod consts {
     pub const FATTR_ATIME: u32 = 1 << 4;
     pub const FATTR_MTIME: u32 = 1 << 5;
     pub const FATTR_FH: u32 = 1 << 6;
-    #[cfg(feature = "abi-7-9")]
     pub const FATTR_ATIME_NOW: u32 = 1 << 7;
-    #[cfg(feature = "abi-7-9")]
     pub const FATTR_MTIME_NOW: u32 = 1 << 8;
-    #[cfg(feature = "abi-7-9")]
     pub const FATTR_LOCKOWNER: u32 = 1 << 9;
-    #[cfg(feature = "abi-7-23")]
     pub const FATTR_CTIME: u32 = 1 << 10;
+    pub const FATTR_KILL_SUIDGID: u32 = 1 << 11;
 
     #[cfg(target_os = "macos")]
     pub const FATTR_CRTIME: u32 = 1 << 28;
@@ -169,12 +159,12 @@ pub mod consts {
     // Flags returned by the open request
     pub const FOPEN_DIRECT_IO: u32 = 1 << 0; // bypass page cache for this open file
     pub const FOPEN_KEEP_CACHE: u32 = 1 << 1; // don't invalidate the data cache on open
-    #[cfg(feature = "abi-7-10")]
     pub const FOPEN_NONSEEKABLE: u32 = 1 << 2; // the file is not seekable
-    #[cfg(feature = "abi-7-28")]
     pub const FOPEN_CACHE_DIR: u32 = 1 << 3; // allow caching this directory
-    #[cfg(feature = "abi-7-31")]
     pub const FOPEN_STREAM: u32 = 1 << 4; // the file is stream-like (no file position at all)
+    pub const FOPEN_NOFLUSH: u32 = 1 << 5; // don't flush data cache on close (unless FUSE_WRITEBACK_CACHE)
+    pub const FOPEN_PARALLEL_DIRECT_WRITES: u32 = 1 << 6; // allow concurrent direct writes on the same inode
+    pub const FOPEN_PASSTHROUGH: u32 = 1 << 7; // passthrough read/write io for this open file
 
     #[cfg(target_os = "macos")]
     pub const FOPEN_PURGE_ATTR: u32 = 1 << 30;
@@ -182,116 +172,141 @@ pub mod consts {
     pub const FOPEN_PURGE_UBC: u32 = 1 << 31;
 
     // Init request/reply flags
-    pub const FUSE_ASYNC_READ: u32 = 1 << 0; // asynchronous read requests
-    pub const FUSE_POSIX_LOCKS: u32 = 1 << 1; // remote locking for POSIX file locks
-    #[cfg(feature = "abi-7-9")]
-    pub const FUSE_FILE_OPS: u32 = 1 << 2; // kernel sends file handle for fstat, etc...
-    #[cfg(feature = "abi-7-9")]
-    pub const FUSE_ATOMIC_O_TRUNC: u32 = 1 << 3; // handles the O_TRUNC open flag in the filesystem
-    #[cfg(feature = "abi-7-10")]
-    pub const FUSE_EXPORT_SUPPORT: u32 = 1 << 4; // filesystem handles lookups of "." and ".."
-    #[cfg(feature = "abi-7-9")]
-    pub const FUSE_BIG_WRITES: u32 = 1 << 5; // filesystem can handle write size larger than 4kB
-    #[cfg(feature = "abi-7-12")]
-    pub const FUSE_DONT_MASK: u32 = 1 << 6; // don't apply umask to file mode on create operations
-    #[cfg(all(feature = "abi-7-14", not(target_os = "macos")))]
-    pub const FUSE_SPLICE_WRITE: u32 = 1 << 7; // kernel supports splice write on the device
-    #[cfg(all(feature = "abi-7-14", not(target_os = "macos")))]
-    pub const FUSE_SPLICE_MOVE: u32 = 1 << 8; // kernel supports splice move on the device
+    pub const FUSE_ASYNC_READ: u64 = 1 << 0; // asynchronous read requests
+    pub const FUSE_POSIX_LOCKS: u64 = 1 << 1; // remote locking for POSIX file locks
+    pub const FUSE_FILE_OPS: u64 = 1 << 2; // kernel sends file handle for fstat, etc...
+    pub const FUSE_ATOMIC_O_TRUNC: u64 = 1 << 3; // handles the O_TRUNC open flag in the filesystem
+    pub const FUSE_EXPORT_SUPPORT: u64 = 1 << 4; // filesystem handles lookups of "." and ".."
+    pub const FUSE_BIG_WRITES: u64 = 1 << 5; // filesystem can handle write size larger than 4kB
+    pub const FUSE_DONT_MASK: u64 = 1 << 6; // don't apply umask to file mode on create operations
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_SPLICE_WRITE: u64 = 1 << 7; // kernel supports splice write on the device
     #[cfg(not(target_os = "macos"))]
-    #[cfg(feature = "abi-7-14")]
-    pub const FUSE_SPLICE_READ: u32 = 1 << 9; // kernel supports splice read on the device
-    #[cfg(feature = "abi-7-17")]
-    pub const FUSE_FLOCK_LOCKS: u32 = 1 << 10; // remote locking for BSD style file locks
-    #[cfg(feature = "abi-7-18")]
-    pub const FUSE_HAS_IOCTL_DIR: u32 = 1 << 11; // kernel supports ioctl on directories
-    #[cfg(feature = "abi-7-20")]
-    pub const FUSE_AUTO_INVAL_DATA: u32 = 1 << 12; // automatically invalidate cached pages
-    #[cfg(feature = "abi-7-21")]
-    pub const FUSE_DO_READDIRPLUS: u32 = 1 << 13; // do READDIRPLUS (READDIR+LOOKUP in one)
-    #[cfg(feature = "abi-7-21")]
-    pub const FUSE_READDIRPLUS_AUTO: u32 = 1 << 14; // adaptive readdirplus
-    #[cfg(feature = "abi-7-22")]
-    pub const FUSE_ASYNC_DIO: u32 = 1 << 15; // asynchronous direct I/O submission
-    #[cfg(feature = "abi-7-23")]
-    pub const FUSE_WRITEBACK_CACHE: u32 = 1 << 16; // use writeback cache for buffered writes
-    #[cfg(feature = "abi-7-23")]
-    pub const FUSE_NO_OPEN_SUPPORT: u32 = 1 << 17; // kernel supports zero-message opens
-    #[cfg(feature = "abi-7-25")]
-    pub const FUSE_PARALLEL_DIROPS: u32 = 1 << 18; // allow parallel lookups and readdir
-    #[cfg(feature = "abi-7-26")]
-    pub const FUSE_HANDLE_KILLPRIV: u32 = 1 << 19; // fs handles killing suid/sgid/cap on write/chown/trunc
-    #[cfg(feature = "abi-7-26")]
-    pub const FUSE_POSIX_ACL: u32 = 1 << 20; // filesystem supports posix acls
-    #[cfg(feature = "abi-7-27")]
-    pub const FUSE_ABORT_ERROR: u32 = 1 << 21; // reading the device after abort returns ECONNABORTED
-    #[cfg(feature = "abi-7-28")]
-    pub const FUSE_MAX_PAGES: u32 = 1 << 22; // init_out.max_pages contains the max number of req pages
-    #[cfg(feature = "abi-7-28")]
-    pub const FUSE_CACHE_SYMLINKS: u32 = 1 << 23; // cache READLINK responses
-    #[cfg(feature = "abi-7-29")]
-    pub const FUSE_NO_OPENDIR_SUPPORT: u32 = 1 << 24; // kernel supports zero-message opendir
-    #[cfg(feature = "abi-7-30")]
-    pub const FUSE_EXPLICIT_INVAL_DATA: u32 = 1 << 25; // only invalidate cached pages on explicit request
+    pub const FUSE_SPLICE_MOVE: u64 = 1 << 8; // kernel supports splice move on the device
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_SPLICE_READ: u64 = 1 << 9; // kernel supports splice read on the device
+    pub const FUSE_FLOCK_LOCKS: u64 = 1 << 10; // remote locking for BSD style file locks
+    pub const FUSE_HAS_IOCTL_DIR: u64 = 1 << 11; // kernel supports ioctl on directories
+    pub const FUSE_AUTO_INVAL_DATA: u64 = 1 << 12; // automatically invalidate cached pages
+    pub const FUSE_DO_READDIRPLUS: u64 = 1 << 13; // do READDIRPLUS (READDIR+LOOKUP in one)
+    pub const FUSE_READDIRPLUS_AUTO: u64 = 1 << 14; // adaptive readdirplus
+    pub const FUSE_ASYNC_DIO: u64 = 1 << 15; // asynchronous direct I/O submission
+    pub const FUSE_WRITEBACK_CACHE: u64 = 1 << 16; // use writeback cache for buffered writes
+    pub const FUSE_NO_OPEN_SUPPORT: u64 = 1 << 17; // kernel supports zero-message opens
+    pub const FUSE_PARALLEL_DIROPS: u64 = 1 << 18; // allow parallel lookups and readdir
+    pub const FUSE_HANDLE_KILLPRIV: u64 = 1 << 19; // fs handles killing suid/sgid/cap on write/chown/trunc
+    pub const FUSE_POSIX_ACL: u64 = 1 << 20; // filesystem supports posix acls
+    pub const FUSE_ABORT_ERROR: u64 = 1 << 21; // reading the device after abort returns ECONNABORTED
+    pub const FUSE_MAX_PAGES: u64 = 1 << 22; // init_out.max_pages contains the max number of req pages
+    pub const FUSE_CACHE_SYMLINKS: u64 = 1 << 23; // cache READLINK responses
+    pub const FUSE_NO_OPENDIR_SUPPORT: u64 = 1 << 24; // kernel supports zero-message opendir
+    pub const FUSE_EXPLICIT_INVAL_DATA: u64 = 1 << 25; // only invalidate cached pages on explicit request
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_MAP_ALIGNMENT: u64 = 1 << 26; // init_out.map_alignment contains log2(byte alignment) for foffset and moffset fields in struct fuse_setupmapping_out and fuse_removemapping_one
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_SUBMOUNTS: u64 = 1 << 27; // kernel supports auto-mounting directory submounts
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_HANDLE_KILLPRIV_V2: u64 = 1 << 28; // fs kills suid/sgid/cap on write/chown/trunc, upon write/truncate suid/sgid is only killed if caller does not have CAP_FSETID, additionally upon write/truncate sgid is killed only if file has group execute permission
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_SETXATTR_EXT: u64 = 1 << 29; // server supports extended struct fuse_setxattr_in
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_INIT_EXT: u64 = 1 << 30; // extended fuse_init_in request
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_INIT_RESERVED: u64 = 1 << 31; // reserved, do not use
+
+    // The following flags are transferred in flags2 of fuse_init_in/fuse_init_out and require
+    // FUSE_INIT_EXT
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_SECURITY_CTX: u64 = 1 << 32; // add security context to create, mkdir, symlink, and mknod
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_HAS_INODE_DAX: u64 = 1 << 33; // use per inode DAX
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_CREATE_SUPP_GROUP: u64 = 1 << 34; // add supplementary group info to create, mkdir, symlink and mknod (single group that matches parent)
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_HAS_EXPIRE_ONLY: u64 = 1 << 35; // kernel supports expiry-only entry invalidation
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_DIRECT_IO_ALLOW_MMAP: u64 = 1 << 36; // allow shared mmap in FOPEN_DIRECT_IO mode
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_PASSTHROUGH: u64 = 1 << 37; // passthrough mode for read/write io
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_NO_EXPORT_SUPPORT: u64 = 1 << 38; // explicitly disable export support
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_HAS_RESEND: u64 = 1 << 39; // kernel supports resending pending requests
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_ALLOW_IDMAP: u64 = 1 << 40; // allow creation of idmapped mounts
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_OVER_IO_URING: u64 = 1 << 41; // indicate that client supports io-uring
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_REQUEST_TIMEOUT: u64 = 1 << 42; // kernel supports timing out requests
 
     #[cfg(target_os = "macos")]
-    pub const FUSE_ALLOCATE: u32 = 1 << 27;
+    pub const FUSE_ALLOCATE: u64 = 1 << 27;
     #[cfg(target_os = "macos")]
-    pub const FUSE_EXCHANGE_DATA: u32 = 1 << 28;
+    pub const FUSE_EXCHANGE_DATA: u64 = 1 << 28;
     #[cfg(target_os = "macos")]
-    pub const FUSE_CASE_INSENSITIVE: u32 = 1 << 29;
+    pub const FUSE_CASE_INSENSITIVE: u64 = 1 << 29;
     #[cfg(target_os = "macos")]
-    pub const FUSE_VOL_RENAME: u32 = 1 << 30;
+    pub const FUSE_VOL_RENAME: u64 = 1 << 30;
     #[cfg(target_os = "macos")]
-    pub const FUSE_XTIMES: u32 = 1 << 31;
+    pub const FUSE_XTIMES: u64 = 1 << 31;
 
     // CUSE init request/reply flags
-    #[cfg(feature = "abi-7-12")]
     pub const CUSE_UNRESTRICTED_IOCTL: u32 = 1 << 0; // use unrestricted ioctl
+    pub const CUSE_INIT_INFO_MAX: usize = 4096; // maximum size of the device info strings
 
     // Release flags
     pub const FUSE_RELEASE_FLUSH: u32 = 1 << 0;
-    #[cfg(feature = "abi-7-17")]
     pub const FUSE_RELEASE_FLOCK_UNLOCK: u32 = 1 << 1;
 
     // Getattr flags
-    #[cfg(feature = "abi-7-9")]
     pub const FUSE_GETATTR_FH: u32 = 1 << 0;
 
     // Lock flags
-    #[cfg(feature = "abi-7-9")]
     pub const FUSE_LK_FLOCK: u32 = 1 << 0;
 
     // Write flags
-    #[cfg(feature = "abi-7-9")]
     pub const FUSE_WRITE_CACHE: u32 = 1 << 0; // delayed write from page cache, file handle is guessed
-    #[cfg(feature = "abi-7-9")]
     pub const FUSE_WRITE_LOCKOWNER: u32 = 1 << 1; // lock_owner field is valid
-    #[cfg(feature = "abi-7-31")]
     pub const FUSE_WRITE_KILL_PRIV: u32 = 1 << 2; // kill suid and sgid bits
+    pub const FUSE_WRITE_KILL_SUIDGID: u32 = FUSE_WRITE_KILL_PRIV; // kill suid and sgid bits
+
+    // Open flags
+    pub const FUSE_OPEN_KILL_SUIDGID: u32 = 1 << 0; // kill suid and sgid if executable
+
+    // Setxattr flags
+    pub const FUSE_SETXATTR_ACL_KILL_SGID: u32 = 1 << 0; // clear SGID when system.posix_acl_access is set
+
+    // Attribute flags (fuse_attr.flags)
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_ATTR_SUBMOUNT: u32 = 1 << 0; // object is a submount root
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_ATTR_DAX: u32 = 1 << 1; // enable DAX for this file in per inode DAX mode
+
+    // Statx mask bits (fuse_statx.mask, see statx(2))
+    pub const STATX_BASIC_STATS: u32 = 0x7ff; // type, mode, nlink, uid, gid, times, ino, size, blocks
+    pub const STATX_BTIME: u32 = 1 << 11; // birth time
+
+    // Request extension types (fuse_ext_header.typ)
+    pub const FUSE_MAX_NR_SECCTX: u32 = 31; // types 0..31 are security contexts, the type is their number
+    pub const FUSE_EXT_GROUPS: u32 = 32; // supplementary groups
+
+    // uid and gid of requests on idmapped mounts that don't create an inode
+    #[cfg(not(target_os = "macos"))]
+    pub const FUSE_INVALID_UIDGID: u32 = u32::MAX;
 
     // Read flags
-    #[cfg(feature = "abi-7-9")]
     pub const FUSE_READ_LOCKOWNER: u32 = 1 << 1;
 
     // IOCTL flags
-    #[cfg(feature = "abi-7-11")]
     pub const FUSE_IOCTL_COMPAT: u32 = 1 << 0; // 32bit compat ioctl on 64bit machine
-    #[cfg(feature = "abi-7-11")]
     pub const FUSE_IOCTL_UNRESTRICTED: u32 = 1 << 1; // not restricted to well-formed ioctls, retry allowed
-    #[cfg(feature = "abi-7-11")]
     pub const FUSE_IOCTL_RETRY: u32 = 1 << 2; // retry with new iovecs
-    #[cfg(feature = "abi-7-16")]
     pub const FUSE_IOCTL_32BIT: u32 = 1 << 3; // 32bit ioctl
-    #[cfg(feature = "abi-7-18")]
     pub const FUSE_IOCTL_DIR: u32 = 1 << 4; // is a directory
-    #[cfg(feature = "abi-7-30")]
     pub const FUSE_IOCTL_COMPAT_X32: u32 = 1 << 5; // x32 compat ioctl on 64bit machine (64bit time_t)
-    #[cfg(feature = "abi-7-11")]
     pub const FUSE_IOCTL_MAX_IOV: u32 = 256; // maximum of in_iovecs + out_iovecs
 
     // Poll flags
-    #[cfg(feature = "abi-7-9")]
     pub const FUSE_POLL_SCHEDULE_NOTIFY: u32 = 1 << 0; // request poll notify
 
     // fsync flags
@@ -345,24 +360,18 @@ pub enum fuse_opcode {
     FUSE_INTERRUPT = 36,
     FUSE_BMAP = 37,
     FUSE_DESTROY = 38,
-    #[cfg(feature = "abi-7-11")]
     FUSE_IOCTL = 39,
-    #[cfg(feature = "abi-7-11")]
     FUSE_POLL = 40,
-    #[cfg(feature = "abi-7-15")]
     FUSE_NOTIFY_REPLY = 41,
-    #[cfg(feature = "abi-7-16")]
     FUSE_BATCH_FORGET = 42,
-    #[cfg(feature = "abi-7-19")]
     FUSE_FALLOCATE = 43,
-    #[cfg(feature = "abi-7-21")]
     FUSE_READDIRPLUS = 44,
-    #[cfg(feature = "abi-7-23")]
     FUSE_RENAME2 = 45,
-    #[cfg(feature = "abi-7-24")]
     FUSE_LSEEK = 46,
-    #[cfg(feature = "abi-7-28")]
     FUSE_COPY_FILE_RANGE = 47,
+    FUSE_SYNCFS = 50,
+    FUSE_TMPFILE = 51,
+    FUSE_STATX = 52,
 
     #[cfg(target_os = "macos")]
     FUSE_SETVOLNAME = 61,
@@ -371,7 +380,6 @@ pub enum fuse_opcode {
     #[cfg(target_os = "macos")]
     FUSE_EXCHANGE = 63,
 
-    #[cfg(feature = "abi-7-12")]
     CUSE_INIT = 4096,
 }
 
@@ -416,24 +424,18 @@ impl TryFrom<u32> for fuse_opcode {
             36 => Ok(fuse_opcode::FUSE_INTERRUPT),
             37 => Ok(fuse_opcode::FUSE_BMAP),
             38 => Ok(fuse_opcode::FUSE_DESTROY),
-            #[cfg(feature = "abi-7-11")]
             39 => Ok(fuse_opcode::FUSE_IOCTL),
-            #[cfg(feature = "abi-7-11")]
             40 => Ok(fuse_opcode::FUSE_POLL),
-            #[cfg(feature = "abi-7-15")]
             41 => Ok(fuse_opcode::FUSE_NOTIFY_REPLY),
-            #[cfg(feature = "abi-7-16")]
             42 => Ok(fuse_opcode::FUSE_BATCH_FORGET),
-            #[cfg(feature = "abi-7-19")]
             43 => Ok(fuse_opcode::FUSE_FALLOCATE),
-            #[cfg(feature = "abi-7-21")]
             44 => Ok(fuse_opcode::FUSE_READDIRPLUS),
-            #[cfg(feature = "abi-7-23")]
             45 => Ok(fuse_opcode::FUSE_RENAME2),
-            #[cfg(feature = "abi-7-24")]
             46 => Ok(fuse_opcode::FUSE_LSEEK),
-            #[cfg(feature = "abi-7-28")]
             47 => Ok(fuse_opcode::FUSE_COPY_FILE_RANGE),
+            50 => Ok(fuse_opcode::FUSE_SYNCFS),
+            51 => Ok(fuse_opcode::FUSE_TMPFILE),
+            52 => Ok(fuse_opcode::FUSE_STATX),
 
             #[cfg(target_os = "macos")]
             61 => Ok(fuse_opcode::FUSE_SETVOLNAME),
@@ -442,7 +444,6 @@ impl TryFrom<u32> for fuse_opcode {
             #[cfg(target_os = "macos")]
             63 => Ok(fuse_opcode::FUSE_EXCHANGE),
 
-            #[cfg(feature = "abi-7-12")]
             4096 => Ok(fuse_opcode::CUSE_INIT),
 
             _ => Err(InvalidOpcodeError),
@@ -451,46 +452,31 @@ impl TryFrom<u32> for fuse_opcode {
 }
 
 /// Invalid notify code error.
-#[cfg(feature = "abi-7-11")]
 #[derive(Debug)]
 pub struct InvalidNotifyCodeError;
 
-#[cfg(feature = "abi-7-11")]
 #[repr(C)]
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
 pub enum fuse_notify_code {
-    #[cfg(feature = "abi-7-11")]
     FUSE_POLL = 1,
-    #[cfg(feature = "abi-7-12")]
     FUSE_NOTIFY_INVAL_INODE = 2,
-    #[cfg(feature = "abi-7-12")]
     FUSE_NOTIFY_INVAL_ENTRY = 3,
-    #[cfg(feature = "abi-7-15")]
     FUSE_NOTIFY_STORE = 4,
-    #[cfg(feature = "abi-7-15")]
     FUSE_NOTIFY_RETRIEVE = 5,
-    #[cfg(feature = "abi-7-18")]
     FUSE_NOTIFY_DELETE = 6,
 }
 
-#[cfg(feature = "abi-7-11")]
 impl TryFrom<u32> for fuse_notify_code {
     type Error = InvalidNotifyCodeError;
 
     fn try_from(n: u32) -> Result<Self, Self::Error> {
         match n {
-            #[cfg(feature = "abi-7-11")]
             1 => Ok(fuse_notify_code::FUSE_POLL),
-            #[cfg(feature = "abi-7-12")]
             2 => Ok(fuse_notify_code::FUSE_NOTIFY_INVAL_INODE),
-            #[cfg(feature = "abi-7-12")]
             3 => Ok(fuse_notify_code::FUSE_NOTIFY_INVAL_ENTRY),
-            #[cfg(feature = "abi-7-15")]
             4 => Ok(fuse_notify_code::FUSE_NOTIFY_STORE),
-            #[cfg(feature = "abi-7-15")]
             5 => Ok(fuse_notify_code::FUSE_NOTIFY_RETRIEVE),
-            #[cfg(feature = "abi-7-18")]
             6 => Ok(fuse_notify_code::FUSE_NOTIFY_DELETE),
 
             _ => Err(InvalidNotifyCodeError),
@@ -499,7 +485,7 @@ impl TryFrom<u32> for fuse_notify_code {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_entry_out {
     pub nodeid: u64,
     pub generation: u64,
@@ -511,30 +497,27 @@ pub struct fuse_entry_out {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_forget_in {
     pub nlookup: u64,
 }
 
-#[cfg(feature = "abi-7-16")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_forget_one {
     pub nodeid: u64,
     pub nlookup: u64,
 }
 
-#[cfg(feature = "abi-7-16")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_batch_forget_in {
     pub count: u32,
     pub dummy: u32,
 }
 
-#[cfg(feature = "abi-7-9")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_getattr_in {
     pub getattr_flags: u32,
     pub dummy: u32,
@@ -542,7 +525,7 @@ pub struct fuse_getattr_in {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_attr_out {
     pub attr_valid: u64,
     pub attr_valid_nsec: u32,
@@ -550,9 +533,86 @@ pub struct fuse_attr_out {
     pub attr: fuse_attr,
 }
 
+/// Header of an extension appended to a request. With `FUSE_SECURITY_CTX`, this is the
+/// `fuse_secctx_header`, with the number of security contexts as type.
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_ext_header {
+    pub size: u32,
+    pub typ: u32,
+}
+
+/// Extension with the supplementary groups of the caller, followed by `nr_groups` gids
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_supp_groups {
+    pub nr_groups: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_secctx {
+    pub size: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_sx_time {
+    pub tv_sec: i64,
+    pub tv_nsec: u32,
+    pub __reserved: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_statx {
+    pub mask: u32,
+    pub blksize: u32,
+    pub attributes: u64,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u16,
+    pub __spare0: [u16; 1],
+    pub ino: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub attributes_mask: u64,
+    pub atime: fuse_sx_time,
+    pub btime: fuse_sx_time,
+    pub ctime: fuse_sx_time,
+    pub mtime: fuse_sx_time,
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    pub __spare2: [u64; 14],
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_statx_in {
+    pub getattr_flags: u32,
+    pub reserved: u32,
+    pub fh: u64,
+    pub sx_flags: u32,
+    pub sx_mask: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_statx_out {
+    pub attr_valid: u64,
+    pub attr_valid_nsec: u32,
+    pub flags: u32,
+    pub spare: [u64; 2],
+    pub stat: fuse_statx,
+}
+
 #[cfg(target_os = "macos")]
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_getxtimes_out {
     pub bkuptime: u64,
     pub crtime: u64,
@@ -561,34 +621,29 @@ pub struct fuse_getxtimes_out {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_mknod_in {
     pub mode: u32,
     pub rdev: u32,
-    #[cfg(feature = "abi-7-12")]
     pub umask: u32,
-    #[cfg(feature = "abi-7-12")]
     pub padding: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_mkdir_in {
     pub mode: u32,
-    #[cfg(not(feature = "abi-7-12"))]
-    pub padding: u32,
-    #[cfg(feature = "abi-7-12")]
     pub umask: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_rename_in {
     pub newdir: u64,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_rename2_in {
     pub newdir: u64,
     pub flags: u32,
@@ -597,7 +652,7 @@ pub struct fuse_rename2_in {
 
 #[cfg(target_os = "macos")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_exchange_in {
     pub olddir: u64,
     pub newdir: u64,
@@ -605,21 +660,18 @@ pub struct fuse_exchange_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_link_in {
     pub oldnodeid: u64,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_setattr_in {
     pub valid: u32,
     pub padding: u32,
     pub fh: u64,
     pub size: u64,
-    #[cfg(not(feature = "abi-7-9"))]
-    pub unused1: u64,
-    #[cfg(feature = "abi-7-9")]
     pub lock_owner: u64,
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is treated as signed
     // to match stat.st_atime
@@ -627,17 +679,11 @@ pub struct fuse_setattr_in {
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is treated as signed
     // to match stat.st_mtime
     pub mtime: i64,
-    #[cfg(not(feature = "abi-7-23"))]
-    pub unused2: u64,
-    #[cfg(feature = "abi-7-23")]
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is treated as signed
     // to match stat.st_ctime
     pub ctime: i64,
     pub atimensec: u32,
     pub mtimensec: u32,
-    #[cfg(not(feature = "abi-7-23"))]
-    pub unused3: u32,
-    #[cfg(feature = "abi-7-23")]
     pub ctimensec: u32,
     pub mode: u32,
     pub unused4: u32,
@@ -661,63 +707,89 @@ pub struct fuse_setattr_in {
 }
 
 impl fuse_setattr_in {
-    #[cfg(feature = "abi-7-9")]
     pub fn atime_now(&self) -> bool {
         self.valid & FATTR_ATIME_NOW != 0
     }
 
-    #[cfg(not(feature = "abi-7-9"))]
-    pub fn atime_now(&self) -> bool {
-        false
-    }
-
-    #[cfg(feature = "abi-7-9")]
     pub fn mtime_now(&self) -> bool {
         self.valid & FATTR_MTIME_NOW != 0
     }
-
-    #[cfg(not(feature = "abi-7-9"))]
-    pub fn mtime_now(&self) -> bool {
-        false
-    }
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_open_in {
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
     // to an i32 when invoking the filesystem's open method and this matches the open() syscall
     pub flags: i32,
-    pub unused: u32,
+    pub open_flags: u32, // FUSE_OPEN_...
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_create_in {
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
     // to an i32 when invoking the filesystem's create method and this matches the open() syscall
     pub flags: i32,
     pub mode: u32,
-    #[cfg(feature = "abi-7-12")]
     pub umask: u32,
-    #[cfg(feature = "abi-7-12")]
-    pub padding: u32,
+    pub open_flags: u32, // FUSE_OPEN_...
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_create_out(pub fuse_entry_out, pub fuse_open_out);
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_open_out {
     pub fh: u64,
     pub open_flags: u32,
+    pub backing_id: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_backing_map {
+    pub fd: i32,
+    pub flags: u32,
+    pub padding: u64,
+}
+
+/// Size of the payload of a ring entry and id to commit its reply with
+#[repr(C)]
+#[derive(Debug, Clone, Copy, AsBytes, FromBytes)]
+pub struct fuse_uring_ent_in_out {
+    pub flags: u64,
+    pub commit_id: u64,
+    pub payload_sz: u32,
     pub padding: u32,
+    pub reserved: u64,
+}
+
+/// Header buffer of a ring entry. The kernel puts the `fuse_in_header` into `in_out` and the
+/// fixed size argument of the operation into `op_in`; a reply puts its `fuse_out_header` into
+/// `in_out`. Any further arguments go through the separate payload buffer.
+#[repr(C)]
+#[derive(Debug)]
+pub struct fuse_uring_req_header {
+    pub in_out: [u8; FUSE_URING_IN_OUT_HEADER_SZ],
+    pub op_in: [u8; FUSE_URING_OP_IN_OUT_SZ],
+    pub ring_ent_in_out: fuse_uring_ent_in_out,
 }
 
+/// Command data in the second half of a 128 byte io_uring submission queue entry
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
+pub struct fuse_uring_cmd_req {
+    pub flags: u64,
+    pub commit_id: u64,
+    pub qid: u16,
+    pub padding: [u8; 6],
+}
+
+#[repr(C)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_release_in {
     pub fh: u64,
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
@@ -728,7 +800,7 @@ pub struct fuse_release_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_flush_in {
     pub fh: u64,
     pub unused: u32,
@@ -737,27 +809,23 @@ pub struct fuse_flush_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_read_in {
     pub fh: u64,
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is then cast
     // to an i64 when invoking the filesystem's read method
     pub offset: i64,
     pub size: u32,
-    #[cfg(feature = "abi-7-9")]
     pub read_flags: u32,
-    #[cfg(feature = "abi-7-9")]
     pub lock_owner: u64,
-    #[cfg(feature = "abi-7-9")]
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
     // to an i32 when invoking the filesystem's read method
     pub flags: i32,
-    #[cfg(feature = "abi-7-9")]
     pub padding: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_write_in {
     pub fh: u64,
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is then cast
@@ -765,31 +833,28 @@ pub struct fuse_write_in {
     pub offset: i64,
     pub size: u32,
     pub write_flags: u32,
-    #[cfg(feature = "abi-7-9")]
     pub lock_owner: u64,
-    #[cfg(feature = "abi-7-9")]
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
     // to an i32 when invoking the filesystem's read method
     pub flags: i32,
-    #[cfg(feature = "abi-7-9")]
     pub padding: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_write_out {
     pub size: u32,
     pub padding: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_statfs_out {
     pub st: fuse_kstatfs,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_fsync_in {
     pub fh: u64,
     pub fsync_flags: u32,
@@ -797,7 +862,7 @@ pub struct fuse_fsync_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_setxattr_in {
     pub size: u32,
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
@@ -810,7 +875,7 @@ pub struct fuse_setxattr_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_getxattr_in {
     pub size: u32,
     pub padding: u32,
@@ -821,32 +886,30 @@ pub struct fuse_getxattr_in {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_getxattr_out {
     pub size: u32,
     pub padding: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_lk_in {
     pub fh: u64,
     pub owner: u64,
     pub lk: fuse_file_lock,
-    #[cfg(feature = "abi-7-9")]
     pub lk_flags: u32,
-    #[cfg(feature = "abi-7-9")]
     pub padding: u32,
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_lk_out {
     pub lk: fuse_file_lock,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_access_in {
     // NOTE: this field is defined as u32 in fuse_kernel.h in libfuse. However, it is then cast
     // to an i32 when invoking the filesystem's access method
@@ -855,43 +918,37 @@ pub struct fuse_access_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, Clone, AsBytes, FromBytes)]
 pub struct fuse_init_in {
     pub major: u32,
     pub minor: u32,
     pub max_readahead: u32,
     pub flags: u32,
+    pub flags2: u32,
+    pub unused: [u32; 11],
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_init_out {
     pub major: u32,
     pub minor: u32,
     pub max_readahead: u32,
     pub flags: u32,
-    #[cfg(not(feature = "abi-7-13"))]
-    pub unused: u32,
-    #[cfg(feature = "abi-7-13")]
     pub max_background: u16,
-    #[cfg(feature = "abi-7-13")]
     pub congestion_threshold: u16,
     pub max_write: u32,
-    #[cfg(feature = "abi-7-23")]
     pub time_gran: u32,
-    #[cfg(all(feature = "abi-7-23", not(feature = "abi-7-28")))]
-    pub reserved: [u32; 9],
-    #[cfg(feature = "abi-7-28")]
     pub max_pages: u16,
-    #[cfg(feature = "abi-7-28")]
-    pub unused2: u16,
-    #[cfg(feature = "abi-7-28")]
-    pub reserved: [u32; 8],
+    pub map_alignment: u16,
+    pub flags2: u32,
+    pub max_stack_depth: u32,
+    pub request_timeout: u16,
+    pub unused: [u16; 11],
 }
 
-#[cfg(feature = "abi-7-12")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct cuse_init_in {
     pub major: u32,
     pub minor: u32,
@@ -899,9 +956,8 @@ pub struct cuse_init_in {
     pub flags: u32,
 }
 
-#[cfg(feature = "abi-7-12")]
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct cuse_init_out {
     pub major: u32,
     pub minor: u32,
@@ -915,13 +971,13 @@ pub struct cuse_init_out {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_interrupt_in {
     pub unique: u64,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_bmap_in {
     pub block: u64,
     pub blocksize: u32,
@@ -929,33 +985,31 @@ pub struct fuse_bmap_in {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_bmap_out {
     pub block: u64,
 }
 
-#[cfg(feature = "abi-7-11")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_ioctl_in {
     pub fh: u64,
     pub flags: u32,
     pub cmd: u32,
-    pub arg: u64, // TODO: this is currently unused, but is defined as a void* in libfuse
+    pub arg: u64, // user space address of the ioctl argument (void* in libfuse)
     pub in_size: u32,
     pub out_size: u32,
 }
 
-#[cfg(feature = "abi-7-16")]
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_ioctl_iovec {
     pub base: u64,
     pub len: u64,
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_ioctl_out {
     pub result: i32,
     pub flags: u32,
@@ -963,37 +1017,30 @@ pub struct fuse_ioctl_out {
     pub out_iovs: u32,
 }
 
-#[cfg(feature = "abi-7-11")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_poll_in {
     pub fh: u64,
     pub kh: u64,
     pub flags: u32,
-    #[cfg(not(feature = "abi-7-21"))]
-    pub padding: u32,
-    #[cfg(feature = "abi-7-21")]
     pub events: u32,
 }
 
-#[cfg(feature = "abi-7-11")]
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_poll_out {
     pub revents: u32,
     pub padding: u32,
 }
 
-#[cfg(feature = "abi-7-11")]
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_notify_poll_wakeup_out {
     pub kh: u64,
 }
 
-#[cfg(feature = "abi-7-19")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_fallocate_in {
     pub fh: u64,
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is treated as signed
@@ -1006,7 +1053,7 @@ pub struct fuse_fallocate_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_in_header {
     pub len: u32,
     pub opcode: u32,
@@ -1019,7 +1066,7 @@ pub struct fuse_in_header {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_out_header {
     pub len: u32,
     pub error: i32,
@@ -1027,7 +1074,7 @@ pub struct fuse_out_header {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_dirent {
     pub ino: u64,
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is treated as signed
@@ -1038,13 +1085,12 @@ pub struct fuse_dirent {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_direntplus {
     pub entry_out: fuse_entry_out,
     pub dirent: fuse_dirent,
 }
 
-#[cfg(feature = "abi-7-12")]
 #[repr(C)]
 #[derive(Debug)]
 pub struct fuse_notify_inval_inode_out {
@@ -1053,7 +1099,6 @@ pub struct fuse_notify_inval_inode_out {
     pub len: i64,
 }
 
-#[cfg(feature = "abi-7-12")]
 #[repr(C)]
 #[derive(Debug)]
 pub struct fuse_notify_inval_entry_out {
@@ -1062,7 +1107,6 @@ pub struct fuse_notify_inval_entry_out {
     pub padding: u32,
 }
 
-#[cfg(feature = "abi-7-18")]
 #[repr(C)]
 #[derive(Debug)]
 pub struct fuse_notify_delete_out {
@@ -1072,7 +1116,6 @@ pub struct fuse_notify_delete_out {
     padding: u32,
 }
 
-#[cfg(feature = "abi-7-15")]
 #[repr(C)]
 #[derive(Debug)]
 pub struct fuse_notify_store_out {
@@ -1082,7 +1125,6 @@ pub struct fuse_notify_store_out {
     pub padding: u32,
 }
 
-#[cfg(feature = "abi-7-15")]
 #[repr(C)]
 #[derive(Debug)]
 pub struct fuse_notify_retrieve_out {
@@ -1093,9 +1135,8 @@ pub struct fuse_notify_retrieve_out {
     pub padding: u32,
 }
 
-#[cfg(feature = "abi-7-15")]
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_notify_retrieve_in {
     // matches the size of fuse_write_in
     pub dummy1: u64,
@@ -1107,7 +1148,7 @@ pub struct fuse_notify_retrieve_in {
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_lseek_in {
     pub fh: u64,
     pub offset: i64,
@@ -1117,13 +1158,13 @@ pub struct fuse_lseek_in {
 }
 
 #[repr(C)]
-#[derive(Debug, AsBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_lseek_out {
     pub offset: i64,
 }
 
 #[repr(C)]
-#[derive(Debug, FromBytes)]
+#[derive(Debug, AsBytes, FromBytes)]
 pub struct fuse_copy_file_range_in {
     pub fh_in: u64,
     // NOTE: this field is defined as u64 in fuse_kernel.h in libfuse. However, it is treated as signed
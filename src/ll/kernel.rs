@@ -0,0 +1,1354 @@
+//! The kernel side of the protocol: encoding requests and decoding their replies.
+//!
+//! This is the inverse of [`AnyRequest`](super::AnyRequest) and [`Response`](super::Response).
+//! It lets tests, proxies and tools act as the kernel driver towards a FUSE server, e.g. one
+//! reached over a socket.
+
+use std::convert::{TryFrom, TryInto};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::{error, fmt, io, iter, mem};
+
+use zerocopy::{AsBytes, FromBytes};
+
+use super::argument::ArgumentIterator;
+use super::fuse_abi::consts::*;
+use super::fuse_abi::*;
+use super::reply::entry_out_size;
+use super::{
+    Errno, FileHandle, INodeNo, Lock, LockOwner, OpenFlags, Protocol, RequestId, WriteFlags,
+};
+
+/// A request encoded like the kernel driver sends it
+#[derive(Debug, Clone)]
+pub struct EncodedRequest {
+    unique: RequestId,
+    // Words of 8 bytes keep the request aligned like the buffers the kernel driver reads into
+    buf: Vec<u64>,
+    len: usize,
+}
+
+impl EncodedRequest {
+    /// The unique id of the request, which its reply carries
+    pub fn unique(&self) -> RequestId {
+        self.unique
+    }
+
+    /// The request starting with its `fuse_in_header`
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.as_slice().as_bytes()[..self.len]
+    }
+}
+
+impl AsRef<[u8]> for EncodedRequest {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// Encodes requests with the layout of the negotiated [`Protocol`], as the kernel driver does.
+///
+/// Every request gets the next unique id, counting in steps of `FUSE_REQ_ID_STEP` like the
+/// kernel, and the credentials set with
+/// [`RequestEncoder::with_credentials`], which are those of root until then. Extensions like
+/// security contexts are not appended to requests creating an inode.
+#[derive(Debug, Clone)]
+pub struct RequestEncoder {
+    protocol: Protocol,
+    next_unique: u64,
+    uid: u32,
+    gid: u32,
+    pid: u32,
+}
+
+impl Default for RequestEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The name as a zero terminated string
+fn c_name(name: &OsStr) -> Vec<u8> {
+    let name = name.as_bytes();
+    assert!(!name.contains(&0), "Name contains a zero byte");
+    let mut v = Vec::with_capacity(name.len() + 1);
+    v.extend_from_slice(name);
+    v.push(0);
+    v
+}
+
+impl RequestEncoder {
+    /// An encoder for the latest ABI, which is what a kernel sends before `FUSE_INIT`
+    /// negotiated the protocol
+    pub fn new() -> Self {
+        Self {
+            protocol: Protocol::default(),
+            next_unique: FUSE_REQ_ID_STEP,
+            uid: 0,
+            gid: 0,
+            pid: 0,
+        }
+    }
+
+    /// Send requests on behalf of the given user, group and process
+    pub fn with_credentials(mut self, uid: u32, gid: u32, pid: u32) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self.pid = pid;
+        self
+    }
+
+    /// Encode requests with the layout of the given protocol, usually the one negotiated by
+    /// `FUSE_INIT`
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+    }
+
+    /// The protocol requests are encoded with
+    pub fn protocol(&self) -> &Protocol {
+        &self.protocol
+    }
+
+    /// Encode a request with the next unique id
+    fn encode(&mut self, opcode: fuse_opcode, nodeid: u64, args: &[&[u8]]) -> EncodedRequest {
+        let unique = self.next_unique;
+        self.next_unique += FUSE_REQ_ID_STEP;
+        self.encode_as(RequestId(unique), opcode, nodeid, args)
+    }
+
+    fn encode_as(
+        &self,
+        unique: RequestId,
+        opcode: fuse_opcode,
+        nodeid: u64,
+        args: &[&[u8]],
+    ) -> EncodedRequest {
+        let len = mem::size_of::<fuse_in_header>() + args.iter().map(|x| x.len()).sum::<usize>();
+        let header = fuse_in_header {
+            len: len.try_into().expect("Too much data"),
+            opcode: opcode as u32,
+            unique: unique.into(),
+            nodeid,
+            uid: self.uid,
+            gid: self.gid,
+            pid: self.pid,
+            padding: 0,
+        };
+        let size = (len + mem::size_of::<u64>() - 1) & !(mem::size_of::<u64>() - 1); // 64bit align
+        let mut buf = vec![0u64; size / mem::size_of::<u64>()];
+        let bytes = buf.as_mut_slice().as_bytes_mut();
+        let mut pos = 0;
+        for arg in iter::once(header.as_bytes()).chain(args.iter().copied()) {
+            bytes[pos..pos + arg.len()].copy_from_slice(arg);
+            pos += arg.len();
+        }
+        EncodedRequest { unique, buf, len }
+    }
+
+    /// The argument struct, shortened to `compat_size` if the protocol predates the minor
+    /// version `since` that extended it
+    fn truncated<'a, T: AsBytes>(&self, arg: &'a T, since: u32, compat_size: usize) -> &'a [u8] {
+        if self.protocol.minor < since {
+            &arg.as_bytes()[..compat_size]
+        } else {
+            arg.as_bytes()
+        }
+    }
+
+    /// Initialize the session, offering the given capabilities. The upper 32 bits are only
+    /// sent if the protocol is at least 7.36 and `FUSE_INIT_EXT` is set.
+    pub fn init(&mut self, max_readahead: u32, capabilities: u64) -> EncodedRequest {
+        let arg = fuse_init_in {
+            major: FUSE_KERNEL_VERSION,
+            minor: self.protocol.minor,
+            max_readahead,
+            flags: capabilities as u32,
+            flags2: (capabilities >> 32) as u32,
+            unused: [0; 11],
+        };
+        let arg = self.truncated(&arg, 36, FUSE_COMPAT_INIT_IN_SIZE);
+        self.encode(fuse_opcode::FUSE_INIT, 0, &[arg])
+    }
+
+    /// Look up a directory entry by name
+    pub fn lookup(&mut self, parent: INodeNo, name: &OsStr) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_LOOKUP, parent.into(), &[&c_name(name)])
+    }
+
+    /// Forget `nlookup` lookups of an inode
+    pub fn forget(&mut self, ino: INodeNo, nlookup: u64) -> EncodedRequest {
+        let arg = fuse_forget_in { nlookup };
+        self.encode(fuse_opcode::FUSE_FORGET, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Forget lookups of several inodes
+    pub fn batch_forget(&mut self, nodes: &[fuse_forget_one]) -> EncodedRequest {
+        let arg = fuse_batch_forget_in {
+            count: nodes.len().try_into().expect("Too many nodes"),
+            dummy: 0,
+        };
+        self.encode(
+            fuse_opcode::FUSE_BATCH_FORGET,
+            0,
+            &[arg.as_bytes(), nodes.as_bytes()],
+        )
+    }
+
+    /// Get the attributes of an inode, of the open file if a handle is given
+    pub fn getattr(&mut self, ino: INodeNo, fh: Option<FileHandle>) -> EncodedRequest {
+        let arg = fuse_getattr_in {
+            getattr_flags: fh.map_or(0, |_| FUSE_GETATTR_FH),
+            dummy: 0,
+            fh: fh.map_or(0, u64::from),
+        };
+        if self.protocol.minor < 9 {
+            return self.encode(fuse_opcode::FUSE_GETATTR, ino.into(), &[]);
+        }
+        self.encode(fuse_opcode::FUSE_GETATTR, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Set the attributes flagged with `FATTR_*` in `arg.valid`
+    pub fn setattr(&mut self, ino: INodeNo, arg: &fuse_setattr_in) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_SETATTR, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Read the target of a symbolic link
+    pub fn readlink(&mut self, ino: INodeNo) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_READLINK, ino.into(), &[])
+    }
+
+    /// Create a symbolic link named `name` pointing to `link`
+    pub fn symlink(&mut self, parent: INodeNo, name: &OsStr, link: &OsStr) -> EncodedRequest {
+        self.encode(
+            fuse_opcode::FUSE_SYMLINK,
+            parent.into(),
+            &[&c_name(name), &c_name(link)],
+        )
+    }
+
+    /// Create a file node
+    pub fn mknod(
+        &mut self,
+        parent: INodeNo,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        rdev: u32,
+    ) -> EncodedRequest {
+        let arg = fuse_mknod_in {
+            mode,
+            rdev,
+            umask,
+            padding: 0,
+        };
+        let arg = self.truncated(&arg, 12, FUSE_COMPAT_MKNOD_IN_SIZE);
+        self.encode(
+            fuse_opcode::FUSE_MKNOD,
+            parent.into(),
+            &[arg, &c_name(name)],
+        )
+    }
+
+    /// Create a directory
+    pub fn mkdir(
+        &mut self,
+        parent: INodeNo,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+    ) -> EncodedRequest {
+        let arg = fuse_mkdir_in { mode, umask };
+        self.encode(
+            fuse_opcode::FUSE_MKDIR,
+            parent.into(),
+            &[arg.as_bytes(), &c_name(name)],
+        )
+    }
+
+    /// Remove a file
+    pub fn unlink(&mut self, parent: INodeNo, name: &OsStr) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_UNLINK, parent.into(), &[&c_name(name)])
+    }
+
+    /// Remove a directory
+    pub fn rmdir(&mut self, parent: INodeNo, name: &OsStr) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_RMDIR, parent.into(), &[&c_name(name)])
+    }
+
+    /// Rename a directory entry
+    pub fn rename(
+        &mut self,
+        parent: INodeNo,
+        name: &OsStr,
+        newparent: INodeNo,
+        newname: &OsStr,
+    ) -> EncodedRequest {
+        let arg = fuse_rename_in {
+            newdir: newparent.into(),
+        };
+        self.encode(
+            fuse_opcode::FUSE_RENAME,
+            parent.into(),
+            &[arg.as_bytes(), &c_name(name), &c_name(newname)],
+        )
+    }
+
+    /// Rename a directory entry with `RENAME_*` flags
+    pub fn rename2(
+        &mut self,
+        parent: INodeNo,
+        name: &OsStr,
+        newparent: INodeNo,
+        newname: &OsStr,
+        flags: u32,
+    ) -> EncodedRequest {
+        let arg = fuse_rename2_in {
+            newdir: newparent.into(),
+            flags,
+            padding: 0,
+        };
+        self.encode(
+            fuse_opcode::FUSE_RENAME2,
+            parent.into(),
+            &[arg.as_bytes(), &c_name(name), &c_name(newname)],
+        )
+    }
+
+    /// Create a hard link to `ino`
+    pub fn link(&mut self, ino: INodeNo, newparent: INodeNo, newname: &OsStr) -> EncodedRequest {
+        let arg = fuse_link_in {
+            oldnodeid: ino.into(),
+        };
+        self.encode(
+            fuse_opcode::FUSE_LINK,
+            newparent.into(),
+            &[arg.as_bytes(), &c_name(newname)],
+        )
+    }
+
+    /// Open a file, with `open_flags` such as `FUSE_OPEN_KILL_SUIDGID`
+    pub fn open(&mut self, ino: INodeNo, flags: OpenFlags, open_flags: u32) -> EncodedRequest {
+        let arg = fuse_open_in {
+            flags: flags.into(),
+            open_flags,
+        };
+        self.encode(fuse_opcode::FUSE_OPEN, ino.into(), &[arg.as_bytes()])
+    }
+
+    fn read_in(
+        &self,
+        fh: FileHandle,
+        offset: i64,
+        size: u32,
+        flags: OpenFlags,
+        lock_owner: Option<LockOwner>,
+    ) -> fuse_read_in {
+        fuse_read_in {
+            fh: fh.into(),
+            offset,
+            size,
+            read_flags: lock_owner.map_or(0, |_| FUSE_READ_LOCKOWNER),
+            lock_owner: lock_owner.map_or(0, u64::from),
+            flags: flags.into(),
+            padding: 0,
+        }
+    }
+
+    /// Read `size` bytes at `offset` of an open file
+    pub fn read(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: i64,
+        size: u32,
+        flags: OpenFlags,
+        lock_owner: Option<LockOwner>,
+    ) -> EncodedRequest {
+        let arg = self.read_in(fh, offset, size, flags, lock_owner);
+        let arg = self.truncated(&arg, 9, FUSE_COMPAT_READ_IN_SIZE);
+        self.encode(fuse_opcode::FUSE_READ, ino.into(), &[arg])
+    }
+
+    /// Write `data` at `offset` of an open file
+    #[allow(clippy::too_many_arguments)]
+    pub fn write(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: i64,
+        data: &[u8],
+        write_flags: WriteFlags,
+        flags: OpenFlags,
+        lock_owner: Option<LockOwner>,
+    ) -> EncodedRequest {
+        let arg = fuse_write_in {
+            fh: fh.into(),
+            offset,
+            size: data.len().try_into().expect("Too much data"),
+            write_flags: u32::from(write_flags) | lock_owner.map_or(0, |_| FUSE_WRITE_LOCKOWNER),
+            lock_owner: lock_owner.map_or(0, u64::from),
+            flags: flags.into(),
+            padding: 0,
+        };
+        let arg = self.truncated(&arg, 9, FUSE_COMPAT_WRITE_IN_SIZE);
+        self.encode(fuse_opcode::FUSE_WRITE, ino.into(), &[arg, data])
+    }
+
+    /// Get file system statistics
+    pub fn statfs(&mut self, ino: INodeNo) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_STATFS, ino.into(), &[])
+    }
+
+    fn release_in(
+        fh: FileHandle,
+        flags: OpenFlags,
+        flush: bool,
+        lock_owner: Option<LockOwner>,
+    ) -> fuse_release_in {
+        let mut release_flags = 0;
+        if flush {
+            release_flags |= FUSE_RELEASE_FLUSH;
+        }
+        if lock_owner.is_some() {
+            release_flags |= FUSE_RELEASE_FLOCK_UNLOCK;
+        }
+        fuse_release_in {
+            fh: fh.into(),
+            flags: flags.into(),
+            release_flags,
+            lock_owner: lock_owner.map_or(0, u64::from),
+        }
+    }
+
+    /// Release an open file. A lock owner is given to release its flock locks.
+    pub fn release(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        flags: OpenFlags,
+        flush: bool,
+        lock_owner: Option<LockOwner>,
+    ) -> EncodedRequest {
+        let arg = Self::release_in(fh, flags, flush, lock_owner);
+        self.encode(fuse_opcode::FUSE_RELEASE, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Synchronize the contents of an open file, only its data if `datasync` is set
+    pub fn fsync(&mut self, ino: INodeNo, fh: FileHandle, datasync: bool) -> EncodedRequest {
+        let arg = fuse_fsync_in {
+            fh: fh.into(),
+            fsync_flags: if datasync { FUSE_FSYNC_FDATASYNC } else { 0 },
+            padding: 0,
+        };
+        self.encode(fuse_opcode::FUSE_FSYNC, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Set an extended attribute. The `FUSE_SETXATTR_*` flags are only sent if
+    /// `FUSE_SETXATTR_EXT` was negotiated.
+    pub fn setxattr(
+        &mut self,
+        ino: INodeNo,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+        setxattr_flags: u32,
+    ) -> EncodedRequest {
+        let mut arg = fuse_setxattr_in::new_zeroed();
+        arg.size = value.len().try_into().expect("Too much data");
+        arg.flags = flags;
+        #[cfg(not(target_os = "macos"))]
+        let ext = if self.protocol.flags & FUSE_SETXATTR_EXT != 0 {
+            // The extended fuse_setxattr_in appends setxattr_flags and padding
+            [setxattr_flags, 0].as_bytes().to_vec()
+        } else {
+            vec![]
+        };
+        #[cfg(target_os = "macos")]
+        let ext = {
+            let _ = setxattr_flags;
+            vec![]
+        };
+        self.encode(
+            fuse_opcode::FUSE_SETXATTR,
+            ino.into(),
+            &[arg.as_bytes(), &ext, &c_name(name), value],
+        )
+    }
+
+    /// Get an extended attribute, or the size of its value if `size` is 0
+    pub fn getxattr(&mut self, ino: INodeNo, name: &OsStr, size: u32) -> EncodedRequest {
+        let mut arg = fuse_getxattr_in::new_zeroed();
+        arg.size = size;
+        self.encode(
+            fuse_opcode::FUSE_GETXATTR,
+            ino.into(),
+            &[arg.as_bytes(), &c_name(name)],
+        )
+    }
+
+    /// List extended attribute names, or get the size of the list if `size` is 0
+    pub fn listxattr(&mut self, ino: INodeNo, size: u32) -> EncodedRequest {
+        let mut arg = fuse_getxattr_in::new_zeroed();
+        arg.size = size;
+        self.encode(fuse_opcode::FUSE_LISTXATTR, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Remove an extended attribute
+    pub fn removexattr(&mut self, ino: INodeNo, name: &OsStr) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_REMOVEXATTR, ino.into(), &[&c_name(name)])
+    }
+
+    /// Flush an open file, on every close of one of its file descriptors
+    pub fn flush(&mut self, ino: INodeNo, fh: FileHandle, lock_owner: LockOwner) -> EncodedRequest {
+        let arg = fuse_flush_in {
+            fh: fh.into(),
+            unused: 0,
+            padding: 0,
+            lock_owner: lock_owner.into(),
+        };
+        self.encode(fuse_opcode::FUSE_FLUSH, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Open a directory
+    pub fn opendir(&mut self, ino: INodeNo, flags: OpenFlags) -> EncodedRequest {
+        let arg = fuse_open_in {
+            flags: flags.into(),
+            open_flags: 0,
+        };
+        self.encode(fuse_opcode::FUSE_OPENDIR, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Read up to `size` bytes of directory entries, starting after the entry at `offset`
+    pub fn readdir(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: i64,
+        size: u32,
+    ) -> EncodedRequest {
+        let arg = self.read_in(fh, offset, size, OpenFlags(0), None);
+        let arg = self.truncated(&arg, 9, FUSE_COMPAT_READ_IN_SIZE);
+        self.encode(fuse_opcode::FUSE_READDIR, ino.into(), &[arg])
+    }
+
+    /// Read up to `size` bytes of directory entries with their attributes, starting after the
+    /// entry at `offset`
+    pub fn readdirplus(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: i64,
+        size: u32,
+    ) -> EncodedRequest {
+        let arg = self.read_in(fh, offset, size, OpenFlags(0), None);
+        self.encode(fuse_opcode::FUSE_READDIRPLUS, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Release an open directory
+    pub fn releasedir(&mut self, ino: INodeNo, fh: FileHandle, flags: OpenFlags) -> EncodedRequest {
+        let arg = Self::release_in(fh, flags, false, None);
+        self.encode(fuse_opcode::FUSE_RELEASEDIR, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Synchronize the contents of an open directory, only its data if `datasync` is set
+    pub fn fsyncdir(&mut self, ino: INodeNo, fh: FileHandle, datasync: bool) -> EncodedRequest {
+        let arg = fuse_fsync_in {
+            fh: fh.into(),
+            fsync_flags: if datasync { FUSE_FSYNC_FDATASYNC } else { 0 },
+            padding: 0,
+        };
+        self.encode(fuse_opcode::FUSE_FSYNCDIR, ino.into(), &[arg.as_bytes()])
+    }
+
+    fn lock(
+        &mut self,
+        opcode: fuse_opcode,
+        ino: INodeNo,
+        fh: FileHandle,
+        owner: LockOwner,
+        lock: &Lock,
+    ) -> EncodedRequest {
+        let arg = fuse_lk_in {
+            fh: fh.into(),
+            owner: owner.into(),
+            lk: fuse_file_lock {
+                start: lock.range.0,
+                end: lock.range.1,
+                typ: lock.typ,
+                pid: lock.pid,
+            },
+            lk_flags: 0,
+            padding: 0,
+        };
+        let arg = self.truncated(&arg, 9, FUSE_COMPAT_LK_IN_SIZE);
+        self.encode(opcode, ino.into(), &[arg])
+    }
+
+    /// Test for a POSIX file lock conflicting with `lock`
+    pub fn getlk(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        owner: LockOwner,
+        lock: &Lock,
+    ) -> EncodedRequest {
+        self.lock(fuse_opcode::FUSE_GETLK, ino, fh, owner, lock)
+    }
+
+    /// Acquire or release a POSIX file lock
+    pub fn setlk(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        owner: LockOwner,
+        lock: &Lock,
+    ) -> EncodedRequest {
+        self.lock(fuse_opcode::FUSE_SETLK, ino, fh, owner, lock)
+    }
+
+    /// Acquire a POSIX file lock, waiting until it is available
+    pub fn setlkw(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        owner: LockOwner,
+        lock: &Lock,
+    ) -> EncodedRequest {
+        self.lock(fuse_opcode::FUSE_SETLKW, ino, fh, owner, lock)
+    }
+
+    /// Check file access permissions
+    pub fn access(&mut self, ino: INodeNo, mask: i32) -> EncodedRequest {
+        let arg = fuse_access_in { mask, padding: 0 };
+        self.encode(fuse_opcode::FUSE_ACCESS, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Create and open a file, with `open_flags` such as `FUSE_OPEN_KILL_SUIDGID`
+    pub fn create(
+        &mut self,
+        parent: INodeNo,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: OpenFlags,
+        open_flags: u32,
+    ) -> EncodedRequest {
+        let arg = fuse_create_in {
+            flags: flags.into(),
+            mode,
+            umask,
+            open_flags,
+        };
+        let arg = self.truncated(&arg, 12, FUSE_COMPAT_CREATE_IN_SIZE);
+        self.encode(
+            fuse_opcode::FUSE_CREATE,
+            parent.into(),
+            &[arg, &c_name(name)],
+        )
+    }
+
+    /// Interrupt the request with the given unique id. Like the kernel, the interrupt gets the
+    /// id of the request with `FUSE_INT_REQ_BIT` set, which the reply of a server carries.
+    pub fn interrupt(&mut self, unique: RequestId) -> EncodedRequest {
+        let arg = fuse_interrupt_in {
+            unique: unique.into(),
+        };
+        self.encode_as(
+            RequestId(arg.unique | FUSE_INT_REQ_BIT),
+            fuse_opcode::FUSE_INTERRUPT,
+            0,
+            &[arg.as_bytes()],
+        )
+    }
+
+    /// Map a block index within a file to a block index within the device
+    pub fn bmap(&mut self, ino: INodeNo, blocksize: u32, block: u64) -> EncodedRequest {
+        let arg = fuse_bmap_in {
+            block,
+            blocksize,
+            padding: 0,
+        };
+        self.encode(fuse_opcode::FUSE_BMAP, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Clean up the session before unmounting
+    pub fn destroy(&mut self) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_DESTROY, 0, &[])
+    }
+
+    /// Perform an ioctl with the given `FUSE_IOCTL_*` flags and input data, accepting up to
+    /// `out_size` bytes of output data
+    #[allow(clippy::too_many_arguments)]
+    pub fn ioctl(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        flags: u32,
+        cmd: u32,
+        arg: u64,
+        in_data: &[u8],
+        out_size: u32,
+    ) -> EncodedRequest {
+        let ioctl_in = fuse_ioctl_in {
+            fh: fh.into(),
+            flags,
+            cmd,
+            arg,
+            in_size: in_data.len().try_into().expect("Too much data"),
+            out_size,
+        };
+        self.encode(
+            fuse_opcode::FUSE_IOCTL,
+            ino.into(),
+            &[ioctl_in.as_bytes(), in_data],
+        )
+    }
+
+    /// Poll an open file for the given events. With `FUSE_POLL_SCHEDULE_NOTIFY` in `flags`,
+    /// the server notifies the kernel handle `kh` about later events.
+    pub fn poll(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        kh: u64,
+        flags: u32,
+        events: u32,
+    ) -> EncodedRequest {
+        let arg = fuse_poll_in {
+            fh: fh.into(),
+            kh,
+            flags,
+            events,
+        };
+        self.encode(fuse_opcode::FUSE_POLL, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Reply to the retrieve notification with the given unique id with the cached `data` at
+    /// `offset` of an inode
+    pub fn notify_reply(
+        &mut self,
+        notify_unique: RequestId,
+        ino: INodeNo,
+        offset: u64,
+        data: &[u8],
+    ) -> EncodedRequest {
+        let arg = fuse_notify_retrieve_in {
+            dummy1: 0,
+            offset,
+            size: data.len().try_into().expect("Too much data"),
+            dummy2: 0,
+            dummy3: 0,
+            dummy4: 0,
+        };
+        self.encode_as(
+            notify_unique,
+            fuse_opcode::FUSE_NOTIFY_REPLY,
+            ino.into(),
+            &[arg.as_bytes(), data],
+        )
+    }
+
+    /// Preallocate or deallocate space of an open file
+    pub fn fallocate(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: i64,
+        length: i64,
+        mode: i32,
+    ) -> EncodedRequest {
+        let arg = fuse_fallocate_in {
+            fh: fh.into(),
+            offset,
+            length,
+            mode,
+            padding: 0,
+        };
+        self.encode(fuse_opcode::FUSE_FALLOCATE, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Reposition the offset of an open file
+    pub fn lseek(
+        &mut self,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: i64,
+        whence: i32,
+    ) -> EncodedRequest {
+        let arg = fuse_lseek_in {
+            fh: fh.into(),
+            offset,
+            whence,
+            padding: 0,
+        };
+        self.encode(fuse_opcode::FUSE_LSEEK, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Copy `len` bytes from one open file to another
+    #[allow(clippy::too_many_arguments)]
+    pub fn copy_file_range(
+        &mut self,
+        ino_in: INodeNo,
+        fh_in: FileHandle,
+        off_in: i64,
+        ino_out: INodeNo,
+        fh_out: FileHandle,
+        off_out: i64,
+        len: u64,
+        flags: u64,
+    ) -> EncodedRequest {
+        let arg = fuse_copy_file_range_in {
+            fh_in: fh_in.into(),
+            off_in,
+            nodeid_out: ino_out.into(),
+            fh_out: fh_out.into(),
+            off_out,
+            len,
+            flags,
+        };
+        self.encode(
+            fuse_opcode::FUSE_COPY_FILE_RANGE,
+            ino_in.into(),
+            &[arg.as_bytes()],
+        )
+    }
+
+    /// Synchronize the file system
+    pub fn syncfs(&mut self, ino: INodeNo) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_SYNCFS, ino.into(), &[])
+    }
+
+    /// Create and open an unnamed file in a directory
+    pub fn tmpfile(
+        &mut self,
+        parent: INodeNo,
+        mode: u32,
+        umask: u32,
+        flags: OpenFlags,
+    ) -> EncodedRequest {
+        let arg = fuse_create_in {
+            flags: flags.into(),
+            mode,
+            umask,
+            open_flags: 0,
+        };
+        let arg = self.truncated(&arg, 12, FUSE_COMPAT_CREATE_IN_SIZE);
+        self.encode(fuse_opcode::FUSE_TMPFILE, parent.into(), &[arg])
+    }
+
+    /// Get the extended attributes in `mask` (`STATX_*`) of an inode, of the open file if a
+    /// handle is given
+    pub fn statx(
+        &mut self,
+        ino: INodeNo,
+        fh: Option<FileHandle>,
+        flags: u32,
+        mask: u32,
+    ) -> EncodedRequest {
+        let arg = fuse_statx_in {
+            getattr_flags: fh.map_or(0, |_| FUSE_GETATTR_FH),
+            reserved: 0,
+            fh: fh.map_or(0, u64::from),
+            sx_flags: flags,
+            sx_mask: mask,
+        };
+        self.encode(fuse_opcode::FUSE_STATX, ino.into(), &[arg.as_bytes()])
+    }
+
+    /// Set the name of the volume
+    #[cfg(target_os = "macos")]
+    pub fn setvolname(&mut self, name: &OsStr) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_SETVOLNAME, 0, &[&c_name(name)])
+    }
+
+    /// Get the backup and creation times of an inode
+    #[cfg(target_os = "macos")]
+    pub fn getxtimes(&mut self, ino: INodeNo) -> EncodedRequest {
+        self.encode(fuse_opcode::FUSE_GETXTIMES, ino.into(), &[])
+    }
+
+    /// Exchange the data of two files
+    #[cfg(target_os = "macos")]
+    pub fn exchange(
+        &mut self,
+        olddir: INodeNo,
+        oldname: &OsStr,
+        newdir: INodeNo,
+        newname: &OsStr,
+        options: u64,
+    ) -> EncodedRequest {
+        let arg = fuse_exchange_in {
+            olddir: olddir.into(),
+            newdir: newdir.into(),
+            options,
+        };
+        self.encode(
+            fuse_opcode::FUSE_EXCHANGE,
+            0,
+            &[arg.as_bytes(), &c_name(oldname), &c_name(newname)],
+        )
+    }
+
+    /// Initialize a CUSE session, offering the given `CUSE_*` flags
+    pub fn cuse_init(&mut self, flags: u32) -> EncodedRequest {
+        let arg = cuse_init_in {
+            major: FUSE_KERNEL_VERSION,
+            minor: self.protocol.minor,
+            unused: 0,
+            flags,
+        };
+        self.encode(fuse_opcode::CUSE_INIT, 0, &[arg.as_bytes()])
+    }
+}
+
+/// Error that may occur while decoding a reply to a request.
+#[derive(Debug)]
+pub enum ReplyError {
+    /// Not enough data for parsing the header (short read).
+    ShortReadHeader(usize),
+    /// Not enough data for the reply the header announces (short read).
+    ShortRead(usize, usize),
+    /// The payload is too short for the expected reply.
+    InsufficientData,
+    /// The request failed with the given error.
+    Errno(Errno),
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplyError::ShortReadHeader(len) => write!(
+                f,
+                "Short read of FUSE reply header ({} < {})",
+                len,
+                mem::size_of::<fuse_out_header>()
+            ),
+            ReplyError::ShortRead(len, total) => {
+                write!(f, "Short read of FUSE reply ({} < {})", len, total)
+            }
+            ReplyError::InsufficientData => write!(f, "Insufficient reply data"),
+            ReplyError::Errno(err) => write!(f, "FUSE request failed with errno {}", err.0),
+        }
+    }
+}
+
+impl error::Error for ReplyError {}
+
+impl From<ReplyError> for io::Error {
+    fn from(err: ReplyError) -> Self {
+        match err {
+            ReplyError::Errno(errno) => io::Error::from_raw_os_error(errno.0.get()),
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// A message from a FUSE server as the kernel driver receives it: a reply to a request or a
+/// notification.
+///
+/// The typed accessors decode the payload of a successful reply to a request of the
+/// corresponding operation, and fail with [`ReplyError::Errno`] if the request failed.
+#[derive(Debug)]
+pub struct AnyReply<'a> {
+    header: fuse_out_header,
+    payload: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for AnyReply<'a> {
+    type Error = ReplyError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let mut args = ArgumentIterator::new(data);
+        let header: fuse_out_header = args
+            .fetch_unaligned()
+            .ok_or(ReplyError::ShortReadHeader(data.len()))?;
+        let len = header.len as usize;
+        if data.len() < len {
+            return Err(ReplyError::ShortRead(data.len(), len));
+        }
+        let payload = data
+            .get(mem::size_of::<fuse_out_header>()..len)
+            .ok_or(ReplyError::InsufficientData)?;
+        Ok(Self { header, payload })
+    }
+}
+
+impl<'a> AnyReply<'a> {
+    /// The unique id of the request this replies to, 0 for notifications
+    pub fn unique(&self) -> RequestId {
+        RequestId(self.header.unique)
+    }
+
+    /// The `fuse_notify_code` if this is a notification
+    pub fn notify_code(&self) -> Option<i32> {
+        if self.header.unique == 0 {
+            Some(self.header.error)
+        } else {
+            None
+        }
+    }
+
+    /// The payload of a successful reply, or the error the request failed with
+    pub fn result(&self) -> Result<&'a [u8], Errno> {
+        match self.header.error {
+            0 => Ok(self.payload),
+            error => Err(Errno::from_i32(-error)),
+        }
+    }
+
+    /// The payload of a reply to a read, readlink, getxattr or listxattr with a size
+    pub fn data(&self) -> Result<&'a [u8], ReplyError> {
+        self.result().map_err(ReplyError::Errno)
+    }
+
+    fn fetch<T: FromBytes>(&self) -> Result<T, ReplyError> {
+        ArgumentIterator::new(self.data()?)
+            .fetch_unaligned()
+            .ok_or(ReplyError::InsufficientData)
+    }
+
+    /// Fetch a struct of which only the first `size` bytes are sent to older kernels
+    fn fetch_truncated<T: FromBytes + AsBytes>(&self, size: usize) -> Result<T, ReplyError> {
+        let data = self.data()?;
+        let size = size.min(mem::size_of::<T>());
+        let mut arg = T::new_zeroed();
+        arg.as_bytes_mut()[..size]
+            .copy_from_slice(data.get(..size).ok_or(ReplyError::InsufficientData)?);
+        Ok(arg)
+    }
+
+    /// The reply to an init. Its size depends on the minor version the server chose.
+    pub fn init(&self) -> Result<fuse_init_out, ReplyError> {
+        self.fetch_truncated(self.data()?.len())
+    }
+
+    /// The reply to a lookup, mknod, mkdir, symlink or link, with the layout of the given
+    /// minor ABI version
+    pub fn entry(&self, minor: u32) -> Result<fuse_entry_out, ReplyError> {
+        self.fetch_truncated(entry_out_size(minor))
+    }
+
+    /// The reply to a getattr or setattr, with the layout of the given minor ABI version
+    pub fn attr(&self, minor: u32) -> Result<fuse_attr_out, ReplyError> {
+        let size = if minor < 9 {
+            FUSE_COMPAT_ATTR_OUT_SIZE
+        } else {
+            mem::size_of::<fuse_attr_out>()
+        };
+        self.fetch_truncated(size)
+    }
+
+    /// The reply to a statx
+    pub fn statx(&self) -> Result<fuse_statx_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to an open or opendir
+    pub fn open(&self) -> Result<fuse_open_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a create or tmpfile, with the layout of the given minor ABI version
+    pub fn create(&self, minor: u32) -> Result<(fuse_entry_out, fuse_open_out), ReplyError> {
+        let entry = self.entry(minor)?;
+        let open = ArgumentIterator::new(&self.data()?[entry_out_size(minor)..])
+            .fetch_unaligned()
+            .ok_or(ReplyError::InsufficientData)?;
+        Ok((entry, open))
+    }
+
+    /// The reply to a write or copy_file_range
+    pub fn write(&self) -> Result<fuse_write_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a statfs
+    pub fn statfs(&self) -> Result<fuse_statfs_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a getlk
+    pub fn lock(&self) -> Result<fuse_lk_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a bmap
+    pub fn bmap(&self) -> Result<fuse_bmap_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a getxattr or listxattr of size 0
+    pub fn xattr_size(&self) -> Result<fuse_getxattr_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to an lseek
+    pub fn lseek(&self) -> Result<fuse_lseek_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a poll
+    pub fn poll(&self) -> Result<fuse_poll_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to an ioctl and the data following it: the output data, or the iovecs to
+    /// retry an unrestricted ioctl with if `FUSE_IOCTL_RETRY` is set
+    pub fn ioctl(&self) -> Result<(fuse_ioctl_out, &'a [u8]), ReplyError> {
+        let mut args = ArgumentIterator::new(self.data()?);
+        let out = args.fetch_unaligned().ok_or(ReplyError::InsufficientData)?;
+        Ok((out, args.fetch_all()))
+    }
+
+    /// The reply to a getxtimes
+    #[cfg(target_os = "macos")]
+    pub fn xtimes(&self) -> Result<fuse_getxtimes_out, ReplyError> {
+        self.fetch()
+    }
+
+    /// The reply to a CUSE init and the device info following it
+    pub fn cuse_init(&self) -> Result<(cuse_init_out, &'a [u8]), ReplyError> {
+        let mut args = ArgumentIterator::new(self.data()?);
+        let out = args.fetch_unaligned().ok_or(ReplyError::InsufficientData)?;
+        Ok((out, args.fetch_all()))
+    }
+
+    /// The entries of a reply to a readdir and their names
+    pub fn dirents(&self) -> Result<Vec<(fuse_dirent, &'a OsStr)>, ReplyError> {
+        self.entries(|x: &fuse_dirent| x.namelen)
+    }
+
+    /// The entries of a reply to a readdirplus and their names
+    pub fn direntplus(&self) -> Result<Vec<(fuse_direntplus, &'a OsStr)>, ReplyError> {
+        self.entries(|x: &fuse_direntplus| x.dirent.namelen)
+    }
+
+    /// Splits the payload into entries, each followed by its name and padded to 8 bytes
+    fn entries<T: FromBytes>(
+        &self,
+        namelen: impl Fn(&T) -> u32,
+    ) -> Result<Vec<(T, &'a OsStr)>, ReplyError> {
+        let mut args = ArgumentIterator::new(self.data()?);
+        let mut entries = vec![];
+        while !args.is_empty() {
+            let entry: T = args.fetch_unaligned().ok_or(ReplyError::InsufficientData)?;
+            let namelen = namelen(&entry) as usize;
+            let name = args
+                .fetch_slice::<u8>(namelen)
+                .ok_or(ReplyError::InsufficientData)?;
+            let entlen = mem::size_of::<T>() + namelen;
+            let entsize = (entlen + mem::size_of::<u64>() - 1) & !(mem::size_of::<u64>() - 1); // 64bit align
+            let padlen = entsize - entlen;
+            args.fetch_slice::<u8>(padlen.min(args.len()));
+            entries.push((entry, OsStr::from_bytes(name)));
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::ffi::OsStr;
+    use std::io::IoSlice;
+
+    use super::super::{AnyRequest, Operation, Protocol, Request, Response};
+    use super::*;
+
+    fn ioslice_to_vec(s: &[IoSlice<'_>]) -> Vec<u8> {
+        s.iter().flat_map(|x| x.to_vec()).collect()
+    }
+
+    #[test]
+    fn encoded_requests_parse() {
+        let mut enc = RequestEncoder::new().with_credentials(0xc001, 0xc002, 0xc003);
+        let name = OsStr::new("foo.txt");
+        let req = enc.lookup(INodeNo(0x11), name);
+        let parsed = AnyRequest::try_from(req.as_bytes()).unwrap();
+        assert_eq!(parsed.unique(), req.unique());
+        assert_eq!(parsed.nodeid(), INodeNo(0x11));
+        assert_eq!(
+            (parsed.uid(), parsed.gid(), parsed.pid()),
+            (0xc001, 0xc002, 0xc003)
+        );
+        match parsed.operation().unwrap() {
+            Operation::Lookup(x) => assert_eq!(x.name(), name),
+            _ => panic!("Unexpected request operation"),
+        }
+
+        let req = enc.write(
+            INodeNo(0x22),
+            FileHandle(0x33),
+            0x44,
+            b"hello",
+            WriteFlags(0),
+            OpenFlags(libc::O_WRONLY),
+            Some(LockOwner(0x55)),
+        );
+        assert_ne!(parsed.unique(), req.unique());
+        let parsed = AnyRequest::try_from(req.as_bytes()).unwrap();
+        match parsed.operation().unwrap() {
+            Operation::Write(x) => {
+                assert_eq!(x.file_handle(), FileHandle(0x33));
+                assert_eq!(x.offset(), 0x44);
+                assert_eq!(x.data(), b"hello");
+                assert_eq!(x.lock_owner(), Some(LockOwner(0x55)));
+                assert_eq!(x.flags(), OpenFlags(libc::O_WRONLY));
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+
+        let req = enc.rename(INodeNo(1), name, INodeNo(2), OsStr::new("bar"));
+        match AnyRequest::try_from(req.as_bytes())
+            .unwrap()
+            .operation()
+            .unwrap()
+        {
+            Operation::Rename(x) => {
+                assert_eq!(x.src().name, name);
+                assert_eq!(x.dest().dir, INodeNo(2));
+                assert_eq!(x.dest().name, OsStr::new("bar"));
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    fn every_request_parses() {
+        let mut enc = RequestEncoder::new();
+        let (ino, fh, owner) = (INodeNo(2), FileHandle(3), LockOwner(4));
+        let name = OsStr::new("name");
+        let lock = Lock {
+            range: (0, 10),
+            typ: libc::F_RDLCK,
+            pid: 5,
+        };
+        let requests = vec![
+            enc.init(0, FUSE_ASYNC_READ),
+            enc.lookup(ino, name),
+            enc.forget(ino, 1),
+            enc.batch_forget(&[fuse_forget_one {
+                nodeid: 2,
+                nlookup: 1,
+            }]),
+            enc.getattr(ino, Some(fh)),
+            enc.setattr(ino, &fuse_setattr_in::new_zeroed()),
+            enc.readlink(ino),
+            enc.symlink(ino, name, OsStr::new("target")),
+            enc.mknod(ino, name, 0o10644, 0o22, 0),
+            enc.mkdir(ino, name, 0o755, 0o22),
+            enc.unlink(ino, name),
+            enc.rmdir(ino, name),
+            enc.rename(ino, name, ino, name),
+            enc.rename2(ino, name, ino, name, 0),
+            enc.link(ino, ino, name),
+            enc.open(ino, OpenFlags(libc::O_RDONLY), 0),
+            enc.read(ino, fh, 0, 10, OpenFlags(libc::O_RDONLY), None),
+            enc.write(ino, fh, 0, b"x", WriteFlags(0), OpenFlags(0), None),
+            enc.statfs(ino),
+            enc.release(ino, fh, OpenFlags(0), true, Some(owner)),
+            enc.fsync(ino, fh, true),
+            enc.setxattr(ino, name, b"value", 0, 0),
+            enc.getxattr(ino, name, 0),
+            enc.listxattr(ino, 0),
+            enc.removexattr(ino, name),
+            enc.flush(ino, fh, owner),
+            enc.opendir(ino, OpenFlags(0)),
+            enc.readdir(ino, fh, 0, 4096),
+            enc.readdirplus(ino, fh, 0, 4096),
+            enc.releasedir(ino, fh, OpenFlags(0)),
+            enc.fsyncdir(ino, fh, false),
+            enc.getlk(ino, fh, owner, &lock),
+            enc.setlk(ino, fh, owner, &lock),
+            enc.setlkw(ino, fh, owner, &lock),
+            enc.access(ino, libc::R_OK),
+            enc.create(ino, name, 0o644, 0o22, OpenFlags(libc::O_RDWR), 0),
+            enc.interrupt(RequestId(1)),
+            enc.bmap(ino, 512, 0),
+            enc.destroy(),
+            enc.ioctl(ino, fh, 0, 0x1234, 0, b"in", 8),
+            enc.poll(ino, fh, 0, 0, libc::POLLIN as u32),
+            enc.notify_reply(RequestId(6), ino, 0, b"cached"),
+            enc.fallocate(ino, fh, 0, 10, 0),
+            enc.lseek(ino, fh, 0, libc::SEEK_END),
+            enc.copy_file_range(ino, fh, 0, ino, fh, 10, 10, 0),
+            enc.syncfs(ino),
+            enc.tmpfile(ino, 0o644, 0o22, OpenFlags(libc::O_RDWR)),
+            enc.statx(ino, None, 0, 0x7ff),
+            enc.cuse_init(0),
+        ];
+        for req in requests {
+            let parsed = AnyRequest::try_from(req.as_bytes()).unwrap();
+            assert_eq!(parsed.unique(), req.unique());
+            assert!(parsed.operation().is_ok(), "{:?}", parsed.opcode());
+        }
+    }
+
+    #[test]
+    fn compat_requests_parse() {
+        let protocol = Protocol { minor: 8, flags: 0 };
+        let mut enc = RequestEncoder::new();
+        enc.set_protocol(protocol);
+        let req = enc.read(
+            INodeNo(0x22),
+            FileHandle(0x33),
+            0x44,
+            0x55,
+            OpenFlags(0),
+            None,
+        );
+        // The fuse_read_in of ABI 7.8 lacks lock_owner, flags and padding
+        assert_eq!(req.as_bytes().len(), 40 + FUSE_COMPAT_READ_IN_SIZE);
+        let parsed = AnyRequest::try_from(req.as_bytes())
+            .unwrap()
+            .with_protocol(protocol);
+        match parsed.operation().unwrap() {
+            Operation::Read(x) => {
+                assert_eq!((x.offset(), x.size()), (0x44, 0x55));
+                assert_eq!(x.lock_owner(), None);
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+
+        let req = enc.mknod(INodeNo(1), OsStr::new("fifo"), 0o10644, 0o22, 0);
+        let parsed = AnyRequest::try_from(req.as_bytes())
+            .unwrap()
+            .with_protocol(protocol);
+        match parsed.operation().unwrap() {
+            Operation::MkNod(x) => {
+                assert_eq!(x.name(), OsStr::new("fifo"));
+                assert_eq!((x.mode(), x.umask()), (0o10644, 0));
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    fn decode_replies() {
+        let data = Response::new_write(0x1234).with_iovec(RequestId(0xdeadbeef), ioslice_to_vec);
+        let reply = AnyReply::try_from(&data[..]).unwrap();
+        assert_eq!(reply.unique(), RequestId(0xdeadbeef));
+        assert_eq!(reply.notify_code(), None);
+        assert_eq!(reply.write().unwrap().size, 0x1234);
+        assert!(matches!(reply.statfs(), Err(ReplyError::InsufficientData)));
+
+        let data = Response::new_error(Errno::ENOENT).with_iovec(RequestId(1), ioslice_to_vec);
+        let reply = AnyReply::try_from(&data[..]).unwrap();
+        match reply.entry(FUSE_KERNEL_MINOR_VERSION) {
+            Err(ReplyError::Errno(err)) => assert_eq!(i32::from(err), libc::ENOENT),
+            _ => panic!("Unexpected reply"),
+        }
+
+        assert!(matches!(
+            AnyReply::try_from(&data[..8]),
+            Err(ReplyError::ShortReadHeader(8))
+        ));
+        let data = Response::new_data(b"hello".as_ref()).with_iovec(RequestId(1), ioslice_to_vec);
+        assert!(matches!(
+            AnyReply::try_from(&data[..18]),
+            Err(ReplyError::ShortRead(18, 21))
+        ));
+        let reply = AnyReply::try_from(&data[..]).unwrap();
+        assert_eq!(reply.data().unwrap(), b"hello");
+    }
+}
@@ -1,15 +1,30 @@
 //! Low-level kernel communication.
+//!
+//! This is the FUSE codec the rest of the crate is built on, usable on its own to write
+//! transports, proxies or tracers. A request read from the device is decoded with
+//! `AnyRequest::try_from` and [`AnyRequest::operation`] into a typed [`Operation`]. A reply
+//! is built with the constructors of [`Response`], or with [`DirEntList`] and
+//! [`DirEntPlusList`] for directory listings, and encoded with [`Response::with_iovec`].
+//!
+//! Requests are parsed with the layout of the latest ABI until [`AnyRequest::with_protocol`]
+//! is given the [`Protocol`] negotiated by `FUSE_INIT`.
 
 mod argument;
 pub mod fuse_abi;
+mod kernel;
 pub(crate) mod reply;
 mod request;
 
 use std::{convert::TryInto, num::NonZeroI32, time::SystemTime};
 
-pub use reply::Response;
+pub use argument::ArgumentIterator;
+pub use kernel::{AnyReply, EncodedRequest, ReplyError, RequestEncoder};
+pub use reply::{
+    Attr, DirEntList, DirEntOffset, DirEntPlusList, DirEntry, DirEntryPlus, Notification, Response,
+};
 pub use request::{
-    AnyRequest, FileHandle, INodeNo, Lock, Operation, Request, RequestError, RequestId, Version,
+    op, AnyRequest, FileHandle, FilenameInDir, INodeNo, Lock, LockOwner, OpenFlags, Operation,
+    Protocol, Request, RequestError, RequestId, Version, WriteFlags,
 };
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -22,6 +37,32 @@ pub enum TimeOrNow {
     Now,
 }
 
+/// A region of the ioctl caller's memory, used to ask the kernel to retry an unrestricted
+/// ioctl with the given input and output buffers
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct IoctlIovec {
+    /// User space address of the buffer
+    pub base: u64,
+    /// Length of the buffer in bytes
+    pub len: u64,
+}
+
+/// Whether an ioctl with the given `FUSE_IOCTL_*` flags was issued with 32 bit addresses,
+/// either by a 32 bit process on a 64 bit kernel or on a 32 bit kernel
+pub(crate) fn ioctl_is_compat(flags: u32) -> bool {
+    let mask = fuse_abi::consts::FUSE_IOCTL_COMPAT | fuse_abi::consts::FUSE_IOCTL_32BIT;
+    flags & mask != 0
+}
+
+/// Whether the kernel waits for a reply to requests with the given opcode
+pub(crate) fn expects_reply(opcode: u32) -> bool {
+    use fuse_abi::fuse_opcode::*;
+    opcode != FUSE_FORGET as u32
+        && opcode != FUSE_BATCH_FORGET as u32
+        && opcode != FUSE_INTERRUPT as u32
+        && opcode != FUSE_NOTIFY_REPLY as u32
+}
+
 macro_rules! errno {
     ($x: expr) => {
         Errno(unsafe {
@@ -222,9 +263,12 @@ impl Errno {
     /// exist.  This resolves to the appropriate platform specific error code.
     #[cfg(target_os = "linux")]
     pub const NO_XATTR: Errno = Self::ENODATA;
+    /// Use this as an error return from getxattr/removexattr to indicate that the xattr doesn't
+    /// exist.  This resolves to the appropriate platform specific error code.
     #[cfg(not(target_os = "linux"))]
     pub const NO_XATTR: Errno = Self::ENOATTR;
 
+    /// The error with the given positive error number, or EIO if it is 0
     pub fn from_i32(err: i32) -> Errno {
         err.try_into().ok().map(Errno).unwrap_or(Errno::EIO)
     }
@@ -9,27 +9,29 @@ use std::{
 
 use crate::FileType;
 
+use super::IoctlIovec;
 use super::{fuse_abi as abi, Errno, FileHandle, Generation, INodeNo};
 use super::{Lock, RequestId};
 use smallvec::{smallvec, SmallVec};
-use zerocopy::AsBytes;
+use zerocopy::{AsBytes, FromBytes};
 
 const INLINE_DATA_THRESHOLD: usize = size_of::<u64>() * 4;
 pub(crate) type ResponseBuf = SmallVec<[u8; INLINE_DATA_THRESHOLD]>;
 
+/// A reply to a request, as sent to the kernel driver
 #[derive(Debug)]
 pub enum Response {
+    /// A reply without payload, carrying the positive error number or 0 for success
     Error(i32),
+    /// A successful reply with the given payload
     Data(ResponseBuf),
 }
 
 #[must_use]
 impl Response {
-    pub(crate) fn with_iovec<F: FnOnce(&[IoSlice<'_>]) -> T, T>(
-        &self,
-        unique: RequestId,
-        f: F,
-    ) -> T {
+    /// Encode the reply to the request with the given id, i.e. a `fuse_out_header` followed
+    /// by the payload, and pass it as a list of buffers to `f`
+    pub fn with_iovec<F: FnOnce(&[IoSlice<'_>]) -> T, T>(&self, unique: RequestId, f: F) -> T {
         let datalen = match &self {
             Response::Error(_) => 0,
             Response::Data(v) => v.len(),
@@ -54,15 +56,18 @@ impl Response {
     }
 
     // Constructors
-    pub(crate) fn new_empty() -> Self {
+    /// A reply without payload, signalling success
+    pub fn new_empty() -> Self {
         Self::Error(0)
     }
 
-    pub(crate) fn new_error(error: Errno) -> Self {
+    /// A reply signalling the given error
+    pub fn new_error(error: Errno) -> Self {
         Self::Error(error.into())
     }
 
-    pub(crate) fn new_data<T: AsRef<[u8]> + Into<Vec<u8>>>(data: T) -> Self {
+    /// A reply with a raw payload, e.g. the data of a read or readlink
+    pub fn new_data<T: AsRef<[u8]> + Into<Vec<u8>>>(data: T) -> Self {
         Self::Data(if data.as_ref().len() <= INLINE_DATA_THRESHOLD {
             data.as_ref().into()
         } else {
@@ -70,12 +75,15 @@ impl Response {
         })
     }
 
-    pub(crate) fn new_entry(
+    /// A reply to a lookup, mknod, mkdir, symlink or link, shortened to the size expected by
+    /// the given minor ABI version
+    pub fn new_entry(
         ino: INodeNo,
         generation: Generation,
         attr: &Attr,
         attr_ttl: Duration,
         entry_ttl: Duration,
+        minor: u32,
     ) -> Self {
         let d = abi::fuse_entry_out {
             nodeid: ino.into(),
@@ -86,21 +94,53 @@ impl Response {
             attr_valid_nsec: attr_ttl.subsec_nanos(),
             attr: attr.attr,
         };
-        Self::from_struct(d.as_bytes())
+        Self::from_struct(&d.as_bytes()[..entry_out_size(minor)])
     }
 
-    pub(crate) fn new_attr(ttl: &Duration, attr: &Attr) -> Self {
+    /// A reply to a getattr or setattr, shortened to the size expected by the given minor ABI
+    /// version
+    pub fn new_attr(ttl: &Duration, attr: &Attr, minor: u32) -> Self {
         let r = abi::fuse_attr_out {
             attr_valid: ttl.as_secs(),
             attr_valid_nsec: ttl.subsec_nanos(),
             dummy: 0,
             attr: attr.attr,
         };
+        let size = if minor < 9 {
+            abi::FUSE_COMPAT_ATTR_OUT_SIZE
+        } else {
+            size_of::<abi::fuse_attr_out>()
+        };
+        Self::from_struct(&r.as_bytes()[..size])
+    }
+
+    /// A reply to a statx
+    pub fn new_statx(ttl: &Duration, statx: &crate::FileStatx) -> Self {
+        let r = abi::fuse_statx_out {
+            attr_valid: ttl.as_secs(),
+            attr_valid_nsec: ttl.subsec_nanos(),
+            flags: 0,
+            spare: [0; 2],
+            stat: fuse_statx_from_statx(statx),
+        };
         Self::from_struct(&r)
     }
 
+    /// A reply to a statx with the basic attributes of a getattr reply
+    pub(crate) fn new_statx_from_attr(attr: &abi::fuse_attr_out) -> Self {
+        let r = abi::fuse_statx_out {
+            attr_valid: attr.attr_valid,
+            attr_valid_nsec: attr.attr_valid_nsec,
+            flags: 0,
+            spare: [0; 2],
+            stat: fuse_statx_from_fuse_attr(&attr.attr),
+        };
+        Self::from_struct(&r)
+    }
+
+    /// A reply to a getxtimes
     #[cfg(target_os = "macos")]
-    pub(crate) fn new_xtimes(bkuptime: SystemTime, crtime: SystemTime) -> Self {
+    pub fn new_xtimes(bkuptime: SystemTime, crtime: SystemTime) -> Self {
         let (bkuptime_secs, bkuptime_nanos) = time_from_system_time(&bkuptime);
         let (crtime_secs, crtime_nanos) = time_from_system_time(&crtime);
         let r = abi::fuse_getxtimes_out {
@@ -112,17 +152,20 @@ impl Response {
         Self::from_struct(&r)
     }
 
+    /// A reply to an open or opendir with the file handle, the `FOPEN_*` flags and the backing
+    /// id of a passthrough file, if any
     // TODO: Could flags be more strongly typed?
-    pub(crate) fn new_open(fh: FileHandle, flags: u32) -> Self {
+    pub fn new_open(fh: FileHandle, flags: u32, backing_id: i32) -> Self {
         let r = abi::fuse_open_out {
             fh: fh.into(),
             open_flags: flags,
-            padding: 0,
+            backing_id,
         };
         Self::from_struct(&r)
     }
 
-    pub(crate) fn new_lock(lock: &Lock) -> Self {
+    /// A reply to a getlk
+    pub fn new_lock(lock: &Lock) -> Self {
         let r = abi::fuse_lk_out {
             lk: abi::fuse_file_lock {
                 start: lock.range.0,
@@ -134,12 +177,14 @@ impl Response {
         Self::from_struct(&r)
     }
 
-    pub(crate) fn new_bmap(block: u64) -> Self {
+    /// A reply to a bmap
+    pub fn new_bmap(block: u64) -> Self {
         let r = abi::fuse_bmap_out { block };
         Self::from_struct(&r)
     }
 
-    pub(crate) fn new_write(written: u32) -> Self {
+    /// A reply to a write with the number of bytes written
+    pub fn new_write(written: u32) -> Self {
         let r = abi::fuse_write_out {
             size: written,
             padding: 0,
@@ -147,8 +192,9 @@ impl Response {
         Self::from_struct(&r)
     }
 
+    /// A reply to a statfs
     #[allow(clippy::too_many_arguments)]
-    pub(crate) fn new_statfs(
+    pub fn new_statfs(
         blocks: u64,
         bfree: u64,
         bavail: u64,
@@ -175,13 +221,16 @@ impl Response {
         Self::from_struct(&r)
     }
 
+    /// A reply to a create, i.e. an entry followed by an open reply
     // TODO: Can flags be more strongly typed?
-    pub(crate) fn new_create(
+    pub fn new_create(
         ttl: &Duration,
         attr: &Attr,
         generation: Generation,
         fh: FileHandle,
         flags: u32,
+        backing_id: i32,
+        minor: u32,
     ) -> Self {
         let r = abi::fuse_create_out(
             abi::fuse_entry_out {
@@ -196,14 +245,18 @@ impl Response {
             abi::fuse_open_out {
                 fh: fh.into(),
                 open_flags: flags,
-                padding: 0,
+                backing_id,
             },
         );
-        Self::from_struct(&r)
+        // The fuse_open_out directly follows the possibly shortened fuse_entry_out
+        let mut v: ResponseBuf = r.0.as_bytes()[..entry_out_size(minor)].into();
+        v.extend_from_slice(r.1.as_bytes());
+        Self::Data(v)
     }
 
+    /// A reply to an ioctl with its result and output data
     // TODO: Are you allowed to send data while result != 0?
-    pub(crate) fn new_ioctl(result: i32, data: &[IoSlice<'_>]) -> Self {
+    pub fn new_ioctl(result: i32, data: &[IoSlice<'_>]) -> Self {
         let r = abi::fuse_ioctl_out {
             result,
             // these fields are only needed for unrestricted ioctls
@@ -219,26 +272,113 @@ impl Response {
         Self::Data(v)
     }
 
+    /// Ask the kernel to retry an unrestricted ioctl with the given input and output buffers.
+    /// `compat` selects the 32 bit iovec layout used by kernels before ABI 7.16 for compat
+    /// ioctls.
+    pub fn new_ioctl_retry(
+        in_iovs: &[IoctlIovec],
+        out_iovs: &[IoctlIovec],
+        compat: bool,
+        minor: u32,
+    ) -> Self {
+        let r = abi::fuse_ioctl_out {
+            result: 0,
+            flags: abi::consts::FUSE_IOCTL_RETRY,
+            in_iovs: in_iovs.len().try_into().expect("Too many iovecs"),
+            out_iovs: out_iovs.len().try_into().expect("Too many iovecs"),
+        };
+        let mut v: ResponseBuf = r.as_bytes().into();
+        for iov in in_iovs.iter().chain(out_iovs) {
+            // Since ABI 7.16 the kernel always expects 64 bit iovecs. Before, it used the
+            // iovec layout of the caller, which is 32 bit wide for compat ioctls.
+            if minor < 16 && compat {
+                v.extend_from_slice((iov.base as u32).as_bytes());
+                v.extend_from_slice((iov.len as u32).as_bytes());
+            } else {
+                v.extend_from_slice(
+                    abi::fuse_ioctl_iovec {
+                        base: iov.base,
+                        len: iov.len,
+                    }
+                    .as_bytes(),
+                );
+            }
+        }
+        Self::Data(v)
+    }
+
     fn new_directory(list: EntListBuf) -> Self {
         assert!(list.buf.len() <= list.max_size);
         Self::Data(list.buf)
     }
 
-    pub(crate) fn new_xattr_size(size: u32) -> Self {
+    /// A reply to a getxattr or listxattr of size 0 with the size the value would need
+    pub fn new_xattr_size(size: u32) -> Self {
         let r = abi::fuse_getxattr_out { size, padding: 0 };
         Self::from_struct(&r)
     }
 
-    pub(crate) fn new_lseek(offset: i64) -> Self {
+    /// A reply to an lseek with the resulting offset
+    pub fn new_lseek(offset: i64) -> Self {
         let r = abi::fuse_lseek_out { offset };
         Self::from_struct(&r)
     }
 
+    /// A reply to a poll with the ready events
+    pub fn new_poll(revents: u32) -> Self {
+        let r = abi::fuse_poll_out {
+            revents,
+            padding: 0,
+        };
+        Self::from_struct(&r)
+    }
+
     fn from_struct<T: AsBytes + ?Sized>(data: &T) -> Self {
         Self::Data(data.as_bytes().into())
     }
 }
 
+/// An unsolicited message from the filesystem to the kernel.
+///
+/// Notifications are sent on the same channel as replies, but carry a zero unique id and
+/// the notification code in the error field of the `fuse_out_header`.
+#[derive(Debug)]
+pub enum Notification {
+    /// Wake up the poll waiters registered under the given kernel handle
+    PollWakeup(u64),
+}
+
+impl Notification {
+    /// Encode the notification, i.e. a `fuse_out_header` followed by the payload, and pass it
+    /// as a list of buffers to `f`
+    pub fn with_iovec<F: FnOnce(&[IoSlice<'_>]) -> T, T>(&self, f: F) -> T {
+        let (code, data) = match self {
+            Notification::PollWakeup(kh) => (
+                abi::fuse_notify_code::FUSE_POLL,
+                abi::fuse_notify_poll_wakeup_out { kh: *kh },
+            ),
+        };
+        let data = data.as_bytes();
+        let header = abi::fuse_out_header {
+            unique: 0,
+            error: code as i32,
+            len: (size_of::<abi::fuse_out_header>() + data.len())
+                .try_into()
+                .expect("Too much data"),
+        };
+        f(&[IoSlice::new(header.as_bytes()), IoSlice::new(data)])
+    }
+}
+
+/// Size of the fuse_entry_out expected by a kernel with the given minor ABI version
+pub(crate) fn entry_out_size(minor: u32) -> usize {
+    if minor < 9 {
+        abi::FUSE_COMPAT_ENTRY_OUT_SIZE
+    } else {
+        size_of::<abi::fuse_entry_out>()
+    }
+}
+
 pub(crate) fn time_from_system_time(system_time: &SystemTime) -> (i64, u32) {
     // Convert to signed 64-bit time with epoch at 0
     match system_time.duration_since(UNIX_EPOCH) {
@@ -294,13 +434,86 @@ pub(crate) fn fuse_attr_from_attr(attr: &crate::FileAttr) -> abi::fuse_attr {
         rdev: attr.rdev,
         #[cfg(target_os = "macos")]
         flags: attr.flags,
-        #[cfg(feature = "abi-7-9")]
         blksize: attr.blksize,
-        #[cfg(feature = "abi-7-9")]
+        #[cfg(target_os = "macos")]
         padding: 0,
+        // Other bits, like chflags(2) flags set for macOS, mustn't make it to the kernel
+        #[cfg(not(target_os = "macos"))]
+        flags: attr.flags & (abi::consts::FUSE_ATTR_SUBMOUNT | abi::consts::FUSE_ATTR_DAX),
+    }
+}
+
+fn sx_time_from_system_time(system_time: &SystemTime) -> abi::fuse_sx_time {
+    let (tv_sec, tv_nsec) = time_from_system_time(system_time);
+    abi::fuse_sx_time {
+        tv_sec,
+        tv_nsec,
+        __reserved: 0,
+    }
+}
+/// Returns a fuse_statx from FileStatx
+pub(crate) fn fuse_statx_from_statx(statx: &crate::FileStatx) -> abi::fuse_statx {
+    let attr = &statx.attr;
+    abi::fuse_statx {
+        mask: statx.mask,
+        blksize: attr.blksize,
+        attributes: statx.attributes,
+        nlink: attr.nlink,
+        uid: attr.uid,
+        gid: attr.gid,
+        mode: mode_from_kind_and_perm(attr.kind, attr.perm) as u16,
+        __spare0: [0],
+        ino: attr.ino,
+        size: attr.size,
+        blocks: attr.blocks,
+        attributes_mask: statx.attributes_mask,
+        atime: sx_time_from_system_time(&attr.atime),
+        btime: sx_time_from_system_time(&statx.btime),
+        ctime: sx_time_from_system_time(&attr.ctime),
+        mtime: sx_time_from_system_time(&attr.mtime),
+        // FileAttr.rdev is encoded like the kernel's new_encode_dev()
+        rdev_major: (attr.rdev & 0xfff00) >> 8,
+        rdev_minor: (attr.rdev & 0xff) | ((attr.rdev >> 12) & 0xfff00),
+        // Filled in by the kernel
+        dev_major: 0,
+        dev_minor: 0,
+        __spare2: [0; 14],
+    }
+}
+
+/// Returns a fuse_statx with the basic attributes of a fuse_attr
+fn fuse_statx_from_fuse_attr(attr: &abi::fuse_attr) -> abi::fuse_statx {
+    let sx_time = |tv_sec, tv_nsec| abi::fuse_sx_time {
+        tv_sec,
+        tv_nsec,
+        __reserved: 0,
+    };
+    abi::fuse_statx {
+        mask: abi::consts::STATX_BASIC_STATS,
+        blksize: attr.blksize,
+        attributes: 0,
+        nlink: attr.nlink,
+        uid: attr.uid,
+        gid: attr.gid,
+        mode: attr.mode as u16,
+        __spare0: [0],
+        ino: attr.ino,
+        size: attr.size,
+        blocks: attr.blocks,
+        attributes_mask: 0,
+        atime: sx_time(attr.atime, attr.atimensec),
+        btime: sx_time(0, 0),
+        ctime: sx_time(attr.ctime, attr.ctimensec),
+        mtime: sx_time(attr.mtime, attr.mtimensec),
+        rdev_major: (attr.rdev & 0xfff00) >> 8,
+        rdev_minor: (attr.rdev & 0xff) | ((attr.rdev >> 12) & 0xfff00),
+        dev_major: 0,
+        dev_minor: 0,
+        __spare2: [0; 14],
     }
 }
 
+/// The attributes of an inode in the layout of the kernel ABI
 // TODO: Add methods for creating this without making a `FileAttr` first.
 #[derive(Debug, Clone, Copy)]
 pub struct Attr {
@@ -341,7 +554,7 @@ impl EntListBuf {
     fn push(&mut self, ent: [&[u8]; 2]) -> bool {
         let entlen = ent[0].len() + ent[1].len();
         let entsize = (entlen + size_of::<u64>() - 1) & !(size_of::<u64>() - 1); // 64bit align
-        if self.buf.len() + entsize > self.max_size {
+        if self.is_full_for(entlen) {
             return true;
         }
         self.buf.extend_from_slice(ent[0]);
@@ -350,8 +563,16 @@ impl EntListBuf {
         self.buf.extend_from_slice(&[0u8; 8][..padlen]);
         false
     }
+
+    /// Returns true if an entry of the given length doesn't fit into the buffer anymore
+    fn is_full_for(&self, entlen: usize) -> bool {
+        let entsize = (entlen + size_of::<u64>() - 1) & !(size_of::<u64>() - 1); // 64bit align
+        self.buf.len() + entsize > self.max_size
+    }
 }
 
+/// The offset of the entry following a directory entry, which the kernel passes to the next
+/// readdir to continue the listing
 #[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
 pub struct DirEntOffset(pub i64);
 impl From<DirEntOffset> for i64 {
@@ -360,6 +581,7 @@ impl From<DirEntOffset> for i64 {
     }
 }
 
+/// An entry of a readdir reply
 #[derive(Debug)]
 pub struct DirEntry<T: AsRef<Path>> {
     ino: INodeNo,
@@ -369,6 +591,7 @@ pub struct DirEntry<T: AsRef<Path>> {
 }
 
 impl<T: AsRef<Path>> DirEntry<T> {
+    /// An entry of the given inode, file type and name
     pub fn new(ino: INodeNo, offset: DirEntOffset, kind: FileType, name: T) -> DirEntry<T> {
         DirEntry::<T> {
             ino,
@@ -379,7 +602,7 @@ impl<T: AsRef<Path>> DirEntry<T> {
     }
 }
 
-/// Used to respond to [ReadDirPlus] requests.
+/// Used to respond to [ReadDir](super::op::ReadDir) requests.
 #[derive(Debug)]
 pub struct DirEntList(EntListBuf);
 impl From<DirEntList> for Response {
@@ -390,7 +613,8 @@ impl From<DirEntList> for Response {
 }
 
 impl DirEntList {
-    pub(crate) fn new(max_size: usize) -> Self {
+    /// An empty list which holds up to `max_size` bytes, the size of the request
+    pub fn new(max_size: usize) -> Self {
         Self(EntListBuf::new(max_size))
     }
     /// Add an entry to the directory reply buffer. Returns true if the buffer is full.
@@ -409,6 +633,7 @@ impl DirEntList {
     }
 }
 
+/// An entry of a readdirplus reply, i.e. a directory entry with its lookup reply
 #[derive(Debug)]
 pub struct DirEntryPlus<T: AsRef<Path>> {
     ino: INodeNo,
@@ -421,6 +646,8 @@ pub struct DirEntryPlus<T: AsRef<Path>> {
 }
 
 impl<T: AsRef<Path>> DirEntryPlus<T> {
+    /// An entry of the given inode and name, with the attributes and the validity of its
+    /// lookup reply
     pub fn new(
         ino: INodeNo,
         generation: Generation,
@@ -442,7 +669,7 @@ impl<T: AsRef<Path>> DirEntryPlus<T> {
     }
 }
 
-/// Used to respond to [ReadDir] requests.
+/// Used to respond to [ReadDirPlus](super::op::ReadDirPlus) requests.
 #[derive(Debug)]
 pub struct DirEntPlusList(EntListBuf);
 impl From<DirEntPlusList> for Response {
@@ -453,7 +680,8 @@ impl From<DirEntPlusList> for Response {
 }
 
 impl DirEntPlusList {
-    pub(crate) fn new(max_size: usize) -> Self {
+    /// An empty list which holds up to `max_size` bytes, the size of the request
+    pub fn new(max_size: usize) -> Self {
         Self(EntListBuf::new(max_size))
     }
     /// Add an entry to the directory reply buffer. Returns true if the buffer is full.
@@ -481,12 +709,41 @@ impl DirEntPlusList {
         };
         self.0.push([header.as_bytes(), name])
     }
+
+    pub(crate) fn max_size(&self) -> usize {
+        self.0.max_size
+    }
+
+    /// Returns true if an entry with a name of the given length doesn't fit into the buffer
+    /// anymore
+    pub(crate) fn is_full_for(&self, namelen: usize) -> bool {
+        self.0
+            .is_full_for(size_of::<abi::fuse_direntplus>() + namelen)
+    }
+
+    /// Add an entry of a readdir reply, with the lookup reply of the entry if there is one.
+    /// Without it, the kernel only lists the entry. Returns true if the buffer is full.
+    #[must_use]
+    pub(crate) fn push_dirent(
+        &mut self,
+        dirent: abi::fuse_dirent,
+        name: &[u8],
+        entry_out: Option<abi::fuse_entry_out>,
+    ) -> bool {
+        let header = abi::fuse_direntplus {
+            entry_out: entry_out.unwrap_or_else(abi::fuse_entry_out::new_zeroed),
+            dirent,
+        };
+        self.0.push([header.as_bytes(), name])
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::convert::TryFrom;
     use std::num::NonZeroI32;
 
+    use super::super::AnyReply;
     use super::*;
 
     #[test]
@@ -556,10 +813,15 @@ mod test {
             ]
         };
 
-        if cfg!(feature = "abi-7-9") {
+        expected[0] = expected.len() as u8;
+        // Kernels before ABI 7.9 expect the fuse_attr without blksize and padding/flags
+        let compat = expected.clone();
+        if cfg!(target_os = "macos") {
             expected.extend(vec![0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        } else {
+            expected.extend(vec![0xbb, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
         }
-        expected[0] = (expected.len()) as u8;
+        expected[0] = expected.len() as u8;
 
         let time = UNIX_EPOCH + Duration::new(0x1234, 0x5678);
         let ttl = Duration::new(0x8765, 0x4321);
@@ -580,11 +842,20 @@ mod test {
             flags: 0x99,
             blksize: 0xbb,
         };
-        let r = Response::new_entry(INodeNo(0x11), Generation(0xaa), &attr.into(), ttl, ttl);
+        let r = Response::new_entry(
+            INodeNo(0x11),
+            Generation(0xaa),
+            &attr.into(),
+            ttl,
+            ttl,
+            abi::FUSE_KERNEL_MINOR_VERSION,
+        );
         assert_eq!(
             r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec),
             expected
         );
+        let r = Response::new_entry(INodeNo(0x11), Generation(0xaa), &attr.into(), ttl, ttl, 8);
+        assert_eq!(r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec), compat);
     }
 
     #[test]
@@ -615,8 +886,14 @@ mod test {
             ]
         };
 
-        if cfg!(feature = "abi-7-9") {
+        expected[0] = expected.len() as u8;
+        // Kernels before ABI 7.9 expect the fuse_attr without blksize and padding/flags
+        let compat = expected.clone();
+        if cfg!(target_os = "macos") {
             expected.extend_from_slice(&[0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        } else {
+            // Only the FUSE_ATTR_* bits of the flags are sent
+            expected.extend_from_slice(&[0xbb, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
         }
         expected[0] = expected.len() as u8;
 
@@ -639,11 +916,13 @@ mod test {
             flags: 0x99,
             blksize: 0xbb,
         };
-        let r = Response::new_attr(&ttl, &attr.into());
+        let r = Response::new_attr(&ttl, &attr.into(), abi::FUSE_KERNEL_MINOR_VERSION);
         assert_eq!(
             r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec),
             expected
         );
+        let r = Response::new_attr(&ttl, &attr.into(), 8);
+        assert_eq!(r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec), compat);
     }
 
     #[test]
@@ -669,7 +948,7 @@ mod test {
             0x00, 0x00, 0x22, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x33, 0x00, 0x00, 0x00,
             0x00, 0x00, 0x00, 0x00,
         ];
-        let r = Response::new_open(FileHandle(0x1122), 0x33);
+        let r = Response::new_open(FileHandle(0x1122), 0x33, 0);
         assert_eq!(
             r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec),
             expected
@@ -740,14 +1019,22 @@ mod test {
             ]
         };
 
-        if cfg!(feature = "abi-7-9") {
-            let insert_at = expected.len() - 16;
+        expected[0] = expected.len() as u8;
+        // Kernels before ABI 7.9 expect the fuse_attr without blksize and padding/flags
+        let compat = expected.clone();
+        let insert_at = expected.len() - 16;
+        if cfg!(target_os = "macos") {
             expected.splice(
                 insert_at..insert_at,
                 vec![0xdd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
             );
+        } else {
+            expected.splice(
+                insert_at..insert_at,
+                vec![0xdd, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00],
+            );
         }
-        expected[0] = (expected.len()) as u8;
+        expected[0] = expected.len() as u8;
 
         let time = UNIX_EPOCH + Duration::new(0x1234, 0x5678);
         let ttl = Duration::new(0x8765, 0x4321);
@@ -768,11 +1055,29 @@ mod test {
             flags: 0x99,
             blksize: 0xdd,
         };
-        let r = Response::new_create(&ttl, &attr.into(), Generation(0xaa), FileHandle(0xbb), 0xcc);
+        let r = Response::new_create(
+            &ttl,
+            &attr.into(),
+            Generation(0xaa),
+            FileHandle(0xbb),
+            0xcc,
+            0,
+            abi::FUSE_KERNEL_MINOR_VERSION,
+        );
         assert_eq!(
             r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec),
             expected
         );
+        let r = Response::new_create(
+            &ttl,
+            &attr.into(),
+            Generation(0xaa),
+            FileHandle(0xbb),
+            0xcc,
+            0,
+            8,
+        );
+        assert_eq!(r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec), compat);
     }
 
     #[test]
@@ -862,6 +1167,111 @@ mod test {
         );
     }
 
+    #[test]
+    fn readdirplus_from_readdir() {
+        let mut buf = DirEntList::new(4096);
+        assert!(!buf.push(&DirEntry::new(
+            INodeNo(0xaabb),
+            DirEntOffset(1),
+            FileType::Directory,
+            "."
+        )));
+        assert!(!buf.push(&DirEntry::new(
+            INodeNo(0xccdd),
+            DirEntOffset(2),
+            FileType::RegularFile,
+            "world.rs"
+        )));
+        let r: Response = buf.into();
+        let listing = r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec);
+        let listing = AnyReply::try_from(&listing[..]).unwrap();
+        let entries = listing.dirents().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].0.ino, entries[0].0.off), (0xaabb, 1));
+        assert_eq!(entries[0].1, ".");
+        assert_eq!(
+            (entries[1].0.ino, entries[1].0.typ),
+            (0xccdd, libc::S_IFREG >> 12)
+        );
+        assert_eq!(entries[1].1, "world.rs");
+
+        let r = Response::new_entry(
+            INodeNo(0xccdd),
+            Generation(0xaa),
+            &Attr::from(&crate::FileAttr {
+                ino: 0xccdd,
+                size: 0,
+                blocks: 0,
+                atime: UNIX_EPOCH,
+                mtime: UNIX_EPOCH,
+                ctime: UNIX_EPOCH,
+                crtime: UNIX_EPOCH,
+                kind: FileType::RegularFile,
+                perm: 0o644,
+                nlink: 1,
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+                blksize: 0,
+                flags: 0,
+            }),
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            abi::FUSE_KERNEL_MINOR_VERSION,
+        );
+        let entry = r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec);
+        let entry_out = AnyReply::try_from(&entry[..])
+            .unwrap()
+            .entry(abi::FUSE_KERNEL_MINOR_VERSION)
+            .unwrap();
+        assert_eq!((entry_out.nodeid, entry_out.generation), (0xccdd, 0xaa));
+
+        let mut buf = DirEntPlusList::new(4096);
+        let mut entries = entries.into_iter();
+        let (dirent, name) = entries.next().unwrap();
+        assert!(!buf.push_dirent(dirent, name.as_bytes(), None));
+        let (dirent, name) = entries.next().unwrap();
+        assert!(!buf.is_full_for(name.len()));
+        assert!(!buf.push_dirent(dirent, name.as_bytes(), Some(entry_out)));
+        let r: Response = buf.into();
+        let reply = r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec);
+        let entsize = size_of::<abi::fuse_direntplus>();
+        // "." is listed without an entry, "world.rs" with the looked up one
+        assert_eq!(reply.len(), 16 + (entsize + 8) * 2);
+        assert_eq!(reply[16..24], [0; 8]);
+        assert_eq!(
+            reply[16 + entsize - 24..16 + entsize - 16],
+            0xaabbu64.to_le_bytes()
+        );
+        let second = 16 + entsize + 8;
+        assert_eq!(reply[second..second + 8], 0xccddu64.to_le_bytes());
+        assert_eq!(reply[second + 8..second + 16], 0xaau64.to_le_bytes());
+    }
+
+    #[test]
+    fn reply_poll() {
+        let r = Response::new_poll(0x41);
+        assert_eq!(
+            r.with_iovec(RequestId(0xdeadbeef), ioslice_to_vec),
+            vec![
+                0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        );
+    }
+
+    #[test]
+    fn notify_poll_wakeup() {
+        let n = Notification::PollWakeup(0x1122);
+        assert_eq!(
+            n.with_iovec(ioslice_to_vec),
+            vec![
+                0x18, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x22, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        );
+    }
+
     fn ioslice_to_vec(s: &[IoSlice<'_>]) -> Vec<u8> {
         let mut v = Vec::with_capacity(s.iter().map(|x| x.len()).sum());
         for x in s {
@@ -80,7 +80,7 @@ impl From<INodeNo> for u64 {
 ///
 /// This corresponds to a single file description in a client program.  These
 /// are generated by the filesystem implementation in replies to [Open],
-/// [OpenDir] and [Create] requests.  It's used as a correlation id across
+/// [OpenDir], [Create] and [TmpFile] requests.  It's used as a correlation id across
 /// [Read], [Write], [FSync], [IoCtl], [Poll], [FAllocate], [ReadDir],
 /// [FSyncDir], [GetLk], [SetLk], [SetLkW], [ReadDirPlus], [Lseek] and
 /// [CopyFileRange] requests.
@@ -140,13 +140,80 @@ impl From<LockOwner> for u64 {
     }
 }
 
+/// Open flags of a file, such as `O_RDWR`, `O_APPEND` or `O_SYNC`, as passed to `open(2)`
+///
+/// See [Open], [Read], [Write], [Release], [OpenDir], [ReleaseDir], [Create] and [TmpFile].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serializable", derive(Serialize, Deserialize))]
+pub struct OpenFlags(pub i32);
+
+impl OpenFlags {
+    /// The access mode: one of `O_RDONLY`, `O_WRONLY` and `O_RDWR`
+    pub fn access_mode(self) -> i32 {
+        self.0 & libc::O_ACCMODE
+    }
+
+    /// Returns true if all of the given `O_*` flags are set
+    pub fn contains(self, flags: i32) -> bool {
+        self.0 & flags == flags
+    }
+}
+
+impl From<OpenFlags> for i32 {
+    fn from(flags: OpenFlags) -> Self {
+        flags.0
+    }
+}
+
+impl fmt::LowerHex for OpenFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+/// The `FUSE_WRITE_*` flags of a [Write]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serializable", derive(Serialize, Deserialize))]
+pub struct WriteFlags(pub u32);
+
+impl WriteFlags {
+    /// Whether this write is from the page cache (`FUSE_WRITE_CACHE`). The pid, uid, gid and
+    /// file handle of the request may then not be the ones of the process that wrote the data.
+    pub fn cache(self) -> bool {
+        self.0 & abi::consts::FUSE_WRITE_CACHE != 0
+    }
+
+    /// Whether the setuid and setgid bits have to be cleared (`FUSE_WRITE_KILL_SUIDGID`)
+    pub fn kill_suidgid(self) -> bool {
+        self.0 & abi::consts::FUSE_WRITE_KILL_SUIDGID != 0
+    }
+}
+
+impl From<WriteFlags> for u32 {
+    fn from(flags: WriteFlags) -> Self {
+        flags.0
+    }
+}
+
+impl fmt::LowerHex for WriteFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+/// A POSIX file lock
+///
+/// See [GetLk], [SetLk] and [SetLkW].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Lock {
     // Unfortunately this can't be a std::ops::Range because Range is not Copy:
     // https://github.com/rust-lang/rfcs/issues/2848
+    /// Start and end of the locked byte range
     pub range: (u64, u64),
     // TODO: Make typ an enum
+    /// Type of the lock: `F_RDLCK`, `F_WRLCK` or `F_UNLCK`
     pub typ: i32,
+    /// Process holding the lock
     pub pid: u32,
 }
 impl Lock {
@@ -164,9 +231,11 @@ impl Lock {
 #[cfg_attr(feature = "serializable", derive(Serialize, Deserialize))]
 pub struct Version(pub u32, pub u32);
 impl Version {
+    /// The major version
     pub fn major(&self) -> u32 {
         self.0
     }
+    /// The minor version
     pub fn minor(&self) -> u32 {
         self.1
     }
@@ -177,6 +246,24 @@ impl Display for Version {
     }
 }
 
+/// Protocol settings negotiated in `FUSE_INIT` which change the layout of later requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protocol {
+    /// Minor version of the ABI in use, the lower one of the kernel's and ours
+    pub minor: u32,
+    /// Capabilities both offered by the kernel and requested by the filesystem
+    pub flags: u64,
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self {
+            minor: abi::FUSE_KERNEL_MINOR_VERSION,
+            flags: 0,
+        }
+    }
+}
+
 /// Represents a filename in a directory
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct FilenameInDir<'a> {
@@ -207,6 +294,8 @@ impl fmt::Display for RequestError {
 }
 
 impl error::Error for RequestError {}
+
+/// The header fields common to all requests
 pub trait Request: Sized {
     /// Returns the unique identifier of this request.
     ///
@@ -264,20 +353,26 @@ macro_rules! impl_request {
     };
 }
 
-mod op {
+/// Typed requests of every operation
+///
+/// Each struct borrows the raw request and decodes its arguments on access, so fields added
+/// by newer kernels only add methods. The header fields are available through [Request].
+pub mod op {
     use crate::ll::Response;
 
-    use super::{
-        super::{argument::ArgumentIterator, TimeOrNow},
-        FilenameInDir, Request,
-    };
-    use super::{
-        abi::consts::*, abi::*, FileHandle, INodeNo, Lock, LockOwner, Operation, RequestId,
+    use super::super::argument::ArgumentIterator;
+    pub use super::super::TimeOrNow;
+    use super::{abi::consts::*, abi::*, Operation, Protocol};
+    pub use super::{
+        FileHandle, FilenameInDir, INodeNo, Lock, LockOwner, OpenFlags, Request, RequestId,
+        Version, WriteFlags,
     };
     use std::{
+        borrow::Cow,
         convert::TryInto,
         ffi::OsStr,
         fmt::Display,
+        mem,
         num::NonZeroU32,
         path::Path,
         time::{Duration, SystemTime},
@@ -296,6 +391,7 @@ mod op {
     }
     impl_request!(Lookup<'_>);
     impl<'a> Lookup<'a> {
+        /// The name to look up
         pub fn name(&self) -> &'a Path {
             self.name.as_ref()
         }
@@ -332,6 +428,36 @@ mod op {
     }
     impl_request!(GetAttr<'_>);
 
+    /// Get extended file attributes, as requested by `statx(2)`.
+    ///
+    /// The kernel only sends this if the caller asked for more than [GetAttr] provides,
+    /// such as the birth time.  If the filesystem replies with `ENOSYS`, the kernel falls
+    /// back to [GetAttr] for the rest of the session.
+    #[derive(Debug)]
+    pub struct Statx<'a> {
+        header: &'a fuse_in_header,
+        arg: &'a fuse_statx_in,
+    }
+    impl_request!(Statx<'a>);
+    impl<'a> Statx<'a> {
+        /// The value set by the [Open] method, if the caller passed an open file
+        /// descriptor. See [FileHandle].
+        pub fn file_handle(&self) -> Option<FileHandle> {
+            match self.arg.getattr_flags & FUSE_GETATTR_FH {
+                0 => None,
+                _ => Some(FileHandle(self.arg.fh)),
+            }
+        }
+        /// `AT_STATX_*` synchronization flags as passed to statx()
+        pub fn flags(&self) -> u32 {
+            self.arg.sx_flags
+        }
+        /// `STATX_*` bits of the attributes the caller is interested in
+        pub fn mask(&self) -> u32 {
+            self.arg.sx_mask
+        }
+    }
+
     /// Set file attributes.
     #[derive(Debug)]
     pub struct SetAttr<'a> {
@@ -340,30 +466,35 @@ mod op {
     }
     impl_request!(SetAttr<'_>);
     impl<'a> SetAttr<'a> {
+        /// The new file mode, if it is to be changed
         pub fn mode(&self) -> Option<u32> {
             match self.arg.valid & FATTR_MODE {
                 0 => None,
                 _ => Some(self.arg.mode),
             }
         }
+        /// The new owner, if it is to be changed
         pub fn uid(&self) -> Option<u32> {
             match self.arg.valid & FATTR_UID {
                 0 => None,
                 _ => Some(self.arg.uid),
             }
         }
+        /// The new group, if it is to be changed
         pub fn gid(&self) -> Option<u32> {
             match self.arg.valid & FATTR_GID {
                 0 => None,
                 _ => Some(self.arg.gid),
             }
         }
+        /// The new size, if the file is to be truncated or extended
         pub fn size(&self) -> Option<u64> {
             match self.arg.valid & FATTR_SIZE {
                 0 => None,
                 _ => Some(self.arg.size),
             }
         }
+        /// The new access time, if it is to be changed
         pub fn atime(&self) -> Option<TimeOrNow> {
             match self.arg.valid & FATTR_ATIME {
                 0 => None,
@@ -377,6 +508,7 @@ mod op {
                 }),
             }
         }
+        /// The new modification time, if it is to be changed
         pub fn mtime(&self) -> Option<TimeOrNow> {
             match self.arg.valid & FATTR_MTIME {
                 0 => None,
@@ -390,14 +522,12 @@ mod op {
                 }),
             }
         }
+        /// The new status change time, if it is to be changed
         pub fn ctime(&self) -> Option<SystemTime> {
-            #[cfg(feature = "abi-7-23")]
             match self.arg.valid & FATTR_CTIME {
                 0 => None,
                 _ => Some(system_time_from_time(self.arg.ctime, self.arg.ctimensec)),
             }
-            #[cfg(not(feature = "abi-7-23"))]
-            None
         }
         /// The value set by the [Open] method. See [FileHandle].
         ///
@@ -409,6 +539,7 @@ mod op {
                 _ => Some(FileHandle(self.arg.fh)),
             }
         }
+        /// macOS only: The new creation time, if it is to be changed
         pub fn crtime(&self) -> Option<SystemTime> {
             #[cfg(target_os = "macos")]
             match self.arg.valid & FATTR_CRTIME {
@@ -420,6 +551,7 @@ mod op {
             #[cfg(not(target_os = "macos"))]
             None
         }
+        /// macOS only: The new change time, if it is to be changed
         pub fn chgtime(&self) -> Option<SystemTime> {
             #[cfg(target_os = "macos")]
             match self.arg.valid & FATTR_CHGTIME {
@@ -431,6 +563,7 @@ mod op {
             #[cfg(not(target_os = "macos"))]
             None
         }
+        /// macOS only: The new backup time, if it is to be changed
         pub fn bkuptime(&self) -> Option<SystemTime> {
             #[cfg(target_os = "macos")]
             match self.arg.valid & FATTR_BKUPTIME {
@@ -443,6 +576,7 @@ mod op {
             #[cfg(not(target_os = "macos"))]
             None
         }
+        /// macOS only: The new `chflags(2)` flags, if they are to be changed
         pub fn flags(&self) -> Option<u32> {
             #[cfg(target_os = "macos")]
             match self.arg.valid & FATTR_FLAGS {
@@ -452,6 +586,12 @@ mod op {
             #[cfg(not(target_os = "macos"))]
             None
         }
+        /// Whether the setuid and setgid bits have to be cleared (the setgid bit only if the
+        /// file is group executable), as the caller truncates the file without `CAP_FSETID`.
+        /// Only set with `FUSE_HANDLE_KILLPRIV_V2`.
+        pub fn kill_suidgid(&self) -> bool {
+            self.arg.valid & FATTR_KILL_SUIDGID != 0
+        }
 
         // TODO: Why does *set*attr want to have an attr response?
     }
@@ -491,15 +631,26 @@ mod op {
         header: &'a fuse_in_header,
         target: &'a Path,
         link: &'a Path,
+        ext: Extensions<'a>,
     }
     impl_request!(SymLink<'_>);
     impl<'a> SymLink<'a> {
+        /// Name of the symbolic link in the directory
         pub fn target(&self) -> &'a Path {
             self.target
         }
+        /// The path the symbolic link points to
         pub fn link(&self) -> &'a Path {
             self.link
         }
+        /// See [MkNod::security_ctx]
+        pub fn security_ctx(&self) -> &[(&'a OsStr, &'a [u8])] {
+            &self.ext.security_ctx
+        }
+        /// See [MkNod::supplementary_groups]
+        pub fn supplementary_groups(&self) -> &[u32] {
+            &self.ext.groups
+        }
     }
 
     /// Create file node.
@@ -507,26 +658,41 @@ mod op {
     #[derive(Debug)]
     pub struct MkNod<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_mknod_in,
+        arg: Cow<'a, fuse_mknod_in>,
         name: &'a Path,
+        ext: Extensions<'a>,
     }
     impl_request!(MkNod<'_>);
     impl<'a> MkNod<'a> {
+        /// Name of the new node in the directory
         pub fn name(&self) -> &'a Path {
             self.name
         }
+        /// File type and permissions of the new node
         pub fn mode(&self) -> u32 {
             self.arg.mode
         }
+        /// The umask of the caller. Only set with `FUSE_DONT_MASK`, otherwise the kernel already
+        /// applied it to the mode.
         pub fn umask(&self) -> u32 {
-            #[cfg(not(feature = "abi-7-12"))]
-            return 0;
-            #[cfg(feature = "abi-7-12")]
             self.arg.umask
         }
+        /// Device number of a character or block device
         pub fn rdev(&self) -> u32 {
             self.arg.rdev
         }
+        /// Security contexts (name and value) of the new inode, if `FUSE_SECURITY_CTX` was
+        /// negotiated.  The filesystem should store them as extended attributes.
+        pub fn security_ctx(&self) -> &[(&'a OsStr, &'a [u8])] {
+            &self.ext.security_ctx
+        }
+        /// Supplementary groups of the caller, if `FUSE_CREATE_SUPP_GROUP` was negotiated.
+        /// The kernel only passes the group of the parent directory, if the caller is a
+        /// member of it and it differs from the caller's gid. On idmapped mounts, it is
+        /// mapped into the filesystem's idmapping just like the ids of the header.
+        pub fn supplementary_groups(&self) -> &[u32] {
+            &self.ext.groups
+        }
     }
 
     /// Create a directory.
@@ -535,21 +701,30 @@ mod op {
         header: &'a fuse_in_header,
         arg: &'a fuse_mkdir_in,
         name: &'a Path,
+        ext: Extensions<'a>,
     }
     impl_request!(MkDir<'_>);
     impl<'a> MkDir<'a> {
+        /// Name of the new directory
         pub fn name(&self) -> &'a Path {
             self.name
         }
+        /// Permissions of the new directory
         pub fn mode(&self) -> u32 {
             self.arg.mode
         }
+        /// See [MkNod::umask]
         pub fn umask(&self) -> u32 {
-            #[cfg(not(feature = "abi-7-12"))]
-            return 0;
-            #[cfg(feature = "abi-7-12")]
             self.arg.umask
         }
+        /// See [MkNod::security_ctx]
+        pub fn security_ctx(&self) -> &[(&'a OsStr, &'a [u8])] {
+            &self.ext.security_ctx
+        }
+        /// See [MkNod::supplementary_groups]
+        pub fn supplementary_groups(&self) -> &[u32] {
+            &self.ext.groups
+        }
     }
 
     /// Remove a file.
@@ -560,6 +735,7 @@ mod op {
     }
     impl_request!(Unlink<'_>);
     impl<'a> Unlink<'a> {
+        /// Name of the file to remove
         pub fn name(&self) -> &'a Path {
             self.name
         }
@@ -569,10 +745,11 @@ mod op {
     #[derive(Debug)]
     pub struct RmDir<'a> {
         header: &'a fuse_in_header,
-        pub name: &'a Path,
+        name: &'a Path,
     }
     impl_request!(RmDir<'_>);
     impl<'a> RmDir<'a> {
+        /// Name of the directory to remove
         pub fn name(&self) -> &'a Path {
             self.name
         }
@@ -588,12 +765,14 @@ mod op {
     }
     impl_request!(Rename<'_>);
     impl<'a> Rename<'a> {
+        /// The directory and name of the file to rename
         pub fn src(&self) -> FilenameInDir<'a> {
             FilenameInDir::<'a> {
                 dir: self.nodeid(),
                 name: self.name,
             }
         }
+        /// The directory and name to rename the file to
         pub fn dest(&self) -> FilenameInDir<'a> {
             FilenameInDir::<'a> {
                 dir: INodeNo(self.arg.newdir),
@@ -616,6 +795,7 @@ mod op {
         pub fn inode_no(&self) -> INodeNo {
             INodeNo(self.arg.oldnodeid)
         }
+        /// The directory and name of the new link
         pub fn dest(&self) -> FilenameInDir<'a> {
             FilenameInDir::<'a> {
                 dir: self.nodeid(),
@@ -640,8 +820,15 @@ mod op {
     }
     impl_request!(Open<'_>);
     impl<'a> Open<'a> {
-        pub fn flags(&self) -> i32 {
-            self.arg.flags
+        /// Flags as passed to open
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
+        }
+        /// Whether the setuid and setgid bits have to be cleared if the file is executable,
+        /// as the caller opens it with `O_TRUNC` without `CAP_FSETID`. Only set with
+        /// `FUSE_HANDLE_KILLPRIV_V2`.
+        pub fn kill_suidgid(&self) -> bool {
+            self.arg.open_flags & FUSE_OPEN_KILL_SUIDGID != 0
         }
     }
 
@@ -655,7 +842,7 @@ mod op {
     #[derive(Debug)]
     pub struct Read<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_read_in,
+        arg: Cow<'a, fuse_read_in>,
     }
     impl_request!(Read<'_>);
     impl<'a> Read<'a> {
@@ -663,17 +850,16 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Offset in the file to read from
         pub fn offset(&self) -> i64 {
             self.arg.offset
         }
+        /// Number of bytes to read
         pub fn size(&self) -> u32 {
             self.arg.size
         }
         /// Only supported with ABI >= 7.9
         pub fn lock_owner(&self) -> Option<LockOwner> {
-            #[cfg(not(feature = "abi-7-9"))]
-            return None;
-            #[cfg(feature = "abi-7-9")]
             if self.arg.read_flags & FUSE_READ_LOCKOWNER != 0 {
                 Some(LockOwner(self.arg.lock_owner))
             } else {
@@ -681,11 +867,8 @@ mod op {
             }
         }
         /// The file flags, such as `O_SYNC`. Only supported with ABI >= 7.9
-        pub fn flags(&self) -> i32 {
-            #[cfg(not(feature = "abi-7-9"))]
-            return 0;
-            #[cfg(feature = "abi-7-9")]
-            self.arg.flags
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
         }
     }
 
@@ -698,7 +881,7 @@ mod op {
     #[derive(Debug)]
     pub struct Write<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_write_in,
+        arg: Cow<'a, fuse_write_in>,
         data: &'a [u8],
     }
     impl_request!(Write<'_>);
@@ -707,38 +890,37 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Offset in the file to write to
         pub fn offset(&self) -> i64 {
             self.arg.offset
         }
+        /// The data to write
         pub fn data(&self) -> &'a [u8] {
             self.data
         }
         /// Will contain FUSE_WRITE_CACHE, if this write is from the page cache. If set,
         /// the pid, uid, gid, and fh may not match the value that would have been sent if write caching
         /// is disabled
-        ///
-        /// TODO: WriteFlags type or remove this
-        pub fn write_flags(&self) -> u32 {
-            self.arg.write_flags
+        pub fn write_flags(&self) -> WriteFlags {
+            WriteFlags(self.arg.write_flags)
         }
         /// lock_owner: only supported with ABI >= 7.9
         pub fn lock_owner(&self) -> Option<LockOwner> {
-            #[cfg(feature = "abi-7-9")]
             if self.arg.write_flags & FUSE_WRITE_LOCKOWNER != 0 {
                 Some(LockOwner(self.arg.lock_owner))
             } else {
                 None
             }
-            #[cfg(not(feature = "abi-7-9"))]
-            None
         }
         /// flags: these are the file flags, such as O_SYNC. Only supported with ABI >= 7.9
-        /// TODO: Make a Flags type specifying valid values
-        pub fn flags(&self) -> i32 {
-            #[cfg(feature = "abi-7-9")]
-            return self.arg.flags;
-            #[cfg(not(feature = "abi-7-9"))]
-            0
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
+        }
+        /// Whether the setuid and setgid bits have to be cleared (the setgid bit only if the
+        /// file is group executable), as the caller writes without `CAP_FSETID`. Only set
+        /// with `FUSE_HANDLE_KILLPRIV_V2`.
+        pub fn kill_suidgid(&self) -> bool {
+            self.arg.write_flags & FUSE_WRITE_KILL_SUIDGID != 0
         }
     }
 
@@ -763,6 +945,7 @@ mod op {
     }
     impl_request!(Release<'_>);
     impl<'a> Release<'a> {
+        /// Whether the file is to be flushed before it's released
         pub fn flush(&self) -> bool {
             self.arg.release_flags & FUSE_RELEASE_FLUSH != 0
         }
@@ -772,13 +955,11 @@ mod op {
         }
         /// The same flags as for open.
         /// TODO: Document what flags are valid, or remove this
-        pub fn flags(&self) -> i32 {
-            self.arg.flags
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
         }
+        /// Owner of the `flock(2)` locks to release, if any
         pub fn lock_owner(&self) -> Option<LockOwner> {
-            #[cfg(not(feature = "abi-7-17"))]
-            return Some(LockOwner(self.arg.lock_owner));
-            #[cfg(feature = "abi-7-17")]
             if self.arg.release_flags & FUSE_RELEASE_FLOCK_UNLOCK != 0 {
                 Some(LockOwner(self.arg.lock_owner))
             } else {
@@ -810,21 +991,30 @@ mod op {
     pub struct SetXAttr<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_setxattr_in,
+        /// Only sent if `FUSE_SETXATTR_EXT` was negotiated
+        setxattr_flags: u32,
         name: &'a OsStr,
         value: &'a [u8],
     }
     impl_request!(SetXAttr<'a>);
     impl<'a> SetXAttr<'a> {
+        /// Name of the XAttr
         pub fn name(&self) -> &'a OsStr {
             self.name
         }
+        /// The value to set
         pub fn value(&self) -> &'a [u8] {
             self.value
         }
         // TODO: Document what are valid flags
+        /// `XATTR_CREATE` or `XATTR_REPLACE`, as passed to setxattr()
         pub fn flags(&self) -> i32 {
             self.arg.flags
         }
+        /// `FUSE_SETXATTR_*` flags. Always 0 unless `FUSE_SETXATTR_EXT` was negotiated.
+        pub fn setxattr_flags(&self) -> u32 {
+            self.setxattr_flags
+        }
         /// This will always be 0 except on MacOS.  It's recommended that
         /// implementations return EINVAL if this is not 0.
         pub fn position(&self) -> u32 {
@@ -871,8 +1061,9 @@ mod op {
         ///
         /// You only need to check this value as an optimisation where there's a
         /// cost difference between checking the size of the data stored in an XAttr
-        /// and actually providing the data.  Otherwise just call [reply()] with the
-        /// data and it will do the right thing.
+        /// and actually providing the data.  Otherwise just call
+        /// [`ReplyXattr::data`](crate::ReplyXattr::data) with the data and it will do the
+        /// right thing.
         pub fn size(&self) -> GetXAttrSizeEnum {
             let s: Result<NonZeroU32, _> = self.arg.size.try_into();
             match s {
@@ -943,53 +1134,67 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Owner of the locks to remove
         pub fn lock_owner(&self) -> LockOwner {
             LockOwner(self.arg.lock_owner)
         }
     }
 
+    /// Initialize the filesystem.
+    ///
+    /// This is the first request of a session. The reply carries the negotiated protocol
+    /// version and settings.
     #[derive(Debug)]
     pub struct Init<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_init_in,
+        arg: Cow<'a, fuse_init_in>,
     }
     impl_request!(Init<'a>);
     impl<'a> Init<'a> {
-        pub fn capabilities(&self) -> u32 {
-            self.arg.flags
+        /// Capabilities offered by the kernel. With `FUSE_INIT_EXT`, the upper 32 bits are
+        /// taken from the second flags word.
+        pub fn capabilities(&self) -> u64 {
+            #[cfg(not(target_os = "macos"))]
+            if self.arg.flags as u64 & FUSE_INIT_EXT != 0 {
+                return self.arg.flags as u64 | (self.arg.flags2 as u64) << 32;
+            }
+            self.arg.flags as u64
         }
+        /// Maximum readahead offered by the kernel
         pub fn max_readahead(&self) -> u32 {
             self.arg.max_readahead
         }
-        pub fn version(&self) -> super::Version {
-            super::Version(self.arg.major, self.arg.minor)
+        /// ABI version of the kernel
+        pub fn version(&self) -> Version {
+            Version(self.arg.major, self.arg.minor)
         }
 
-        pub fn reply(&self, config: &crate::KernelConfig) -> Response {
+        pub(crate) fn reply(&self, config: &crate::KernelConfig) -> Response {
+            // use requested features and reported as capable
+            let flags = self.capabilities() & config.requested;
             let init = fuse_init_out {
                 major: FUSE_KERNEL_VERSION,
                 minor: FUSE_KERNEL_MINOR_VERSION,
                 max_readahead: config.max_readahead,
-                flags: self.capabilities() & config.requested, // use requested features and reported as capable
-                #[cfg(not(feature = "abi-7-13"))]
-                unused: 0,
-                #[cfg(feature = "abi-7-13")]
+                flags: flags as u32,
                 max_background: config.max_background,
-                #[cfg(feature = "abi-7-13")]
                 congestion_threshold: config.congestion_threshold(),
                 max_write: config.max_write,
-                #[cfg(feature = "abi-7-23")]
                 time_gran: config.time_gran.as_nanos() as u32,
-                #[cfg(all(feature = "abi-7-23", not(feature = "abi-7-28")))]
-                reserved: [0; 9],
-                #[cfg(feature = "abi-7-28")]
                 max_pages: config.max_pages(),
-                #[cfg(feature = "abi-7-28")]
-                unused2: 0,
-                #[cfg(feature = "abi-7-28")]
-                reserved: [0; 8],
+                map_alignment: config.map_alignment(),
+                flags2: (flags >> 32) as u32,
+                max_stack_depth: config.max_stack_depth(),
+                request_timeout: config.request_timeout(),
+                unused: [0; 11],
+            };
+            // Kernels with an older ABI expect a shorter reply
+            let size = match self.arg.minor {
+                minor if minor < 5 => FUSE_COMPAT_INIT_OUT_SIZE,
+                minor if minor < 23 => FUSE_COMPAT_22_INIT_OUT_SIZE,
+                _ => mem::size_of::<fuse_init_out>(),
             };
-            Response::new_data(init.as_bytes())
+            Response::new_data(&init.as_bytes()[..size])
         }
     }
 
@@ -1011,8 +1216,8 @@ mod op {
     impl_request!(OpenDir<'a>);
     impl<'a> OpenDir<'a> {
         /// Flags as passed to open
-        pub fn flags(&self) -> i32 {
-            self.arg.flags
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
         }
     }
 
@@ -1020,7 +1225,7 @@ mod op {
     #[derive(Debug)]
     pub struct ReadDir<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_read_in,
+        arg: Cow<'a, fuse_read_in>,
     }
     impl_request!(ReadDir<'a>);
     impl<'a> ReadDir<'a> {
@@ -1028,9 +1233,12 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Offset of the entry to continue at, as set in a previous reply, or 0 to start at the
+        /// first entry
         pub fn offset(&self) -> i64 {
             self.arg.offset
         }
+        /// Maximum size of the reply in bytes
         pub fn size(&self) -> u32 {
             self.arg.size
         }
@@ -1050,13 +1258,12 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Whether the directory is to be flushed before it's released
         pub fn flush(&self) -> bool {
             self.arg.release_flags & consts::FUSE_RELEASE_FLUSH != 0
         }
+        /// Owner of the `flock(2)` locks to release, if any
         pub fn lock_owner(&self) -> Option<LockOwner> {
-            #[cfg(not(feature = "abi-7-17"))]
-            return Some(LockOwner(self.arg.lock_owner));
-            #[cfg(feature = "abi-7-17")]
             if self.arg.release_flags & FUSE_RELEASE_FLOCK_UNLOCK != 0 {
                 Some(LockOwner(self.arg.lock_owner))
             } else {
@@ -1064,8 +1271,8 @@ mod op {
             }
         }
         /// TODO: Document what values this may take
-        pub fn flags(&self) -> i32 {
-            self.arg.flags
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
         }
     }
 
@@ -1091,7 +1298,7 @@ mod op {
     #[derive(Debug)]
     pub struct GetLk<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_lk_in,
+        arg: Cow<'a, fuse_lk_in>,
     }
     impl_request!(GetLk<'a>);
     impl<'a> GetLk<'a> {
@@ -1099,9 +1306,11 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// The lock to test for
         pub fn lock(&self) -> Lock {
             Lock::from_abi(&self.arg.lk)
         }
+        /// Owner of the lock to test for
         pub fn lock_owner(&self) -> LockOwner {
             LockOwner(self.arg.owner)
         }
@@ -1118,7 +1327,7 @@ mod op {
     #[derive(Debug)]
     pub struct SetLk<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_lk_in,
+        arg: Cow<'a, fuse_lk_in>,
     }
     impl_request!(SetLk<'a>);
     impl<'a> SetLk<'a> {
@@ -1126,17 +1335,21 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// The lock to acquire, modify or release
         pub fn lock(&self) -> Lock {
             Lock::from_abi(&self.arg.lk)
         }
+        /// Owner of the lock
         pub fn lock_owner(&self) -> LockOwner {
             LockOwner(self.arg.owner)
         }
     }
+    /// Like [SetLk], but waits until the lock can be acquired instead of failing with
+    /// `EAGAIN`.
     #[derive(Debug)]
     pub struct SetLkW<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_lk_in,
+        arg: Cow<'a, fuse_lk_in>,
     }
     impl_request!(SetLkW<'a>);
     impl<'a> SetLkW<'a> {
@@ -1144,9 +1357,11 @@ mod op {
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// The lock to acquire, modify or release
         pub fn lock(&self) -> Lock {
             Lock::from_abi(&self.arg.lk)
         }
+        /// Owner of the lock
         pub fn lock_owner(&self) -> LockOwner {
             LockOwner(self.arg.owner)
         }
@@ -1163,6 +1378,7 @@ mod op {
     }
     impl_request!(Access<'a>);
     impl<'a> Access<'a> {
+        /// `R_OK`, `W_OK` and `X_OK` bits to check, or `F_OK` to check for existence
         pub fn mask(&self) -> i32 {
             self.arg.mask
         }
@@ -1182,25 +1398,65 @@ mod op {
     #[derive(Debug)]
     pub struct Create<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_create_in,
+        arg: Cow<'a, fuse_create_in>,
         name: &'a Path,
+        ext: Extensions<'a>,
     }
     impl_request!(Create<'a>);
     impl<'a> Create<'a> {
+        /// Name of the new file
         pub fn name(&self) -> &'a Path {
             self.name
         }
+        /// File type and permissions of the new file
         pub fn mode(&self) -> u32 {
             self.arg.mode
         }
         /// Flags as passed to the creat() call
-        pub fn flags(&self) -> i32 {
-            self.arg.flags
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
         }
+        /// See [MkNod::umask]
+        pub fn umask(&self) -> u32 {
+            self.arg.umask
+        }
+        /// See [Open::kill_suidgid]
+        pub fn kill_suidgid(&self) -> bool {
+            self.arg.open_flags & FUSE_OPEN_KILL_SUIDGID != 0
+        }
+        /// See [MkNod::security_ctx]
+        pub fn security_ctx(&self) -> &[(&'a OsStr, &'a [u8])] {
+            &self.ext.security_ctx
+        }
+        /// See [MkNod::supplementary_groups]
+        pub fn supplementary_groups(&self) -> &[u32] {
+            &self.ext.groups
+        }
+    }
+
+    /// Create and open an unnamed file in a directory, as requested by `open(2)` with
+    /// `O_TMPFILE`.
+    ///
+    /// The arguments have the same layout as [Create], but the name is only a placeholder
+    /// and is not exposed.  The new inode starts out with a link count of zero; it is
+    /// given a name by a later [Link] request if the client calls `linkat(2)` on it.
+    #[derive(Debug)]
+    pub struct TmpFile<'a> {
+        header: &'a fuse_in_header,
+        arg: Cow<'a, fuse_create_in>,
+    }
+    impl_request!(TmpFile<'a>);
+    impl<'a> TmpFile<'a> {
+        /// File type and permissions of the new file
+        pub fn mode(&self) -> u32 {
+            self.arg.mode
+        }
+        /// Flags as passed to the open() call
+        pub fn flags(&self) -> OpenFlags {
+            OpenFlags(self.arg.flags)
+        }
+        /// See [MkNod::umask]
         pub fn umask(&self) -> u32 {
-            #[cfg(not(feature = "abi-7-12"))]
-            return 0;
-            #[cfg(feature = "abi-7-12")]
             self.arg.umask
         }
     }
@@ -1225,7 +1481,7 @@ mod op {
     ///
     /// The userspace filesystem may ignore the [Interrupt] requests entirely,
     /// or may honor them by sending a reply to the **original** request, with
-    /// the error set to [Errno::EINTR].
+    /// the error set to [Errno::EINTR](crate::ll::Errno::EINTR).
     ///
     /// It is also possible that there's a race between processing the
     /// original request and its [Interrupt] request.  There are two
@@ -1239,7 +1495,7 @@ mod op {
     ///
     /// If the filesystem cannot find the original request, it should wait for
     /// some timeout and/or a number of new requests to arrive, after which it
-    /// should reply to the [Interrupt] request with an [Errno::EAGAIN] error.
+    /// should reply to the [Interrupt] request with an [Errno::EAGAIN](crate::ll::Errno::EAGAIN) error.
     /// In case (1) the [Interrupt] request will be requeued.  In case (2) the
     /// [Interrupt] reply will be ignored.
     #[derive(Debug)]
@@ -1249,6 +1505,7 @@ mod op {
     }
     impl_request!(Interrupt<'a>);
     impl<'a> Interrupt<'a> {
+        /// Unique ID of the request to interrupt
         pub fn unique(&self) -> RequestId {
             RequestId(self.arg.unique)
         }
@@ -1264,98 +1521,120 @@ mod op {
     }
     impl_request!(BMap<'a>);
     impl<'a> BMap<'a> {
+        /// Block size of the filesystem
         pub fn block_size(&self) -> u32 {
             self.arg.blocksize
         }
+        /// Block index within the file
         pub fn block(&self) -> u64 {
             self.arg.block
         }
     }
 
+    /// Clean up the filesystem. This is the last request of a session.
     #[derive(Debug)]
     pub struct Destroy<'a> {
         header: &'a fuse_in_header,
     }
     impl_request!(Destroy<'a>);
     impl<'a> Destroy<'a> {
-        pub fn reply(&self) -> Response {
+        pub(crate) fn reply(&self) -> Response {
             Response::new_empty()
         }
     }
 
     /// Control device
-    #[cfg(feature = "abi-7-11")]
     #[derive(Debug)]
     pub struct IoCtl<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_ioctl_in,
         data: &'a [u8],
     }
-    #[cfg(feature = "abi-7-11")]
     impl_request!(IoCtl<'a>);
-    #[cfg(feature = "abi-7-11")]
     impl<'a> IoCtl<'a> {
+        /// The input data of the ioctl
         pub fn in_data(&self) -> &[u8] {
             &self.data[..self.arg.in_size as usize]
         }
+        /// Whether the ioctl is not restricted to well-formed ioctls. The input and
+        /// output buffers of an unrestricted ioctl are unknown to the kernel at first and
+        /// must be requested with a retry reply.
         pub fn unrestricted(&self) -> bool {
             self.arg.flags & consts::FUSE_IOCTL_UNRESTRICTED != 0
         }
+        /// Whether the ioctl was issued by a 32 bit process on a 64 bit kernel. Addresses
+        /// in a retry reply must then fit into 32 bits.
+        pub fn compat(&self) -> bool {
+            crate::ll::ioctl_is_compat(self.arg.flags)
+        }
+        /// User space address of the ioctl argument, used to build the iovecs of a retry
+        pub fn arg(&self) -> u64 {
+            self.arg.arg
+        }
         /// The value set by the [Open] method. See [FileHandle].
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
-        /// TODO: What are valid values here?
+        /// `FUSE_IOCTL_*` flags
         pub fn flags(&self) -> u32 {
             self.arg.flags
         }
-        /// TODO: What does this mean?
+        /// The ioctl request code
         pub fn command(&self) -> u32 {
             self.arg.cmd
         }
+        /// Maximum size of the output data
         pub fn out_size(&self) -> u32 {
             self.arg.out_size
         }
     }
 
-    /// Poll.  TODO: currently unsupported by fuser
-    #[cfg(feature = "abi-7-11")]
+    /// Poll for IO readiness events.
+    ///
+    /// Only served for CUSE character devices so far; FUSE filesystems receive `ENOSYS`.
     #[derive(Debug)]
     pub struct Poll<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_poll_in,
     }
-    #[cfg(feature = "abi-7-11")]
     impl_request!(Poll<'a>);
-    #[cfg(feature = "abi-7-11")]
     impl<'a> Poll<'a> {
         /// The value set by the [Open] method. See [FileHandle].
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Kernel handle identifying this poll waiter. Pass it to a poll wakeup
+        /// notification once one of the requested events becomes ready.
+        pub fn kernel_handle(&self) -> u64 {
+            self.arg.kh
+        }
+        /// Poll flags. `FUSE_POLL_SCHEDULE_NOTIFY` is set if the caller wants to be
+        /// notified when the readiness changes.
+        pub fn flags(&self) -> u32 {
+            self.arg.flags
+        }
+        /// Requested poll events (`POLLIN`, `POLLOUT`, ...). Only supported with ABI >= 7.21
+        pub fn events(&self) -> u32 {
+            self.arg.events
+        }
     }
 
     /// NotifyReply.  TODO: currently unsupported by fuser
-    #[cfg(feature = "abi-7-15")]
     #[derive(Debug)]
     pub struct NotifyReply<'a> {
         header: &'a fuse_in_header,
         arg: &'a [u8],
     }
-    #[cfg(feature = "abi-7-15")]
     impl_request!(NotifyReply<'a>);
 
     /// BatchForget: TODO: merge with Forget
-    #[cfg(feature = "abi-7-16")]
     #[derive(Debug)]
     pub struct BatchForget<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_batch_forget_in,
         nodes: &'a [fuse_forget_one],
     }
-    #[cfg(feature = "abi-7-16")]
     impl_request!(BatchForget<'a>);
-    #[cfg(feature = "abi-7-16")]
     impl<'a> BatchForget<'a> {
         /// TODO: Don't return fuse_forget_one, this should be private
         pub fn nodes(&self) -> &'a [fuse_forget_one] {
@@ -1366,23 +1645,24 @@ mod op {
     /// Preallocate or deallocate space to a file
     ///
     /// Implementations should return EINVAL if offset or length are < 0
-    #[cfg(feature = "abi-7-19")]
     #[derive(Debug)]
     pub struct FAllocate<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_fallocate_in,
     }
-    #[cfg(feature = "abi-7-19")]
     impl_request!(FAllocate<'a>);
-    #[cfg(feature = "abi-7-19")]
+    // The length is an argument of the request, not of a collection
+    #[allow(clippy::len_without_is_empty)]
     impl<'a> FAllocate<'a> {
         /// The value set by the [Open] method. See [FileHandle].
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Offset in the file to start at
         pub fn offset(&self) -> i64 {
             self.arg.offset
         }
+        /// Number of bytes to allocate or deallocate
         pub fn len(&self) -> i64 {
             self.arg.length
         }
@@ -1395,32 +1675,45 @@ mod op {
     /// Read directory.
     ///
     /// TODO: Document when this is called rather than ReadDirectory
-    #[cfg(feature = "abi-7-21")]
     #[derive(Debug)]
     pub struct ReadDirPlus<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_read_in,
     }
-    #[cfg(feature = "abi-7-21")]
     impl_request!(ReadDirPlus<'a>);
-    #[cfg(feature = "abi-7-21")]
     impl<'a> ReadDirPlus<'a> {
         /// The value set by the [Open] method. See [FileHandle].
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Offset of the entry to continue at, as set in a previous reply, or 0 to start at the
+        /// first entry
         pub fn offset(&self) -> i64 {
             self.arg.offset
         }
+        /// Maximum size of the reply in bytes
         pub fn size(&self) -> u32 {
             self.arg.size
         }
+        /// The [ReadDir] request listing the same entries, for filesystems that fall back to it
+        pub(crate) fn readdir(&self) -> ReadDir<'a> {
+            ReadDir {
+                header: self.header,
+                arg: Cow::Borrowed(self.arg),
+            }
+        }
+        /// A [Lookup] request of an entry of this directory
+        pub(crate) fn lookup<'b>(&'b self, name: &'b OsStr) -> Lookup<'b> {
+            Lookup {
+                header: self.header,
+                name,
+            }
+        }
     }
 
     /// Rename a file.
     ///
-    /// TODO: Document the differences to [Rename] and [Exchange]
-    #[cfg(feature = "abi-7-23")]
+    /// TODO: Document the differences to [Rename] and `Exchange` (macOS only)
     #[derive(Debug)]
     pub struct Rename2<'a> {
         header: &'a fuse_in_header,
@@ -1429,16 +1722,16 @@ mod op {
         newname: &'a Path,
         old_parent: INodeNo,
     }
-    #[cfg(feature = "abi-7-23")]
     impl_request!(Rename2<'a>);
-    #[cfg(feature = "abi-7-23")]
     impl<'a> Rename2<'a> {
+        /// The directory and name of the file to rename
         pub fn from(&self) -> FilenameInDir<'a> {
             FilenameInDir::<'a> {
                 dir: self.old_parent,
                 name: self.name,
             }
         }
+        /// The directory and name to rename the file to
         pub fn to(&self) -> FilenameInDir<'a> {
             FilenameInDir::<'a> {
                 dir: INodeNo(self.arg.newdir),
@@ -1459,20 +1752,18 @@ mod op {
     /// Reposition read/write file offset
     ///
     /// TODO: Document when you need to implement this.  Read and Write provide the offset anyway.
-    #[cfg(feature = "abi-7-24")]
     #[derive(Debug)]
     pub struct Lseek<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_lseek_in,
     }
-    #[cfg(feature = "abi-7-24")]
     impl_request!(Lseek<'a>);
-    #[cfg(feature = "abi-7-24")]
     impl<'a> Lseek<'a> {
         /// The value set by the [Open] method. See [FileHandle].
         pub fn file_handle(&self) -> FileHandle {
             FileHandle(self.arg.fh)
         }
+        /// Offset relative to `whence`
         pub fn offset(&self) -> i64 {
             self.arg.offset
         }
@@ -1482,23 +1773,26 @@ mod op {
         }
     }
 
-    /// Copy the specified range from the source inode to the destination inode
+    /// Source or destination of a [CopyFileRange]
     #[derive(Debug, Clone, Copy)]
     pub struct CopyFileRangeFile {
+        /// The inode of the file
         pub inode: INodeNo,
         /// The value set by the [Open] method. See [FileHandle].
         pub file_handle: FileHandle,
+        /// Offset in the file
         pub offset: i64,
     }
-    #[cfg(feature = "abi-7-28")]
+    /// Copy the specified range from the source inode to the destination inode, as requested
+    /// by `copy_file_range(2)`
     #[derive(Debug)]
     pub struct CopyFileRange<'a> {
         header: &'a fuse_in_header,
         arg: &'a fuse_copy_file_range_in,
     }
-    #[cfg(feature = "abi-7-28")]
     impl_request!(CopyFileRange<'a>);
-    #[cfg(feature = "abi-7-28")]
+    // The length is an argument of the request, not of a collection
+    #[allow(clippy::len_without_is_empty)]
     impl<'a> CopyFileRange<'a> {
         /// File and offset to copy data from
         pub fn src(&self) -> CopyFileRangeFile {
@@ -1521,11 +1815,22 @@ mod op {
             self.arg.len
         }
         // API TODO: Return a specific flags type
+        /// Flags as passed to copy_file_range(). Always 0 so far
         pub fn flags(&self) -> u64 {
             self.arg.flags
         }
     }
 
+    /// Synchronize the whole filesystem, as requested by `syncfs(2)`.
+    ///
+    /// The node ID is the root of the mount.  Filesystems that cache writes should flush
+    /// all dirty data and metadata to the backing store before replying.
+    #[derive(Debug)]
+    pub struct SyncFs<'a> {
+        header: &'a fuse_in_header,
+    }
+    impl_request!(SyncFs<'a>);
+
     /// MacOS only: Rename the volume. Set `fuse_init_out.flags` during init to
     /// `FUSE_VOL_RENAME` to enable
     #[cfg(target_os = "macos")]
@@ -1582,15 +1887,44 @@ mod op {
             self.arg.options
         }
     }
-    /// TODO: Document
-    #[cfg(feature = "abi-7-12")]
+    /// Initialize a CUSE character device.
+    ///
+    /// This is the first request sent on a `/dev/cuse` channel. The reply carries the
+    /// negotiated settings, the device number and the device info strings (e.g.
+    /// `DEVNAME=foo`) the kernel uses to create the device node.
     #[derive(Debug)]
     pub struct CuseInit<'a> {
         header: &'a fuse_in_header,
-        arg: &'a fuse_init_in,
+        arg: &'a cuse_init_in,
     }
-    #[cfg(feature = "abi-7-12")]
     impl_request!(CuseInit<'a>);
+    impl<'a> CuseInit<'a> {
+        /// ABI version of the kernel
+        pub fn version(&self) -> Version {
+            Version(self.arg.major, self.arg.minor)
+        }
+        /// `CUSE_*` flags offered by the kernel
+        pub fn flags(&self) -> u32 {
+            self.arg.flags
+        }
+
+        pub(crate) fn reply(&self, config: &crate::CuseConfig) -> Response {
+            let init = cuse_init_out {
+                major: FUSE_KERNEL_VERSION,
+                minor: FUSE_KERNEL_MINOR_VERSION,
+                unused: 0,
+                flags: self.flags() & config.flags(),
+                max_read: config.max_read,
+                max_write: config.max_write,
+                dev_major: config.dev_major,
+                dev_minor: config.dev_minor,
+                spare: [0; 10],
+            };
+            let mut data = init.as_bytes().to_vec();
+            data.extend_from_slice(&config.device_info());
+            Response::new_data(data)
+        }
+    }
 
     fn system_time_from_time(secs: i64, nsecs: u32) -> SystemTime {
         if secs >= 0 {
@@ -1599,10 +1933,75 @@ mod op {
             SystemTime::UNIX_EPOCH - Duration::new((-secs) as u64, nsecs)
         }
     }
+    /// Size of the argument struct `T` as sent by the kernel, which is `compat_size` if the
+    /// negotiated ABI predates the minor version `since` that extended the struct
+    fn arg_size<T>(protocol: &Protocol, since: u32, compat_size: usize) -> usize {
+        if protocol.minor < since {
+            compat_size
+        } else {
+            mem::size_of::<T>()
+        }
+    }
+
+    /// Security contexts (name and value) of a new inode
+    type SecurityContexts<'a> = Vec<(&'a OsStr, &'a [u8])>;
+
+    /// Extensions the kernel appends to requests creating an inode
+    #[derive(Debug, Default)]
+    struct Extensions<'a> {
+        security_ctx: SecurityContexts<'a>,
+        groups: Vec<u32>,
+    }
+
+    /// Fetch the extensions the kernel appends to requests creating an inode, if
+    /// `FUSE_SECURITY_CTX` or `FUSE_CREATE_SUPP_GROUP` was negotiated. They directly follow
+    /// the last name, so nothing is aligned.
+    fn fetch_extensions<'a>(
+        data: &mut ArgumentIterator<'a>,
+        protocol: &Protocol,
+    ) -> Option<Extensions<'a>> {
+        let mut extensions = Extensions::default();
+        #[cfg(not(target_os = "macos"))]
+        let negotiated = protocol.flags & (FUSE_SECURITY_CTX | FUSE_CREATE_SUPP_GROUP) != 0;
+        #[cfg(target_os = "macos")]
+        let negotiated = false;
+        if !negotiated {
+            return Some(extensions);
+        }
+        while !data.is_empty() {
+            let ext: fuse_ext_header = data.fetch_unaligned()?;
+            let size = (ext.size as usize).checked_sub(mem::size_of::<fuse_ext_header>())?;
+            let mut ext_data = ArgumentIterator::new(data.fetch_slice::<u8>(size)?);
+            match ext.typ {
+                FUSE_EXT_GROUPS => {
+                    let groups: fuse_supp_groups = ext_data.fetch_unaligned()?;
+                    for _ in 0..groups.nr_groups {
+                        extensions.groups.push(ext_data.fetch_unaligned()?);
+                    }
+                }
+                count if count <= FUSE_MAX_NR_SECCTX => {
+                    for _ in 0..count {
+                        let ctx: fuse_secctx = ext_data.fetch_unaligned()?;
+                        let name = ext_data.fetch_str()?;
+                        let value = ext_data.fetch_slice::<u8>(ctx.size as usize)?;
+                        // Each context is padded to 8 bytes
+                        let len = mem::size_of::<fuse_secctx>() + name.len() + 1 + value.len();
+                        let padding = (8 - len % 8) % 8;
+                        ext_data.fetch_slice::<u8>(padding.min(ext_data.len()))?;
+                        extensions.security_ctx.push((name, value));
+                    }
+                }
+                // Unknown extensions
+                _ => {}
+            }
+        }
+        Some(extensions)
+    }
     pub(crate) fn parse<'a>(
         header: &'a fuse_in_header,
         opcode: &fuse_opcode,
         data: &'a [u8],
+        protocol: &Protocol,
     ) -> Option<Operation<'a>> {
         let mut data = ArgumentIterator::new(data);
         Some(match opcode {
@@ -1624,16 +2023,23 @@ mod op {
                 header,
                 target: data.fetch_str()?.as_ref(),
                 link: data.fetch_str()?.as_ref(),
+                ext: fetch_extensions(&mut data, protocol)?,
             }),
             fuse_opcode::FUSE_MKNOD => Operation::MkNod(MkNod {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_mknod_in>(
+                    protocol,
+                    12,
+                    FUSE_COMPAT_MKNOD_IN_SIZE,
+                ))?,
                 name: data.fetch_str()?.as_ref(),
+                ext: fetch_extensions(&mut data, protocol)?,
             }),
             fuse_opcode::FUSE_MKDIR => Operation::MkDir(MkDir {
                 header,
                 arg: data.fetch()?,
                 name: data.fetch_str()?.as_ref(),
+                ext: fetch_extensions(&mut data, protocol)?,
             }),
             fuse_opcode::FUSE_UNLINK => Operation::Unlink(Unlink {
                 header,
@@ -1660,12 +2066,20 @@ mod op {
             }),
             fuse_opcode::FUSE_READ => Operation::Read(Read {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_read_in>(
+                    protocol,
+                    9,
+                    FUSE_COMPAT_READ_IN_SIZE,
+                ))?,
             }),
             fuse_opcode::FUSE_WRITE => Operation::Write({
                 let out = Write {
                     header,
-                    arg: data.fetch()?,
+                    arg: data.fetch_truncated(arg_size::<fuse_write_in>(
+                        protocol,
+                        9,
+                        FUSE_COMPAT_WRITE_IN_SIZE,
+                    ))?,
                     data: data.fetch_all(),
                 };
                 assert!(out.data().len() == out.arg.size as usize);
@@ -1681,9 +2095,22 @@ mod op {
                 arg: data.fetch()?,
             }),
             fuse_opcode::FUSE_SETXATTR => Operation::SetXAttr({
+                let arg = data.fetch()?;
+                #[cfg(not(target_os = "macos"))]
+                let setxattr_flags = if protocol.flags & FUSE_SETXATTR_EXT != 0 {
+                    // The extended fuse_setxattr_in appends setxattr_flags and padding
+                    let setxattr_flags: &u32 = data.fetch()?;
+                    let _padding: &u32 = data.fetch()?;
+                    *setxattr_flags
+                } else {
+                    0
+                };
+                #[cfg(target_os = "macos")]
+                let setxattr_flags = 0;
                 let out = SetXAttr {
                     header,
-                    arg: data.fetch()?,
+                    arg,
+                    setxattr_flags,
                     name: data.fetch_str()?,
                     value: data.fetch_all(),
                 };
@@ -1709,7 +2136,15 @@ mod op {
             }),
             fuse_opcode::FUSE_INIT => Operation::Init(Init {
                 header,
-                arg: data.fetch()?,
+                arg: {
+                    // Kernels before 7.36 send the short fuse_init_in without flags2
+                    let size = if data.len() < mem::size_of::<fuse_init_in>() {
+                        FUSE_COMPAT_INIT_IN_SIZE
+                    } else {
+                        mem::size_of::<fuse_init_in>()
+                    };
+                    data.fetch_truncated(size)?
+                },
             }),
             fuse_opcode::FUSE_OPENDIR => Operation::OpenDir(OpenDir {
                 header,
@@ -1717,7 +2152,11 @@ mod op {
             }),
             fuse_opcode::FUSE_READDIR => Operation::ReadDir(ReadDir {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_read_in>(
+                    protocol,
+                    9,
+                    FUSE_COMPAT_READ_IN_SIZE,
+                ))?,
             }),
             fuse_opcode::FUSE_RELEASEDIR => Operation::ReleaseDir(ReleaseDir {
                 header,
@@ -1729,15 +2168,27 @@ mod op {
             }),
             fuse_opcode::FUSE_GETLK => Operation::GetLk(GetLk {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_lk_in>(
+                    protocol,
+                    9,
+                    FUSE_COMPAT_LK_IN_SIZE,
+                ))?,
             }),
             fuse_opcode::FUSE_SETLK => Operation::SetLk(SetLk {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_lk_in>(
+                    protocol,
+                    9,
+                    FUSE_COMPAT_LK_IN_SIZE,
+                ))?,
             }),
             fuse_opcode::FUSE_SETLKW => Operation::SetLkW(SetLkW {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_lk_in>(
+                    protocol,
+                    9,
+                    FUSE_COMPAT_LK_IN_SIZE,
+                ))?,
             }),
             fuse_opcode::FUSE_ACCESS => Operation::Access(Access {
                 header,
@@ -1745,8 +2196,13 @@ mod op {
             }),
             fuse_opcode::FUSE_CREATE => Operation::Create(Create {
                 header,
-                arg: data.fetch()?,
+                arg: data.fetch_truncated(arg_size::<fuse_create_in>(
+                    protocol,
+                    12,
+                    FUSE_COMPAT_CREATE_IN_SIZE,
+                ))?,
                 name: data.fetch_str()?.as_ref(),
+                ext: fetch_extensions(&mut data, protocol)?,
             }),
             fuse_opcode::FUSE_INTERRUPT => Operation::Interrupt(Interrupt {
                 header,
@@ -1757,23 +2213,19 @@ mod op {
                 arg: data.fetch()?,
             }),
             fuse_opcode::FUSE_DESTROY => Operation::Destroy(Destroy { header }),
-            #[cfg(feature = "abi-7-11")]
             fuse_opcode::FUSE_IOCTL => Operation::IoCtl(IoCtl {
                 header,
                 arg: data.fetch()?,
                 data: data.fetch_all(),
             }),
-            #[cfg(feature = "abi-7-11")]
             fuse_opcode::FUSE_POLL => Operation::Poll(Poll {
                 header,
                 arg: data.fetch()?,
             }),
-            #[cfg(feature = "abi-7-15")]
             fuse_opcode::FUSE_NOTIFY_REPLY => Operation::NotifyReply(NotifyReply {
                 header,
                 arg: data.fetch_all(),
             }),
-            #[cfg(feature = "abi-7-16")]
             fuse_opcode::FUSE_BATCH_FORGET => {
                 let arg = data.fetch()?;
                 Operation::BatchForget(BatchForget {
@@ -1782,17 +2234,14 @@ mod op {
                     nodes: data.fetch_slice(arg.count as usize)?,
                 })
             }
-            #[cfg(feature = "abi-7-19")]
             fuse_opcode::FUSE_FALLOCATE => Operation::FAllocate(FAllocate {
                 header,
                 arg: data.fetch()?,
             }),
-            #[cfg(feature = "abi-7-21")]
             fuse_opcode::FUSE_READDIRPLUS => Operation::ReadDirPlus(ReadDirPlus {
                 header,
                 arg: data.fetch()?,
             }),
-            #[cfg(feature = "abi-7-23")]
             fuse_opcode::FUSE_RENAME2 => Operation::Rename2(Rename2 {
                 header,
                 arg: data.fetch()?,
@@ -1800,16 +2249,27 @@ mod op {
                 newname: data.fetch_str()?.as_ref(),
                 old_parent: INodeNo(header.nodeid),
             }),
-            #[cfg(feature = "abi-7-24")]
             fuse_opcode::FUSE_LSEEK => Operation::Lseek(Lseek {
                 header,
                 arg: data.fetch()?,
             }),
-            #[cfg(feature = "abi-7-28")]
             fuse_opcode::FUSE_COPY_FILE_RANGE => Operation::CopyFileRange(CopyFileRange {
                 header,
                 arg: data.fetch()?,
             }),
+            fuse_opcode::FUSE_SYNCFS => Operation::SyncFs(SyncFs { header }),
+            fuse_opcode::FUSE_TMPFILE => Operation::TmpFile(TmpFile {
+                header,
+                arg: data.fetch_truncated(arg_size::<fuse_create_in>(
+                    protocol,
+                    12,
+                    FUSE_COMPAT_CREATE_IN_SIZE,
+                ))?,
+            }),
+            fuse_opcode::FUSE_STATX => Operation::Statx(Statx {
+                header,
+                arg: data.fetch()?,
+            }),
 
             #[cfg(target_os = "macos")]
             fuse_opcode::FUSE_SETVOLNAME => Operation::SetVolName(SetVolName {
@@ -1826,7 +2286,6 @@ mod op {
                 newname: data.fetch_str()?.as_ref(),
             }),
 
-            #[cfg(feature = "abi-7-12")]
             fuse_opcode::CUSE_INIT => Operation::CuseInit(CuseInit {
                 header,
                 arg: data.fetch()?,
@@ -1877,24 +2336,18 @@ pub enum Operation<'a> {
     Interrupt(Interrupt<'a>),
     BMap(BMap<'a>),
     Destroy(Destroy<'a>),
-    #[cfg(feature = "abi-7-11")]
     IoCtl(IoCtl<'a>),
-    #[cfg(feature = "abi-7-11")]
     Poll(Poll<'a>),
-    #[cfg(feature = "abi-7-15")]
     NotifyReply(NotifyReply<'a>),
-    #[cfg(feature = "abi-7-16")]
     BatchForget(BatchForget<'a>),
-    #[cfg(feature = "abi-7-19")]
     FAllocate(FAllocate<'a>),
-    #[cfg(feature = "abi-7-21")]
     ReadDirPlus(ReadDirPlus<'a>),
-    #[cfg(feature = "abi-7-23")]
     Rename2(Rename2<'a>),
-    #[cfg(feature = "abi-7-24")]
     Lseek(Lseek<'a>),
-    #[cfg(feature = "abi-7-28")]
     CopyFileRange(CopyFileRange<'a>),
+    SyncFs(SyncFs<'a>),
+    TmpFile(TmpFile<'a>),
+    Statx(Statx<'a>),
 
     #[cfg(target_os = "macos")]
     SetVolName(SetVolName<'a>),
@@ -1903,7 +2356,6 @@ pub enum Operation<'a> {
     #[cfg(target_os = "macos")]
     Exchange(Exchange<'a>),
 
-    #[cfg(feature = "abi-7-12")]
     CuseInit(CuseInit<'a>),
 }
 
@@ -1927,7 +2379,7 @@ impl<'a> fmt::Display for Operation<'a> {
             ),
             Operation::MkDir(x) => write!(f, "MKDIR name {:?}, mode {:#05o}", x.name(), x.mode()),
             Operation::Unlink(x) => write!(f, "UNLINK name {:?}", x.name()),
-            Operation::RmDir(x) => write!(f, "RMDIR name {:?}", x.name),
+            Operation::RmDir(x) => write!(f, "RMDIR name {:?}", x.name()),
             Operation::Rename(x) => write!(f, "RENAME src {:?}, dest {:?}", x.src(), x.dest()),
             Operation::Link(x) => write!(f, "LINK ino {:?}, dest {:?}", x.inode_no(), x.dest()),
             Operation::Open(x) => write!(f, "OPEN flags {:#x}", x.flags()),
@@ -2037,7 +2489,6 @@ impl<'a> fmt::Display for Operation<'a> {
             Operation::Interrupt(x) => write!(f, "INTERRUPT unique {:?}", x.unique()),
             Operation::BMap(x) => write!(f, "BMAP blocksize {}, ids {}", x.block_size(), x.block()),
             Operation::Destroy(_) => write!(f, "DESTROY"),
-            #[cfg(feature = "abi-7-11")]
             Operation::IoCtl(x) => write!(
                 f,
                 "IOCTL fh {:?}, cmd {}, data size {}, flags {:#x}",
@@ -2046,15 +2497,17 @@ impl<'a> fmt::Display for Operation<'a> {
                 x.in_data().len(),
                 x.flags()
             ),
-            #[cfg(feature = "abi-7-11")]
-            Operation::Poll(x) => write!(f, "POLL fh {:?}", x.file_handle()),
-            #[cfg(feature = "abi-7-15")]
+            Operation::Poll(x) => write!(
+                f,
+                "POLL fh {:?}, kh {}, flags {:#x}, events {:#x}",
+                x.file_handle(),
+                x.kernel_handle(),
+                x.flags(),
+                x.events()
+            ),
             Operation::NotifyReply(_) => write!(f, "NOTIFYREPLY"),
-            #[cfg(feature = "abi-7-16")]
             Operation::BatchForget(x) => write!(f, "BATCHFORGET nodes {:?}", x.nodes()),
-            #[cfg(feature = "abi-7-19")]
             Operation::FAllocate(_) => write!(f, "FALLOCATE"),
-            #[cfg(feature = "abi-7-21")]
             Operation::ReadDirPlus(x) => write!(
                 f,
                 "READDIRPLUS fh {:?}, offset {}, size {}",
@@ -2062,9 +2515,7 @@ impl<'a> fmt::Display for Operation<'a> {
                 x.offset(),
                 x.size()
             ),
-            #[cfg(feature = "abi-7-23")]
             Operation::Rename2(x) => write!(f, "RENAME2 from {:?}, to {:?}", x.from(), x.to()),
-            #[cfg(feature = "abi-7-24")]
             Operation::Lseek(x) => write!(
                 f,
                 "LSEEK fh {:?}, offset {}, whence {}",
@@ -2072,7 +2523,6 @@ impl<'a> fmt::Display for Operation<'a> {
                 x.offset(),
                 x.whence()
             ),
-            #[cfg(feature = "abi-7-28")]
             Operation::CopyFileRange(x) => write!(
                 f,
                 "COPY_FILE_RANGE src {:?}, dest {:?}, len {}",
@@ -2080,6 +2530,17 @@ impl<'a> fmt::Display for Operation<'a> {
                 x.dest(),
                 x.len()
             ),
+            Operation::SyncFs(_) => write!(f, "SYNCFS"),
+            Operation::TmpFile(x) => {
+                write!(f, "TMPFILE mode {:#05o}, flags {:#x}", x.mode(), x.flags())
+            }
+            Operation::Statx(x) => write!(
+                f,
+                "STATX fh {:?}, mask {:#x}, flags {:#x}",
+                x.file_handle(),
+                x.mask(),
+                x.flags()
+            ),
 
             #[cfg(target_os = "macos")]
             Operation::SetVolName(x) => write!(f, "SETVOLNAME name {:?}", x.name()),
@@ -2094,8 +2555,12 @@ impl<'a> fmt::Display for Operation<'a> {
                 x.options()
             ),
 
-            #[cfg(feature = "abi-7-12")]
-            Operation::CuseInit(_) => write!(f, "CUSE_INIT"),
+            Operation::CuseInit(x) => write!(
+                f,
+                "CUSE_INIT kernel ABI {}, flags {:#x}",
+                x.version(),
+                x.flags()
+            ),
         }
     }
 }
@@ -2105,16 +2570,34 @@ impl<'a> fmt::Display for Operation<'a> {
 pub struct AnyRequest<'a> {
     header: &'a fuse_in_header,
     data: &'a [u8],
+    protocol: Protocol,
 }
 impl_request!(AnyRequest<'_>);
 
 impl<'a> AnyRequest<'a> {
+    /// Parse the arguments with the layout of the given negotiated protocol
+    pub fn with_protocol(self, protocol: Protocol) -> Self {
+        Self { protocol, ..self }
+    }
+
+    /// The negotiated protocol this request is parsed with
+    pub fn protocol(&self) -> &Protocol {
+        &self.protocol
+    }
+
+    /// The opcode of the operation, which may be unknown
+    pub fn opcode(&self) -> u32 {
+        self.header.opcode
+    }
+
+    /// Parse the operation and its arguments
     pub fn operation(&self) -> Result<Operation<'a>, RequestError> {
         // Parse/check opcode
         let opcode = fuse_opcode::try_from(self.header.opcode)
             .map_err(|_: InvalidOpcodeError| RequestError::UnknownOperation(self.header.opcode))?;
         // Parse/check operation arguments
-        op::parse(&self.header, &opcode, self.data).ok_or(RequestError::InsufficientData)
+        op::parse(&self.header, &opcode, self.data, &self.protocol)
+            .ok_or(RequestError::InsufficientData)
     }
 }
 
@@ -2155,6 +2638,7 @@ impl<'a> TryFrom<&'a [u8]> for AnyRequest<'a> {
         Ok(Self {
             header,
             data: &data[mem::size_of::<fuse_in_header>()..header.len as usize],
+            protocol: Protocol::default(),
         })
     }
 }
@@ -2163,6 +2647,8 @@ impl<'a> TryFrom<&'a [u8]> for AnyRequest<'a> {
 mod tests {
     use super::super::test::AlignedData;
     use super::*;
+    #[allow(unused_imports)]
+    use abi::consts::*;
     use std::ffi::OsStr;
 
     #[cfg(target_endian = "big")]
@@ -2198,18 +2684,7 @@ mod tests {
         0x66, 0x6f, 0x6f, 0x2e, 0x74, 0x78, 0x74, 0x00, // name
     ];
 
-    #[cfg(all(target_endian = "little", not(feature = "abi-7-12")))]
-    const MKNOD_REQUEST: AlignedData<[u8; 56]> = AlignedData([
-        0x38, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, // len, opcode
-        0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
-        0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
-        0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
-        0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
-        0xa4, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mode, rdev
-        0x66, 0x6f, 0x6f, 0x2e, 0x74, 0x78, 0x74, 0x00, // name
-    ]);
-
-    #[cfg(all(target_endian = "little", feature = "abi-7-12"))]
+    #[cfg(target_endian = "little")]
     const MKNOD_REQUEST: AlignedData<[u8; 64]> = AlignedData([
         0x40, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, // len, opcode
         0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
@@ -2251,6 +2726,171 @@ mod tests {
             Operation::Init(x) => {
                 assert_eq!(x.version(), Version(7, 8));
                 assert_eq!(x.max_readahead(), 4096);
+                assert_eq!(x.capabilities(), 0);
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_endian = "little", not(target_os = "macos")))]
+    fn init_ext() {
+        let mut data = AlignedData([0u8; 104]);
+        data[..48].copy_from_slice(&INIT_REQUEST[..48]);
+        data[0] = 104; // len
+        data[44] = 36; // minor
+        data[52..56].copy_from_slice(&((FUSE_INIT_EXT | FUSE_ASYNC_READ) as u32).to_le_bytes());
+        data[56..60].copy_from_slice(&((FUSE_SECURITY_CTX >> 32) as u32).to_le_bytes());
+        let req = AnyRequest::try_from(&data[..]).unwrap();
+        match req.operation().unwrap() {
+            Operation::Init(x) => {
+                assert_eq!(x.version(), Version(7, 36));
+                assert_eq!(
+                    x.capabilities(),
+                    FUSE_INIT_EXT | FUSE_ASYNC_READ | FUSE_SECURITY_CTX
+                );
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_endian = "little", not(target_os = "macos")))]
+    fn init_request_timeout() {
+        let mut data = AlignedData([0u8; 104]);
+        data[..48].copy_from_slice(&INIT_REQUEST[..48]);
+        data[0] = 104; // len
+        data[44] = 43; // minor
+        data[52..56].copy_from_slice(&(FUSE_INIT_EXT as u32).to_le_bytes());
+        data[56..60].copy_from_slice(&((FUSE_REQUEST_TIMEOUT >> 32) as u32).to_le_bytes());
+        let req = AnyRequest::try_from(&data[..]).unwrap();
+        match req.operation().unwrap() {
+            Operation::Init(x) => {
+                let mut config = crate::KernelConfig::new(x.capabilities(), x.max_readahead());
+                config.add_capabilities(FUSE_REQUEST_TIMEOUT).unwrap();
+                assert_eq!(
+                    config.add_capabilities(crate::Capabilities::PASSTHROUGH),
+                    Err(crate::Capabilities::PASSTHROUGH)
+                );
+                assert!(config
+                    .requested()
+                    .contains(crate::Capabilities::REQUEST_TIMEOUT));
+                assert!(!config.offered().contains(crate::Capabilities::PASSTHROUGH));
+                assert_eq!(
+                    config.set_request_timeout(std::time::Duration::from_millis(1500)),
+                    Err(std::time::Duration::from_secs(1))
+                );
+                config
+                    .set_request_timeout(std::time::Duration::from_secs(30))
+                    .unwrap();
+                let reply = x
+                    .reply(&config)
+                    .with_iovec(RequestId(0), |iov| iov[1].to_vec());
+                let flags2 = u32::from_le_bytes([reply[32], reply[33], reply[34], reply[35]]);
+                assert_eq!(flags2 as u64, FUSE_REQUEST_TIMEOUT >> 32);
+                assert_eq!(u16::from_le_bytes([reply[40], reply[41]]), 30);
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_endian = "little", not(target_os = "macos")))]
+    fn setxattr_ext() {
+        const SETXATTR_REQUEST: AlignedData<[u8; 64]> = AlignedData([
+            0x3e, 0x00, 0x00, 0x00, 0x15, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // size, flags
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // setxattr_flags, padding
+            0x75, 0x2e, 0x61, 0x00, 0x68, 0x69, 0x00, 0x00, // name, value
+        ]);
+        let req = AnyRequest::try_from(&SETXATTR_REQUEST[..62])
+            .unwrap()
+            .with_protocol(Protocol {
+                flags: FUSE_SETXATTR_EXT,
+                ..Default::default()
+            });
+        match req.operation().unwrap() {
+            Operation::SetXAttr(x) => {
+                assert_eq!(x.flags(), 1);
+                assert_eq!(x.setxattr_flags(), FUSE_SETXATTR_ACL_KILL_SGID);
+                assert_eq!(x.name(), OsStr::new("u.a"));
+                assert_eq!(x.value(), b"hi");
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_endian = "little", not(target_os = "macos")))]
+    fn mkdir_security_ctx() {
+        const MKDIR_REQUEST: AlignedData<[u8; 96]> = AlignedData([
+            0x5c, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0xed, 0x01, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, // mode, umask
+            0x66, 0x6f, 0x6f, 0x00, 0x28, 0x00, 0x00, 0x00, // name, secctx_header.size
+            0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, // nr_secctx, secctx.size
+            0x00, 0x00, 0x00, 0x00, 0x73, 0x65, 0x63, 0x75, // padding, name
+            0x72, 0x69, 0x74, 0x79, 0x2e, 0x73, 0x65, 0x6c, // name
+            0x69, 0x6e, 0x75, 0x78, 0x00, 0x63, 0x74, 0x78, // name, value
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // value, padding
+        ]);
+        let req = AnyRequest::try_from(&MKDIR_REQUEST[..92])
+            .unwrap()
+            .with_protocol(Protocol {
+                flags: FUSE_SECURITY_CTX,
+                ..Default::default()
+            });
+        match req.operation().unwrap() {
+            Operation::MkDir(x) => {
+                assert_eq!(x.mode(), 0o755);
+                assert_eq!(x.name(), OsStr::new("foo"));
+                assert_eq!(
+                    x.security_ctx(),
+                    &[(OsStr::new("security.selinux"), &b"ctx\0"[..])]
+                );
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+        // Without FUSE_SECURITY_CTX, trailing data is not a security context
+        let req = AnyRequest::try_from(&MKDIR_REQUEST[..92]).unwrap();
+        match req.operation().unwrap() {
+            Operation::MkDir(x) => assert!(x.security_ctx().is_empty()),
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_endian = "little", not(target_os = "macos")))]
+    fn mkdir_supp_groups() {
+        const MKDIR_REQUEST: AlignedData<[u8; 72]> = AlignedData([
+            0x44, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0xed, 0x01, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, // mode, umask
+            0x66, 0x6f, 0x6f, 0x00, 0x10, 0x00, 0x00, 0x00, // name, ext_header.size
+            0x20, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // FUSE_EXT_GROUPS, nr_groups
+            0xe8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // group
+        ]);
+        let req = AnyRequest::try_from(&MKDIR_REQUEST[..68])
+            .unwrap()
+            .with_protocol(Protocol {
+                flags: FUSE_CREATE_SUPP_GROUP,
+                ..Default::default()
+            });
+        match req.operation().unwrap() {
+            Operation::MkDir(x) => {
+                assert_eq!(x.name(), OsStr::new("foo"));
+                assert!(x.security_ctx().is_empty());
+                assert_eq!(x.supplementary_groups(), &[1000]);
             }
             _ => panic!("Unexpected request operation"),
         }
@@ -2259,9 +2899,6 @@ mod tests {
     #[test]
     fn mknod() {
         let req = AnyRequest::try_from(&MKNOD_REQUEST[..]).unwrap();
-        #[cfg(not(feature = "abi-7-12"))]
-        assert_eq!(req.header.len, 56);
-        #[cfg(feature = "abi-7-12")]
         assert_eq!(req.header.len, 64);
         assert_eq!(req.header.opcode, 8);
         assert_eq!(req.unique(), RequestId(0xdead_beef_baad_f00d));
@@ -2272,11 +2909,181 @@ mod tests {
         match req.operation().unwrap() {
             Operation::MkNod(x) => {
                 assert_eq!(x.mode(), 0o644);
-                #[cfg(feature = "abi-7-12")]
                 assert_eq!(x.umask(), 0o755);
                 assert_eq!(x.name(), OsStr::new("foo.txt"));
             }
             _ => panic!("Unexpected request operation"),
         }
     }
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn mknod_compat() {
+        // Before ABI 7.12, fuse_mknod_in has no umask and padding
+        const MKNOD_REQUEST: AlignedData<[u8; 56]> = AlignedData([
+            0x38, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0xa4, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mode, rdev
+            0x66, 0x6f, 0x6f, 0x2e, 0x74, 0x78, 0x74, 0x00, // name
+        ]);
+        let req = AnyRequest::try_from(&MKNOD_REQUEST[..])
+            .unwrap()
+            .with_protocol(Protocol {
+                minor: 11,
+                ..Default::default()
+            });
+        match req.operation().unwrap() {
+            Operation::MkNod(x) => {
+                assert_eq!(x.mode(), 0o644);
+                assert_eq!(x.umask(), 0);
+                assert_eq!(x.name(), OsStr::new("foo.txt"));
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn syncfs() {
+        const SYNCFS_REQUEST: AlignedData<[u8; 48]> = AlignedData([
+            0x30, 0x00, 0x00, 0x00, 0x32, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // padding
+        ]);
+        let req = AnyRequest::try_from(&SYNCFS_REQUEST[..]).unwrap();
+        assert_eq!(req.unique(), RequestId(0xdead_beef_baad_f00d));
+        assert_eq!(req.nodeid(), INodeNo(1));
+        match req.operation().unwrap() {
+            Operation::SyncFs(_) => (),
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn tmpfile() {
+        const TMPFILE_REQUEST: AlignedData<[u8; 64]> = AlignedData([
+            0x3e, 0x00, 0x00, 0x00, 0x33, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0x02, 0x00, 0x41, 0x00, 0x80, 0x81, 0x00, 0x00, // flags, mode
+            0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // umask, open_flags
+            0x2f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // name
+        ]);
+        let req = AnyRequest::try_from(&TMPFILE_REQUEST[..62]).unwrap();
+        assert_eq!(req.nodeid(), INodeNo(0x1122_3344_5566_7788));
+        match req.operation().unwrap() {
+            Operation::TmpFile(x) => {
+                assert_eq!(x.flags(), OpenFlags(0x41_0002));
+                assert_eq!(x.flags().access_mode(), libc::O_RDWR);
+                assert!(x.flags().contains(libc::O_TMPFILE));
+                assert_eq!(x.mode(), 0o100600);
+                assert_eq!(x.umask(), 0o22);
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn write_compat() {
+        // Before ABI 7.9, fuse_write_in has no lock_owner, flags and padding
+        const WRITE_REQUEST: AlignedData<[u8; 72]> = AlignedData([
+            0x48, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // fh
+            0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // offset
+            0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // size, write_flags
+            0x64, 0x61, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, // data
+        ]);
+        let req = AnyRequest::try_from(&WRITE_REQUEST[..])
+            .unwrap()
+            .with_protocol(Protocol {
+                minor: 8,
+                ..Default::default()
+            });
+        match req.operation().unwrap() {
+            Operation::Write(x) => {
+                assert_eq!(x.file_handle(), FileHandle(1));
+                assert_eq!(x.offset(), 0x1000);
+                assert_eq!(x.flags(), OpenFlags(0));
+                assert_eq!(x.lock_owner(), None);
+                assert_eq!(x.data(), b"datadata");
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn write_kill_suidgid() {
+        const WRITE_REQUEST: AlignedData<[u8; 88]> = AlignedData([
+            0x58, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // fh
+            0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // offset
+            0x08, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, // size, write_flags
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // lock_owner
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // flags, padding
+            0x64, 0x61, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, // data
+        ]);
+        let req = AnyRequest::try_from(&WRITE_REQUEST[..]).unwrap();
+        match req.operation().unwrap() {
+            Operation::Write(x) => {
+                assert!(x.kill_suidgid());
+                assert!(x.write_flags().kill_suidgid());
+                assert!(!x.write_flags().cache());
+                assert_eq!(x.lock_owner(), None);
+                assert_eq!(x.flags(), OpenFlags(libc::O_RDWR));
+                assert_eq!(x.data(), b"datadata");
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn readdirplus_fallback_requests() {
+        const READDIRPLUS_REQUEST: AlignedData<[u8; 80]> = AlignedData([
+            0x50, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, // len, opcode
+            0x0d, 0xf0, 0xad, 0xba, 0xef, 0xbe, 0xad, 0xde, // unique
+            0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, // nodeid
+            0x0d, 0xd0, 0x01, 0xc0, 0xfe, 0xca, 0x01, 0xc0, // uid, gid
+            0x5e, 0xba, 0xde, 0xc0, 0x00, 0x00, 0x00, 0x00, // pid, padding
+            0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // fh
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // offset
+            0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // size, read_flags
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // lock_owner
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // flags, padding
+        ]);
+        let req = AnyRequest::try_from(&READDIRPLUS_REQUEST[..]).unwrap();
+        match req.operation().unwrap() {
+            Operation::ReadDirPlus(x) => {
+                let readdir = x.readdir();
+                assert_eq!(readdir.nodeid(), INodeNo(0x1122_3344_5566_7788));
+                assert_eq!(readdir.file_handle(), FileHandle(3));
+                assert_eq!(readdir.offset(), 2);
+                assert_eq!(readdir.size(), 4096);
+                let name = OsStr::new("foo.txt");
+                let lookup = x.lookup(name);
+                assert_eq!(lookup.unique(), RequestId(0xdead_beef_baad_f00d));
+                assert_eq!(lookup.nodeid(), INodeNo(0x1122_3344_5566_7788));
+                assert_eq!(lookup.name(), name);
+            }
+            _ => panic!("Unexpected request operation"),
+        }
+    }
 }
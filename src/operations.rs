@@ -0,0 +1,296 @@
+//! Operations implemented by a filesystem
+//!
+//! A [`Filesystem`](crate::Filesystem) declares the operations it implements with
+//! [`supported_ops`](crate::Filesystem::supported_ops). Capabilities that make the kernel send
+//! other operations are not requested in init, and requests of other operations are replied to
+//! with `ENOSYS` without calling the filesystem.
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Sub};
+
+use crate::ll::fuse_abi::fuse_opcode::*;
+use crate::Capabilities;
+
+/// Set of filesystem operations. Every flag corresponds to the [`Filesystem`](crate::Filesystem)
+/// method of the same name.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Operations(u64);
+
+macro_rules! operations {
+    ($($(#[$attr:meta])* $name:ident = $($opcode:ident)|+;)*) => {
+        impl Operations {
+            $(
+                $(#[$attr])*
+                pub const $name: Operations = Operations($(1 << $opcode as u64)|+);
+            )*
+
+            /// All operations that have a flag
+            pub const fn all() -> Self {
+                let mut bits = 0;
+                $(
+                    // Only the cfg attributes matter here
+                    #[allow(unused_doc_comments)]
+                    $(#[$attr])*
+                    {
+                        bits |= Operations::$name.0;
+                    }
+                )*
+                Operations(bits)
+            }
+        }
+
+        impl fmt::Debug for Operations {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut set = f.debug_set();
+                $(
+                    // Only the cfg attributes matter here
+                    #[allow(unused_doc_comments)]
+                    $(#[$attr])*
+                    {
+                        if self.contains(Operations::$name) {
+                            set.entry(&format_args!(stringify!($name)));
+                        }
+                    }
+                )*
+                set.finish()
+            }
+        }
+    };
+}
+
+operations! {
+    /// [`lookup`](crate::Filesystem::lookup)
+    LOOKUP = FUSE_LOOKUP;
+    /// [`getattr`](crate::Filesystem::getattr)
+    GETATTR = FUSE_GETATTR;
+    /// [`setattr`](crate::Filesystem::setattr)
+    SETATTR = FUSE_SETATTR;
+    /// [`readlink`](crate::Filesystem::readlink)
+    READLINK = FUSE_READLINK;
+    /// [`symlink`](crate::Filesystem::symlink)
+    SYMLINK = FUSE_SYMLINK;
+    /// [`mknod`](crate::Filesystem::mknod)
+    MKNOD = FUSE_MKNOD;
+    /// [`mkdir`](crate::Filesystem::mkdir)
+    MKDIR = FUSE_MKDIR;
+    /// [`unlink`](crate::Filesystem::unlink)
+    UNLINK = FUSE_UNLINK;
+    /// [`rmdir`](crate::Filesystem::rmdir)
+    RMDIR = FUSE_RMDIR;
+    /// [`rename`](crate::Filesystem::rename), with and without flags
+    RENAME = FUSE_RENAME | FUSE_RENAME2;
+    /// [`link`](crate::Filesystem::link)
+    LINK = FUSE_LINK;
+    /// [`read`](crate::Filesystem::read)
+    READ = FUSE_READ;
+    /// [`write`](crate::Filesystem::write)
+    WRITE = FUSE_WRITE;
+    /// [`fsync`](crate::Filesystem::fsync)
+    FSYNC = FUSE_FSYNC;
+    /// [`setxattr`](crate::Filesystem::setxattr)
+    SETXATTR = FUSE_SETXATTR;
+    /// [`getxattr`](crate::Filesystem::getxattr)
+    GETXATTR = FUSE_GETXATTR;
+    /// [`listxattr`](crate::Filesystem::listxattr)
+    LISTXATTR = FUSE_LISTXATTR;
+    /// [`removexattr`](crate::Filesystem::removexattr)
+    REMOVEXATTR = FUSE_REMOVEXATTR;
+    /// [`flush`](crate::Filesystem::flush)
+    FLUSH = FUSE_FLUSH;
+    /// [`readdir`](crate::Filesystem::readdir)
+    READDIR = FUSE_READDIR;
+    /// [`fsyncdir`](crate::Filesystem::fsyncdir)
+    FSYNCDIR = FUSE_FSYNCDIR;
+    /// [`getlk`](crate::Filesystem::getlk)
+    GETLK = FUSE_GETLK;
+    /// [`setlk`](crate::Filesystem::setlk), sleeping or not
+    SETLK = FUSE_SETLK | FUSE_SETLKW;
+    /// [`access`](crate::Filesystem::access)
+    ACCESS = FUSE_ACCESS;
+    /// [`create`](crate::Filesystem::create)
+    CREATE = FUSE_CREATE;
+    /// [`bmap`](crate::Filesystem::bmap)
+    BMAP = FUSE_BMAP;
+    /// [`ioctl`](crate::Filesystem::ioctl)
+    IOCTL = FUSE_IOCTL;
+    /// [`fallocate`](crate::Filesystem::fallocate)
+    FALLOCATE = FUSE_FALLOCATE;
+    /// [`readdirplus`](crate::Filesystem::readdirplus)
+    READDIRPLUS = FUSE_READDIRPLUS;
+    /// [`lseek`](crate::Filesystem::lseek)
+    LSEEK = FUSE_LSEEK;
+    /// [`copy_file_range`](crate::Filesystem::copy_file_range)
+    COPY_FILE_RANGE = FUSE_COPY_FILE_RANGE;
+    /// [`syncfs`](crate::Filesystem::syncfs)
+    SYNCFS = FUSE_SYNCFS;
+    /// [`tmpfile`](crate::Filesystem::tmpfile)
+    TMPFILE = FUSE_TMPFILE;
+    /// [`statx`](crate::Filesystem::statx)
+    STATX = FUSE_STATX;
+    /// [`setvolname`](crate::Filesystem::setvolname)
+    #[cfg(target_os = "macos")]
+    SETVOLNAME = FUSE_SETVOLNAME;
+    /// [`getxtimes`](crate::Filesystem::getxtimes)
+    #[cfg(target_os = "macos")]
+    GETXTIMES = FUSE_GETXTIMES;
+    /// [`exchange`](crate::Filesystem::exchange)
+    #[cfg(target_os = "macos")]
+    EXCHANGE = FUSE_EXCHANGE;
+}
+
+impl Operations {
+    /// The empty set
+    pub const fn empty() -> Self {
+        Operations(0)
+    }
+
+    /// Returns true if no operation is set
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if all operations of `other` are set
+    pub const fn contains(self, other: Operations) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Adds the operations of `other`
+    pub fn insert(&mut self, other: Operations) {
+        self.0 |= other.0;
+    }
+
+    /// Removes the operations of `other`
+    pub fn remove(&mut self, other: Operations) {
+        self.0 &= !other.0;
+    }
+
+    /// Returns true if requests with the given opcode are passed to the filesystem. Opcodes
+    /// without a flag, like `FUSE_OPEN`, always are.
+    pub(crate) fn handles(self, opcode: u32) -> bool {
+        let bit = match 1u64.checked_shl(opcode) {
+            Some(bit) if Operations::all().0 & bit != 0 => bit,
+            _ => return true,
+        };
+        self.with_fallbacks().0 & bit != 0
+    }
+
+    /// Adds the operations that the library implements with the operations in this set
+    fn with_fallbacks(mut self) -> Self {
+        if self.contains(Operations::READDIR | Operations::LOOKUP) {
+            self.insert(Operations::READDIRPLUS);
+        }
+        self
+    }
+
+    /// Capabilities that make the kernel send operations of which some are not in this set
+    pub(crate) fn unsupported_capabilities(self) -> Capabilities {
+        let operations = self.with_fallbacks();
+        let mut capabilities = Capabilities::empty();
+        let mut require = |required: Operations, required_by: Capabilities| {
+            if !operations.contains(required) {
+                capabilities |= required_by;
+            }
+        };
+        require(
+            Operations::READDIRPLUS,
+            Capabilities::DO_READDIRPLUS | Capabilities::READDIRPLUS_AUTO,
+        );
+        require(
+            Operations::GETLK | Operations::SETLK,
+            Capabilities::POSIX_LOCKS,
+        );
+        require(Operations::SETLK, Capabilities::FLOCK_LOCKS);
+        require(
+            Operations::READ | Operations::WRITE,
+            Capabilities::ASYNC_DIO,
+        );
+        require(Operations::WRITE, Capabilities::WRITEBACK_CACHE);
+        require(Operations::IOCTL, Capabilities::HAS_IOCTL_DIR);
+        require(Operations::READLINK, Capabilities::CACHE_SYMLINKS);
+        require(
+            Operations::GETXATTR | Operations::SETXATTR,
+            Capabilities::POSIX_ACL,
+        );
+        #[cfg(not(target_os = "macos"))]
+        require(Operations::SETXATTR, Capabilities::SETXATTR_EXT);
+        #[cfg(target_os = "macos")]
+        {
+            require(Operations::FALLOCATE, Capabilities::ALLOCATE);
+            require(Operations::EXCHANGE, Capabilities::EXCHANGE_DATA);
+            require(Operations::SETVOLNAME, Capabilities::VOL_RENAME);
+            require(Operations::GETXTIMES, Capabilities::XTIMES);
+        }
+        capabilities
+    }
+}
+
+impl BitOr for Operations {
+    type Output = Operations;
+
+    fn bitor(self, rhs: Operations) -> Operations {
+        Operations(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Operations {
+    fn bitor_assign(&mut self, rhs: Operations) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Operations {
+    type Output = Operations;
+
+    fn bitand(self, rhs: Operations) -> Operations {
+        Operations(self.0 & rhs.0)
+    }
+}
+
+impl Sub for Operations {
+    type Output = Operations;
+
+    fn sub(self, rhs: Operations) -> Operations {
+        Operations(self.0 & !rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Operations;
+    use crate::ll::fuse_abi::fuse_opcode::*;
+    use crate::Capabilities;
+
+    #[test]
+    fn handles() {
+        let ops = Operations::LOOKUP | Operations::SETLK;
+        assert!(ops.handles(FUSE_LOOKUP as u32));
+        assert!(ops.handles(FUSE_SETLKW as u32));
+        assert!(!ops.handles(FUSE_GETLK as u32));
+        assert!(!ops.handles(FUSE_RENAME2 as u32));
+        assert!(!ops.handles(FUSE_READDIRPLUS as u32));
+        assert!((Operations::READDIR | Operations::LOOKUP).handles(FUSE_READDIRPLUS as u32));
+        // Operations without a flag are always passed to the filesystem
+        assert!(ops.handles(FUSE_OPEN as u32));
+        assert!(ops.handles(FUSE_FORGET as u32));
+        assert!(ops.handles(CUSE_INIT as u32));
+        assert!(Operations::all().handles(FUSE_RENAME2 as u32));
+    }
+
+    #[test]
+    fn unsupported_capabilities() {
+        assert!(Operations::all().unsupported_capabilities().is_empty());
+        let unsupported = (Operations::all() - Operations::READDIRPLUS - Operations::GETLK)
+            .unsupported_capabilities();
+        assert!(unsupported.contains(Capabilities::POSIX_LOCKS));
+        assert!(!unsupported.contains(Capabilities::FLOCK_LOCKS));
+        // Readdirplus falls back to readdir and lookup
+        assert!(!unsupported.contains(Capabilities::DO_READDIRPLUS));
+        let unsupported = (Operations::all() - Operations::READDIRPLUS - Operations::LOOKUP)
+            .unsupported_capabilities();
+        assert!(unsupported.contains(Capabilities::DO_READDIRPLUS));
+        assert_eq!(
+            format!("{:?}", Operations::GETLK | Operations::SETLK),
+            "{GETLK, SETLK}"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Readdirplus from readdir and lookup
+//!
+//! Filesystems that don't implement [`Filesystem::readdirplus`](crate::Filesystem::readdirplus)
+//! still benefit from `FUSE_DO_READDIRPLUS`: the entries of readdir are looked up with lookup
+//! and replied to together with their attributes.
+
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::io::{self, IoSlice};
+use std::sync::mpsc::{channel, Sender};
+
+use libc::ENOENT;
+use log::warn;
+
+use crate::ll::fuse_abi as abi;
+use crate::ll::{AnyReply, ReplyError};
+use crate::reply::{Reply, ReplyDirectory, ReplyDirectoryPlus, ReplySender};
+use crate::{ReplyEntry, Request};
+
+/// Sender that hands a reply back to the library instead of sending it to the kernel
+#[derive(Debug)]
+struct Capture(Sender<Vec<u8>>);
+
+impl ReplySender for Capture {
+    fn send(&self, data: &[IoSlice<'_>]) -> io::Result<()> {
+        let reply = data.iter().flat_map(|x| x.iter().copied()).collect();
+        self.0
+            .send(reply)
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+/// Calls the filesystem with a capturing reply sender, and waits until it replied (which it
+/// may do from another thread)
+fn capture(call: impl FnOnce(Capture)) -> Vec<u8> {
+    let (sender, receiver) = channel();
+    call(Capture(sender));
+    // A reply that is dropped without being sent replies with EIO, so this only fails if
+    // the filesystem leaked it
+    receiver.recv().unwrap_or_default()
+}
+
+/// Replies to a readdirplus request with the entries listed by `readdir`, looked up with
+/// `lookup`. Every entry with a successful lookup counts as looked up, like after a lookup
+/// request, except "." and "..", which aren't looked up. Entries are only looked up if they fit
+/// into the reply, and are listed without attributes if the lookup failed.
+pub(crate) fn readdirplus<FS: ?Sized>(
+    filesystem: &mut FS,
+    req: &Request<'_>,
+    mut reply: ReplyDirectoryPlus,
+    readdir: impl FnOnce(&mut FS, ReplyDirectory),
+    mut lookup: impl FnMut(&mut FS, &OsStr, ReplyEntry),
+) {
+    let size = reply.size();
+    let listing = capture(|sender| {
+        readdir(filesystem, ReplyDirectory::new(req.unique(), sender, size));
+    });
+    let entries = match AnyReply::try_from(&listing[..]).and_then(|x| x.dirents()) {
+        Ok(entries) => entries,
+        Err(ReplyError::Errno(err)) => {
+            reply.error(err.into());
+            return;
+        }
+        Err(err) => {
+            warn!("readdirplus: invalid readdir reply: {}", err);
+            reply.error(libc::EIO);
+            return;
+        }
+    };
+    for (dirent, name) in entries {
+        if reply.is_full_for(name) {
+            break;
+        }
+        let entry_out = if name == "." || name == ".." {
+            None
+        } else {
+            let entry = capture(|sender| {
+                lookup(filesystem, name, Reply::new(req.unique(), sender));
+            });
+            entry_out(&entry, name)
+        };
+        let full = reply.add_dirent(dirent, name, entry_out);
+        debug_assert!(!full);
+    }
+    reply.ok();
+}
+
+/// Decodes the reply to the lookup of `name`
+fn entry_out(entry: &[u8], name: &OsStr) -> Option<abi::fuse_entry_out> {
+    let entry = AnyReply::try_from(entry).and_then(|x| x.entry(abi::FUSE_KERNEL_MINOR_VERSION));
+    match entry {
+        Ok(entry) => Some(entry),
+        Err(ReplyError::Errno(err)) if err.0.get() == ENOENT => None,
+        Err(err) => {
+            warn!(
+                "readdirplus: lookup of {:?} failed with {}, listing it without attributes",
+                name, err
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use libc::ENOENT;
+
+    use crate::ll::fuse_abi::fuse_entry_out;
+    use crate::ll::{FileHandle, INodeNo};
+    use crate::testing::Harness;
+    use crate::{FileAttr, FileType, Filesystem, ReplyDirectory, ReplyEntry, Request};
+
+    /// Size of a readdirplus entry with a name of up to 8 bytes
+    const ENTRY_SIZE: u32 = 160;
+
+    /// A directory with ten files, which only implements readdir and lookup
+    #[derive(Default)]
+    struct Listing {
+        /// Lookups of each name, including failed ones
+        lookups: HashMap<String, u64>,
+    }
+
+    fn names() -> Vec<String> {
+        let files = (0..10).map(|i| format!("file{}", i));
+        vec![".".to_owned(), "..".to_owned()]
+            .into_iter()
+            .chain(files)
+            .collect()
+    }
+
+    impl Filesystem for Listing {
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            let name = name.to_str().unwrap();
+            *self.lookups.entry(name.to_owned()).or_default() += 1;
+            let ino = match names().iter().skip(2).position(|x| x == name) {
+                Some(i) if parent == 1 => i as u64 + 2,
+                _ => return reply.error(ENOENT),
+            };
+            let attr = FileAttr {
+                ino,
+                size: 0,
+                blocks: 0,
+                atime: UNIX_EPOCH,
+                mtime: UNIX_EPOCH,
+                ctime: UNIX_EPOCH,
+                crtime: UNIX_EPOCH,
+                kind: FileType::RegularFile,
+                perm: 0o644,
+                nlink: 1,
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+                blksize: 512,
+                flags: 0,
+            };
+            reply.entry(&Duration::from_secs(1), &attr, 0);
+        }
+
+        fn readdir(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            mut reply: ReplyDirectory,
+        ) {
+            assert_eq!(ino, 1);
+            for (i, name) in names().iter().enumerate().skip(offset as usize) {
+                let (ino, kind) = match i {
+                    0 | 1 => (1, FileType::Directory),
+                    _ => (i as u64, FileType::RegularFile),
+                };
+                if reply.add(ino, i as i64 + 1, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        }
+    }
+
+    /// Reads a page of the directory, and returns the names and entries
+    fn readdirplus(
+        harness: &mut Harness<Listing>,
+        offset: i64,
+    ) -> Vec<(String, i64, fuse_entry_out)> {
+        let request =
+            harness
+                .encoder()
+                .readdirplus(INodeNo(1), FileHandle(0), offset, 4 * ENTRY_SIZE);
+        let reply = harness.request(&request).unwrap();
+        let entries = reply.decode().unwrap().direntplus().unwrap();
+        entries
+            .into_iter()
+            .map(|(x, name)| (name.to_str().unwrap().to_owned(), x.dirent.off, x.entry_out))
+            .collect()
+    }
+
+    #[test]
+    fn readdirplus_fallback() {
+        let mut harness = Harness::new(Listing::default());
+        harness.init(0).unwrap();
+
+        // Only the entries that fit are looked up, but not "." and ".."
+        let page = readdirplus(&mut harness, 0);
+        let mut listed: Vec<_> = page.iter().map(|x| x.0.clone()).collect();
+        assert_eq!(listed, [".", "..", "file0", "file1"]);
+        assert_eq!(page[0].2.nodeid, 0);
+        assert_eq!(page[1].2.nodeid, 0);
+        assert_eq!((page[2].2.nodeid, page[2].2.attr.ino), (2, 2));
+        assert_eq!((page[3].2.nodeid, page[3].2.attr.ino), (3, 3));
+        let lookups = &harness.filesystem().lookups;
+        assert_eq!(lookups.len(), 2);
+        assert_eq!((lookups["file0"], lookups["file1"]), (1, 1));
+
+        // The next pages continue after the offset of the last entry, looking up every
+        // entry once
+        let mut offset = page.last().unwrap().1;
+        loop {
+            let page = readdirplus(&mut harness, offset);
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 4);
+            for (name, off, entry) in page {
+                assert_eq!(entry.nodeid, off as u64 - 1);
+                listed.push(name);
+                offset = off;
+            }
+        }
+        assert_eq!(listed, names());
+        let lookups = &harness.filesystem().lookups;
+        assert_eq!(lookups.len(), 10);
+        assert!(lookups.values().all(|&x| x == 1));
+    }
+}
@@ -0,0 +1,661 @@
+//! FUSE over stream sockets
+//!
+//! A filesystem can run in a different process, or on a different host, than its mount. The
+//! server side runs a [`Session`](crate::Session) on a [`SocketTransport`], which exchanges
+//! the raw FUSE messages of the kernel driver over a Unix or TCP stream socket. The mount side
+//! runs a [`Relay`], which forwards the requests read from `/dev/fuse` to the server and its
+//! replies back to the kernel. Every FUSE message starts with its length, so the messages are
+//! written to the socket as they are, without additional framing.
+//!
+//! The relay reconnects if the connection to the server is lost, and replays `FUSE_INIT` and
+//! every request that wasn't answered yet to the new server. The mount survives restarts of
+//! the server this way, as long as the restarted server uses the same inode numbers and file
+//! handles as the previous one (or the filesystem holds no state worth keeping).
+
+use libc::{EAGAIN, EINTR, ENODEV, ENOENT};
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, IoSlice, Read, Write};
+use std::mem::size_of;
+use std::net::{Shutdown, TcpStream};
+use std::ops::DerefMut;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use zerocopy::FromBytes;
+
+use crate::channel::{Channel, Transport};
+use crate::ll::{self, fuse_abi as abi};
+use crate::mnt::Mount;
+use crate::reply::ReplySender;
+use crate::session::{aligned_sub_buf, BUFFER_SIZE};
+use crate::MountOption;
+
+/// Delay before the first attempt to reconnect to the server
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(10);
+/// Delay between attempts to reconnect to a server that is still unreachable
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// A stream socket that FUSE messages can be exchanged over
+pub trait SocketStream: Read + Write + Send + fmt::Debug + Sized + 'static {
+    /// Returns another handle to the same socket
+    fn try_clone(&self) -> io::Result<Self>;
+
+    /// Shuts down both directions of the socket, which ends blocking reads of other handles
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+impl SocketStream for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+impl SocketStream for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/// Reads the next FUSE message into the buffer. The message size is the first field of both
+/// the request and the reply header. Returns `None` if the stream ended before the message.
+fn read_message<S: Read>(
+    stream: &mut S,
+    buf: &mut [u8],
+    header_size: usize,
+) -> io::Result<Option<usize>> {
+    let mut pos = 0;
+    while pos < size_of::<u32>() {
+        match stream.read(&mut buf[pos..size_of::<u32>()]) {
+            Ok(0) if pos == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => pos += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let len = u32::from_ne_bytes(buf[..size_of::<u32>()].try_into().unwrap()) as usize;
+    if len < header_size || len > buf.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid FUSE message size {}", len),
+        ));
+    }
+    stream.read_exact(&mut buf[size_of::<u32>()..len])?;
+    Ok(Some(len))
+}
+
+/// Transport that receives FUSE requests from a stream socket, e.g. from a [`Relay`]
+///
+/// The session ends when the other side closes the connection.
+#[derive(Debug)]
+pub struct SocketTransport<S: SocketStream> {
+    reader: Mutex<S>,
+    sender: SocketSender<S>,
+}
+
+impl<S: SocketStream> SocketTransport<S> {
+    /// Create a transport on the given connected socket
+    pub fn new(stream: S) -> io::Result<Self> {
+        let writer = stream.try_clone()?;
+        Ok(Self {
+            reader: Mutex::new(stream),
+            sender: SocketSender(Arc::new(Mutex::new(writer))),
+        })
+    }
+}
+
+impl<S: SocketStream> Transport for SocketTransport<S> {
+    type Sender = SocketSender<S>;
+
+    fn receive(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut reader = self.reader.lock().unwrap();
+        match read_message(reader.deref_mut(), buf, size_of::<abi::fuse_in_header>())? {
+            Some(len) => Ok(len),
+            // The connection was closed, which ends the session like an unmount
+            None => Err(io::Error::from_raw_os_error(ENODEV)),
+        }
+    }
+
+    fn sender(&self) -> SocketSender<S> {
+        self.sender.clone()
+    }
+}
+
+/// Sender of replies to a stream socket
+#[derive(Debug)]
+pub struct SocketSender<S: SocketStream>(Arc<Mutex<S>>);
+
+impl<S: SocketStream> Clone for SocketSender<S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<S: SocketStream> ReplySender for SocketSender<S> {
+    fn send(&self, bufs: &[IoSlice<'_>]) -> io::Result<()> {
+        // Write the message at once, so that replies sent by different threads don't mix
+        let message: Vec<u8> = bufs.iter().flat_map(|x| x.iter().copied()).collect();
+        self.0.lock().unwrap().write_all(&message)
+    }
+}
+
+/// State shared by the two directions of a relay
+#[derive(Debug)]
+struct RelayState<S> {
+    /// Connection to the server, or `None` while reconnecting
+    server: Option<S>,
+    /// Counts the connections to the server, to tell if `server` was replaced
+    connection: u64,
+    /// The `FUSE_INIT` request, to initialize a new server after reconnecting
+    init: Option<Vec<u8>>,
+    /// Requests forwarded to the server that weren't answered yet, by their unique id
+    in_flight: BTreeMap<u64, Vec<u8>>,
+    /// Set when the filesystem was unmounted
+    stopped: bool,
+}
+
+/// The state of a relay, and the lock that keeps writes to the server in order. Requests are
+/// written outside of the state lock: the server may not read the next request before its
+/// replies are forwarded, which needs the state lock.
+#[derive(Debug)]
+struct Shared<S> {
+    state: Mutex<RelayState<S>>,
+    writer: Mutex<()>,
+}
+
+impl<S: SocketStream> Shared<S> {
+    /// Drops the connection to the server if it is still the given one, so that the reply
+    /// thread reconnects
+    fn disconnect(&self, connection: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.connection == connection {
+            if let Some(server) = state.server.take() {
+                let _ = server.shutdown();
+            }
+        }
+    }
+}
+
+/// Forwards FUSE requests from a mount to a server on a stream socket, and the replies back
+///
+/// The relay connects to the server with the given function and calls it again to
+/// reconnect whenever the connection is lost. It retries until the function succeeds,
+/// waiting up to a second between attempts.
+pub struct Relay<S: SocketStream, T: Transport = Channel> {
+    kernel: T,
+    connect: Box<dyn FnMut() -> io::Result<S> + Send>,
+    mount: Option<Mount>,
+}
+
+impl<S: SocketStream> Relay<S> {
+    /// Mount a filesystem served by the server that the given function connects to
+    pub fn mount<C>(mountpoint: &Path, options: &[MountOption], connect: C) -> io::Result<Self>
+    where
+        C: FnMut() -> io::Result<S> + Send + 'static,
+    {
+        info!("Mounting {}", mountpoint.display());
+        let (file, mount) = Mount::new(mountpoint, options)?;
+        let mut relay = Relay::with_transport(Channel::new(file), connect);
+        relay.mount = Some(mount);
+        Ok(relay)
+    }
+}
+
+impl<S: SocketStream, T: Transport> Relay<S, T> {
+    /// Create a relay that receives requests from the given transport instead of a mounted
+    /// FUSE device
+    pub fn with_transport<C>(kernel: T, connect: C) -> Self
+    where
+        C: FnMut() -> io::Result<S> + Send + 'static,
+    {
+        Self {
+            kernel,
+            connect: Box::new(connect),
+            mount: None,
+        }
+    }
+
+    /// Run the relay until the filesystem is unmounted. Replies are forwarded by a second
+    /// thread, which also reconnects to the server.
+    pub fn run(self) -> io::Result<()> {
+        let Relay {
+            kernel,
+            connect,
+            mount,
+        } = self;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(RelayState {
+                server: None,
+                connection: 0,
+                init: None,
+                in_flight: BTreeMap::new(),
+                stopped: false,
+            }),
+            writer: Mutex::new(()),
+        });
+        let replies = {
+            let shared = shared.clone();
+            let sender = kernel.sender();
+            thread::spawn(move || relay_replies(shared, connect, sender))
+        };
+
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let buf = aligned_sub_buf(&mut buffer, std::mem::align_of::<abi::fuse_in_header>());
+        let result = loop {
+            match kernel.receive(buf) {
+                Ok(size) => forward_request(&shared, &buf[..size]),
+                Err(err) => match err.raw_os_error() {
+                    Some(ENOENT) | Some(EINTR) | Some(EAGAIN) => continue,
+                    // Filesystem was unmounted
+                    Some(ENODEV) => break Ok(()),
+                    _ => break Err(err),
+                },
+            }
+        };
+
+        {
+            let mut state = shared.state.lock().unwrap();
+            state.stopped = true;
+            if let Some(server) = state.server.take() {
+                let _ = server.shutdown();
+            }
+        }
+        let _ = replies.join();
+        drop(mount);
+        result
+    }
+}
+
+impl<S: SocketStream, T: Transport + fmt::Debug> fmt::Debug for Relay<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Relay")
+            .field("kernel", &self.kernel)
+            .field("mount", &self.mount)
+            .finish()
+    }
+}
+
+/// Forwards a request from the kernel to the server, and keeps it to replay it after
+/// reconnecting until it is answered
+fn forward_request<S: SocketStream>(shared: &Shared<S>, request: &[u8]) {
+    let header = match abi::fuse_in_header::read_from_prefix(request) {
+        Some(header) => header,
+        None => return warn!("Dropping a request of {} bytes", request.len()),
+    };
+    let (server, connection) = {
+        let mut state = shared.state.lock().unwrap();
+        let opcode = header.opcode;
+        if opcode == abi::fuse_opcode::FUSE_INIT as u32 {
+            state.init = Some(request.to_vec());
+        }
+        if ll::expects_reply(opcode) {
+            state.in_flight.insert(header.unique, request.to_vec());
+        }
+        match &state.server {
+            Some(server) => (server.try_clone(), state.connection),
+            // Replayed once connected
+            None => return,
+        }
+    };
+    // A replay to a new server holds this lock until it is done, so the request follows it
+    let _writer = shared.writer.lock().unwrap();
+    if let Err(err) = server.and_then(|mut server| server.write_all(request)) {
+        // Let the reply thread reconnect, which replays the request
+        warn!("Failed to forward request to the server: {}", err);
+        shared.disconnect(connection);
+    }
+}
+
+/// Connects to the server, replays the requests that weren't answered yet and forwards the
+/// replies to the kernel, until the relay stops
+fn relay_replies<S: SocketStream, R: ReplySender>(
+    shared: Arc<Shared<S>>,
+    mut connect: Box<dyn FnMut() -> io::Result<S> + Send>,
+    kernel: R,
+) {
+    let mut buf = vec![0; BUFFER_SIZE];
+    let mut delay = MIN_RECONNECT_DELAY;
+    while !shared.state.lock().unwrap().stopped {
+        let mut stream = match connect() {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Failed to connect to the server: {}", err);
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                continue;
+            }
+        };
+        delay = MIN_RECONNECT_DELAY;
+        // Replay from another thread, as the server may answer before it read all requests
+        let replay = match stream.try_clone() {
+            Ok(writer) => {
+                let shared = shared.clone();
+                thread::spawn(move || replay(&shared, writer))
+            }
+            Err(err) => {
+                warn!("Failed to replay requests to the server: {}", err);
+                continue;
+            }
+        };
+
+        loop {
+            match read_message(&mut stream, &mut buf, size_of::<abi::fuse_out_header>()) {
+                Ok(Some(len)) => forward_reply(&shared.state, &kernel, &buf[..len]),
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("Failed to receive reply from the server: {}", err);
+                    break;
+                }
+            }
+        }
+        let _ = replay.join();
+        let mut state = shared.state.lock().unwrap();
+        if let Some(server) = state.server.take() {
+            let _ = server.shutdown();
+        }
+        if !state.stopped {
+            info!("Lost the connection to the server, reconnecting");
+        }
+    }
+}
+
+/// Initializes a new server like the previous one and sends it the requests that weren't
+/// answered yet, before the relay forwards new requests to it
+fn replay<S: SocketStream>(shared: &Shared<S>, mut stream: S) {
+    let _writer = shared.writer.lock().unwrap();
+    let (requests, connection) = {
+        let mut state = shared.state.lock().unwrap();
+        if state.stopped {
+            // Makes the reply thread's next read end
+            let _ = stream.shutdown();
+            return;
+        }
+        let server = match stream.try_clone() {
+            Ok(server) => server,
+            Err(err) => {
+                warn!("Failed to replay requests to the server: {}", err);
+                let _ = stream.shutdown();
+                return;
+            }
+        };
+        let mut requests = vec![];
+        // An answered init is replayed too, and its second reply dropped
+        if let Some(init) = &state.init {
+            let unique = abi::fuse_in_header::read_from_prefix(&init[..])
+                .unwrap()
+                .unique;
+            if !state.in_flight.contains_key(&unique) {
+                requests.push(init.clone());
+            }
+        }
+        requests.extend(state.in_flight.values().cloned());
+        state.server = Some(server);
+        state.connection += 1;
+        (requests, state.connection)
+    };
+    for request in requests.iter() {
+        if let Err(err) = stream.write_all(request) {
+            warn!("Failed to replay requests to the server: {}", err);
+            return shared.disconnect(connection);
+        }
+    }
+    if !requests.is_empty() {
+        debug!("Replayed {} requests", requests.len());
+    }
+}
+
+/// Forwards a reply from the server to the kernel, unless it answers a request that was
+/// already answered before a reconnect
+fn forward_reply<S: SocketStream, R: ReplySender>(
+    state: &Mutex<RelayState<S>>,
+    kernel: &R,
+    reply: &[u8],
+) {
+    let unique = match abi::fuse_out_header::read_from_prefix(reply) {
+        Some(header) => header.unique,
+        None => return warn!("Dropping a reply of {} bytes", reply.len()),
+    };
+    // Notifications have no unique id, and interrupts aren't kept in flight, as the server
+    // only answers them to ask for them to be sent again (EAGAIN) or to refuse them (ENOSYS)
+    let tracked = unique != 0 && unique & abi::FUSE_INT_REQ_BIT == 0;
+    if tracked && state.lock().unwrap().in_flight.remove(&unique).is_none() {
+        return debug!("Dropping a second reply to request {}", unique);
+    }
+    if let Err(err) = kernel.send(&[IoSlice::new(reply)]) {
+        // The kernel rejects replies to interrupted requests with ENOENT
+        debug!("Failed to forward reply to request {}: {}", unique, err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use std::io::{self, Write};
+    use std::mem::size_of;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::{Receiver, Sender};
+    use std::thread;
+    use std::time::Duration;
+
+    use libc::{ENOENT, ENOSYS};
+    use zerocopy::{AsBytes, FromBytes};
+
+    use super::{read_message, Relay, SocketStream, SocketTransport};
+    use crate::ll::fuse_abi::{
+        fuse_in_header, fuse_out_header, FUSE_INT_REQ_BIT, FUSE_KERNEL_VERSION,
+    };
+    use crate::ll::{
+        AnyReply, FileHandle, INodeNo, OpenFlags, ReplyError, RequestEncoder, WriteFlags,
+    };
+    use crate::session::BUFFER_SIZE;
+    use crate::testing::fixtures::{KernelStub, RootOnly};
+    use crate::{Filesystem, ReplyAttr, Request, Session};
+
+    /// Filesystem with only a root directory, which drops the connection on the first
+    /// getattr if it was given one
+    struct CrashOnce(Option<UnixStream>);
+
+    impl Filesystem for CrashOnce {
+        fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+            if let Some(stream) = self.0.take() {
+                SocketStream::shutdown(&stream).unwrap();
+            }
+            RootOnly.getattr(req, ino, reply);
+        }
+    }
+
+    /// Starts a server on one end of a socket pair and returns the other end
+    fn serve(crash: bool) -> io::Result<UnixStream> {
+        let (client, server) = UnixStream::pair()?;
+        let fs = CrashOnce(if crash {
+            Some(server.try_clone()?)
+        } else {
+            None
+        });
+        let transport = SocketTransport::new(server)?;
+        thread::spawn(move || Session::with_transport(fs, transport).run().unwrap());
+        Ok(client)
+    }
+
+    /// Starts a server that answers every request with a notification and a large reply,
+    /// without reading the next request in between
+    fn serve_chatty() -> io::Result<UnixStream> {
+        let (client, mut server) = UnixStream::pair()?;
+        thread::spawn(move || {
+            let mut buf = vec![0; BUFFER_SIZE];
+            let header_size = size_of::<fuse_in_header>();
+            while let Ok(Some(len)) = read_message(&mut server, &mut buf, header_size) {
+                let unique = fuse_in_header::read_from_prefix(&buf[..len])
+                    .unwrap()
+                    .unique;
+                for (unique, size) in [(0, 64), (unique, 1 << 20)] {
+                    let header = fuse_out_header {
+                        len: size as u32,
+                        // FUSE_NOTIFY_INVAL_INODE for the notification
+                        error: if unique == 0 { 2 } else { 0 },
+                        unique,
+                    };
+                    let mut message = vec![0; size];
+                    message[..size_of::<fuse_out_header>()].copy_from_slice(header.as_bytes());
+                    if server.write_all(&message).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(client)
+    }
+
+    /// Runs a relay to the servers that the given function connects to
+    fn relay_to(
+        connect: impl FnMut() -> io::Result<UnixStream> + Send + 'static,
+    ) -> (Sender<Vec<u8>>, Receiver<Vec<u8>>, thread::JoinHandle<()>) {
+        let (kernel, request_tx, reply_rx) = KernelStub::new();
+        let relay = Relay::with_transport(kernel, connect);
+        let handle = thread::spawn(move || relay.run().unwrap());
+        (request_tx, reply_rx, handle)
+    }
+
+    /// Runs a relay to servers that crash on the first getattr the given number of times
+    fn relay(crashes: usize) -> (Sender<Vec<u8>>, Receiver<Vec<u8>>, thread::JoinHandle<()>) {
+        let mut connections = 0;
+        relay_to(move || {
+            connections += 1;
+            serve(connections <= crashes)
+        })
+    }
+
+    fn receive(replies: &Receiver<Vec<u8>>) -> Vec<u8> {
+        replies.recv_timeout(Duration::from_secs(10)).unwrap()
+    }
+
+    #[test]
+    fn relay_requests() {
+        let (requests, replies, handle) = relay(0);
+        let mut enc = RequestEncoder::new();
+
+        let init = enc.init(4096, 0);
+        requests.send(init.as_bytes().to_vec()).unwrap();
+        let reply = receive(&replies);
+        let reply = AnyReply::try_from(&reply[..]).unwrap();
+        assert_eq!(reply.unique(), init.unique());
+        let init = reply.init().unwrap();
+        assert_eq!(init.major, FUSE_KERNEL_VERSION);
+
+        let getattr = enc.getattr(INodeNo(2), None);
+        requests.send(getattr.as_bytes().to_vec()).unwrap();
+        let reply = receive(&replies);
+        let reply = AnyReply::try_from(&reply[..]).unwrap();
+        assert_eq!(reply.unique(), getattr.unique());
+        match reply.attr(init.minor) {
+            Err(ReplyError::Errno(err)) => assert_eq!(err.0.get(), ENOENT),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+
+        drop(requests);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn relay_interrupt() {
+        let (requests, replies, handle) = relay(0);
+        let mut enc = RequestEncoder::new();
+
+        let init = enc.init(4096, 0);
+        requests.send(init.as_bytes().to_vec()).unwrap();
+        receive(&replies);
+
+        let getattr = enc.getattr(INodeNo(1), None);
+        let interrupt = enc.interrupt(getattr.unique());
+        assert_eq!(interrupt.unique().0, getattr.unique().0 | FUSE_INT_REQ_BIT);
+        requests.send(getattr.as_bytes().to_vec()).unwrap();
+        requests.send(interrupt.as_bytes().to_vec()).unwrap();
+        let reply = receive(&replies);
+        assert_eq!(
+            AnyReply::try_from(&reply[..]).unwrap().unique(),
+            getattr.unique()
+        );
+        // The server doesn't handle interrupts, which the kernel has to learn from its reply
+        let reply = receive(&replies);
+        let reply = AnyReply::try_from(&reply[..]).unwrap();
+        assert_eq!(reply.unique(), interrupt.unique());
+        assert_eq!(reply.result().unwrap_err().0.get(), ENOSYS);
+
+        drop(requests);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn replay_after_reconnect() {
+        let (requests, replies, handle) = relay(2);
+        let mut enc = RequestEncoder::new();
+
+        let init = enc.init(4096, 0);
+        requests.send(init.as_bytes().to_vec()).unwrap();
+        let reply = receive(&replies);
+        let minor = AnyReply::try_from(&reply[..])
+            .unwrap()
+            .init()
+            .unwrap()
+            .minor;
+
+        // The first two servers drop the connection instead of answering
+        let getattr = enc.getattr(INodeNo(1), None);
+        requests.send(getattr.as_bytes().to_vec()).unwrap();
+        let reply = receive(&replies);
+        let reply = AnyReply::try_from(&reply[..]).unwrap();
+        assert_eq!(reply.unique(), getattr.unique());
+        assert_eq!(reply.attr(minor).unwrap().attr.ino, 1);
+        // The replayed init isn't answered twice
+        assert!(replies.recv_timeout(Duration::from_millis(100)).is_err());
+
+        drop(requests);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn server_replies_before_reading() {
+        let (requests, replies, handle) = relay_to(serve_chatty);
+        let mut enc = RequestEncoder::new();
+
+        // Each request fills the socket while the server is busy replying to the previous one
+        let data = vec![0; 1 << 20];
+        let mut uniques = vec![];
+        for _ in 0..16 {
+            let write = enc.write(
+                INodeNo(1),
+                FileHandle(0),
+                0,
+                &data,
+                WriteFlags(0),
+                OpenFlags(0),
+                None,
+            );
+            uniques.push(write.unique());
+            requests.send(write.as_bytes().to_vec()).unwrap();
+        }
+        for unique in uniques {
+            let notification = receive(&replies);
+            assert_eq!(notification.len(), 64);
+            let reply = receive(&replies);
+            assert_eq!(AnyReply::try_from(&reply[..]).unwrap().unique(), unique);
+        }
+
+        drop(requests);
+        handle.join().unwrap();
+    }
+}
@@ -6,6 +6,8 @@
 //! data without cloning the data. A reply *must always* be used (by calling either ok() or
 //! error() exactly once).
 
+use crate::ll::fuse_abi::consts::FOPEN_PASSTHROUGH;
+use crate::ll::IoctlIovec;
 use crate::ll::{
     self,
     reply::{DirEntPlusList, DirEntryPlus},
@@ -17,21 +19,37 @@ use crate::ll::{
 };
 use libc::c_int;
 use log::{error, warn};
-use std::convert::AsRef;
+use std::convert::{AsRef, TryFrom};
 use std::ffi::OsStr;
 use std::fmt;
 use std::io::IoSlice;
+use std::os::unix::ffi::OsStrExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::RawFd;
 use std::time::Duration;
 
 #[cfg(target_os = "macos")]
 use std::time::SystemTime;
 
-use crate::{FileAttr, FileType};
+use crate::{FileAttr, FileStatx, FileType, InodeGenerations};
 
 /// Generic reply callback to send data
 pub trait ReplySender: Send + 'static {
     /// Send data.
     fn send(&self, data: &[IoSlice<'_>]) -> std::io::Result<()>;
+
+    /// Registers the given file as backing file for passthrough I/O and returns its backing
+    /// id. Only the FUSE device supports this.
+    #[cfg(target_os = "linux")]
+    fn open_backing(&self, _fd: RawFd) -> std::io::Result<u32> {
+        Err(std::io::Error::from_raw_os_error(libc::EOPNOTSUPP))
+    }
+
+    /// Unregisters a backing file registered with `open_backing`
+    #[cfg(target_os = "linux")]
+    fn close_backing(&self, _backing_id: u32) -> std::io::Result<()> {
+        Err(std::io::Error::from_raw_os_error(libc::EOPNOTSUPP))
+    }
 }
 
 impl fmt::Debug for Box<dyn ReplySender> {
@@ -46,6 +64,12 @@ pub trait Reply {
     fn new<S: ReplySender>(unique: u64, sender: S) -> Self;
 }
 
+/// Reply whose layout depends on the ABI version negotiated with the kernel
+pub(crate) trait VersionedReply: Reply {
+    /// Encode the reply for the given minor ABI version
+    fn with_minor(self, minor: u32) -> Self;
+}
+
 ///
 /// Raw reply
 ///
@@ -55,6 +79,8 @@ pub(crate) struct ReplyRaw {
     unique: ll::RequestId,
     /// Closure to call for sending the reply
     sender: Option<Box<dyn ReplySender>>,
+    /// Minor version of the ABI negotiated with the kernel
+    minor: u32,
 }
 
 impl Reply for ReplyRaw {
@@ -63,10 +89,18 @@ impl Reply for ReplyRaw {
         ReplyRaw {
             unique: ll::RequestId(unique),
             sender: Some(sender),
+            minor: ll::fuse_abi::FUSE_KERNEL_MINOR_VERSION,
         }
     }
 }
 
+impl VersionedReply for ReplyRaw {
+    fn with_minor(mut self, minor: u32) -> Self {
+        self.minor = minor;
+        self
+    }
+}
+
 impl ReplyRaw {
     /// Reply to a request with the given error code and data. Must be called
     /// only once (the `ok` and `error` methods ensure this by consuming `self`)
@@ -175,18 +209,54 @@ impl Reply for ReplyEntry {
     }
 }
 
+impl VersionedReply for ReplyEntry {
+    fn with_minor(self, minor: u32) -> Self {
+        ReplyEntry {
+            reply: self.reply.with_minor(minor),
+        }
+    }
+}
+
 impl ReplyEntry {
-    /// Reply to a request with the given entry
+    /// Reply to a request with the given entry. The pair of inode number and generation
+    /// must be unique over the lifetime of the filesystem if it is exported (see
+    /// [`InodeGenerations`] and [`entry_checked`](Self::entry_checked)).
     pub fn entry(self, ttl: &Duration, attr: &FileAttr, generation: u64) {
+        let minor = self.reply.minor;
         self.reply.send_ll(&ll::Response::new_entry(
             ll::INodeNo(attr.ino),
             ll::Generation(generation),
             &attr.into(),
             *ttl,
             *ttl,
+            minor,
         ));
     }
 
+    /// Reply to a request with the given entry, if the generation is the current one of the
+    /// inode number. If the inode number was reused since, the entry refers to an inode that
+    /// is gone, and the request fails with `ESTALE` instead.
+    pub fn entry_checked(
+        self,
+        generations: &InodeGenerations,
+        ttl: &Duration,
+        attr: &FileAttr,
+        generation: u64,
+    ) {
+        match generations.validate(attr.ino, generation) {
+            Ok(()) => self.entry(ttl, attr, generation),
+            Err(err) => {
+                warn!(
+                    "Stale entry of inode {:#x}: generation {}, current {}",
+                    attr.ino,
+                    generation,
+                    generations.generation(attr.ino)
+                );
+                self.error(err);
+            }
+        }
+    }
+
     /// Reply to a request with the given error code
     pub fn error(self, err: c_int) {
         self.reply.error(err);
@@ -209,11 +279,20 @@ impl Reply for ReplyAttr {
     }
 }
 
+impl VersionedReply for ReplyAttr {
+    fn with_minor(self, minor: u32) -> Self {
+        ReplyAttr {
+            reply: self.reply.with_minor(minor),
+        }
+    }
+}
+
 impl ReplyAttr {
     /// Reply to a request with the given attribute
     pub fn attr(self, ttl: &Duration, attr: &FileAttr) {
+        let minor = self.reply.minor;
         self.reply
-            .send_ll(&ll::Response::new_attr(ttl, &attr.into()));
+            .send_ll(&ll::Response::new_attr(ttl, &attr.into(), minor));
     }
 
     /// Reply to a request with the given error code
@@ -222,6 +301,60 @@ impl ReplyAttr {
     }
 }
 
+///
+/// Statx Reply
+///
+#[derive(Debug)]
+pub struct ReplyStatx {
+    reply: ReplyRaw,
+}
+
+impl Reply for ReplyStatx {
+    fn new<S: ReplySender>(unique: u64, sender: S) -> ReplyStatx {
+        ReplyStatx {
+            reply: Reply::new(unique, sender),
+        }
+    }
+}
+
+impl ReplyStatx {
+    /// Reply to a request with the given extended attributes
+    pub fn statx(self, ttl: &Duration, statx: &FileStatx) {
+        self.reply.send_ll(&ll::Response::new_statx(ttl, statx));
+    }
+
+    /// A reply to getattr that answers this statx request with the basic attributes
+    pub(crate) fn getattr_fallback(mut self) -> ReplyAttr {
+        let sender = self.reply.sender.take().unwrap();
+        Reply::new(self.reply.unique.into(), StatxFromAttr(sender))
+    }
+
+    /// Reply to a request with the given error code
+    pub fn error(self, err: c_int) {
+        self.reply.error(err);
+    }
+}
+
+/// Sends the reply to a getattr as the reply to a statx
+#[derive(Debug)]
+struct StatxFromAttr(Box<dyn ReplySender>);
+
+impl ReplySender for StatxFromAttr {
+    fn send(&self, data: &[IoSlice<'_>]) -> std::io::Result<()> {
+        let data: Vec<u8> = data.iter().flat_map(|x| x.iter().copied()).collect();
+        let reply = match ll::AnyReply::try_from(&data[..]) {
+            Ok(reply) => reply,
+            Err(_) => return self.0.send(&[IoSlice::new(&data)]),
+        };
+        match reply.attr(ll::fuse_abi::FUSE_KERNEL_MINOR_VERSION) {
+            Ok(attr) => ll::Response::new_statx_from_attr(&attr)
+                .with_iovec(reply.unique(), |iov| self.0.send(iov)),
+            // Errors look the same for both
+            Err(_) => self.0.send(&[IoSlice::new(&data)]),
+        }
+    }
+}
+
 ///
 /// XTimes Reply
 ///
@@ -274,7 +407,18 @@ impl ReplyOpen {
     /// Reply to a request with the given open result
     pub fn opened(self, fh: u64, flags: u32) {
         self.reply
-            .send_ll(&ll::Response::new_open(ll::FileHandle(fh), flags))
+            .send_ll(&ll::Response::new_open(ll::FileHandle(fh), flags, 0))
+    }
+
+    /// Reply to a request with the given open result, passing reads and writes through to
+    /// the backing file registered with [`Request::open_backing`](crate::Request::open_backing).
+    /// `FOPEN_PASSTHROUGH` is added to flags.
+    pub fn opened_passthrough(self, fh: u64, flags: u32, backing_id: u32) {
+        self.reply.send_ll(&ll::Response::new_open(
+            ll::FileHandle(fh),
+            flags | FOPEN_PASSTHROUGH,
+            backing_id as i32,
+        ))
     }
 
     /// Reply to a request with the given error code
@@ -368,15 +512,50 @@ impl Reply for ReplyCreate {
     }
 }
 
+impl VersionedReply for ReplyCreate {
+    fn with_minor(self, minor: u32) -> Self {
+        ReplyCreate {
+            reply: self.reply.with_minor(minor),
+        }
+    }
+}
+
 impl ReplyCreate {
     /// Reply to a request with the given entry
     pub fn created(self, ttl: &Duration, attr: &FileAttr, generation: u64, fh: u64, flags: u32) {
+        let minor = self.reply.minor;
         self.reply.send_ll(&ll::Response::new_create(
             ttl,
             &attr.into(),
             ll::Generation(generation),
             ll::FileHandle(fh),
             flags,
+            0,
+            minor,
+        ))
+    }
+
+    /// Reply to a request with the given entry, passing reads and writes through to the
+    /// backing file registered with [`Request::open_backing`](crate::Request::open_backing).
+    /// `FOPEN_PASSTHROUGH` is added to flags.
+    pub fn created_passthrough(
+        self,
+        ttl: &Duration,
+        attr: &FileAttr,
+        generation: u64,
+        fh: u64,
+        flags: u32,
+        backing_id: u32,
+    ) {
+        let minor = self.reply.minor;
+        self.reply.send_ll(&ll::Response::new_create(
+            ttl,
+            &attr.into(),
+            ll::Generation(generation),
+            ll::FileHandle(fh),
+            flags | FOPEN_PASSTHROUGH,
+            backing_id as i32,
+            minor,
         ))
     }
 
@@ -452,23 +631,76 @@ impl ReplyBmap {
 #[derive(Debug)]
 pub struct ReplyIoctl {
     reply: ReplyRaw,
+    /// `FUSE_IOCTL_*` flags of the request, needed to validate a retry
+    flags: u32,
 }
 
 impl Reply for ReplyIoctl {
     fn new<S: ReplySender>(unique: u64, sender: S) -> ReplyIoctl {
         ReplyIoctl {
             reply: Reply::new(unique, sender),
+            flags: 0,
+        }
+    }
+}
+
+impl VersionedReply for ReplyIoctl {
+    fn with_minor(self, minor: u32) -> Self {
+        ReplyIoctl {
+            reply: self.reply.with_minor(minor),
+            ..self
         }
     }
 }
 
 impl ReplyIoctl {
+    /// Remember the flags of the request this reply belongs to
+    pub(crate) fn with_flags(mut self, flags: u32) -> Self {
+        self.flags = flags;
+        self
+    }
+
     /// Reply to a request with the given open result
     pub fn ioctl(self, result: i32, data: &[u8]) {
         self.reply
             .send_ll(&ll::Response::new_ioctl(result, &[IoSlice::new(data)]));
     }
 
+    /// Ask the kernel to retry an unrestricted ioctl. The kernel copies the caller's memory
+    /// described by `in_iovs` into the input data of the retried request, and copies the
+    /// reply data of the retried request back into the memory described by `out_iovs`.
+    /// Only valid for requests with the `FUSE_IOCTL_UNRESTRICTED` flag. At most
+    /// `FUSE_IOCTL_MAX_IOV` iovecs may be given, and for compat ioctls of 32 bit processes
+    /// all addresses and lengths must fit into 32 bits.
+    pub fn retry(self, in_iovs: &[IoctlIovec], out_iovs: &[IoctlIovec]) {
+        if self.flags & ll::fuse_abi::consts::FUSE_IOCTL_UNRESTRICTED == 0 {
+            error!("Ioctl retry is only possible for unrestricted ioctls");
+            return self.error(libc::EINVAL);
+        }
+        if in_iovs.len() + out_iovs.len() > ll::fuse_abi::consts::FUSE_IOCTL_MAX_IOV as usize {
+            error!(
+                "Too many iovecs for ioctl retry: {} in, {} out",
+                in_iovs.len(),
+                out_iovs.len()
+            );
+            return self.error(libc::EINVAL);
+        }
+        let compat = ll::ioctl_is_compat(self.flags);
+        if compat
+            && in_iovs
+                .iter()
+                .chain(out_iovs)
+                .any(|iov| iov.base > u32::MAX as u64 || iov.len > u32::MAX as u64)
+        {
+            error!("Ioctl retry iovecs of a compat ioctl exceed 32 bits");
+            return self.error(libc::EINVAL);
+        }
+        let minor = self.reply.minor;
+        self.reply.send_ll(&ll::Response::new_ioctl_retry(
+            in_iovs, out_iovs, compat, minor,
+        ));
+    }
+
     /// Reply to a request with the given error code
     pub fn error(self, err: c_int) {
         self.reply.error(err);
@@ -560,6 +792,27 @@ impl ReplyDirectoryPlus {
         ))
     }
 
+    /// Size of the directory reply buffer
+    pub(crate) fn size(&self) -> usize {
+        self.buf.max_size()
+    }
+
+    /// Returns true if an entry with the given name doesn't fit into the buffer anymore
+    pub(crate) fn is_full_for(&self, name: &OsStr) -> bool {
+        self.buf.is_full_for(name.len())
+    }
+
+    /// Add an entry of a readdir reply, with the lookup reply of the entry if there is one.
+    /// Returns true if the buffer is full.
+    pub(crate) fn add_dirent(
+        &mut self,
+        dirent: ll::fuse_abi::fuse_dirent,
+        name: &OsStr,
+        entry_out: Option<ll::fuse_abi::fuse_entry_out>,
+    ) -> bool {
+        self.buf.push_dirent(dirent, name.as_bytes(), entry_out)
+    }
+
     /// Reply to a request with the filled directory buffer
     pub fn ok(self) {
         self.reply.send_ll(&self.buf.into());
@@ -632,6 +885,34 @@ impl ReplyLseek {
     }
 }
 
+///
+/// Poll Reply
+///
+#[derive(Debug)]
+pub struct ReplyPoll {
+    reply: ReplyRaw,
+}
+
+impl Reply for ReplyPoll {
+    fn new<S: ReplySender>(unique: u64, sender: S) -> ReplyPoll {
+        ReplyPoll {
+            reply: Reply::new(unique, sender),
+        }
+    }
+}
+
+impl ReplyPoll {
+    /// Reply to a request with the returned poll events (`POLLIN`, `POLLOUT`, ...)
+    pub fn poll(self, revents: u32) {
+        self.reply.send_ll(&ll::Response::new_poll(revents))
+    }
+
+    /// Reply to a request with the given error code
+    pub fn error(self, err: c_int) {
+        self.reply.error(err);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -770,10 +1051,12 @@ mod test {
             ]
         };
 
-        if cfg!(feature = "abi-7-9") {
+        if cfg!(target_os = "macos") {
             expected.extend(vec![0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        } else {
+            expected.extend(vec![0xbb, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
         }
-        expected[0] = (expected.len()) as u8;
+        expected[0] = expected.len() as u8;
 
         let sender = AssertSender { expected };
         let reply: ReplyEntry = Reply::new(0xdeadbeef, sender);
@@ -799,6 +1082,57 @@ mod test {
         reply.entry(&ttl, &attr, 0xaa);
     }
 
+    #[test]
+    fn reply_entry_checked() {
+        let ttl = Duration::new(0x8765, 0x4321);
+        let attr = FileAttr {
+            ino: 0x11,
+            size: 0x22,
+            blocks: 0x33,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 0x55,
+            uid: 0x66,
+            gid: 0x77,
+            rdev: 0x88,
+            flags: 0,
+            blksize: 0xbb,
+        };
+        let mut generations = InodeGenerations::new();
+        for _ in 0..0xaa {
+            generations.reuse(0x11);
+        }
+
+        // The entry is sent if the generation is the current one
+        let entry = ll::Response::new_entry(
+            ll::INodeNo(0x11),
+            ll::Generation(0xaa),
+            &(&attr).into(),
+            ttl,
+            ttl,
+            ll::fuse_abi::FUSE_KERNEL_MINOR_VERSION,
+        );
+        let sender = AssertSender {
+            expected: entry.with_iovec(ll::RequestId(0xdeadbeef), |iov| {
+                iov.iter().flat_map(|x| x.to_vec()).collect()
+            }),
+        };
+        let reply: ReplyEntry = Reply::new(0xdeadbeef, sender);
+        reply.entry_checked(&generations, &ttl, &attr, 0xaa);
+
+        // An entry of an older generation refers to an inode that is gone
+        let mut expected = vec![0x10, 0x00, 0x00, 0x00];
+        expected.extend_from_slice(&(-libc::ESTALE).to_le_bytes());
+        expected.extend_from_slice(&[0xef, 0xbe, 0xad, 0xde, 0x00, 0x00, 0x00, 0x00]);
+        let sender = AssertSender { expected };
+        let reply: ReplyEntry = Reply::new(0xdeadbeef, sender);
+        reply.entry_checked(&generations, &ttl, &attr, 0xa9);
+    }
+
     #[test]
     fn reply_attr() {
         let mut expected = if cfg!(target_os = "macos") {
@@ -827,8 +1161,10 @@ mod test {
             ]
         };
 
-        if cfg!(feature = "abi-7-9") {
+        if cfg!(target_os = "macos") {
             expected.extend_from_slice(&[0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        } else {
+            expected.extend_from_slice(&[0xbb, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
         }
         expected[0] = expected.len() as u8;
 
@@ -856,6 +1192,79 @@ mod test {
         reply.attr(&ttl, &attr);
     }
 
+    fn statx_expected(
+        mask: u32,
+        attributes: u64,
+        attributes_mask: u64,
+        btime: (u64, u32),
+    ) -> Vec<u8> {
+        let mut expected = vec![
+            0x30, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+            0x00, 0x00, 0x65, 0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0x43, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        expected.extend_from_slice(&[0; 16]);
+        expected.extend_from_slice(&mask.to_le_bytes());
+        expected.extend_from_slice(&[0xbb, 0x00, 0x00, 0x00]);
+        expected.extend_from_slice(&attributes.to_le_bytes());
+        expected.extend_from_slice(&[
+            0x55, 0x00, 0x00, 0x00, 0x66, 0x00, 0x00, 0x00, 0x77, 0x00, 0x00, 0x00, 0xa4, 0x81,
+            0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x33, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        expected.extend_from_slice(&attributes_mask.to_le_bytes());
+        for (secs, nsecs) in [(0x1234, 0x5678), btime, (0x1234, 0x5678), (0x1234, 0x5678)] {
+            expected.extend_from_slice(&u64::to_le_bytes(secs));
+            expected.extend_from_slice(&u32::to_le_bytes(nsecs));
+            expected.extend_from_slice(&[0; 4]);
+        }
+        expected.extend_from_slice(&[0x08, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00]);
+        expected.extend_from_slice(&[0; 8 + 14 * 8]);
+        expected
+    }
+
+    #[test]
+    fn reply_statx() {
+        let time = UNIX_EPOCH + Duration::new(0x1234, 0x5678);
+        let ttl = Duration::new(0x8765, 0x4321);
+        let attr = FileAttr {
+            ino: 0x11,
+            size: 0x22,
+            blocks: 0x33,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 0x55,
+            uid: 0x66,
+            gid: 0x77,
+            rdev: 0x0803,
+            flags: 0x99,
+            blksize: 0xbb,
+        };
+        let sender = AssertSender {
+            expected: statx_expected(0xfff, 0x20, 0x30, (0x2345, 0x5678)),
+        };
+        let reply: ReplyStatx = Reply::new(0xdeadbeef, sender);
+        let statx = FileStatx {
+            attr,
+            btime: UNIX_EPOCH + Duration::new(0x2345, 0x5678),
+            mask: 0xfff,
+            attributes: 0x20,
+            attributes_mask: 0x30,
+        };
+        reply.statx(&ttl, &statx);
+
+        // Answering with the attributes of getattr doesn't report crtime as birth time
+        let sender = AssertSender {
+            expected: statx_expected(0x7ff, 0, 0, (0, 0)),
+        };
+        let reply: ReplyStatx = Reply::new(0xdeadbeef, sender);
+        reply.getattr_fallback().attr(&ttl, &attr);
+    }
+
     #[test]
     #[cfg(target_os = "macos")]
     fn reply_xtimes() {
@@ -884,6 +1293,19 @@ mod test {
         reply.opened(0x1122, 0x33);
     }
 
+    #[test]
+    fn reply_open_passthrough() {
+        let sender = AssertSender {
+            expected: vec![
+                0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00, 0x22, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x81, 0x00, 0x00, 0x00,
+                0x05, 0x00, 0x00, 0x00,
+            ],
+        };
+        let reply: ReplyOpen = Reply::new(0xdeadbeef, sender);
+        reply.opened_passthrough(0x1122, 0x01, 5);
+    }
+
     #[test]
     fn reply_write() {
         let sender = AssertSender {
@@ -946,14 +1368,20 @@ mod test {
             ]
         };
 
-        if cfg!(feature = "abi-7-9") {
+        if cfg!(target_os = "macos") {
             let insert_at = expected.len() - 16;
             expected.splice(
                 insert_at..insert_at,
                 vec![0xdd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
             );
+        } else {
+            let insert_at = expected.len() - 16;
+            expected.splice(
+                insert_at..insert_at,
+                vec![0xdd, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00],
+            );
         }
-        expected[0] = (expected.len()) as u8;
+        expected[0] = expected.len() as u8;
 
         let sender = AssertSender { expected };
         let reply: ReplyCreate = Reply::new(0xdeadbeef, sender);
@@ -1004,6 +1432,103 @@ mod test {
         reply.bmap(0x1234);
     }
 
+    #[test]
+    fn reply_ioctl_retry() {
+        let sender = AssertSender {
+            expected: vec![
+                0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        };
+        let reply: ReplyIoctl = Reply::new(0xdeadbeef, sender);
+        reply
+            .with_flags(crate::consts::FUSE_IOCTL_UNRESTRICTED)
+            .retry(
+                &[IoctlIovec {
+                    base: 0x1000,
+                    len: 8,
+                }],
+                &[IoctlIovec {
+                    base: 0x2000,
+                    len: 16,
+                }],
+            );
+    }
+
+    #[test]
+    fn reply_ioctl_retry_compat() {
+        // Before ABI 7.16, compat ioctls take 32 bit iovecs
+        let sender = AssertSender {
+            expected: vec![
+                0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x20,
+                0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
+            ],
+        };
+        let reply: ReplyIoctl = Reply::new(0xdeadbeef, sender);
+        reply
+            .with_flags(crate::consts::FUSE_IOCTL_UNRESTRICTED | crate::consts::FUSE_IOCTL_COMPAT)
+            .with_minor(15)
+            .retry(
+                &[IoctlIovec {
+                    base: 0x1000,
+                    len: 8,
+                }],
+                &[IoctlIovec {
+                    base: 0x2000,
+                    len: 16,
+                }],
+            );
+    }
+
+    #[test]
+    fn reply_ioctl_retry_restricted() {
+        let sender = AssertSender {
+            expected: vec![
+                0x10, 0x00, 0x00, 0x00, 0xea, 0xff, 0xff, 0xff, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+        };
+        let reply: ReplyIoctl = Reply::new(0xdeadbeef, sender);
+        reply.retry(&[], &[]);
+    }
+
+    #[test]
+    fn reply_ioctl_retry_compat_overflow() {
+        let sender = AssertSender {
+            expected: vec![
+                0x10, 0x00, 0x00, 0x00, 0xea, 0xff, 0xff, 0xff, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+        };
+        let reply: ReplyIoctl = Reply::new(0xdeadbeef, sender);
+        reply
+            .with_flags(crate::consts::FUSE_IOCTL_UNRESTRICTED | crate::consts::FUSE_IOCTL_COMPAT)
+            .retry(
+                &[IoctlIovec {
+                    base: 0x1_0000_0000,
+                    len: 8,
+                }],
+                &[],
+            );
+    }
+
+    #[test]
+    fn reply_poll() {
+        let sender = AssertSender {
+            expected: vec![
+                0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef, 0xbe, 0xad, 0xde, 0x00, 0x00,
+                0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        };
+        let reply: ReplyPoll = Reply::new(0xdeadbeef, sender);
+        reply.poll(0x5);
+    }
+
     #[test]
     fn reply_directory() {
         let sender = AssertSender {
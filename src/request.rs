@@ -1,55 +1,123 @@
 //! Filesystem operation request
 //!
 //! A request represents information about a filesystem operation the kernel driver wants us to
-//! perform.
-//!
-//! TODO: This module is meant to go away soon in favor of `ll::Request`.
+//! perform. It wraps a request decoded by the [`ll`](crate::ll) codec with the session it was
+//! received on, to dispatch it to the filesystem and send back its reply.
 
 use crate::ll::{fuse_abi as abi, Errno, Response};
 use log::{debug, error, warn};
+use std::cmp::min;
 use std::convert::TryFrom;
-#[cfg(feature = "abi-7-28")]
-use std::convert::TryInto;
-use std::path::Path;
+use std::fmt;
+use std::io::{self, IoSlice};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::{AsRawFd, RawFd};
 
-use crate::channel::ChannelSender;
+use crate::channel::Transport;
+use crate::cuse::{CharDevice, CuseSession};
 use crate::ll::Request as _;
-#[cfg(feature = "abi-7-21")]
 use crate::reply::ReplyDirectoryPlus;
-use crate::reply::{Reply, ReplyDirectory, ReplySender};
+use crate::reply::ReplyIoctl;
+use crate::reply::{Reply, ReplyDirectory, ReplySender, VersionedReply};
 use crate::session::{Session, SessionACL};
-use crate::Filesystem;
+use crate::FilesystemV2;
 use crate::{ll, KernelConfig};
 
+/// Sender of the transport a request was received on, with its type erased so that requests
+/// (and the filesystem traits taking them) don't depend on the transport
+trait RequestSender: ReplySender {
+    fn clone_box(&self) -> Box<dyn RequestSender>;
+}
+
+impl<S: ReplySender + Clone> RequestSender for S {
+    fn clone_box(&self) -> Box<dyn RequestSender> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn RequestSender> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+impl fmt::Debug for Box<dyn RequestSender> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Box<RequestSender>")
+    }
+}
+
+impl ReplySender for Box<dyn RequestSender> {
+    fn send(&self, data: &[IoSlice<'_>]) -> io::Result<()> {
+        (**self).send(data)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_backing(&self, fd: RawFd) -> io::Result<u32> {
+        (**self).open_backing(fd)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn close_backing(&self, backing_id: u32) -> io::Result<()> {
+        (**self).close_backing(backing_id)
+    }
+}
+
 /// Request data structure
 #[derive(Debug)]
 pub struct Request<'a> {
-    /// Channel sender for sending the reply
-    ch: ChannelSender,
+    /// Sender for the reply, of the transport the request was received on
+    ch: Box<dyn RequestSender>,
     /// Request raw data
     data: &'a [u8],
     /// Parsed request
     request: ll::AnyRequest<'a>,
+    /// Supplementary groups of the caller, passed with requests creating an inode
+    supplementary_groups: Vec<u32>,
+}
+
+/// Parses the supplementary groups out of a request creating an inode
+fn supplementary_groups(request: &ll::AnyRequest<'_>) -> Vec<u32> {
+    #[cfg(not(target_os = "macos"))]
+    if request.protocol().flags & abi::consts::FUSE_CREATE_SUPP_GROUP != 0 {
+        match request.operation() {
+            Ok(ll::Operation::MkNod(x)) => return x.supplementary_groups().to_vec(),
+            Ok(ll::Operation::MkDir(x)) => return x.supplementary_groups().to_vec(),
+            Ok(ll::Operation::SymLink(x)) => return x.supplementary_groups().to_vec(),
+            Ok(ll::Operation::Create(x)) => return x.supplementary_groups().to_vec(),
+            _ => {}
+        }
+    }
+    Vec::new()
 }
 
 impl<'a> Request<'a> {
     /// Create a new request from the given data
-    pub(crate) fn new(ch: ChannelSender, data: &'a [u8]) -> Option<Request<'a>> {
+    pub(crate) fn new<S: ReplySender + Clone>(
+        ch: S,
+        data: &'a [u8],
+        protocol: ll::Protocol,
+    ) -> Option<Request<'a>> {
         let request = match ll::AnyRequest::try_from(data) {
-            Ok(request) => request,
+            Ok(request) => request.with_protocol(protocol),
             Err(err) => {
                 error!("{}", err);
                 return None;
             }
         };
 
-        Some(Self { ch, data, request })
+        Some(Self {
+            ch: Box::new(ch),
+            data,
+            supplementary_groups: supplementary_groups(&request),
+            request,
+        })
     }
 
     /// Dispatch request to the given filesystem.
     /// This calls the appropriate filesystem operation method for the
     /// request and sends back the returned reply to the kernel
-    pub(crate) fn dispatch<FS: Filesystem>(&self, se: &mut Session<FS>) {
+    pub(crate) fn dispatch<FS: FilesystemV2, T: Transport>(&self, se: &mut Session<FS, T>) {
         debug!("{}", self.request);
         let unique = self.request.unique();
 
@@ -65,9 +133,9 @@ impl<'a> Request<'a> {
         }
     }
 
-    fn dispatch_req<FS: Filesystem>(
+    fn dispatch_req<FS: FilesystemV2, T: Transport>(
         &self,
-        se: &mut Session<FS>,
+        se: &mut Session<FS, T>,
     ) -> Result<Option<Response>, Errno> {
         let op = self.request.operation().map_err(|_| Errno::ENOSYS)?;
         // Implement allow_root & access check for auto_unmount
@@ -76,64 +144,22 @@ impl<'a> Request<'a> {
             && self.request.uid() != 0)
             || (se.allowed == SessionACL::Owner && self.request.uid() != se.session_owner)
         {
-            #[cfg(feature = "abi-7-21")]
-            {
-                match op {
-                    // Only allow operations that the kernel may issue without a uid set
-                    ll::Operation::Init(_)
-                    | ll::Operation::Destroy(_)
-                    | ll::Operation::Read(_)
-                    | ll::Operation::ReadDir(_)
-                    | ll::Operation::ReadDirPlus(_)
-                    | ll::Operation::BatchForget(_)
-                    | ll::Operation::Forget(_)
-                    | ll::Operation::Write(_)
-                    | ll::Operation::FSync(_)
-                    | ll::Operation::FSyncDir(_)
-                    | ll::Operation::Release(_)
-                    | ll::Operation::ReleaseDir(_) => {}
-                    _ => {
-                        return Err(Errno::EACCES);
-                    }
-                }
-            }
-            #[cfg(all(feature = "abi-7-16", not(feature = "abi-7-21")))]
-            {
-                match op {
-                    // Only allow operations that the kernel may issue without a uid set
-                    ll::Operation::Init(_)
-                    | ll::Operation::Destroy(_)
-                    | ll::Operation::Read(_)
-                    | ll::Operation::ReadDir(_)
-                    | ll::Operation::BatchForget(_)
-                    | ll::Operation::Forget(_)
-                    | ll::Operation::Write(_)
-                    | ll::Operation::FSync(_)
-                    | ll::Operation::FSyncDir(_)
-                    | ll::Operation::Release(_)
-                    | ll::Operation::ReleaseDir(_) => {}
-                    _ => {
-                        return Err(Errno::EACCES);
-                    }
-                }
-            }
-            #[cfg(not(feature = "abi-7-16"))]
-            {
-                match op {
-                    // Only allow operations that the kernel may issue without a uid set
-                    ll::Operation::Init(_)
-                    | ll::Operation::Destroy(_)
-                    | ll::Operation::Read(_)
-                    | ll::Operation::ReadDir(_)
-                    | ll::Operation::Forget(_)
-                    | ll::Operation::Write(_)
-                    | ll::Operation::FSync(_)
-                    | ll::Operation::FSyncDir(_)
-                    | ll::Operation::Release(_)
-                    | ll::Operation::ReleaseDir(_) => {}
-                    _ => {
-                        return Err(Errno::EACCES);
-                    }
+            match op {
+                // Only allow operations that the kernel may issue without a uid set
+                ll::Operation::Init(_)
+                | ll::Operation::Destroy(_)
+                | ll::Operation::Read(_)
+                | ll::Operation::ReadDir(_)
+                | ll::Operation::ReadDirPlus(_)
+                | ll::Operation::BatchForget(_)
+                | ll::Operation::Forget(_)
+                | ll::Operation::Write(_)
+                | ll::Operation::FSync(_)
+                | ll::Operation::FSyncDir(_)
+                | ll::Operation::Release(_)
+                | ll::Operation::ReleaseDir(_) => {}
+                _ => {
+                    return Err(Errno::EACCES);
                 }
             }
         }
@@ -146,15 +172,26 @@ impl<'a> Request<'a> {
                     error!("Unsupported FUSE ABI version {}", v);
                     return Err(Errno::EPROTO);
                 }
-                // Remember ABI version supported by kernel
+                // Remember ABI version supported by kernel. Requests and replies use the
+                // layout of the lower one of its and our minor version from now on.
                 se.proto_major = v.major();
-                se.proto_minor = v.minor();
+                se.proto_minor = min(v.minor(), abi::FUSE_KERNEL_MINOR_VERSION);
 
                 let mut config = KernelConfig::new(x.capabilities(), x.max_readahead());
                 // Call filesystem init method and give it a chance to return an error
                 se.filesystem
                     .init(self, &mut config)
                     .map_err(Errno::from_i32)?;
+                config.restrict_to(se.operations);
+                // With idmap, the kernel sends most requests with FUSE_INVALID_UIDGID as uid,
+                // on any mount, so access can't be restricted to the owner of the session
+                #[cfg(not(target_os = "macos"))]
+                if se.allowed != SessionACL::All {
+                    config.restrict_idmap("access is restricted to the owner");
+                } else if !se.default_permissions {
+                    // The kernel fails the init of idmapped mounts without it
+                    config.restrict_idmap("the default_permissions mount option isn't set");
+                }
 
                 // Reply with our desired version and settings. If the kernel supports a
                 // larger major version, it'll re-send a matching init message. If it
@@ -163,10 +200,16 @@ impl<'a> Request<'a> {
                     "INIT response: ABI {}.{}, flags {:#x}, max readahead {}, max write {}",
                     abi::FUSE_KERNEL_VERSION,
                     abi::FUSE_KERNEL_MINOR_VERSION,
-                    x.capabilities() & config.requested,
+                    config.requested().bits(),
                     config.max_readahead,
                     config.max_write
                 );
+                se.proto_flags = config.requested().bits();
+                se.negotiated = Some(config.negotiated(abi::FUSE_KERNEL_VERSION, se.proto_minor));
+                #[cfg(target_os = "linux")]
+                if se.proto_flags & abi::consts::FUSE_OVER_IO_URING != 0 {
+                    se.uring = Some(config.uring_config());
+                }
                 se.initialized = true;
                 return Ok(Some(x.reply(&config)));
             }
@@ -186,6 +229,10 @@ impl<'a> Request<'a> {
                 warn!("Ignoring FUSE operation after destroy: {}", self.request);
                 return Err(Errno::EIO);
             }
+            // Operations the filesystem doesn't implement
+            _ if !se.operations.handles(self.request.opcode()) => {
+                return Err(Errno::ENOSYS);
+            }
 
             ll::Operation::Interrupt(_) => {
                 // TODO: handle FUSE_INTERRUPT
@@ -193,426 +240,314 @@ impl<'a> Request<'a> {
             }
 
             ll::Operation::Lookup(x) => {
-                se.filesystem.lookup(
-                    self,
-                    self.request.nodeid().into(),
-                    &x.name().as_ref(),
-                    self.reply(),
-                );
+                se.filesystem.lookup(self, x, self.versioned_reply());
             }
             ll::Operation::Forget(x) => {
                 se.filesystem
-                    .forget(self, self.request.nodeid().into(), x.nlookup()); // no reply
+                    .forget(self, self.request.nodeid(), x.nlookup()); // no reply
             }
-            ll::Operation::GetAttr(_) => {
-                se.filesystem
-                    .getattr(self, self.request.nodeid().into(), self.reply());
+            ll::Operation::GetAttr(x) => {
+                se.filesystem.getattr(self, x, self.versioned_reply());
             }
             ll::Operation::SetAttr(x) => {
-                se.filesystem.setattr(
-                    self,
-                    self.request.nodeid().into(),
-                    x.mode(),
-                    x.uid(),
-                    x.gid(),
-                    x.size(),
-                    x.atime(),
-                    x.mtime(),
-                    x.ctime(),
-                    x.file_handle().map(|fh| fh.into()),
-                    x.crtime(),
-                    x.chgtime(),
-                    x.bkuptime(),
-                    x.flags(),
-                    self.reply(),
-                );
+                se.filesystem.setattr(self, x, self.versioned_reply());
             }
-            ll::Operation::ReadLink(_) => {
-                se.filesystem
-                    .readlink(self, self.request.nodeid().into(), self.reply());
+            ll::Operation::ReadLink(x) => {
+                se.filesystem.readlink(self, x, self.reply());
             }
             ll::Operation::MkNod(x) => {
-                se.filesystem.mknod(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name().as_ref(),
-                    x.mode(),
-                    x.umask(),
-                    x.rdev(),
-                    self.reply(),
-                );
+                se.filesystem.mknod(self, x, self.versioned_reply());
             }
             ll::Operation::MkDir(x) => {
-                se.filesystem.mkdir(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name().as_ref(),
-                    x.mode(),
-                    x.umask(),
-                    self.reply(),
-                );
+                se.filesystem.mkdir(self, x, self.versioned_reply());
             }
             ll::Operation::Unlink(x) => {
-                se.filesystem.unlink(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name().as_ref(),
-                    self.reply(),
-                );
+                se.filesystem.unlink(self, x, self.reply());
             }
             ll::Operation::RmDir(x) => {
-                se.filesystem.rmdir(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name().as_ref(),
-                    self.reply(),
-                );
+                se.filesystem.rmdir(self, x, self.reply());
             }
             ll::Operation::SymLink(x) => {
-                se.filesystem.symlink(
-                    self,
-                    self.request.nodeid().into(),
-                    x.target().as_ref(),
-                    &Path::new(x.link()),
-                    self.reply(),
-                );
+                se.filesystem.symlink(self, x, self.versioned_reply());
             }
             ll::Operation::Rename(x) => {
-                se.filesystem.rename(
-                    self,
-                    self.request.nodeid().into(),
-                    x.src().name.as_ref(),
-                    x.dest().dir.into(),
-                    x.dest().name.as_ref(),
-                    0,
-                    self.reply(),
-                );
+                se.filesystem.rename(self, x, self.reply());
             }
             ll::Operation::Link(x) => {
-                se.filesystem.link(
-                    self,
-                    x.inode_no().into(),
-                    self.request.nodeid().into(),
-                    x.dest().name.as_ref(),
-                    self.reply(),
-                );
+                se.filesystem.link(self, x, self.versioned_reply());
             }
             ll::Operation::Open(x) => {
-                se.filesystem
-                    .open(self, self.request.nodeid().into(), x.flags(), self.reply());
+                se.filesystem.open(self, x, self.reply());
             }
             ll::Operation::Read(x) => {
-                se.filesystem.read(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.offset(),
-                    x.size(),
-                    x.flags(),
-                    x.lock_owner().map(|l| l.into()),
-                    self.reply(),
-                );
+                se.filesystem.read(self, x, self.reply());
             }
             ll::Operation::Write(x) => {
-                se.filesystem.write(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.offset(),
-                    x.data(),
-                    x.write_flags(),
-                    x.flags(),
-                    x.lock_owner().map(|l| l.into()),
-                    self.reply(),
-                );
+                se.filesystem.write(self, x, self.reply());
             }
             ll::Operation::Flush(x) => {
-                se.filesystem.flush(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.lock_owner().into(),
-                    self.reply(),
-                );
+                se.filesystem.flush(self, x, self.reply());
             }
             ll::Operation::Release(x) => {
-                se.filesystem.release(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.flags(),
-                    x.lock_owner().map(|x| x.into()),
-                    x.flush(),
-                    self.reply(),
-                );
+                se.filesystem.release(self, x, self.reply());
             }
             ll::Operation::FSync(x) => {
-                se.filesystem.fsync(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.fdatasync(),
-                    self.reply(),
-                );
+                se.filesystem.fsync(self, x, self.reply());
             }
             ll::Operation::OpenDir(x) => {
-                se.filesystem
-                    .opendir(self, self.request.nodeid().into(), x.flags(), self.reply());
+                se.filesystem.opendir(self, x, self.reply());
             }
             ll::Operation::ReadDir(x) => {
-                se.filesystem.readdir(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.offset(),
-                    ReplyDirectory::new(
-                        self.request.unique().into(),
-                        self.ch.clone(),
-                        x.size() as usize,
-                    ),
+                let reply = ReplyDirectory::new(
+                    self.request.unique().into(),
+                    self.ch.clone(),
+                    x.size() as usize,
                 );
+                se.filesystem.readdir(self, x, reply);
             }
             ll::Operation::ReleaseDir(x) => {
-                se.filesystem.releasedir(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.flags(),
-                    self.reply(),
-                );
+                se.filesystem.releasedir(self, x, self.reply());
             }
             ll::Operation::FSyncDir(x) => {
-                se.filesystem.fsyncdir(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.fdatasync(),
-                    self.reply(),
-                );
+                se.filesystem.fsyncdir(self, x, self.reply());
             }
-            ll::Operation::StatFs(_) => {
-                se.filesystem
-                    .statfs(self, self.request.nodeid().into(), self.reply());
+            ll::Operation::StatFs(x) => {
+                se.filesystem.statfs(self, x, self.reply());
             }
             ll::Operation::SetXAttr(x) => {
-                se.filesystem.setxattr(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name(),
-                    x.value(),
-                    x.flags(),
-                    x.position(),
-                    self.reply(),
-                );
+                se.filesystem.setxattr(self, x, self.reply());
             }
             ll::Operation::GetXAttr(x) => {
-                se.filesystem.getxattr(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name(),
-                    x.size_u32(),
-                    self.reply(),
-                );
+                se.filesystem.getxattr(self, x, self.reply());
             }
             ll::Operation::ListXAttr(x) => {
-                se.filesystem
-                    .listxattr(self, self.request.nodeid().into(), x.size(), self.reply());
+                se.filesystem.listxattr(self, x, self.reply());
             }
             ll::Operation::RemoveXAttr(x) => {
-                se.filesystem.removexattr(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name(),
-                    self.reply(),
-                );
+                se.filesystem.removexattr(self, x, self.reply());
             }
             ll::Operation::Access(x) => {
-                se.filesystem
-                    .access(self, self.request.nodeid().into(), x.mask(), self.reply());
+                se.filesystem.access(self, x, self.reply());
             }
             ll::Operation::Create(x) => {
-                se.filesystem.create(
-                    self,
-                    self.request.nodeid().into(),
-                    x.name().as_ref(),
-                    x.mode(),
-                    x.umask(),
-                    x.flags(),
-                    self.reply(),
-                );
+                se.filesystem.create(self, x, self.versioned_reply());
             }
             ll::Operation::GetLk(x) => {
-                se.filesystem.getlk(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.lock_owner().into(),
-                    x.lock().range.0,
-                    x.lock().range.1,
-                    x.lock().typ,
-                    x.lock().pid,
-                    self.reply(),
-                );
+                se.filesystem.getlk(self, x, self.reply());
             }
             ll::Operation::SetLk(x) => {
-                se.filesystem.setlk(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.lock_owner().into(),
-                    x.lock().range.0,
-                    x.lock().range.1,
-                    x.lock().typ,
-                    x.lock().pid,
-                    false,
-                    self.reply(),
-                );
+                se.filesystem.setlk(self, x, self.reply());
             }
             ll::Operation::SetLkW(x) => {
-                se.filesystem.setlk(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.lock_owner().into(),
-                    x.lock().range.0,
-                    x.lock().range.1,
-                    x.lock().typ,
-                    x.lock().pid,
-                    true,
-                    self.reply(),
-                );
+                se.filesystem.setlkw(self, x, self.reply());
             }
             ll::Operation::BMap(x) => {
-                se.filesystem.bmap(
-                    self,
-                    self.request.nodeid().into(),
-                    x.block_size(),
-                    x.block(),
-                    self.reply(),
-                );
+                se.filesystem.bmap(self, x, self.reply());
             }
 
-            #[cfg(feature = "abi-7-11")]
             ll::Operation::IoCtl(x) => {
-                if x.unrestricted() {
-                    return Err(Errno::ENOSYS);
-                } else {
-                    se.filesystem.ioctl(
-                        self,
-                        self.request.nodeid().into(),
-                        x.file_handle().into(),
-                        x.flags(),
-                        x.command(),
-                        x.in_data(),
-                        x.out_size(),
-                        self.reply(),
-                    );
-                }
+                let reply: ReplyIoctl = self.versioned_reply();
+                let reply = reply.with_flags(x.flags());
+                se.filesystem.ioctl(self, x, reply);
             }
-            #[cfg(feature = "abi-7-11")]
             ll::Operation::Poll(_) => {
                 // TODO: handle FUSE_POLL
                 return Err(Errno::ENOSYS);
             }
-            #[cfg(feature = "abi-7-15")]
             ll::Operation::NotifyReply(_) => {
                 // TODO: handle FUSE_NOTIFY_REPLY
                 return Err(Errno::ENOSYS);
             }
-            #[cfg(feature = "abi-7-16")]
             ll::Operation::BatchForget(x) => {
-                se.filesystem.batch_forget(self, x.nodes()); // no reply
+                se.filesystem.batch_forget(self, x); // no reply
             }
-            #[cfg(feature = "abi-7-19")]
             ll::Operation::FAllocate(x) => {
-                se.filesystem.fallocate(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.offset(),
-                    x.len(),
-                    x.mode(),
-                    self.reply(),
-                );
+                se.filesystem.fallocate(self, x, self.reply());
             }
-            #[cfg(feature = "abi-7-21")]
             ll::Operation::ReadDirPlus(x) => {
-                se.filesystem.readdirplus(
-                    self,
-                    self.request.nodeid().into(),
-                    x.file_handle().into(),
-                    x.offset(),
-                    ReplyDirectoryPlus::new(
-                        self.request.unique().into(),
-                        self.ch.clone(),
-                        x.size() as usize,
-                    ),
+                let reply = ReplyDirectoryPlus::new(
+                    self.request.unique().into(),
+                    self.ch.clone(),
+                    x.size() as usize,
                 );
+                se.filesystem.readdirplus(self, x, reply);
             }
-            #[cfg(feature = "abi-7-23")]
             ll::Operation::Rename2(x) => {
-                se.filesystem.rename(
+                se.filesystem.rename2(self, x, self.reply());
+            }
+            ll::Operation::Lseek(x) => {
+                se.filesystem.lseek(self, x, self.reply());
+            }
+            ll::Operation::CopyFileRange(x) => {
+                se.filesystem.copy_file_range(self, x, self.reply());
+            }
+            ll::Operation::SyncFs(x) => {
+                se.filesystem.syncfs(self, x, self.reply());
+            }
+            ll::Operation::Statx(x) => {
+                se.filesystem.statx(self, x, self.reply());
+            }
+            ll::Operation::TmpFile(x) => {
+                se.filesystem.tmpfile(self, x, self.versioned_reply());
+            }
+            #[cfg(target_os = "macos")]
+            ll::Operation::SetVolName(x) => {
+                se.filesystem.setvolname(self, x, self.reply());
+            }
+            #[cfg(target_os = "macos")]
+            ll::Operation::GetXTimes(x) => {
+                se.filesystem.getxtimes(self, x, self.reply());
+            }
+            #[cfg(target_os = "macos")]
+            ll::Operation::Exchange(x) => {
+                se.filesystem.exchange(self, x, self.reply());
+            }
+
+            ll::Operation::CuseInit(_) => {
+                // TODO: handle CUSE_INIT
+                return Err(Errno::ENOSYS);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Dispatch request to the given character device.
+    /// This calls the appropriate device operation method for the
+    /// request and sends back the returned reply to the kernel
+    pub(crate) fn dispatch_cuse<D: CharDevice>(&self, se: &mut CuseSession<D>) {
+        debug!("{}", self.request);
+        let unique = self.request.unique();
+
+        let res = match self.dispatch_cuse_req(se) {
+            Ok(Some(resp)) => resp,
+            Ok(None) => return,
+            Err(errno) => self.request.reply_err(errno),
+        }
+        .with_iovec(unique, |iov| self.ch.send(iov));
+
+        if let Err(err) = res {
+            warn!("Request {:?}: Failed to send reply: {}", unique, err)
+        }
+    }
+
+    fn dispatch_cuse_req<D: CharDevice>(
+        &self,
+        se: &mut CuseSession<D>,
+    ) -> Result<Option<Response>, Errno> {
+        let op = self.request.operation().map_err(|_| Errno::ENOSYS)?;
+        match op {
+            // Device initialization
+            ll::Operation::CuseInit(x) => {
+                let v = x.version();
+                if v < ll::Version(7, 12) {
+                    error!("Unsupported CUSE ABI version {}", v);
+                    return Err(Errno::EPROTO);
+                }
+                se.proto_minor = min(v.minor(), abi::FUSE_KERNEL_MINOR_VERSION);
+                se.device
+                    .init(self, &mut se.config)
+                    .map_err(Errno::from_i32)?;
+                debug!(
+                    "CUSE_INIT response: ABI {}.{}, flags {:#x}, max read {}, max write {}, dev {}:{}",
+                    abi::FUSE_KERNEL_VERSION,
+                    abi::FUSE_KERNEL_MINOR_VERSION,
+                    x.flags() & se.config.flags(),
+                    se.config.max_read,
+                    se.config.max_write,
+                    se.config.dev_major,
+                    se.config.dev_minor
+                );
+                se.initialized = true;
+                return Ok(Some(x.reply(&se.config)));
+            }
+            // Any operation is invalid before initialization
+            _ if !se.initialized => {
+                warn!("Ignoring CUSE operation before init: {}", self.request);
+                return Err(Errno::EIO);
+            }
+            ll::Operation::Destroy(x) => {
+                se.device.destroy();
+                se.destroyed = true;
+                return Ok(Some(x.reply()));
+            }
+            // Any operation is invalid after destroy
+            _ if se.destroyed => {
+                warn!("Ignoring CUSE operation after destroy: {}", self.request);
+                return Err(Errno::EIO);
+            }
+
+            ll::Operation::Interrupt(_) => {
+                // TODO: handle FUSE_INTERRUPT
+                return Err(Errno::ENOSYS);
+            }
+
+            ll::Operation::Open(x) => {
+                se.device.open(self, x.flags().into(), self.reply());
+            }
+            ll::Operation::Read(x) => {
+                se.device.read(
                     self,
-                    x.from().dir.into(),
-                    x.from().name.as_ref(),
-                    x.to().dir.into(),
-                    x.to().name.as_ref(),
-                    x.flags(),
+                    x.file_handle().into(),
+                    x.offset(),
+                    x.size(),
+                    x.flags().into(),
                     self.reply(),
                 );
             }
-            #[cfg(feature = "abi-7-24")]
-            ll::Operation::Lseek(x) => {
-                se.filesystem.lseek(
+            ll::Operation::Write(x) => {
+                se.device.write(
                     self,
-                    self.request.nodeid().into(),
                     x.file_handle().into(),
                     x.offset(),
-                    x.whence(),
+                    x.data(),
+                    x.write_flags().into(),
+                    x.flags().into(),
                     self.reply(),
                 );
             }
-            #[cfg(feature = "abi-7-28")]
-            ll::Operation::CopyFileRange(x) => {
-                let (i, o) = (x.src(), x.dest());
-                se.filesystem.copy_file_range(
+            ll::Operation::Flush(x) => {
+                se.device.flush(
                     self,
-                    i.inode.into(),
-                    i.file_handle.into(),
-                    i.offset,
-                    o.inode.into(),
-                    o.file_handle.into(),
-                    o.offset,
-                    x.len(),
-                    x.flags().try_into().unwrap(),
+                    x.file_handle().into(),
+                    x.lock_owner().into(),
                     self.reply(),
                 );
             }
-            #[cfg(target_os = "macos")]
-            ll::Operation::SetVolName(x) => {
-                se.filesystem.setvolname(self, x.name(), self.reply());
+            ll::Operation::Release(x) => {
+                se.device
+                    .release(self, x.file_handle().into(), x.flags().into(), self.reply());
             }
-            #[cfg(target_os = "macos")]
-            ll::Operation::GetXTimes(_) => {
-                se.filesystem
-                    .getxtimes(self, self.request.nodeid().into(), self.reply());
+            ll::Operation::FSync(x) => {
+                se.device
+                    .fsync(self, x.file_handle().into(), x.fdatasync(), self.reply());
             }
-            #[cfg(target_os = "macos")]
-            ll::Operation::Exchange(x) => {
-                se.filesystem.exchange(
+            ll::Operation::IoCtl(x) => {
+                let reply: ReplyIoctl = self.versioned_reply();
+                se.device.ioctl(
+                    self,
+                    x.file_handle().into(),
+                    x.flags(),
+                    x.command(),
+                    x.arg(),
+                    x.in_data(),
+                    x.out_size(),
+                    reply.with_flags(x.flags()),
+                );
+            }
+            ll::Operation::Poll(x) => {
+                se.device.poll(
                     self,
-                    x.from().dir.into(),
-                    x.from().name.as_ref(),
-                    x.to().dir.into(),
-                    x.to().name.as_ref(),
-                    x.options(),
+                    x.file_handle().into(),
+                    x.kernel_handle(),
+                    x.events(),
+                    x.flags(),
                     self.reply(),
                 );
             }
 
-            #[cfg(feature = "abi-7-12")]
-            ll::Operation::CuseInit(_) => {
-                // TODO: handle CUSE_INIT
+            // Anything else is a filesystem operation a character device can't serve
+            _ => {
+                warn!("Ignoring non-CUSE operation: {}", self.request);
                 return Err(Errno::ENOSYS);
             }
         }
@@ -625,27 +560,62 @@ impl<'a> Request<'a> {
         Reply::new(self.request.unique().into(), self.ch.clone())
     }
 
+    /// Create a reply object for this request whose layout follows the ABI version
+    /// negotiated with the kernel
+    fn versioned_reply<T: VersionedReply>(&self) -> T {
+        self.reply::<T>().with_minor(self.request.protocol().minor)
+    }
+
     /// Returns the unique identifier of this request
     #[inline]
     pub fn unique(&self) -> u64 {
         self.request.unique().into()
     }
 
-    /// Returns the uid of this request
+    /// Returns the uid of this request. If `FUSE_ALLOW_IDMAP` was negotiated, requests on
+    /// idmapped mounts carry the caller's uid mapped into the filesystem's idmapping if they
+    /// create an inode, and [`FUSE_INVALID_UIDGID`](crate::consts::FUSE_INVALID_UIDGID)
+    /// otherwise. As the owner of the session can't be told apart then, `FUSE_ALLOW_IDMAP`
+    /// isn't requested if access is restricted to the owner with `allow_root` or
+    /// `auto_unmount`.
     #[inline]
     pub fn uid(&self) -> u32 {
         self.request.uid()
     }
 
-    /// Returns the gid of this request
+    /// Returns the gid of this request. See [`uid`](Self::uid) for idmapped mounts.
     #[inline]
     pub fn gid(&self) -> u32 {
         self.request.gid()
     }
 
+    /// Returns the supplementary groups of the caller that the kernel passes with requests
+    /// creating an inode if `FUSE_CREATE_SUPP_GROUP` was negotiated, mapped just like the
+    /// gid on idmapped mounts
+    pub fn supplementary_groups(&self) -> &[u32] {
+        &self.supplementary_groups
+    }
+
     /// Returns the pid of this request
     #[inline]
     pub fn pid(&self) -> u32 {
         self.request.pid()
     }
+
+    /// Registers an open file as backing file for passthrough I/O and returns its backing id.
+    /// Reply with [`ReplyOpen::opened_passthrough`](crate::ReplyOpen::opened_passthrough) or
+    /// [`ReplyCreate::created_passthrough`](crate::ReplyCreate::created_passthrough) to let
+    /// the kernel read and write the backing file directly. Requires `FUSE_PASSTHROUGH`,
+    /// a max stack depth set in [`KernelConfig`] and `CAP_SYS_ADMIN`.
+    #[cfg(target_os = "linux")]
+    pub fn open_backing(&self, file: &impl AsRawFd) -> io::Result<u32> {
+        self.ch.open_backing(file.as_raw_fd())
+    }
+
+    /// Unregisters a backing file. Files that were opened with it keep using it until they
+    /// are released.
+    #[cfg(target_os = "linux")]
+    pub fn close_backing(&self, backing_id: u32) -> io::Result<()> {
+        self.ch.close_backing(backing_id)
+    }
 }
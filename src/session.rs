@@ -5,8 +5,10 @@
 //! filesystem is mounted, the session loop receives, dispatches and replies to kernel requests
 //! for filesystem operations under its mount point.
 
-use libc::{EAGAIN, EINTR, ENODEV, ENOENT};
+use libc::{EAGAIN, ECONNABORTED, EINTR, ENODEV, ENOENT, ENOTCONN};
 use log::{info, warn};
+#[cfg(target_os = "linux")]
+use std::any::Any;
 use std::fmt;
 use std::fs::File;
 use std::os::raw::c_int;
@@ -16,11 +18,15 @@ use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::{io, ops::DerefMut};
 
-use crate::ll::fuse_abi as abi;
+use crate::channel::{Channel, Transport};
+use crate::ll::{self, fuse_abi as abi};
+use crate::mnt::Mount;
 use crate::request::Request;
-use crate::Filesystem;
+#[cfg(target_os = "linux")]
+use crate::uring::{Completion, UringChannel, UringConfig};
+use crate::FilesystemV2;
 use crate::MountOption;
-use crate::{channel::Channel, mnt::Mount};
+use crate::{NegotiatedConfig, Operations};
 
 /// The max size of write requests from the kernel. The absolute minimum is 4k,
 /// FUSE recommends at least 128k, max 16M. The FUSE default is 16M on macOS
@@ -29,7 +35,7 @@ pub const MAX_WRITE_SIZE: usize = 16 * 1024 * 1024;
 
 /// Size of the buffer for reading a request from the kernel. Since the kernel may send
 /// up to MAX_WRITE_SIZE bytes in a write request, we use that value plus some extra space.
-const BUFFER_SIZE: usize = MAX_WRITE_SIZE + 4096;
+pub(crate) const BUFFER_SIZE: usize = MAX_WRITE_SIZE + 4096;
 
 #[derive(Debug, Eq, PartialEq)]
 pub(crate) enum SessionACL {
@@ -40,11 +46,11 @@ pub(crate) enum SessionACL {
 
 /// The session data structure
 #[derive(Debug)]
-pub struct Session<FS: Filesystem> {
+pub struct Session<FS: FilesystemV2, T: Transport = Channel> {
     /// Filesystem operation implementations
     pub(crate) filesystem: FS,
     /// Communication channel to the kernel driver
-    ch: Channel,
+    ch: T,
     /// Handle to the mount.  Dropping this unmounts.
     mount: Option<Mount>,
     /// Mount point
@@ -54,17 +60,30 @@ pub struct Session<FS: Filesystem> {
     pub(crate) allowed: SessionACL,
     /// User that launched the fuser process
     pub(crate) session_owner: u32,
+    /// Whether the kernel checks permissions (`default_permissions`), which idmapped mounts
+    /// require. Assumed for other transports, whose mount is set up elsewhere.
+    #[cfg(not(target_os = "macos"))]
+    pub(crate) default_permissions: bool,
     /// FUSE protocol major version
     pub(crate) proto_major: u32,
     /// FUSE protocol minor version
     pub(crate) proto_minor: u32,
+    /// Capabilities negotiated in init, which change the layout of some requests
+    pub(crate) proto_flags: u64,
+    /// io_uring transport to switch to, if negotiated in init
+    #[cfg(target_os = "linux")]
+    pub(crate) uring: Option<UringConfig>,
+    /// Settings agreed on in init
+    pub(crate) negotiated: Option<NegotiatedConfig>,
+    /// Operations implemented by the filesystem
+    pub(crate) operations: Operations,
     /// True if the filesystem is initialized (init operation done)
     pub(crate) initialized: bool,
     /// True if the filesystem was destroyed (destroy operation done)
     pub(crate) destroyed: bool,
 }
 
-impl<FS: Filesystem> Session<FS> {
+impl<FS: FilesystemV2> Session<FS> {
     /// Create a new session by mounting the given filesystem to the given mountpoint
     pub fn new(
         filesystem: FS,
@@ -96,6 +115,7 @@ impl<FS: Filesystem> Session<FS> {
             SessionACL::Owner
         };
 
+        let operations = filesystem.supported_ops();
         Ok(Session {
             filesystem,
             ch,
@@ -103,8 +123,15 @@ impl<FS: Filesystem> Session<FS> {
             mountpoint: mountpoint.to_owned(),
             allowed,
             session_owner: unsafe { libc::geteuid() },
+            #[cfg(not(target_os = "macos"))]
+            default_permissions: options.contains(&MountOption::DefaultPermissions),
             proto_major: 0,
             proto_minor: 0,
+            proto_flags: 0,
+            #[cfg(target_os = "linux")]
+            uring: None,
+            negotiated: None,
+            operations,
             initialized: false,
             destroyed: false,
         })
@@ -124,6 +151,7 @@ impl<FS: Filesystem> Session<FS> {
             );
         };
 
+        let operations = filesystem.supported_ops();
         Session {
             filesystem: filesystem,
             ch: Channel::new(Arc::new(unsafe { File::from_raw_fd(fd as RawFd) })),
@@ -131,9 +159,45 @@ impl<FS: Filesystem> Session<FS> {
             mountpoint: mountpoint,
             allowed: SessionACL::All,
             session_owner: 0,
+            #[cfg(not(target_os = "macos"))]
+            default_permissions: true,
+            // The version negotiated by the previous owner of the channel is unknown, assume
+            // the kernel speaks ours
+            proto_major: abi::FUSE_KERNEL_VERSION,
+            proto_minor: abi::FUSE_KERNEL_MINOR_VERSION,
+            proto_flags: 0,
+            uring: None,
+            negotiated: None,
+            operations,
+            initialized: true,
+            destroyed: false,
+        }
+    }
+}
+
+impl<FS: FilesystemV2, T: Transport> Session<FS, T> {
+    /// Create a new session that receives requests from the given transport instead of a
+    /// mounted FUSE device. Requests of any user are accepted, since whatever sends them is
+    /// responsible for checking permissions, like the kernel does with `allow_other`.
+    pub fn with_transport(filesystem: FS, transport: T) -> Session<FS, T> {
+        let operations = filesystem.supported_ops();
+        Session {
+            filesystem,
+            ch: transport,
+            mount: None,
+            mountpoint: PathBuf::new(),
+            allowed: SessionACL::All,
+            session_owner: unsafe { libc::geteuid() },
+            #[cfg(not(target_os = "macos"))]
+            default_permissions: true,
             proto_major: 0,
             proto_minor: 0,
-            initialized: true,
+            proto_flags: 0,
+            #[cfg(target_os = "linux")]
+            uring: None,
+            negotiated: None,
+            operations,
+            initialized: false,
             destroyed: false,
         }
     }
@@ -143,10 +207,20 @@ impl<FS: Filesystem> Session<FS> {
         &self.mountpoint
     }
 
+    /// Return the settings agreed on with the kernel in init, or None before init (or if the
+    /// session was restored from an already initialized channel)
+    pub fn negotiated(&self) -> Option<&NegotiatedConfig> {
+        self.negotiated.as_ref()
+    }
+
     /// Run the session loop that receives kernel requests and dispatches them to method
     /// calls into the filesystem. This read-dispatch-loop is non-concurrent to prevent
     /// having multiple buffers (which take up much memory), but the filesystem methods
     /// may run concurrent by spawning threads.
+    ///
+    /// Returns when the filesystem is unmounted. If the kernel aborted the connection instead
+    /// (e.g. because a request timed out or it was aborted through the fusectl filesystem),
+    /// returns an error of kind [`ConnectionAborted`](io::ErrorKind::ConnectionAborted).
     pub fn run(&mut self) -> io::Result<()> {
         // Buffer for receiving requests from the kernel. Only one is allocated and
         // it is reused immediately after dispatching to conserve memory and allocations.
@@ -159,7 +233,7 @@ impl<FS: Filesystem> Session<FS> {
             // Read the next request from the given channel to kernel driver
             // The kernel driver makes sure that we get exactly one request per read
             match self.ch.receive(buf) {
-                Ok(size) => match Request::new(self.ch.sender(), &buf[..size]) {
+                Ok(size) => match Request::new(self.ch.sender(), &buf[..size], self.protocol()) {
                     // Dispatch request
                     Some(req) => req.dispatch(self),
                     // Quit loop on illegal request
@@ -172,23 +246,110 @@ impl<FS: Filesystem> Session<FS> {
                     Some(EINTR) => continue,
                     // Explicitly try again
                     Some(EAGAIN) => continue,
+                    // Connection was aborted by the kernel
+                    Some(ECONNABORTED) => return Err(connection_aborted()),
+                    Some(ENODEV) if self.aborted() => return Err(connection_aborted()),
                     // Filesystem was unmounted, quit the loop
                     Some(ENODEV) => break,
                     // Unhandled error
                     _ => return Err(err),
                 },
             }
+            // Switch to io_uring once init negotiated it
+            #[cfg(target_os = "linux")]
+            if let Some(config) = self.uring.take() {
+                // Only the FUSE device can pass requests through io_uring
+                match (&self.ch as &dyn Any).downcast_ref::<Channel>() {
+                    Some(ch) => match UringChannel::new(ch.sender(), &config) {
+                        Ok(uring) => return self.run_uring(uring, buf),
+                        Err(err) => warn!(
+                            "Failed to set up FUSE-over-io_uring, using the FUSE device: {}",
+                            err
+                        ),
+                    },
+                    None => warn!("FUSE-over-io_uring needs the FUSE device as transport"),
+                }
+            }
         }
         Ok(())
     }
 
+    /// Run the session loop on the io_uring transport. Requests are received and replied
+    /// through the ring entries, except for forgets and interrupts, which the kernel keeps
+    /// sending through the FUSE device. The loop is non-concurrent just like the one on
+    /// the FUSE device.
+    #[cfg(target_os = "linux")]
+    fn run_uring(&mut self, mut uring: UringChannel, buf: &mut [u8]) -> io::Result<()> {
+        loop {
+            uring.submit_and_wait()?;
+            while let Some(completion) = uring.next_completion() {
+                match completion {
+                    Completion::Request(entry) => {
+                        let (sender, data) = uring.request(entry);
+                        match Request::new(sender, data, self.protocol()) {
+                            Some(req) => req.dispatch(self),
+                            // Quit loop on illegal request
+                            None => return Ok(()),
+                        }
+                    }
+                    Completion::Device(res) => {
+                        match res.and_then(|_| self.ch.receive(buf)) {
+                            Ok(size) => {
+                                match Request::new(self.ch.sender(), &buf[..size], self.protocol())
+                                {
+                                    Some(req) => req.dispatch(self),
+                                    None => return Ok(()),
+                                }
+                            }
+                            Err(err) => match err.raw_os_error() {
+                                Some(ENOENT) | Some(EINTR) | Some(EAGAIN) => {}
+                                Some(ECONNABORTED) => return Err(connection_aborted()),
+                                Some(ENODEV) if self.aborted() => return Err(connection_aborted()),
+                                Some(ENODEV) => return Ok(()),
+                                _ => return Err(err),
+                            },
+                        }
+                        uring.poll_device()?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns true if the connection to the kernel was aborted while the filesystem is still
+    /// mounted. Kernels that don't return `ECONNABORTED` from reads of the FUSE device (or
+    /// didn't negotiate `FUSE_ABORT_ERROR`) return `ENODEV` for both an abort and an unmount,
+    /// but only an aborted mount point fails with `ENOTCONN`.
+    fn aborted(&self) -> bool {
+        match std::fs::metadata(&self.mountpoint) {
+            Err(err) => err.raw_os_error() == Some(ENOTCONN),
+            Ok(_) => false,
+        }
+    }
+
+    /// Protocol settings for parsing requests, as negotiated in init
+    pub(crate) fn protocol(&self) -> ll::Protocol {
+        ll::Protocol {
+            minor: self.proto_minor,
+            flags: self.proto_flags,
+        }
+    }
+
     /// Unmount the filesystem
     pub fn unmount(&mut self) {
         drop(std::mem::take(&mut self.mount));
     }
 }
 
-fn aligned_sub_buf(buf: &mut [u8], alignment: usize) -> &mut [u8] {
+/// Error returned by the session loop if the kernel aborted the connection
+fn connection_aborted() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "FUSE connection aborted by the kernel",
+    )
+}
+
+pub(crate) fn aligned_sub_buf(buf: &mut [u8], alignment: usize) -> &mut [u8] {
     let off = alignment - (buf.as_ptr() as usize) % alignment;
     if off == alignment {
         buf
@@ -197,14 +358,14 @@ fn aligned_sub_buf(buf: &mut [u8], alignment: usize) -> &mut [u8] {
     }
 }
 
-impl<FS: 'static + Filesystem + Send> Session<FS> {
+impl<FS: 'static + FilesystemV2 + Send, T: Transport> Session<FS, T> {
     /// Run the session loop in a background thread
     pub fn spawn(self) -> io::Result<BackgroundSession> {
         BackgroundSession::new(self)
     }
 }
 
-impl<FS: Filesystem> Drop for Session<FS> {
+impl<FS: FilesystemV2, T: Transport> Drop for Session<FS, T> {
     fn drop(&mut self) {
         if !self.destroyed {
             self.filesystem.destroy();
@@ -228,8 +389,8 @@ impl BackgroundSession {
     /// Create a new background session for the given session by running its
     /// session loop in a background thread. If the returned handle is dropped,
     /// the filesystem is unmounted and the given session ends.
-    pub fn new<FS: Filesystem + Send + 'static>(
-        mut se: Session<FS>,
+    pub fn new<FS: FilesystemV2 + Send + 'static, T: Transport>(
+        mut se: Session<FS, T>,
     ) -> io::Result<BackgroundSession> {
         let mountpoint = se.mountpoint().to_path_buf();
         // Take the fuse_session, so that we can unmount it
@@ -268,3 +429,162 @@ impl<'a> fmt::Debug for BackgroundSession {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    #[cfg(not(target_os = "macos"))]
+    use libc::EACCES;
+    use libc::ENOENT;
+
+    use super::Session;
+    #[cfg(not(target_os = "macos"))]
+    use super::SessionACL;
+    #[cfg(not(target_os = "macos"))]
+    use crate::ll::fuse_abi::consts::{FUSE_ALLOW_IDMAP, FUSE_INIT_EXT, FUSE_INVALID_UIDGID};
+    use crate::ll::fuse_abi::consts::{STATX_BASIC_STATS, STATX_BTIME};
+    use crate::ll::fuse_abi::{FUSE_KERNEL_MINOR_VERSION, FUSE_KERNEL_VERSION};
+    use crate::ll::{AnyReply, INodeNo, Protocol, ReplyError, RequestEncoder};
+    use crate::testing::fixtures::{KernelStub, RootOnly};
+    use crate::testing::Harness;
+    #[cfg(not(target_os = "macos"))]
+    use crate::{Filesystem, KernelConfig, ReplyAttr, Request};
+
+    #[test]
+    fn run_with_transport() {
+        let mut enc = RequestEncoder::new();
+        let requests = [
+            enc.init(4096, 0),
+            enc.getattr(INodeNo(1), None),
+            enc.getattr(INodeNo(2), None),
+        ];
+        let (transport, replies) = KernelStub::with_requests(&requests);
+        let mut se = Session::with_transport(RootOnly, transport);
+        se.run().unwrap();
+        assert!(se.negotiated().is_some());
+
+        let replies: Vec<_> = replies.try_iter().collect();
+        assert_eq!(replies.len(), 3);
+        for (req, reply) in requests.iter().zip(replies.iter()) {
+            assert_eq!(
+                AnyReply::try_from(&reply[..]).unwrap().unique(),
+                req.unique()
+            );
+        }
+        let init = AnyReply::try_from(&replies[0][..]).unwrap().init().unwrap();
+        assert_eq!(init.major, FUSE_KERNEL_VERSION);
+        let minor = init.minor;
+        let attr = AnyReply::try_from(&replies[1][..])
+            .unwrap()
+            .attr(minor)
+            .unwrap();
+        assert_eq!((attr.attr.ino, attr.attr.nlink), (1, 2));
+        match AnyReply::try_from(&replies[2][..]).unwrap().attr(minor) {
+            Err(ReplyError::Errno(err)) => assert_eq!(err.0.get(), ENOENT),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn statx_getattr_fallback() {
+        let mut harness = Harness::new(RootOnly);
+        harness.init(0).unwrap();
+        let statx = harness
+            .encoder()
+            .statx(INodeNo(1), None, 0, STATX_BASIC_STATS | STATX_BTIME);
+        let reply = harness.request(&statx).unwrap();
+        let statx = reply.decode().unwrap().statx().unwrap();
+        assert_eq!((statx.attr_valid, statx.attr_valid_nsec), (1, 0));
+        // Without birth time, as getattr doesn't provide it
+        assert_eq!(statx.stat.mask, STATX_BASIC_STATS);
+        assert_eq!((statx.stat.ino, statx.stat.nlink), (1, 2));
+        assert_eq!(u32::from(statx.stat.mode), libc::S_IFDIR | 0o755);
+        assert_eq!(statx.stat.blksize, 512);
+
+        let statx = harness
+            .encoder()
+            .statx(INodeNo(2), None, 0, STATX_BASIC_STATS);
+        let reply = harness.request(&statx).unwrap();
+        match reply.decode().unwrap().statx() {
+            Err(ReplyError::Errno(err)) => assert_eq!(err.0.get(), ENOENT),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn init_newer_kernel() {
+        let mut enc = RequestEncoder::new();
+        enc.set_protocol(Protocol {
+            minor: FUSE_KERNEL_MINOR_VERSION + 1,
+            flags: 0,
+        });
+        let (transport, replies) = KernelStub::with_requests(&[enc.init(4096, 0)]);
+        let mut se = Session::with_transport(RootOnly, transport);
+        se.run().unwrap();
+
+        // Replies keep the layout of the version we implement
+        assert_eq!(se.proto_minor, FUSE_KERNEL_MINOR_VERSION);
+        assert_eq!(
+            se.negotiated().unwrap().proto_minor,
+            FUSE_KERNEL_MINOR_VERSION
+        );
+        let init = replies.try_recv().unwrap();
+        let init = AnyReply::try_from(&init[..]).unwrap().init().unwrap();
+        assert_eq!(init.minor, FUSE_KERNEL_MINOR_VERSION);
+    }
+
+    /// Requests idmap support
+    #[cfg(not(target_os = "macos"))]
+    struct Idmapped;
+
+    #[cfg(not(target_os = "macos"))]
+    impl Filesystem for Idmapped {
+        fn init(&mut self, _req: &Request<'_>, config: &mut KernelConfig) -> Result<(), i32> {
+            config.add_capabilities(FUSE_ALLOW_IDMAP).unwrap();
+            Ok(())
+        }
+
+        fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+            RootOnly.getattr(req, ino, reply);
+        }
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn idmap_restricted() {
+        for (acl, default_permissions, idmap) in [
+            (SessionACL::All, true, true),
+            (SessionACL::Owner, true, false),
+            (SessionACL::All, false, false),
+        ] {
+            // Like the kernel after idmap was negotiated, send requests without a uid
+            let mut enc =
+                RequestEncoder::new().with_credentials(FUSE_INVALID_UIDGID, FUSE_INVALID_UIDGID, 1);
+            let requests = [
+                enc.init(4096, FUSE_INIT_EXT | FUSE_ALLOW_IDMAP),
+                enc.getattr(INodeNo(1), None),
+            ];
+            let (transport, replies) = KernelStub::with_requests(&requests);
+            let mut se = Session::with_transport(Idmapped, transport);
+            let owner_only = acl != SessionACL::All;
+            se.allowed = acl;
+            se.session_owner = 1000;
+            se.default_permissions = default_permissions;
+            se.run().unwrap();
+
+            let replies: Vec<_> = replies.try_iter().collect();
+            let init = AnyReply::try_from(&replies[0][..]).unwrap().init().unwrap();
+            let flags = u64::from(init.flags) | u64::from(init.flags2) << 32;
+            assert_eq!(flags & FUSE_ALLOW_IDMAP != 0, idmap);
+            match AnyReply::try_from(&replies[1][..])
+                .unwrap()
+                .attr(init.minor)
+            {
+                Ok(attr) if !owner_only => assert_eq!(attr.attr.ino, 1),
+                Err(ReplyError::Errno(err)) if owner_only => assert_eq!(err.0.get(), EACCES),
+                other => panic!("Unexpected reply {:?}", other),
+            }
+        }
+    }
+}
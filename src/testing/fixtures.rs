@@ -0,0 +1,101 @@
+//! Fixtures shared by the unit tests of the crate
+
+use std::io::{self, IoSlice};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+use libc::{ENODEV, ENOENT};
+
+use crate::channel::Transport;
+use crate::ll::EncodedRequest;
+use crate::reply::ReplySender;
+use crate::{FileAttr, FileType, Filesystem, ReplyAttr, Request};
+
+/// Stand-in for the kernel side of a mount, which hands out the requests sent to it until
+/// their sender is dropped like an unmount does
+#[derive(Debug)]
+pub(crate) struct KernelStub {
+    requests: Mutex<Receiver<Vec<u8>>>,
+    replies: KernelSender,
+}
+
+impl KernelStub {
+    /// A stub with the sender of its requests and the receiver of its replies
+    pub(crate) fn new() -> (Self, Sender<Vec<u8>>, Receiver<Vec<u8>>) {
+        let (request_tx, request_rx) = channel();
+        let (reply_tx, reply_rx) = channel();
+        let stub = Self {
+            requests: Mutex::new(request_rx),
+            replies: KernelSender(reply_tx),
+        };
+        (stub, request_tx, reply_rx)
+    }
+
+    /// A stub that unmounts after handing out the given requests
+    pub(crate) fn with_requests(requests: &[EncodedRequest]) -> (Self, Receiver<Vec<u8>>) {
+        let (stub, request_tx, reply_rx) = Self::new();
+        for request in requests {
+            request_tx.send(request.as_bytes().to_vec()).unwrap();
+        }
+        (stub, reply_rx)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct KernelSender(Sender<Vec<u8>>);
+
+impl ReplySender for KernelSender {
+    fn send(&self, data: &[IoSlice<'_>]) -> io::Result<()> {
+        let _ = self.0.send(data.iter().flat_map(|x| x.to_vec()).collect());
+        Ok(())
+    }
+}
+
+impl Transport for KernelStub {
+    type Sender = KernelSender;
+
+    fn receive(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.requests.lock().unwrap().recv() {
+            Ok(req) => {
+                buf[..req.len()].copy_from_slice(&req);
+                Ok(req.len())
+            }
+            // Unmounted
+            Err(_) => Err(io::Error::from_raw_os_error(ENODEV)),
+        }
+    }
+
+    fn sender(&self) -> KernelSender {
+        self.replies.clone()
+    }
+}
+
+/// Filesystem with only a root directory
+pub(crate) struct RootOnly;
+
+impl Filesystem for RootOnly {
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        if ino != 1 {
+            return reply.error(ENOENT);
+        }
+        let attr = FileAttr {
+            ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 2,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        };
+        reply.attr(&Duration::from_secs(1), &attr);
+    }
+}
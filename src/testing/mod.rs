@@ -0,0 +1,324 @@
+//! Testing filesystems without mounting them
+//!
+//! A [`Harness`] drives a filesystem like the kernel driver would, but in-process: requests
+//! encoded with [`RequestEncoder`] go through the same dispatch code as those read from
+//! `/dev/fuse`, and the replies are captured instead of sent, to be decoded with
+//! [`AnyReply`]. No privileges or FUSE device are needed.
+//!
+//! On top of it, [`Vfs`] simulates the kernel VFS to test filesystems with path-level calls
+//! like `open`, `rename` or `readdir`, checking that they handle lookup counts and `..`.
+//!
+//! ```
+//! use rofuse::ll::INodeNo;
+//! use rofuse::testing::Harness;
+//! use rofuse::Filesystem;
+//!
+//! struct NullFs;
+//! impl Filesystem for NullFs {}
+//!
+//! let mut harness = Harness::new(NullFs);
+//! harness.init(0).unwrap();
+//! let request = harness.encoder().statfs(INodeNo(1));
+//! let reply = harness.request(&request).unwrap();
+//! assert_eq!(reply.decode().unwrap().statfs().unwrap().st.bsize, 512);
+//! ```
+
+use libc::ENODEV;
+use std::convert::TryFrom;
+use std::io::{self, IoSlice};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use crate::channel::Transport;
+use crate::ll::fuse_abi::fuse_init_out;
+use crate::ll::{AnyReply, EncodedRequest, ReplyError, RequestEncoder, RequestId};
+use crate::reply::ReplySender;
+use crate::request::Request;
+use crate::session::Session;
+use crate::{FilesystemV2, NegotiatedConfig};
+
+#[cfg(test)]
+pub(crate) mod fixtures;
+mod vfs;
+
+pub use vfs::{DirEntry, Fd, Vfs};
+
+/// Default maximum readahead offered by [`Harness::init`]
+const MAX_READAHEAD: u32 = 128 * 1024;
+
+/// Sender that keeps the replies and notifications
+#[derive(Clone, Debug, Default)]
+struct CaptureSender(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl ReplySender for CaptureSender {
+    fn send(&self, data: &[IoSlice<'_>]) -> io::Result<()> {
+        let reply = data.iter().flat_map(|x| x.iter().copied()).collect();
+        self.0.lock().unwrap().push(reply);
+        Ok(())
+    }
+}
+
+impl CaptureSender {
+    /// Takes the first captured message with the given unique id
+    fn take(&self, unique: u64) -> Option<CapturedReply> {
+        let mut replies = self.0.lock().unwrap();
+        let pos = replies.iter().position(|x| {
+            AnyReply::try_from(&x[..]).ok().map(|x| x.unique()) == Some(RequestId(unique))
+        })?;
+        Some(CapturedReply(replies.remove(pos)))
+    }
+}
+
+/// Transport of a harness, which never receives requests itself
+#[derive(Debug)]
+struct Capture(CaptureSender);
+
+impl Transport for Capture {
+    type Sender = CaptureSender;
+
+    fn receive(&self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::from_raw_os_error(ENODEV))
+    }
+
+    fn sender(&self) -> CaptureSender {
+        self.0.clone()
+    }
+}
+
+/// A reply or notification sent by the filesystem
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedReply(Vec<u8>);
+
+impl CapturedReply {
+    /// Decode the reply header. The payload is decoded with the typed methods of
+    /// [`AnyReply`], which take the minor version from [`Harness::minor`] if needed.
+    pub fn decode(&self) -> Result<AnyReply<'_>, ReplyError> {
+        AnyReply::try_from(&self.0[..])
+    }
+
+    /// The reply as sent on the wire
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Runs requests through a filesystem without mounting it
+///
+/// Requests of any user are dispatched, as with the `allow_other` mount option.
+#[derive(Debug)]
+pub struct Harness<FS: FilesystemV2> {
+    session: Session<FS, Capture>,
+    encoder: RequestEncoder,
+    sender: CaptureSender,
+}
+
+impl<FS: FilesystemV2> Harness<FS> {
+    /// Create a harness for the given filesystem. Like a newly mounted one, it must be
+    /// initialized with [`init`](Self::init) before it serves other requests.
+    pub fn new(filesystem: FS) -> Self {
+        let sender = CaptureSender::default();
+        Self {
+            session: Session::with_transport(filesystem, Capture(sender.clone())),
+            encoder: RequestEncoder::new(),
+            sender,
+        }
+    }
+
+    /// The filesystem under test
+    pub fn filesystem(&self) -> &FS {
+        &self.session.filesystem
+    }
+
+    /// The filesystem under test
+    pub fn filesystem_mut(&mut self) -> &mut FS {
+        &mut self.session.filesystem
+    }
+
+    /// The encoder for requests to the filesystem. It encodes with the layout negotiated by
+    /// [`init`](Self::init).
+    pub fn encoder(&mut self) -> &mut RequestEncoder {
+        &mut self.encoder
+    }
+
+    /// Send the following requests on behalf of the given user, group and process
+    pub fn set_credentials(&mut self, uid: u32, gid: u32, pid: u32) {
+        self.encoder = mem::take(&mut self.encoder).with_credentials(uid, gid, pid);
+    }
+
+    /// The minor ABI version negotiated by `FUSE_INIT`, which the replies are laid out with
+    pub fn minor(&self) -> u32 {
+        self.session.protocol().minor
+    }
+
+    /// The settings negotiated by `FUSE_INIT`, or None before init
+    pub fn negotiated(&self) -> Option<&NegotiatedConfig> {
+        self.session.negotiated()
+    }
+
+    /// Dispatch a request to the filesystem. Its reply, if it has one, is captured to be
+    /// taken with [`reply`](Self::reply).
+    pub fn dispatch(&mut self, request: &EncodedRequest) {
+        let protocol = self.session.protocol();
+        let req = Request::new(self.sender.clone(), request.as_bytes(), protocol)
+            .expect("Failed to parse the encoded request");
+        req.dispatch(&mut self.session);
+        // Encode the next requests with the negotiated layout, like the kernel does
+        if self.session.negotiated().is_some() {
+            self.encoder.set_protocol(self.session.protocol());
+        }
+    }
+
+    /// Take the reply to the request with the given unique id, if the filesystem sent it
+    /// already. Filesystems that reply from other threads may send it after dispatch.
+    pub fn reply(&mut self, unique: RequestId) -> Option<CapturedReply> {
+        self.sender.take(unique.into())
+    }
+
+    /// Dispatch a request and return its reply, or None if the filesystem didn't reply
+    /// during dispatch
+    pub fn request(&mut self, request: &EncodedRequest) -> Option<CapturedReply> {
+        self.dispatch(request);
+        self.reply(request.unique())
+    }
+
+    /// Take the notifications the filesystem sent
+    pub fn notifications(&mut self) -> Vec<CapturedReply> {
+        let mut notifications = Vec::new();
+        while let Some(notification) = self.sender.take(0) {
+            notifications.push(notification);
+        }
+        notifications
+    }
+
+    /// Initialize the filesystem, offering the given `FUSE_*` capabilities, and return the
+    /// reply to `FUSE_INIT`
+    pub fn init(&mut self, capabilities: u64) -> Result<fuse_init_out, ReplyError> {
+        let request = self.encoder.init(MAX_READAHEAD, capabilities);
+        self.request(&request)
+            .ok_or(ReplyError::InsufficientData)?
+            .decode()?
+            .init()
+    }
+
+    /// Destroy the filesystem like an unmount does
+    pub fn destroy(&mut self) -> Result<(), ReplyError> {
+        let request = self.encoder.destroy();
+        self.request(&request)
+            .ok_or(ReplyError::InsufficientData)?
+            .decode()?
+            .data()
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libc::{EIO, ENOENT, ENOSYS};
+    use std::ffi::OsStr;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::Harness;
+    use crate::ll::fuse_abi::{consts::FUSE_ASYNC_READ, FUSE_KERNEL_MINOR_VERSION};
+    use crate::ll::{INodeNo, ReplyError};
+    use crate::{FileAttr, FileType, Filesystem, ReplyEntry, Request};
+
+    /// A filesystem with one file, which counts its lookups
+    #[derive(Default)]
+    struct OneFile {
+        lookups: u64,
+        destroyed: bool,
+    }
+
+    impl Filesystem for OneFile {
+        fn destroy(&mut self) {
+            self.destroyed = true;
+        }
+
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            if parent != 1 || name != "file" {
+                return reply.error(ENOENT);
+            }
+            self.lookups += 1;
+            let attr = FileAttr {
+                ino: 2,
+                size: 13,
+                blocks: 1,
+                atime: UNIX_EPOCH,
+                mtime: UNIX_EPOCH,
+                ctime: UNIX_EPOCH,
+                crtime: UNIX_EPOCH,
+                kind: FileType::RegularFile,
+                perm: 0o644,
+                nlink: 1,
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+                blksize: 512,
+                flags: 0,
+            };
+            reply.entry(&Duration::from_secs(1), &attr, 0);
+        }
+
+        fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+            assert_eq!(ino, 2);
+            self.lookups -= nlookup;
+        }
+    }
+
+    fn errno(result: Result<impl std::fmt::Debug, ReplyError>) -> i32 {
+        match result {
+            Err(ReplyError::Errno(err)) => err.0.get(),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut harness = Harness::new(OneFile::default());
+        let lookup = harness.encoder().lookup(INodeNo(1), OsStr::new("file"));
+        let reply = harness.request(&lookup).unwrap();
+        assert_eq!(errno(reply.decode().unwrap().entry(harness.minor())), EIO);
+
+        let init = harness.init(FUSE_ASYNC_READ).unwrap();
+        assert_eq!(init.minor, FUSE_KERNEL_MINOR_VERSION);
+        assert_eq!(harness.minor(), FUSE_KERNEL_MINOR_VERSION);
+        assert!(harness.negotiated().is_some());
+
+        harness.destroy().unwrap();
+        assert!(harness.filesystem().destroyed);
+        let lookup = harness.encoder().lookup(INodeNo(1), OsStr::new("file"));
+        let reply = harness.request(&lookup).unwrap();
+        assert_eq!(errno(reply.decode().unwrap().entry(harness.minor())), EIO);
+    }
+
+    #[test]
+    fn requests() {
+        let mut harness = Harness::new(OneFile::default());
+        harness.init(0).unwrap();
+
+        let lookup = harness.encoder().lookup(INodeNo(1), OsStr::new("file"));
+        let reply = harness.request(&lookup).unwrap();
+        let reply = reply.decode().unwrap();
+        assert_eq!(reply.unique(), lookup.unique());
+        let entry = reply.entry(harness.minor()).unwrap();
+        assert_eq!((entry.nodeid, entry.attr.size), (2, 13));
+        assert_eq!(harness.filesystem().lookups, 1);
+
+        let lookup = harness.encoder().lookup(INodeNo(1), OsStr::new("missing"));
+        let reply = harness.request(&lookup).unwrap();
+        assert_eq!(
+            errno(reply.decode().unwrap().entry(harness.minor())),
+            ENOENT
+        );
+
+        // Forget has no reply
+        let forget = harness.encoder().forget(INodeNo(2), 1);
+        assert!(harness.request(&forget).is_none());
+        assert_eq!(harness.filesystem().lookups, 0);
+
+        let readlink = harness.encoder().readlink(INodeNo(2));
+        let reply = harness.request(&readlink).unwrap();
+        assert_eq!(errno(reply.decode().unwrap().data()), ENOSYS);
+        assert!(harness.notifications().is_empty());
+    }
+}
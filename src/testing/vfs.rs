@@ -0,0 +1,1336 @@
+//! A simulated kernel VFS
+//!
+//! [`Vfs`] offers POSIX-like calls on paths and file descriptors, and translates them into
+//! FUSE requests the way Linux does: it walks paths with `FUSE_LOOKUP` one component at a
+//! time, resolves `..` from the directories it has seen instead of asking the filesystem,
+//! follows symlinks, keeps count of the lookups of each inode to forget them later, opens and
+//! releases file handles and pages through directories by their offsets. It doesn't cache
+//! anything, so every call walks its path again, like a kernel with zero entry timeouts.
+
+use libc::{
+    EBADF, EBUSY, EEXIST, EINVAL, EISDIR, ELOOP, ENOENT, ENOSYS, ENOTDIR, EPERM, ETIMEDOUT,
+    O_ACCMODE, O_APPEND, O_CREAT, O_DIRECTORY, O_EXCL, O_NOCTTY, O_NOFOLLOW, O_RDONLY, O_TRUNC,
+    O_WRONLY,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, SeekFrom};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use zerocopy::FromBytes;
+
+use super::Harness;
+use crate::ll::fuse_abi::consts::*;
+use crate::ll::fuse_abi::{fuse_attr, fuse_entry_out, fuse_forget_one, fuse_setattr_in};
+use crate::ll::{
+    AnyReply, EncodedRequest, FileHandle, INodeNo, LockOwner, OpenFlags, ReplyError, WriteFlags,
+};
+use crate::{FilesystemV2, FUSE_ROOT_ID};
+
+/// Capabilities the simulated kernel offers in `FUSE_INIT`
+const CAPABILITIES: u64 = FUSE_ASYNC_READ
+    | FUSE_ATOMIC_O_TRUNC
+    | FUSE_BIG_WRITES
+    | FUSE_DO_READDIRPLUS
+    | FUSE_READDIRPLUS_AUTO;
+/// Most symlinks followed while resolving a path, like Linux's `MAXSYMLINKS`
+const MAX_SYMLINKS: usize = 40;
+/// Size of the buffer of a readdir request
+const READDIR_SIZE: u32 = 4096;
+/// Largest read request
+const MAX_READ: usize = 128 * 1024;
+/// Lock owner of the flush requests
+const LOCK_OWNER: LockOwner = LockOwner(1);
+/// How long to wait for a reply from a filesystem that replies from other threads
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// File types as in the mode of FUSE attributes, as mode_t is u16 on macOS
+#[allow(clippy::unnecessary_cast)]
+const S_IFMT: u32 = libc::S_IFMT as u32;
+#[allow(clippy::unnecessary_cast)]
+const S_IFDIR: u32 = libc::S_IFDIR as u32;
+#[allow(clippy::unnecessary_cast)]
+const S_IFLNK: u32 = libc::S_IFLNK as u32;
+#[allow(clippy::unnecessary_cast)]
+const S_IFREG: u32 = libc::S_IFREG as u32;
+
+fn errno(code: i32) -> io::Error {
+    io::Error::from_raw_os_error(code)
+}
+
+/// Splits a path into its components, dropping empty ones and `.`
+fn components(path: &OsStr) -> VecDeque<OsString> {
+    path.as_bytes()
+        .split(|x| *x == b'/')
+        .filter(|x| !x.is_empty() && *x != b".")
+        .map(|x| OsStr::from_bytes(x).to_owned())
+        .collect()
+}
+
+/// An inode the kernel holds a reference on
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    ino: u64,
+    mode: u32,
+    nlink: u32,
+}
+
+impl Node {
+    fn dir(ino: u64) -> Self {
+        Node {
+            ino,
+            mode: S_IFDIR,
+            nlink: 0,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+}
+
+/// A file descriptor of a [`Vfs`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Fd(u64);
+
+#[derive(Clone, Debug)]
+struct OpenFile {
+    node: Node,
+    fh: FileHandle,
+    flags: i32,
+    pos: u64,
+}
+
+/// An entry of a directory listing
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DirEntry {
+    /// Inode number
+    pub ino: u64,
+    /// File type as `DT_*` value
+    pub kind: u32,
+    /// Name of the entry
+    pub name: OsString,
+}
+
+/// A simulated kernel that serves POSIX-like calls from a filesystem
+///
+/// Paths are resolved from the root of the filesystem, as there is no working directory.
+/// Errors are the `errno` values the calls would fail with, as raw OS errors.
+#[derive(Debug)]
+pub struct Vfs<FS: FilesystemV2> {
+    harness: Harness<FS>,
+    /// Capabilities negotiated by `FUSE_INIT`
+    capabilities: u64,
+    max_write: usize,
+    /// Lookups of each inode that weren't forgotten yet
+    lookups: HashMap<u64, u64>,
+    /// Parent of each directory that was looked up, to resolve `..`
+    parents: HashMap<u64, u64>,
+    /// Removed inodes, which are forgotten once their last file is closed
+    unlinked: HashSet<u64>,
+    files: HashMap<Fd, OpenFile>,
+    next_fd: u64,
+    /// Set once the filesystem doesn't implement create or flush, which aren't sent again
+    no_create: bool,
+    no_flush: bool,
+}
+
+impl<FS: FilesystemV2> Vfs<FS> {
+    /// Initialize the filesystem, offering the capabilities the simulation supports
+    pub fn new(filesystem: FS) -> io::Result<Self> {
+        Self::with_capabilities(filesystem, CAPABILITIES)
+    }
+
+    /// Initialize the filesystem, offering the given `FUSE_*` capabilities. Of those, the
+    /// simulation behaves differently for `FUSE_ATOMIC_O_TRUNC` and `FUSE_DO_READDIRPLUS`.
+    pub fn with_capabilities(filesystem: FS, capabilities: u64) -> io::Result<Self> {
+        let mut harness = Harness::new(filesystem);
+        let init = harness.init(capabilities)?;
+        let mut negotiated = u64::from(init.flags);
+        if negotiated & FUSE_INIT_EXT != 0 {
+            negotiated |= u64::from(init.flags2) << 32;
+        }
+        Ok(Self {
+            harness,
+            capabilities: negotiated,
+            max_write: init.max_write.max(4096) as usize,
+            lookups: HashMap::new(),
+            parents: HashMap::new(),
+            unlinked: HashSet::new(),
+            files: HashMap::new(),
+            next_fd: 0,
+            no_create: false,
+            no_flush: false,
+        })
+    }
+
+    /// The filesystem under test
+    pub fn filesystem(&self) -> &FS {
+        self.harness.filesystem()
+    }
+
+    /// The filesystem under test
+    pub fn filesystem_mut(&mut self) -> &mut FS {
+        self.harness.filesystem_mut()
+    }
+
+    /// The harness the requests go through, to send requests the simulation doesn't
+    pub fn harness(&mut self) -> &mut Harness<FS> {
+        &mut self.harness
+    }
+
+    /// Make the following calls on behalf of the given user, group and process
+    pub fn set_credentials(&mut self, uid: u32, gid: u32, pid: u32) {
+        self.harness.set_credentials(uid, gid, pid);
+    }
+
+    /// The lookups of each inode that the kernel holds and will forget eventually
+    pub fn lookups(&self) -> &HashMap<u64, u64> {
+        &self.lookups
+    }
+
+    /// The lookups of the given inode that the kernel holds
+    pub fn lookup_count(&self, ino: u64) -> u64 {
+        self.lookups.get(&ino).copied().unwrap_or(0)
+    }
+
+    /// Send a request and decode its reply, waiting for filesystems that reply from other
+    /// threads
+    fn call<T>(
+        &mut self,
+        request: EncodedRequest,
+        decode: impl FnOnce(&AnyReply<'_>, u32) -> Result<T, ReplyError>,
+    ) -> io::Result<T> {
+        self.harness.dispatch(&request);
+        let start = Instant::now();
+        let reply = loop {
+            if let Some(reply) = self.harness.reply(request.unique()) {
+                break reply;
+            }
+            if start.elapsed() > REPLY_TIMEOUT {
+                return Err(errno(ETIMEDOUT));
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+        let minor = self.harness.minor();
+        Ok(decode(&reply.decode()?, minor)?)
+    }
+
+    /// Counts the lookup an entry reply implies
+    fn add_entry(&mut self, parent: u64, entry: &fuse_entry_out) -> Node {
+        let node = Node {
+            ino: entry.nodeid,
+            mode: entry.attr.mode,
+            nlink: entry.attr.nlink,
+        };
+        *self.lookups.entry(node.ino).or_default() += 1;
+        if node.is_dir() {
+            self.parents.insert(node.ino, parent);
+        }
+        node
+    }
+
+    fn lookup(&mut self, dir: Node, name: &OsStr) -> io::Result<Node> {
+        if !dir.is_dir() {
+            return Err(errno(ENOTDIR));
+        }
+        let request = self.harness.encoder().lookup(INodeNo(dir.ino), name);
+        let entry = self.call(request, |reply, minor| reply.entry(minor))?;
+        // A negative entry
+        if entry.nodeid == 0 {
+            return Err(errno(ENOENT));
+        }
+        Ok(self.add_entry(dir.ino, &entry))
+    }
+
+    fn readlink_node(&mut self, node: Node) -> io::Result<OsString> {
+        let request = self.harness.encoder().readlink(INodeNo(node.ino));
+        self.call(request, |reply, _| {
+            Ok(OsStr::from_bytes(reply.data()?).to_owned())
+        })
+    }
+
+    /// Resolves a path, following symlinks in its last component too if `follow` is set
+    fn resolve(&mut self, path: &Path, follow: bool) -> io::Result<Node> {
+        let path = path.as_os_str();
+        let must_be_dir = path.as_bytes().ends_with(b"/")
+            || path.as_bytes().ends_with(b"/.")
+            || path.as_bytes().ends_with(b"/..");
+        let mut pending = components(path);
+        let mut node = Node::dir(FUSE_ROOT_ID);
+        let mut links = 0;
+        while let Some(name) = pending.pop_front() {
+            if name == ".." {
+                if !node.is_dir() {
+                    return Err(errno(ENOTDIR));
+                }
+                // The kernel knows the parent of every directory it looked up
+                let parent = self.parents.get(&node.ino).copied();
+                node = Node::dir(parent.unwrap_or(FUSE_ROOT_ID));
+                continue;
+            }
+            let next = self.lookup(node, &name)?;
+            if next.is_symlink() && (follow || must_be_dir || !pending.is_empty()) {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return Err(errno(ELOOP));
+                }
+                let target = self.readlink_node(next)?;
+                if target.is_empty() {
+                    return Err(errno(ENOENT));
+                }
+                if target.as_bytes().starts_with(b"/") {
+                    node = Node::dir(FUSE_ROOT_ID);
+                }
+                for component in components(&target).into_iter().rev() {
+                    pending.push_front(component);
+                }
+                continue;
+            }
+            node = next;
+        }
+        if must_be_dir && !node.is_dir() {
+            return Err(errno(ENOTDIR));
+        }
+        Ok(node)
+    }
+
+    /// Resolves the directory of the last component of a path, and returns it with the name
+    fn resolve_parent(&mut self, path: &Path) -> io::Result<(Node, OsString)> {
+        let bytes = path.as_os_str().as_bytes();
+        let end = bytes.iter().rposition(|x| *x != b'/').map_or(0, |x| x + 1);
+        let start = bytes[..end]
+            .iter()
+            .rposition(|x| *x == b'/')
+            .map_or(0, |x| x + 1);
+        let name = OsStr::from_bytes(&bytes[start..end]);
+        if name.is_empty() {
+            // The root
+            return Err(errno(EBUSY));
+        }
+        if name == "." || name == ".." {
+            return Err(errno(EINVAL));
+        }
+        let dir = self.resolve(Path::new(OsStr::from_bytes(&bytes[..start])), true)?;
+        if !dir.is_dir() {
+            return Err(errno(ENOTDIR));
+        }
+        Ok((dir, name.to_owned()))
+    }
+
+    /// Whether `ancestor` is `dir` or one of its parents
+    fn is_ancestor(&self, ancestor: u64, mut dir: u64) -> bool {
+        loop {
+            if dir == ancestor {
+                return true;
+            }
+            match self.parents.get(&dir) {
+                Some(parent) if dir != FUSE_ROOT_ID => dir = *parent,
+                _ => return false,
+            }
+        }
+    }
+
+    fn is_open(&self, ino: u64) -> bool {
+        self.files.values().any(|x| x.node.ino == ino)
+    }
+
+    /// Forgets an inode once it isn't open anymore, like the kernel evicts removed inodes
+    fn evict(&mut self, ino: u64) {
+        if self.is_open(ino) {
+            self.unlinked.insert(ino);
+        } else {
+            self.forget(ino);
+        }
+    }
+
+    fn forget(&mut self, ino: u64) {
+        self.unlinked.remove(&ino);
+        self.parents.remove(&ino);
+        if let Some(nlookup) = self.lookups.remove(&ino) {
+            let request = self.harness.encoder().forget(INodeNo(ino), nlookup);
+            self.harness.dispatch(&request);
+        }
+    }
+
+    fn file(&self, fd: Fd) -> io::Result<OpenFile> {
+        self.files.get(&fd).cloned().ok_or_else(|| errno(EBADF))
+    }
+
+    fn getattr(&mut self, node: Node, fh: Option<FileHandle>) -> io::Result<fuse_attr> {
+        let request = self.harness.encoder().getattr(INodeNo(node.ino), fh);
+        self.call(request, |reply, minor| Ok(reply.attr(minor)?.attr))
+    }
+
+    /// Open a file, creating it with the given mode if `flags` contain `O_CREAT`
+    pub fn open(&mut self, path: impl AsRef<Path>, flags: i32, mode: u32) -> io::Result<Fd> {
+        let path = path.as_ref();
+        let node = if flags & O_CREAT != 0 {
+            let (dir, name) = self.resolve_parent(path)?;
+            match self.lookup(dir, &name) {
+                Ok(_) if flags & O_EXCL != 0 => return Err(errno(EEXIST)),
+                Ok(node) if node.is_symlink() && flags & O_NOFOLLOW == 0 => {
+                    self.resolve(path, true)?
+                }
+                Ok(node) => node,
+                Err(err) if err.raw_os_error() == Some(ENOENT) => {
+                    return self.create(dir, &name, flags, mode)
+                }
+                Err(err) => return Err(err),
+            }
+        } else {
+            self.resolve(path, flags & O_NOFOLLOW == 0)?
+        };
+        self.open_node(node, flags)
+    }
+
+    fn create(&mut self, dir: Node, name: &OsStr, flags: i32, mode: u32) -> io::Result<Fd> {
+        if !self.no_create {
+            let request = self.harness.encoder().create(
+                INodeNo(dir.ino),
+                name,
+                S_IFREG | mode,
+                0,
+                OpenFlags(flags & !O_NOCTTY),
+                0,
+            );
+            match self.call(request, |reply, minor| reply.create(minor)) {
+                Ok((entry, open)) => {
+                    let node = self.add_entry(dir.ino, &entry);
+                    return Ok(self.add_file(node, FileHandle(open.fh), flags));
+                }
+                Err(err) if err.raw_os_error() == Some(ENOSYS) => self.no_create = true,
+                Err(err) => return Err(err),
+            }
+        }
+        // Like the kernel, fall back to mknod and open
+        let request = self
+            .harness
+            .encoder()
+            .mknod(INodeNo(dir.ino), name, S_IFREG | mode, 0, 0);
+        let entry = self.call(request, |reply, minor| reply.entry(minor))?;
+        let node = self.add_entry(dir.ino, &entry);
+        self.open_node(node, flags & !O_TRUNC)
+    }
+
+    fn open_node(&mut self, node: Node, flags: i32) -> io::Result<Fd> {
+        if node.is_symlink() {
+            return Err(errno(ELOOP));
+        }
+        if flags & O_DIRECTORY != 0 && !node.is_dir() {
+            return Err(errno(ENOTDIR));
+        }
+        if node.is_dir() && flags & O_ACCMODE != O_RDONLY {
+            return Err(errno(EISDIR));
+        }
+        let atomic_trunc = self.capabilities & FUSE_ATOMIC_O_TRUNC != 0;
+        let mut open_flags = flags & !(O_CREAT | O_EXCL | O_NOCTTY);
+        if !atomic_trunc {
+            open_flags &= !O_TRUNC;
+        }
+        let request = if node.is_dir() {
+            let encoder = self.harness.encoder();
+            encoder.opendir(INodeNo(node.ino), OpenFlags(open_flags))
+        } else {
+            let encoder = self.harness.encoder();
+            encoder.open(INodeNo(node.ino), OpenFlags(open_flags), 0)
+        };
+        let open = self.call(request, |reply, _| reply.open())?;
+        let fd = self.add_file(node, FileHandle(open.fh), flags);
+        if flags & O_TRUNC != 0 && flags & O_ACCMODE != O_RDONLY && !atomic_trunc {
+            if let Err(err) = self.ftruncate(fd, 0) {
+                let _ = self.close(fd);
+                return Err(err);
+            }
+        }
+        Ok(fd)
+    }
+
+    fn add_file(&mut self, node: Node, fh: FileHandle, flags: i32) -> Fd {
+        self.next_fd += 1;
+        let fd = Fd(self.next_fd);
+        let file = OpenFile {
+            node,
+            fh,
+            flags,
+            pos: 0,
+        };
+        self.files.insert(fd, file);
+        fd
+    }
+
+    /// Close a file, flushing and releasing its file handle
+    pub fn close(&mut self, fd: Fd) -> io::Result<()> {
+        let file = self.files.remove(&fd).ok_or_else(|| errno(EBADF))?;
+        let (ino, fh, flags) = (INodeNo(file.node.ino), file.fh, OpenFlags(file.flags));
+        let mut result = Ok(());
+        if file.node.is_dir() {
+            let request = self.harness.encoder().releasedir(ino, fh, flags);
+            let _ = self.call(request, |reply, _| reply.data().map(|_| ()));
+        } else {
+            if !self.no_flush {
+                let request = self.harness.encoder().flush(ino, fh, LOCK_OWNER);
+                match self.call(request, |reply, _| reply.data().map(|_| ())) {
+                    Err(err) if err.raw_os_error() == Some(ENOSYS) => self.no_flush = true,
+                    other => result = other,
+                }
+            }
+            // The kernel ignores errors of release
+            let request = self.harness.encoder().release(ino, fh, flags, false, None);
+            let _ = self.call(request, |reply, _| reply.data().map(|_| ()));
+        }
+        if self.unlinked.contains(&file.node.ino) && !self.is_open(file.node.ino) {
+            self.forget(file.node.ino);
+        }
+        result
+    }
+
+    /// Read up to `len` bytes at the file position, and advance it
+    pub fn read(&mut self, fd: Fd, len: usize) -> io::Result<Vec<u8>> {
+        let pos = self.file(fd)?.pos;
+        let data = self.pread(fd, pos, len)?;
+        if let Some(file) = self.files.get_mut(&fd) {
+            file.pos += data.len() as u64;
+        }
+        Ok(data)
+    }
+
+    /// Read up to `len` bytes at the given offset
+    pub fn pread(&mut self, fd: Fd, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let file = self.file(fd)?;
+        if file.node.is_dir() {
+            return Err(errno(EISDIR));
+        }
+        if file.flags & O_ACCMODE == O_WRONLY {
+            return Err(errno(EBADF));
+        }
+        let mut data = Vec::new();
+        while data.len() < len {
+            let size = (len - data.len()).min(MAX_READ);
+            let request = self.harness.encoder().read(
+                INodeNo(file.node.ino),
+                file.fh,
+                (offset + data.len() as u64) as i64,
+                size as u32,
+                OpenFlags(file.flags),
+                None,
+            );
+            let chunk = self.call(request, |reply, _| Ok(reply.data()?.to_vec()))?;
+            let short = chunk.len() < size;
+            data.extend(chunk.into_iter().take(size));
+            if short {
+                break;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Write at the file position, or at the end of the file if it was opened with
+    /// `O_APPEND`, and advance the position
+    pub fn write(&mut self, fd: Fd, data: &[u8]) -> io::Result<usize> {
+        let file = self.file(fd)?;
+        let pos = if file.flags & O_APPEND != 0 {
+            self.getattr(file.node, Some(file.fh))?.size
+        } else {
+            file.pos
+        };
+        let written = self.pwrite(fd, pos, data)?;
+        if let Some(file) = self.files.get_mut(&fd) {
+            file.pos = pos + written as u64;
+        }
+        Ok(written)
+    }
+
+    /// Write at the given offset
+    pub fn pwrite(&mut self, fd: Fd, offset: u64, data: &[u8]) -> io::Result<usize> {
+        let file = self.file(fd)?;
+        if file.node.is_dir() || file.flags & O_ACCMODE == O_RDONLY {
+            return Err(errno(EBADF));
+        }
+        let mut written = 0;
+        for chunk in data.chunks(self.max_write) {
+            let request = self.harness.encoder().write(
+                INodeNo(file.node.ino),
+                file.fh,
+                (offset + written as u64) as i64,
+                chunk,
+                WriteFlags(0),
+                OpenFlags(file.flags),
+                None,
+            );
+            let size = self.call(request, |reply, _| reply.write())?.size as usize;
+            written += size.min(chunk.len());
+            if size < chunk.len() {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Set the file position
+    pub fn seek(&mut self, fd: Fd, pos: SeekFrom) -> io::Result<u64> {
+        let file = self.file(fd)?;
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => (0, pos as i64),
+            SeekFrom::Current(offset) => (file.pos, offset),
+            SeekFrom::End(offset) => (self.getattr(file.node, Some(file.fh))?.size, offset),
+        };
+        let pos = (base as i64)
+            .checked_add(offset)
+            .filter(|x| *x >= 0)
+            .ok_or_else(|| errno(EINVAL))? as u64;
+        if let Some(file) = self.files.get_mut(&fd) {
+            file.pos = pos;
+        }
+        Ok(pos)
+    }
+
+    /// Truncate or extend an open file to the given size
+    pub fn ftruncate(&mut self, fd: Fd, size: u64) -> io::Result<()> {
+        let file = self.file(fd)?;
+        if file.node.is_dir() || file.flags & O_ACCMODE == O_RDONLY {
+            return Err(errno(EINVAL));
+        }
+        let mut arg = fuse_setattr_in::new_zeroed();
+        arg.valid = FATTR_SIZE | FATTR_FH;
+        arg.size = size;
+        arg.fh = file.fh.into();
+        let request = self.harness.encoder().setattr(INodeNo(file.node.ino), &arg);
+        self.call(request, |reply, minor| reply.attr(minor).map(|_| ()))
+    }
+
+    /// The attributes of the file at the given path, following symlinks
+    pub fn stat(&mut self, path: impl AsRef<Path>) -> io::Result<fuse_attr> {
+        let node = self.resolve(path.as_ref(), true)?;
+        self.getattr(node, None)
+    }
+
+    /// The attributes of the file at the given path, or of the symlink
+    pub fn lstat(&mut self, path: impl AsRef<Path>) -> io::Result<fuse_attr> {
+        let node = self.resolve(path.as_ref(), false)?;
+        self.getattr(node, None)
+    }
+
+    /// The attributes of an open file
+    pub fn fstat(&mut self, fd: Fd) -> io::Result<fuse_attr> {
+        let file = self.file(fd)?;
+        self.getattr(file.node, Some(file.fh))
+    }
+
+    /// List a directory, including `.` and `..` if the filesystem returns them
+    pub fn read_dir(&mut self, path: impl AsRef<Path>) -> io::Result<Vec<DirEntry>> {
+        let fd = self.open(path, O_RDONLY | O_DIRECTORY, 0)?;
+        let entries = self.read_dir_fd(fd);
+        let closed = self.close(fd);
+        let entries = entries?;
+        closed?;
+        Ok(entries)
+    }
+
+    /// Reads the entries of an open directory, one buffer at a time
+    fn read_dir_fd(&mut self, fd: Fd) -> io::Result<Vec<DirEntry>> {
+        let file = self.file(fd)?;
+        let ino = INodeNo(file.node.ino);
+        let plus = self.capabilities & FUSE_DO_READDIRPLUS != 0;
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        loop {
+            let batch: Vec<(DirEntry, i64)> = if plus {
+                let encoder = self.harness.encoder();
+                let request = encoder.readdirplus(ino, file.fh, offset, READDIR_SIZE);
+                let list = self.call(request, |reply, _| {
+                    let list = reply.direntplus()?;
+                    Ok(list
+                        .into_iter()
+                        .map(|(x, name)| (x, name.to_owned()))
+                        .collect::<Vec<_>>())
+                })?;
+                let mut batch = Vec::with_capacity(list.len());
+                for (entry, name) in list {
+                    // Each entry but . and .. implies a lookup, unless it has no inode
+                    if entry.entry_out.nodeid != 0 && name != "." && name != ".." {
+                        self.add_entry(file.node.ino, &entry.entry_out);
+                    }
+                    let dirent = &entry.dirent;
+                    let (ino, kind, off) = (dirent.ino, dirent.typ, dirent.off);
+                    batch.push((DirEntry { ino, kind, name }, off));
+                }
+                batch
+            } else {
+                let encoder = self.harness.encoder();
+                let request = encoder.readdir(ino, file.fh, offset, READDIR_SIZE);
+                self.call(request, |reply, _| {
+                    Ok(reply
+                        .dirents()?
+                        .into_iter()
+                        .map(|(x, name)| {
+                            let name = name.to_owned();
+                            (
+                                DirEntry {
+                                    ino: x.ino,
+                                    kind: x.typ,
+                                    name,
+                                },
+                                x.off,
+                            )
+                        })
+                        .collect())
+                })?
+            };
+            let next = match batch.last() {
+                Some((_, next)) => *next,
+                None => break,
+            };
+            if next == offset {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Directory offset didn't advance",
+                ));
+            }
+            offset = next;
+            entries.extend(batch.into_iter().map(|(entry, _)| entry));
+        }
+        Ok(entries)
+    }
+
+    /// Create a directory
+    pub fn mkdir(&mut self, path: impl AsRef<Path>, mode: u32) -> io::Result<()> {
+        let (dir, name) = self.resolve_parent(path.as_ref())?;
+        match self.lookup(dir, &name) {
+            Ok(_) => return Err(errno(EEXIST)),
+            Err(err) if err.raw_os_error() == Some(ENOENT) => {}
+            Err(err) => return Err(err),
+        }
+        let request = self
+            .harness
+            .encoder()
+            .mkdir(INodeNo(dir.ino), &name, mode, 0);
+        let entry = self.call(request, |reply, minor| reply.entry(minor))?;
+        self.add_entry(dir.ino, &entry);
+        Ok(())
+    }
+
+    /// Create a symlink at `path` pointing to `target`
+    pub fn symlink(&mut self, target: impl AsRef<Path>, path: impl AsRef<Path>) -> io::Result<()> {
+        let (dir, name) = self.resolve_parent(path.as_ref())?;
+        match self.lookup(dir, &name) {
+            Ok(_) => return Err(errno(EEXIST)),
+            Err(err) if err.raw_os_error() == Some(ENOENT) => {}
+            Err(err) => return Err(err),
+        }
+        let target = target.as_ref().as_os_str();
+        let request = self
+            .harness
+            .encoder()
+            .symlink(INodeNo(dir.ino), &name, target);
+        let entry = self.call(request, |reply, minor| reply.entry(minor))?;
+        self.add_entry(dir.ino, &entry);
+        Ok(())
+    }
+
+    /// The target of a symlink
+    pub fn readlink(&mut self, path: impl AsRef<Path>) -> io::Result<OsString> {
+        let node = self.resolve(path.as_ref(), false)?;
+        if !node.is_symlink() {
+            return Err(errno(EINVAL));
+        }
+        self.readlink_node(node)
+    }
+
+    /// Create a hard link at `path` to the file at `existing`
+    pub fn link(&mut self, existing: impl AsRef<Path>, path: impl AsRef<Path>) -> io::Result<()> {
+        let node = self.resolve(existing.as_ref(), false)?;
+        if node.is_dir() {
+            return Err(errno(EPERM));
+        }
+        let (dir, name) = self.resolve_parent(path.as_ref())?;
+        match self.lookup(dir, &name) {
+            Ok(_) => return Err(errno(EEXIST)),
+            Err(err) if err.raw_os_error() == Some(ENOENT) => {}
+            Err(err) => return Err(err),
+        }
+        let request = self
+            .harness
+            .encoder()
+            .link(INodeNo(node.ino), INodeNo(dir.ino), &name);
+        let entry = self.call(request, |reply, minor| reply.entry(minor))?;
+        self.add_entry(dir.ino, &entry);
+        Ok(())
+    }
+
+    /// Remove a file
+    pub fn unlink(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (dir, name) = self.resolve_parent(path.as_ref())?;
+        let victim = self.lookup(dir, &name)?;
+        if victim.is_dir() {
+            return Err(errno(EISDIR));
+        }
+        let request = self.harness.encoder().unlink(INodeNo(dir.ino), &name);
+        self.call(request, |reply, _| reply.data().map(|_| ()))?;
+        if victim.nlink <= 1 {
+            self.evict(victim.ino);
+        }
+        Ok(())
+    }
+
+    /// Remove an empty directory
+    pub fn rmdir(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (dir, name) = self.resolve_parent(path.as_ref())?;
+        let victim = self.lookup(dir, &name)?;
+        if !victim.is_dir() {
+            return Err(errno(ENOTDIR));
+        }
+        let request = self.harness.encoder().rmdir(INodeNo(dir.ino), &name);
+        self.call(request, |reply, _| reply.data().map(|_| ()))?;
+        self.evict(victim.ino);
+        Ok(())
+    }
+
+    /// Rename a file or directory, replacing the target if it exists
+    pub fn rename(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+        let (src_dir, src_name) = self.resolve_parent(from.as_ref())?;
+        let (dst_dir, dst_name) = self.resolve_parent(to.as_ref())?;
+        let src = self.lookup(src_dir, &src_name)?;
+        let dst = match self.lookup(dst_dir, &dst_name) {
+            Ok(dst) => Some(dst),
+            Err(err) if err.raw_os_error() == Some(ENOENT) => None,
+            Err(err) => return Err(err),
+        };
+        if let Some(dst) = dst {
+            if dst.ino == src.ino {
+                return Ok(());
+            }
+            if src.is_dir() && !dst.is_dir() {
+                return Err(errno(ENOTDIR));
+            }
+            if !src.is_dir() && dst.is_dir() {
+                return Err(errno(EISDIR));
+            }
+        }
+        // A directory can't be moved into itself
+        if src.is_dir() && self.is_ancestor(src.ino, dst_dir.ino) {
+            return Err(errno(EINVAL));
+        }
+        let request = self.harness.encoder().rename(
+            INodeNo(src_dir.ino),
+            &src_name,
+            INodeNo(dst_dir.ino),
+            &dst_name,
+        );
+        self.call(request, |reply, _| reply.data().map(|_| ()))?;
+        if src.is_dir() {
+            self.parents.insert(src.ino, dst_dir.ino);
+        }
+        if let Some(dst) = dst {
+            if dst.is_dir() || dst.nlink <= 1 {
+                self.evict(dst.ino);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget every inode that isn't open, like the kernel does under memory pressure
+    pub fn drop_caches(&mut self) {
+        let nodes: Vec<fuse_forget_one> = self
+            .lookups
+            .iter()
+            .filter(|(ino, _)| !self.is_open(**ino))
+            .map(|(ino, nlookup)| fuse_forget_one {
+                nodeid: *ino,
+                nlookup: *nlookup,
+            })
+            .collect();
+        for node in nodes.iter() {
+            self.lookups.remove(&node.nodeid);
+            self.parents.remove(&node.nodeid);
+            self.unlinked.remove(&node.nodeid);
+        }
+        if nodes.len() > 1 && self.harness.minor() >= 16 {
+            let request = self.harness.encoder().batch_forget(&nodes);
+            self.harness.dispatch(&request);
+        } else {
+            for node in nodes {
+                let request = self
+                    .harness
+                    .encoder()
+                    .forget(INodeNo(node.nodeid), node.nlookup);
+                self.harness.dispatch(&request);
+            }
+        }
+    }
+
+    /// Forget every inode and destroy the filesystem. Fails with `EBUSY` while files are
+    /// open.
+    pub fn unmount(&mut self) -> io::Result<()> {
+        if !self.files.is_empty() {
+            return Err(errno(EBUSY));
+        }
+        self.drop_caches();
+        Ok(self.harness.destroy()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libc::{EEXIST, EINVAL, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, O_CREAT, O_EXCL, O_RDONLY};
+    use libc::{O_RDWR, O_TRUNC, O_WRONLY};
+    use std::collections::{BTreeMap, HashMap};
+    use std::ffi::{OsStr, OsString};
+    use std::io;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::Vfs;
+    use crate::ll::fuse_abi::consts::{FUSE_ASYNC_READ, FUSE_DO_READDIRPLUS};
+    use crate::{
+        FileAttr, FileType, Filesystem, KernelConfig, ReplyAttr, ReplyCreate, ReplyData,
+        ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyWrite, Request, TimeOrNow,
+    };
+    use std::time::SystemTime;
+
+    const TTL: Duration = Duration::from_secs(1);
+
+    struct Inode {
+        kind: FileType,
+        parent: u64,
+        children: BTreeMap<OsString, u64>,
+        data: Vec<u8>,
+        nlink: u32,
+        lookups: u64,
+    }
+
+    /// An in-memory filesystem, which drops inodes once they are unlinked and forgotten
+    struct MemFs {
+        inodes: HashMap<u64, Inode>,
+        next_ino: u64,
+        readdirplus: bool,
+        dotdot_lookups: u64,
+    }
+
+    impl MemFs {
+        fn new(readdirplus: bool) -> Self {
+            let root = Inode {
+                kind: FileType::Directory,
+                parent: 1,
+                children: BTreeMap::new(),
+                data: Vec::new(),
+                nlink: 2,
+                lookups: 0,
+            };
+            Self {
+                inodes: vec![(1, root)].into_iter().collect(),
+                next_ino: 2,
+                readdirplus,
+                dotdot_lookups: 0,
+            }
+        }
+
+        fn attr(&self, ino: u64) -> FileAttr {
+            let inode = &self.inodes[&ino];
+            FileAttr {
+                ino,
+                size: inode.data.len() as u64,
+                blocks: 0,
+                atime: UNIX_EPOCH,
+                mtime: UNIX_EPOCH,
+                ctime: UNIX_EPOCH,
+                crtime: UNIX_EPOCH,
+                kind: inode.kind,
+                perm: 0o755,
+                nlink: inode.nlink,
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+                blksize: 512,
+                flags: 0,
+            }
+        }
+
+        fn child(&self, parent: u64, name: &OsStr) -> Result<u64, i32> {
+            let dir = self.inodes.get(&parent).ok_or(ENOENT)?;
+            if dir.kind != FileType::Directory {
+                return Err(ENOTDIR);
+            }
+            dir.children.get(name).copied().ok_or(ENOENT)
+        }
+
+        fn add(&mut self, parent: u64, name: &OsStr, kind: FileType) -> Result<u64, i32> {
+            if self.child(parent, name).is_ok() {
+                return Err(EEXIST);
+            }
+            let ino = self.next_ino;
+            self.next_ino += 1;
+            let inode = Inode {
+                kind,
+                parent,
+                children: BTreeMap::new(),
+                data: Vec::new(),
+                nlink: if kind == FileType::Directory { 2 } else { 1 },
+                lookups: 1,
+            };
+            self.inodes.insert(ino, inode);
+            let dir = self.inodes.get_mut(&parent).unwrap();
+            dir.children.insert(name.to_owned(), ino);
+            Ok(ino)
+        }
+
+        /// Removes a directory entry, and drops the inode if the kernel doesn't know it
+        fn remove(&mut self, parent: u64, name: &OsStr) {
+            let ino = self.inodes.get_mut(&parent).unwrap().children.remove(name);
+            let inode = self.inodes.get_mut(&ino.unwrap()).unwrap();
+            inode.nlink = 0;
+            if inode.lookups == 0 {
+                self.inodes.remove(&ino.unwrap());
+            }
+        }
+    }
+
+    impl Filesystem for MemFs {
+        fn init(&mut self, _req: &Request<'_>, config: &mut KernelConfig) -> Result<(), i32> {
+            if self.readdirplus {
+                config.add_capabilities(FUSE_DO_READDIRPLUS).unwrap();
+            }
+            Ok(())
+        }
+
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            if name == ".." {
+                self.dotdot_lookups += 1;
+            }
+            match self.child(parent, name) {
+                Ok(ino) => {
+                    self.inodes.get_mut(&ino).unwrap().lookups += 1;
+                    reply.entry(&TTL, &self.attr(ino), 0);
+                }
+                Err(err) => reply.error(err),
+            }
+        }
+
+        fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+            let inode = self.inodes.get_mut(&ino).unwrap();
+            inode.lookups -= nlookup;
+            if inode.lookups == 0 && inode.nlink == 0 {
+                self.inodes.remove(&ino);
+            }
+        }
+
+        fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+            reply.attr(&TTL, &self.attr(ino));
+        }
+
+        fn setattr(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _mode: Option<u32>,
+            _uid: Option<u32>,
+            _gid: Option<u32>,
+            size: Option<u64>,
+            _atime: Option<TimeOrNow>,
+            _mtime: Option<TimeOrNow>,
+            _ctime: Option<SystemTime>,
+            _fh: Option<u64>,
+            _crtime: Option<SystemTime>,
+            _chgtime: Option<SystemTime>,
+            _bkuptime: Option<SystemTime>,
+            _flags: Option<u32>,
+            _kill_suidgid: bool,
+            reply: ReplyAttr,
+        ) {
+            if let Some(size) = size {
+                let inode = self.inodes.get_mut(&ino).unwrap();
+                inode.data.resize(size as usize, 0);
+            }
+            reply.attr(&TTL, &self.attr(ino));
+        }
+
+        fn mkdir(
+            &mut self,
+            _req: &Request<'_>,
+            parent: u64,
+            name: &OsStr,
+            _mode: u32,
+            _umask: u32,
+            _security_ctx: &[(&OsStr, &[u8])],
+            reply: ReplyEntry,
+        ) {
+            match self.add(parent, name, FileType::Directory) {
+                Ok(ino) => reply.entry(&TTL, &self.attr(ino), 0),
+                Err(err) => reply.error(err),
+            }
+        }
+
+        fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+            match self.child(parent, name) {
+                Ok(_) => {
+                    self.remove(parent, name);
+                    reply.ok();
+                }
+                Err(err) => reply.error(err),
+            }
+        }
+
+        fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+            match self.child(parent, name) {
+                Ok(ino) if !self.inodes[&ino].children.is_empty() => reply.error(ENOTEMPTY),
+                Ok(_) => {
+                    self.remove(parent, name);
+                    reply.ok();
+                }
+                Err(err) => reply.error(err),
+            }
+        }
+
+        fn rename(
+            &mut self,
+            _req: &Request<'_>,
+            parent: u64,
+            name: &OsStr,
+            newparent: u64,
+            newname: &OsStr,
+            _flags: u32,
+            reply: ReplyEmpty,
+        ) {
+            let ino = match self.child(parent, name) {
+                Ok(ino) => ino,
+                Err(err) => return reply.error(err),
+            };
+            if let Ok(target) = self.child(newparent, newname) {
+                if !self.inodes[&target].children.is_empty() {
+                    return reply.error(ENOTEMPTY);
+                }
+                self.remove(newparent, newname);
+            }
+            self.inodes.get_mut(&parent).unwrap().children.remove(name);
+            let dir = self.inodes.get_mut(&newparent).unwrap();
+            dir.children.insert(newname.to_owned(), ino);
+            self.inodes.get_mut(&ino).unwrap().parent = newparent;
+            reply.ok();
+        }
+
+        fn create(
+            &mut self,
+            _req: &Request<'_>,
+            parent: u64,
+            name: &OsStr,
+            _mode: u32,
+            _umask: u32,
+            _flags: i32,
+            _kill_suidgid: bool,
+            _security_ctx: &[(&OsStr, &[u8])],
+            reply: ReplyCreate,
+        ) {
+            match self.add(parent, name, FileType::RegularFile) {
+                Ok(ino) => reply.created(&TTL, &self.attr(ino), 0, ino, 0),
+                Err(err) => reply.error(err),
+            }
+        }
+
+        fn read(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            size: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyData,
+        ) {
+            let data = &self.inodes[&ino].data;
+            let start = (offset as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            reply.data(&data[start..end]);
+        }
+
+        fn write(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            data: &[u8],
+            _write_flags: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            _kill_suidgid: bool,
+            reply: ReplyWrite,
+        ) {
+            let file = &mut self.inodes.get_mut(&ino).unwrap().data;
+            let end = offset as usize + data.len();
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            file[offset as usize..end].copy_from_slice(data);
+            reply.written(data.len() as u32);
+        }
+
+        fn readdir(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            mut reply: ReplyDirectory,
+        ) {
+            let dir = &self.inodes[&ino];
+            let entries = vec![
+                (ino, FileType::Directory, OsString::from(".")),
+                (dir.parent, FileType::Directory, OsString::from("..")),
+            ];
+            let children = dir
+                .children
+                .iter()
+                .map(|(name, ino)| (*ino, self.inodes[ino].kind, name.clone()));
+            let entries = entries.into_iter().chain(children);
+            for (i, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+                if reply.add(ino, i as i64 + 1, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        }
+    }
+
+    fn errno<T: std::fmt::Debug>(result: io::Result<T>) -> i32 {
+        result.unwrap_err().raw_os_error().unwrap()
+    }
+
+    /// Checks that the kernel and the filesystem agree on the lookup count of every inode
+    fn check_lookups(vfs: &Vfs<MemFs>) {
+        for (ino, inode) in vfs.filesystem().inodes.iter() {
+            if *ino != 1 {
+                assert_eq!(vfs.lookup_count(*ino), inode.lookups, "inode {}", ino);
+            }
+        }
+        for ino in vfs.lookups().keys() {
+            assert!(vfs.filesystem().inodes.contains_key(ino), "inode {}", ino);
+        }
+    }
+
+    fn names(vfs: &mut Vfs<MemFs>, path: &str) -> Vec<OsString> {
+        let entries = vfs.read_dir(path).unwrap();
+        entries.into_iter().map(|x| x.name).collect()
+    }
+
+    #[test]
+    fn files() {
+        let mut vfs = Vfs::new(MemFs::new(false)).unwrap();
+        vfs.mkdir("/a", 0o755).unwrap();
+        vfs.mkdir("/a/b", 0o755).unwrap();
+        let fd = vfs.open("/a/b/f", O_CREAT | O_WRONLY, 0o644).unwrap();
+        assert_eq!(vfs.write(fd, b"hello world").unwrap(), 11);
+        vfs.close(fd).unwrap();
+
+        let fd = vfs.open("a/b/../b/./f", O_RDONLY, 0).unwrap();
+        assert_eq!(vfs.read(fd, 5).unwrap(), b"hello");
+        assert_eq!(vfs.read(fd, 100).unwrap(), b" world");
+        assert_eq!(vfs.read(fd, 100).unwrap(), b"");
+        vfs.close(fd).unwrap();
+
+        // Truncated with a setattr, as atomic O_TRUNC isn't negotiated
+        let fd = vfs.open("/a/b/f", O_RDWR | O_TRUNC, 0).unwrap();
+        vfs.write(fd, b"bye").unwrap();
+        vfs.close(fd).unwrap();
+
+        vfs.rename("/a/b/f", "/a/g").unwrap();
+        assert_eq!(vfs.stat("/a/g").unwrap().size, 3);
+        assert_eq!(names(&mut vfs, "/a"), [".", "..", "b", "g"]);
+        check_lookups(&vfs);
+
+        vfs.drop_caches();
+        assert!(vfs.lookups().is_empty());
+        check_lookups(&vfs);
+        vfs.unmount().unwrap();
+    }
+
+    #[test]
+    fn dotdot() {
+        let mut vfs = Vfs::new(MemFs::new(false)).unwrap();
+        vfs.mkdir("/a", 0o755).unwrap();
+        vfs.mkdir("/a/b", 0o755).unwrap();
+        let a = vfs.stat("/a").unwrap().ino;
+        assert_eq!(vfs.stat("/a/b/..").unwrap().ino, a);
+        assert_eq!(vfs.stat("/a/b/../../a/./b/../..").unwrap().ino, 1);
+        assert_eq!(vfs.stat("/..").unwrap().ino, 1);
+
+        // The parent follows renames
+        vfs.rename("/a/b", "/c").unwrap();
+        assert_eq!(vfs.stat("/c/..").unwrap().ino, 1);
+        assert_eq!(errno(vfs.stat("/a/b/..")), ENOENT);
+        assert_eq!(vfs.filesystem().dotdot_lookups, 0);
+        check_lookups(&vfs);
+    }
+
+    #[test]
+    fn readdir_paging() {
+        for readdirplus in [false, true].iter() {
+            let mut vfs = Vfs::new(MemFs::new(*readdirplus)).unwrap();
+            for i in 0..200 {
+                let fd = vfs.open(format!("/file{}", i), O_CREAT, 0o644).unwrap();
+                vfs.close(fd).unwrap();
+            }
+            vfs.drop_caches();
+            let mut names = names(&mut vfs, "/");
+            assert_eq!(names.len(), 202);
+            names.sort();
+            names.dedup();
+            assert_eq!(names.len(), 202);
+            // Only readdirplus looks up the entries
+            assert_eq!(vfs.lookups().len(), if *readdirplus { 200 } else { 0 });
+            check_lookups(&vfs);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut vfs = Vfs::with_capabilities(MemFs::new(false), FUSE_ASYNC_READ).unwrap();
+        vfs.mkdir("/dir", 0o755).unwrap();
+        let fd = vfs.open("/dir/file", O_CREAT | O_WRONLY, 0o644).unwrap();
+        vfs.close(fd).unwrap();
+
+        assert_eq!(errno(vfs.open("/missing", O_RDONLY, 0)), ENOENT);
+        assert_eq!(errno(vfs.open("/dir/file/x", O_RDONLY, 0)), ENOTDIR);
+        assert_eq!(errno(vfs.stat("/dir/file/")), ENOTDIR);
+        assert_eq!(errno(vfs.open("/dir", O_WRONLY, 0)), EISDIR);
+        let excl = vfs.open("/dir/file", O_CREAT | O_EXCL | O_WRONLY, 0o644);
+        assert_eq!(errno(excl), EEXIST);
+        assert_eq!(errno(vfs.mkdir("/dir", 0o755)), EEXIST);
+        assert_eq!(errno(vfs.rmdir("/dir")), ENOTEMPTY);
+        assert_eq!(errno(vfs.rmdir("/dir/file")), ENOTDIR);
+        assert_eq!(errno(vfs.unlink("/dir")), EISDIR);
+        assert_eq!(errno(vfs.rename("/dir", "/dir/sub")), EINVAL);
+        assert_eq!(errno(vfs.rename("/dir", "/dir/file")), ENOTDIR);
+        check_lookups(&vfs);
+
+        vfs.unlink("/dir/file").unwrap();
+        vfs.rmdir("/dir").unwrap();
+        assert_eq!(vfs.filesystem().inodes.len(), 1);
+        check_lookups(&vfs);
+    }
+
+    #[test]
+    fn unlink_open_file() {
+        let mut vfs = Vfs::new(MemFs::new(false)).unwrap();
+        let fd = vfs.open("/file", O_CREAT | O_RDWR, 0o644).unwrap();
+        vfs.write(fd, b"data").unwrap();
+        vfs.unlink("/file").unwrap();
+        assert_eq!(errno(vfs.stat("/file")), ENOENT);
+
+        // The inode stays around until the file is closed
+        assert_eq!(vfs.filesystem().inodes.len(), 2);
+        assert_eq!(vfs.pread(fd, 0, 10).unwrap(), b"data");
+        check_lookups(&vfs);
+        assert!(vfs.unmount().is_err());
+        vfs.close(fd).unwrap();
+        assert_eq!(vfs.filesystem().inodes.len(), 1);
+        assert!(vfs.lookups().is_empty());
+        vfs.unmount().unwrap();
+    }
+}
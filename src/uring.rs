@@ -0,0 +1,643 @@
+//! FUSE-over-io_uring transport
+//!
+//! With `FUSE_OVER_IO_URING`, the kernel passes requests through ring entries that the session
+//! registers for every queue (one queue per possible CPU) instead of through reads from the
+//! FUSE device. Each ring entry consists of a header buffer and a payload buffer. A request
+//! arrives as completion of the entry's `FUSE_IO_URING_CMD_REGISTER` or
+//! `FUSE_IO_URING_CMD_COMMIT_AND_FETCH` command; its reply is written into the same buffers
+//! and committed with the next `FUSE_IO_URING_CMD_COMMIT_AND_FETCH` command, which also
+//! fetches the next request into the entry. Forgets and interrupts keep coming through the
+//! FUSE device, which is polled through the same io_uring instance.
+//!
+//! The ring is driven by raw syscalls, since the libc crate we depend on has no bindings for
+//! io_uring.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IoSlice};
+use std::mem::{self, size_of};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use libc::{c_long, c_void};
+use log::{debug, warn};
+use zerocopy::AsBytes;
+
+use crate::channel::ChannelSender;
+use crate::ll::fuse_abi as abi;
+use crate::session::aligned_sub_buf;
+
+const SYS_IO_URING_SETUP: c_long = 425;
+const SYS_IO_URING_ENTER: c_long = 426;
+
+const IORING_SETUP_SQE128: u32 = 1 << 10;
+const IORING_ENTER_GETEVENTS: u32 = 1 << 0;
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x800_0000;
+const IORING_OFF_SQES: i64 = 0x1000_0000;
+const IORING_OP_POLL_ADD: u8 = 6;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_URING_CMD: u8 = 46;
+
+// user_data of the submissions. Ring entries use their index plus ENTRY_BASE.
+const DEVICE: u64 = 0;
+const WAKEUP: u64 = 1;
+const ENTRY_BASE: u64 = 2;
+
+/// Settings of the io_uring transport, as negotiated in init
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UringConfig {
+    /// Number of ring entries per queue
+    pub(crate) queue_depth: u16,
+    /// Size of the payload buffer of a ring entry, which the kernel requires to hold the
+    /// largest request or reply
+    pub(crate) payload_size: usize,
+}
+
+/// Something that happened on the io_uring transport
+#[derive(Debug)]
+pub(crate) enum Completion {
+    /// The FUSE device has a request to read (or failed)
+    Device(io::Result<()>),
+    /// The ring entry with the given index received a request
+    Request(usize),
+}
+
+/// Channel to the kernel driver that receives requests through io_uring
+pub(crate) struct UringChannel {
+    ring: Ring,
+    sender: ChannelSender,
+    shared: Arc<Shared>,
+    /// Header and payload buffer of each ring entry, as given to the kernel on registration
+    iovecs: Vec<[libc::iovec; 2]>,
+    /// Id of the request each ring entry is serving, to commit its reply with
+    commit_ids: Vec<u64>,
+    /// Buffer to reassemble a request from the buffers of its ring entry
+    request: Vec<u8>,
+    /// Completions that arrived while registering the ring entries
+    pending: VecDeque<Cqe>,
+    /// Buffer for reading the eventfd
+    wakeup: Box<u64>,
+}
+
+/// State shared between the session loop and the replies of requests received through io_uring
+struct Shared {
+    entries: Vec<Mutex<Entry>>,
+    /// Ring entries whose replies are ready to be committed
+    commits: Mutex<Vec<usize>>,
+    /// Wakes up the session loop if a reply is sent from another thread
+    eventfd: File,
+    /// Thread that runs the session loop
+    thread: ThreadId,
+}
+
+/// Buffers of a ring entry
+struct Entry {
+    qid: u16,
+    header: Box<abi::fuse_uring_req_header>,
+    payload: Vec<u8>,
+}
+
+impl UringChannel {
+    /// Set up an io_uring instance and register ring entries with the kernel for all queues.
+    /// Fails if the kernel does not accept the ring entries, in which case the kernel keeps
+    /// sending requests through the FUSE device.
+    pub(crate) fn new(sender: ChannelSender, config: &UringConfig) -> io::Result<Self> {
+        // The kernel has a queue per possible CPU and only switches to io_uring once all
+        // queues have ring entries
+        let queues = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) }.max(1) as usize;
+        let depth = config.queue_depth.max(1) as usize;
+        let count = queues * depth;
+        let ring = Ring::new(count as u32 + 2)?;
+
+        let eventfd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if eventfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let eventfd = unsafe { File::from_raw_fd(eventfd) };
+
+        let mut entries = Vec::with_capacity(count);
+        let mut iovecs = Vec::with_capacity(count);
+        for i in 0..count {
+            let mut entry = Entry {
+                qid: (i / depth) as u16,
+                header: Box::new(unsafe { mem::zeroed() }),
+                payload: vec![0; config.payload_size],
+            };
+            iovecs.push([
+                libc::iovec {
+                    iov_base: &mut *entry.header as *mut _ as *mut c_void,
+                    iov_len: size_of::<abi::fuse_uring_req_header>(),
+                },
+                libc::iovec {
+                    iov_base: entry.payload.as_mut_ptr() as *mut c_void,
+                    iov_len: entry.payload.len(),
+                },
+            ]);
+            entries.push(Mutex::new(entry));
+        }
+
+        let mut channel = UringChannel {
+            ring,
+            sender,
+            shared: Arc::new(Shared {
+                entries,
+                commits: Mutex::new(Vec::new()),
+                eventfd,
+                thread: thread::current().id(),
+            }),
+            iovecs,
+            commit_ids: vec![0; count],
+            request: vec![0; size_of::<abi::fuse_in_header>() + 4096 + config.payload_size],
+            pending: VecDeque::new(),
+            wakeup: Box::new(0),
+        };
+        for i in 0..count {
+            channel.push_register(i)?;
+        }
+        channel.push_wakeup()?;
+        channel.poll_device()?;
+        channel.ring.enter(0)?;
+
+        // Registration errors complete right away
+        while let Some(cqe) = channel.ring.pop() {
+            if cqe.user_data >= ENTRY_BASE && cqe.res < 0 {
+                return Err(io::Error::from_raw_os_error(-cqe.res));
+            }
+            channel.pending.push_back(cqe);
+        }
+        Ok(channel)
+    }
+
+    /// Wait until the FUSE device has a request to read. Completes with
+    /// [`Completion::Device`].
+    pub(crate) fn poll_device(&mut self) -> io::Result<()> {
+        let mut sqe = Sqe::new(IORING_OP_POLL_ADD, self.sender.raw_fd(), DEVICE);
+        let events = libc::POLLIN as u32;
+        // The kernel expects the halfwords of the event mask swapped on big endian systems
+        sqe.op_flags = if cfg!(target_endian = "big") {
+            events.rotate_left(16)
+        } else {
+            events
+        };
+        self.ring.push(&sqe)
+    }
+
+    /// Submit the commits of sent replies and wait for at least one completion
+    pub(crate) fn submit_and_wait(&mut self) -> io::Result<()> {
+        let commits = mem::take(&mut *self.shared.commits.lock().unwrap());
+        for entry in commits {
+            self.push_cmd(
+                entry,
+                abi::FUSE_IO_URING_CMD_COMMIT_AND_FETCH,
+                self.commit_ids[entry],
+            )?;
+        }
+        match self.ring.enter(1) {
+            Err(err) if err.raw_os_error() == Some(libc::EINTR) => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Returns the next completion, if any
+    pub(crate) fn next_completion(&mut self) -> Option<Completion> {
+        loop {
+            let cqe = match self.pending.pop_front() {
+                Some(cqe) => cqe,
+                None => self.ring.pop()?,
+            };
+            match cqe.user_data {
+                DEVICE if cqe.res < 0 => {
+                    return Some(Completion::Device(Err(io::Error::from_raw_os_error(
+                        -cqe.res,
+                    ))))
+                }
+                DEVICE => return Some(Completion::Device(Ok(()))),
+                WAKEUP => {
+                    if cqe.res < 0 {
+                        warn!(
+                            "Failed to read eventfd: {}",
+                            io::Error::from_raw_os_error(-cqe.res)
+                        );
+                    }
+                    if let Err(err) = self.push_wakeup() {
+                        warn!("Failed to read eventfd: {}", err);
+                    }
+                }
+                // The kernel stops ring entries with an error when the connection ends
+                _ if cqe.res < 0 => debug!(
+                    "io_uring entry {} stopped: {}",
+                    cqe.user_data - ENTRY_BASE,
+                    io::Error::from_raw_os_error(-cqe.res)
+                ),
+                _ => return Some(Completion::Request((cqe.user_data - ENTRY_BASE) as usize)),
+            }
+        }
+    }
+
+    /// Returns the request received by the given ring entry together with a sender that
+    /// commits the reply through the ring entry
+    pub(crate) fn request(&mut self, entry: usize) -> (ChannelSender, &[u8]) {
+        let sender = self.sender.with_ring_entry(RingEntrySender {
+            shared: self.shared.clone(),
+            entry,
+        });
+        let buf = aligned_sub_buf(&mut self.request, mem::align_of::<abi::fuse_in_header>());
+        let ent = self.shared.entries[entry].lock().unwrap();
+        let ent_in_out = ent.header.ring_ent_in_out;
+        self.commit_ids[entry] = ent_in_out.commit_id;
+
+        // The request is the in header, followed by the fixed size argument of the
+        // operation in op_in and the remaining arguments in the payload
+        let header_size = size_of::<abi::fuse_in_header>();
+        let payload_size = ent_in_out.payload_sz as usize;
+        let mut len_bytes = [0; 4];
+        len_bytes.copy_from_slice(&ent.header.in_out[..4]);
+        let len = u32::from_ne_bytes(len_bytes) as usize;
+        let op_in_size = match len.checked_sub(header_size + payload_size) {
+            Some(size)
+                if size <= abi::FUSE_URING_OP_IN_OUT_SZ
+                    && payload_size <= ent.payload.len()
+                    && len <= buf.len() =>
+            {
+                size
+            }
+            // Leave it to the parser to reject the request
+            _ => return (sender, &[]),
+        };
+        buf[..header_size].copy_from_slice(&ent.header.in_out[..header_size]);
+        buf[header_size..header_size + op_in_size].copy_from_slice(&ent.header.op_in[..op_in_size]);
+        buf[header_size + op_in_size..len].copy_from_slice(&ent.payload[..payload_size]);
+        (sender, &buf[..len])
+    }
+
+    fn push_register(&mut self, entry: usize) -> io::Result<()> {
+        let mut sqe = self.cmd(entry, abi::FUSE_IO_URING_CMD_REGISTER, 0);
+        sqe.addr = self.iovecs[entry].as_ptr() as u64;
+        sqe.len = self.iovecs[entry].len() as u32;
+        self.ring.push(&sqe)
+    }
+
+    fn push_cmd(&mut self, entry: usize, cmd_op: u32, commit_id: u64) -> io::Result<()> {
+        let sqe = self.cmd(entry, cmd_op, commit_id);
+        self.ring.push(&sqe)
+    }
+
+    fn cmd(&self, entry: usize, cmd_op: u32, commit_id: u64) -> Sqe {
+        let mut sqe = Sqe::new(
+            IORING_OP_URING_CMD,
+            self.sender.raw_fd(),
+            ENTRY_BASE + entry as u64,
+        );
+        sqe.cmd_op = cmd_op;
+        let req = abi::fuse_uring_cmd_req {
+            flags: 0,
+            commit_id,
+            qid: self.shared.entries[entry].lock().unwrap().qid,
+            padding: [0; 6],
+        };
+        sqe.cmd[..size_of::<abi::fuse_uring_cmd_req>()].copy_from_slice(req.as_bytes());
+        sqe
+    }
+
+    fn push_wakeup(&mut self) -> io::Result<()> {
+        let mut sqe = Sqe::new(IORING_OP_READ, self.shared.eventfd.as_raw_fd(), WAKEUP);
+        sqe.addr = &mut *self.wakeup as *mut u64 as u64;
+        sqe.len = size_of::<u64>() as u32;
+        self.ring.push(&sqe)
+    }
+}
+
+impl Shared {
+    /// Queue the reply in the given ring entry for commit by the session loop
+    fn commit(&self, entry: usize) -> io::Result<()> {
+        self.commits.lock().unwrap().push(entry);
+        if thread::current().id() == self.thread {
+            // The session loop submits commits before it waits again
+            return Ok(());
+        }
+        let value: u64 = 1;
+        let rc = unsafe {
+            libc::write(
+                self.eventfd.as_raw_fd(),
+                &value as *const u64 as *const c_void,
+                size_of::<u64>(),
+            )
+        };
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Sends the reply to a request through the ring entry that received it
+#[derive(Clone)]
+pub(crate) struct RingEntrySender {
+    shared: Arc<Shared>,
+    entry: usize,
+}
+
+impl RingEntrySender {
+    pub(crate) fn send(&self, bufs: &[IoSlice<'_>]) -> io::Result<()> {
+        let mut entry = self.shared.entries[self.entry].lock().unwrap();
+        let entry = &mut *entry;
+        let header_size = size_of::<abi::fuse_out_header>();
+        let (header, args) = match bufs.split_first() {
+            Some((header, args)) if header.len() == header_size => (header, args),
+            _ => return Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+        entry.header.in_out[..header_size].copy_from_slice(header);
+        let mut size = 0;
+        let mut res = Ok(());
+        for arg in args {
+            match entry.payload.get_mut(size..size + arg.len()) {
+                Some(buf) => buf.copy_from_slice(arg),
+                None => {
+                    // Still commit the entry, or it would never serve another request
+                    let error = -libc::EIO;
+                    entry.header.in_out[4..8].copy_from_slice(&error.to_ne_bytes());
+                    size = 0;
+                    res = Err(io::Error::from_raw_os_error(libc::EOVERFLOW));
+                    break;
+                }
+            }
+            size += arg.len();
+        }
+        entry.header.ring_ent_in_out.payload_sz = size as u32;
+        self.shared.commit(self.entry).and(res)
+    }
+}
+
+impl fmt::Debug for RingEntrySender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingEntrySender")
+            .field("entry", &self.entry)
+            .finish()
+    }
+}
+
+/// Submission queue entry, in the 128 byte layout that URING_CMD needs
+#[repr(C)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    cmd_op: u32,
+    pad1: u32,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    file_index: u32,
+    cmd: [u8; 80],
+}
+
+impl Sqe {
+    fn new(opcode: u8, fd: RawFd, user_data: u64) -> Self {
+        let mut sqe: Sqe = unsafe { mem::zeroed() };
+        sqe.opcode = opcode;
+        sqe.fd = fd;
+        sqe.user_data = user_data;
+        sqe
+    }
+}
+
+/// Completion queue entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// Memory mapped region of an io_uring instance
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: RawFd, len: usize, offset: i64) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Mmap { ptr, len })
+        }
+    }
+
+    /// Pointer to the given offset into the region
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { (self.ptr as *mut u8).add(offset as usize) as *mut T }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// Minimal io_uring instance with 128 byte submission queue entries
+struct Ring {
+    // Unmapped before the io_uring file is closed
+    sq_ring: Mmap,
+    cq_ring: Mmap,
+    sqes: Mmap,
+    fd: File,
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params {
+            flags: IORING_SETUP_SQE128,
+            ..Default::default()
+        };
+        let fd = unsafe { libc::syscall(SYS_IO_URING_SETUP, entries, &mut params as *mut Params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { File::from_raw_fd(fd as RawFd) };
+        let raw = fd.as_raw_fd();
+        let sq_ring = Mmap::new(
+            raw,
+            params.sq_off.array as usize + params.sq_entries as usize * size_of::<u32>(),
+            IORING_OFF_SQ_RING,
+        )?;
+        let cq_ring = Mmap::new(
+            raw,
+            params.cq_off.cqes as usize + params.cq_entries as usize * size_of::<Cqe>(),
+            IORING_OFF_CQ_RING,
+        )?;
+        let sqes = Mmap::new(
+            raw,
+            params.sq_entries as usize * size_of::<Sqe>(),
+            IORING_OFF_SQES,
+        )?;
+        Ok(Ring {
+            sq_ring,
+            cq_ring,
+            sqes,
+            fd,
+            sq_off: params.sq_off,
+            cq_off: params.cq_off,
+        })
+    }
+
+    fn sq_head(&self) -> &AtomicU32 {
+        unsafe { &*self.sq_ring.at(self.sq_off.head) }
+    }
+
+    fn sq_tail(&self) -> &AtomicU32 {
+        unsafe { &*self.sq_ring.at(self.sq_off.tail) }
+    }
+
+    /// Queue a submission
+    fn push(&mut self, sqe: &Sqe) -> io::Result<()> {
+        let entries = unsafe { *self.sq_ring.at::<u32>(self.sq_off.ring_entries) };
+        let mask = unsafe { *self.sq_ring.at::<u32>(self.sq_off.ring_mask) };
+        let head = self.sq_head().load(Ordering::Acquire);
+        let tail = self.sq_tail().load(Ordering::Relaxed);
+        if tail.wrapping_sub(head) >= entries {
+            return Err(io::Error::from_raw_os_error(libc::EBUSY));
+        }
+        let index = tail & mask;
+        unsafe {
+            ptr::copy_nonoverlapping(sqe, self.sqes.at::<Sqe>(0).add(index as usize), 1);
+            *self
+                .sq_ring
+                .at::<u32>(self.sq_off.array)
+                .add(index as usize) = index;
+        }
+        self.sq_tail()
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Submit the queued submissions and wait for the given number of completions
+    fn enter(&mut self, min_complete: u32) -> io::Result<()> {
+        let to_submit = self
+            .sq_tail()
+            .load(Ordering::Relaxed)
+            .wrapping_sub(self.sq_head().load(Ordering::Acquire));
+        let rc = unsafe {
+            libc::syscall(
+                SYS_IO_URING_ENTER,
+                self.fd.as_raw_fd(),
+                to_submit,
+                min_complete,
+                IORING_ENTER_GETEVENTS,
+                ptr::null::<libc::sigset_t>(),
+                0usize,
+            )
+        };
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Take the next completion, if any
+    fn pop(&mut self) -> Option<Cqe> {
+        let (head, tail) = unsafe {
+            (
+                &*self.cq_ring.at::<AtomicU32>(self.cq_off.head),
+                &*self.cq_ring.at::<AtomicU32>(self.cq_off.tail),
+            )
+        };
+        let mask = unsafe { *self.cq_ring.at::<u32>(self.cq_off.ring_mask) };
+        let current = head.load(Ordering::Relaxed);
+        if current == tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let cqe = unsafe {
+            *self
+                .cq_ring
+                .at::<Cqe>(self.cq_off.cqes)
+                .add((current & mask) as usize)
+        };
+        head.store(current.wrapping_add(1), Ordering::Release);
+        Some(cqe)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cqe, Params, Sqe};
+    use crate::ll::fuse_abi as abi;
+    use std::mem::size_of;
+
+    #[test]
+    fn abi_sizes() {
+        assert_eq!(size_of::<Sqe>(), 128);
+        assert_eq!(size_of::<Cqe>(), 16);
+        assert_eq!(size_of::<Params>(), 120);
+        assert_eq!(size_of::<abi::fuse_uring_req_header>(), 288);
+        assert_eq!(size_of::<abi::fuse_uring_cmd_req>(), 24);
+    }
+}
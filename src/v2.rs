@@ -0,0 +1,1084 @@
+//! Filesystem trait with typed requests
+//!
+//! [`FilesystemV2`] passes each operation its typed request from [`op`] instead of a long list
+//! of positional arguments, so arguments added by newer kernels only add methods to the request
+//! types. Every [`Filesystem`] is a [`FilesystemV2`], and both can be mounted.
+
+use libc::{c_int, ENOSYS};
+use log::{debug, warn};
+use std::convert::TryInto;
+
+use crate::ll::Request as _;
+use crate::op::{self, INodeNo};
+use crate::readdirplus;
+#[cfg(target_os = "macos")]
+use crate::ReplyXTimes;
+use crate::{
+    Filesystem, KernelConfig, Operations, ReplyAttr, ReplyBmap, ReplyCreate, ReplyData,
+    ReplyDirectory, ReplyDirectoryPlus, ReplyEmpty, ReplyEntry, ReplyIoctl, ReplyLock, ReplyLseek,
+    ReplyOpen, ReplyStatfs, ReplyStatx, ReplyWrite, ReplyXattr, Request,
+};
+
+/// Filesystem trait with typed requests.
+///
+/// Like [`Filesystem`], but every operation receives its request from [`op`], which carries
+/// all arguments the kernel sent, together with its reply. The header fields of a request, like
+/// the inode it is targeted to, are available through [`op::Request`]. The default
+/// implementations behave like the ones of [`Filesystem`], whose documentation describes the
+/// operations in more detail.
+pub trait FilesystemV2 {
+    /// Initialize filesystem. See [`Filesystem::init`].
+    fn init(&mut self, _req: &Request<'_>, _config: &mut KernelConfig) -> Result<(), c_int> {
+        Ok(())
+    }
+
+    /// Clean up filesystem. Called on filesystem exit.
+    fn destroy(&mut self) {}
+
+    /// Operations the filesystem implements. See [`Filesystem::supported_ops`].
+    fn supported_ops(&self) -> Operations {
+        Operations::all()
+    }
+
+    /// Look up a directory entry by name and get its attributes. See [`Filesystem::lookup`].
+    fn lookup(&mut self, _req: &Request<'_>, op: op::Lookup<'_>, reply: ReplyEntry) {
+        warn!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Forget about an inode. See [`Filesystem::forget`].
+    fn forget(&mut self, _req: &Request<'_>, _ino: INodeNo, _nlookup: u64) {}
+
+    /// Like forget, but take multiple forget requests at once for performance. The default
+    /// implementation will fallback to forget.
+    fn batch_forget(&mut self, req: &Request<'_>, op: op::BatchForget<'_>) {
+        for node in op.nodes() {
+            self.forget(req, INodeNo(node.nodeid), node.nlookup);
+        }
+    }
+
+    /// Get file attributes.
+    fn getattr(&mut self, _req: &Request<'_>, op: op::GetAttr<'_>, reply: ReplyAttr) {
+        warn!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Get extended file attributes, see statx(2). If this method is not implemented,
+    /// the kernel stops sending statx requests and calls getattr() instead.
+    fn statx(&mut self, _req: &Request<'_>, op: op::Statx<'_>, reply: ReplyStatx) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Set file attributes. Only the attributes that are `Some` are changed.
+    fn setattr(&mut self, _req: &Request<'_>, op: op::SetAttr<'_>, reply: ReplyAttr) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Read symbolic link.
+    fn readlink(&mut self, _req: &Request<'_>, op: op::ReadLink<'_>, reply: ReplyData) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Create file node. See [`Filesystem::mknod`].
+    fn mknod(&mut self, _req: &Request<'_>, op: op::MkNod<'_>, reply: ReplyEntry) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Create a directory.
+    fn mkdir(&mut self, _req: &Request<'_>, op: op::MkDir<'_>, reply: ReplyEntry) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Remove a file.
+    fn unlink(&mut self, _req: &Request<'_>, op: op::Unlink<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Remove a directory.
+    fn rmdir(&mut self, _req: &Request<'_>, op: op::RmDir<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Create a symbolic link.
+    fn symlink(&mut self, _req: &Request<'_>, op: op::SymLink<'_>, reply: ReplyEntry) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Rename a file.
+    fn rename(&mut self, _req: &Request<'_>, op: op::Rename<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Rename a file with `renameat2(2)` flags, such as `RENAME_NOREPLACE`.
+    fn rename2(&mut self, _req: &Request<'_>, op: op::Rename2<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Create a hard link. See [`Filesystem::link`].
+    fn link(&mut self, _req: &Request<'_>, op: op::Link<'_>, reply: ReplyEntry) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Open a file. See [`Filesystem::open`].
+    fn open(&mut self, _req: &Request<'_>, _op: op::Open<'_>, reply: ReplyOpen) {
+        reply.opened(0, 0);
+    }
+
+    /// Read data. See [`Filesystem::read`].
+    fn read(&mut self, _req: &Request<'_>, op: op::Read<'_>, reply: ReplyData) {
+        warn!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Write data. See [`Filesystem::write`].
+    fn write(&mut self, _req: &Request<'_>, op: op::Write<'_>, reply: ReplyWrite) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Flush method. See [`Filesystem::flush`].
+    fn flush(&mut self, _req: &Request<'_>, op: op::Flush<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Release an open file. See [`Filesystem::release`].
+    fn release(&mut self, _req: &Request<'_>, _op: op::Release<'_>, reply: ReplyEmpty) {
+        reply.ok();
+    }
+
+    /// Synchronize file contents.
+    fn fsync(&mut self, _req: &Request<'_>, op: op::FSync<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Open a directory. See [`Filesystem::opendir`].
+    fn opendir(&mut self, _req: &Request<'_>, _op: op::OpenDir<'_>, reply: ReplyOpen) {
+        reply.opened(0, 0);
+    }
+
+    /// Read directory. See [`Filesystem::readdir`].
+    fn readdir(&mut self, _req: &Request<'_>, op: op::ReadDir<'_>, reply: ReplyDirectory) {
+        warn!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Read directory with the attributes of its entries. See [`Filesystem::readdirplus`].
+    /// If this method is not implemented, the entries of readdir() are looked up with
+    /// lookup() instead.
+    fn readdirplus(
+        &mut self,
+        req: &Request<'_>,
+        op: op::ReadDirPlus<'_>,
+        reply: ReplyDirectoryPlus,
+    ) {
+        debug!(
+            "[Not Implemented] {:?}, falling back to readdir and lookup",
+            op
+        );
+        readdirplus::readdirplus(
+            self,
+            req,
+            reply,
+            |fs, listing| fs.readdir(req, op.readdir(), listing),
+            |fs, name, entry| fs.lookup(req, op.lookup(name), entry),
+        );
+    }
+
+    /// Release an open directory. See [`Filesystem::releasedir`].
+    fn releasedir(&mut self, _req: &Request<'_>, _op: op::ReleaseDir<'_>, reply: ReplyEmpty) {
+        reply.ok();
+    }
+
+    /// Synchronize directory contents.
+    fn fsyncdir(&mut self, _req: &Request<'_>, op: op::FSyncDir<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Get file system statistics.
+    fn statfs(&mut self, _req: &Request<'_>, _op: op::StatFs<'_>, reply: ReplyStatfs) {
+        reply.statfs(0, 0, 0, 0, 0, 512, 255, 0);
+    }
+
+    /// Set an extended attribute.
+    fn setxattr(&mut self, _req: &Request<'_>, op: op::SetXAttr<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Get an extended attribute. See [`Filesystem::getxattr`].
+    fn getxattr(&mut self, _req: &Request<'_>, op: op::GetXAttr<'_>, reply: ReplyXattr) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// List extended attribute names. See [`Filesystem::listxattr`].
+    fn listxattr(&mut self, _req: &Request<'_>, op: op::ListXAttr<'_>, reply: ReplyXattr) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Remove an extended attribute.
+    fn removexattr(&mut self, _req: &Request<'_>, op: op::RemoveXAttr<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Check file access permissions. See [`Filesystem::access`].
+    fn access(&mut self, _req: &Request<'_>, op: op::Access<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Create and open a file. See [`Filesystem::create`].
+    fn create(&mut self, _req: &Request<'_>, op: op::Create<'_>, reply: ReplyCreate) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Create and open an unnamed file (`O_TMPFILE`). See [`Filesystem::tmpfile`].
+    fn tmpfile(&mut self, _req: &Request<'_>, op: op::TmpFile<'_>, reply: ReplyCreate) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Test for a POSIX file lock.
+    fn getlk(&mut self, _req: &Request<'_>, op: op::GetLk<'_>, reply: ReplyLock) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Acquire, modify or release a POSIX file lock without waiting. See
+    /// [`Filesystem::setlk`].
+    fn setlk(&mut self, _req: &Request<'_>, op: op::SetLk<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Acquire, modify or release a POSIX file lock, waiting until it's available.
+    fn setlkw(&mut self, _req: &Request<'_>, op: op::SetLkW<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Map block index within file to block index within device.
+    fn bmap(&mut self, _req: &Request<'_>, op: op::BMap<'_>, reply: ReplyBmap) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// control device. See [`Filesystem::ioctl`].
+    fn ioctl(&mut self, _req: &Request<'_>, op: op::IoCtl<'_>, reply: ReplyIoctl) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Preallocate or deallocate space to a file
+    fn fallocate(&mut self, _req: &Request<'_>, op: op::FAllocate<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Reposition read/write file offset
+    fn lseek(&mut self, _req: &Request<'_>, op: op::Lseek<'_>, reply: ReplyLseek) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Copy the specified range from the source inode to the destination inode
+    fn copy_file_range(
+        &mut self,
+        _req: &Request<'_>,
+        op: op::CopyFileRange<'_>,
+        reply: ReplyWrite,
+    ) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// Synchronize the whole filesystem. See [`Filesystem::syncfs`].
+    fn syncfs(&mut self, _req: &Request<'_>, op: op::SyncFs<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// macOS only: Rename the volume. See [`Filesystem::setvolname`].
+    #[cfg(target_os = "macos")]
+    fn setvolname(&mut self, _req: &Request<'_>, op: op::SetVolName<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// macOS only (undocumented)
+    #[cfg(target_os = "macos")]
+    fn exchange(&mut self, _req: &Request<'_>, op: op::Exchange<'_>, reply: ReplyEmpty) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+
+    /// macOS only: Query extended times (bkuptime and crtime). See
+    /// [`Filesystem::getxtimes`].
+    #[cfg(target_os = "macos")]
+    fn getxtimes(&mut self, _req: &Request<'_>, op: op::GetXTimes<'_>, reply: ReplyXTimes) {
+        debug!("[Not Implemented] {:?}", op);
+        reply.error(ENOSYS);
+    }
+}
+
+impl<T: Filesystem> FilesystemV2 for T {
+    fn init(&mut self, req: &Request<'_>, config: &mut KernelConfig) -> Result<(), c_int> {
+        Filesystem::init(self, req, config)
+    }
+
+    fn destroy(&mut self) {
+        Filesystem::destroy(self);
+    }
+
+    fn supported_ops(&self) -> Operations {
+        Filesystem::supported_ops(self)
+    }
+
+    fn lookup(&mut self, req: &Request<'_>, op: op::Lookup<'_>, reply: ReplyEntry) {
+        Filesystem::lookup(self, req, op.nodeid().into(), op.name().as_ref(), reply);
+    }
+
+    fn forget(&mut self, req: &Request<'_>, ino: INodeNo, nlookup: u64) {
+        Filesystem::forget(self, req, ino.into(), nlookup);
+    }
+
+    fn batch_forget(&mut self, req: &Request<'_>, op: op::BatchForget<'_>) {
+        Filesystem::batch_forget(self, req, op.nodes());
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, op: op::GetAttr<'_>, reply: ReplyAttr) {
+        Filesystem::getattr(self, req, op.nodeid().into(), reply);
+    }
+
+    fn statx(&mut self, req: &Request<'_>, op: op::Statx<'_>, reply: ReplyStatx) {
+        Filesystem::statx(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().map(|fh| fh.into()),
+            op.flags(),
+            op.mask(),
+            reply,
+        );
+    }
+
+    fn setattr(&mut self, req: &Request<'_>, op: op::SetAttr<'_>, reply: ReplyAttr) {
+        Filesystem::setattr(
+            self,
+            req,
+            op.nodeid().into(),
+            op.mode(),
+            op.uid(),
+            op.gid(),
+            op.size(),
+            op.atime(),
+            op.mtime(),
+            op.ctime(),
+            op.file_handle().map(|fh| fh.into()),
+            op.crtime(),
+            op.chgtime(),
+            op.bkuptime(),
+            op.flags(),
+            op.kill_suidgid(),
+            reply,
+        );
+    }
+
+    fn readlink(&mut self, req: &Request<'_>, op: op::ReadLink<'_>, reply: ReplyData) {
+        Filesystem::readlink(self, req, op.nodeid().into(), reply);
+    }
+
+    fn mknod(&mut self, req: &Request<'_>, op: op::MkNod<'_>, reply: ReplyEntry) {
+        Filesystem::mknod(
+            self,
+            req,
+            op.nodeid().into(),
+            op.name().as_ref(),
+            op.mode(),
+            op.umask(),
+            op.rdev(),
+            op.security_ctx(),
+            reply,
+        );
+    }
+
+    fn mkdir(&mut self, req: &Request<'_>, op: op::MkDir<'_>, reply: ReplyEntry) {
+        Filesystem::mkdir(
+            self,
+            req,
+            op.nodeid().into(),
+            op.name().as_ref(),
+            op.mode(),
+            op.umask(),
+            op.security_ctx(),
+            reply,
+        );
+    }
+
+    fn unlink(&mut self, req: &Request<'_>, op: op::Unlink<'_>, reply: ReplyEmpty) {
+        Filesystem::unlink(self, req, op.nodeid().into(), op.name().as_ref(), reply);
+    }
+
+    fn rmdir(&mut self, req: &Request<'_>, op: op::RmDir<'_>, reply: ReplyEmpty) {
+        Filesystem::rmdir(self, req, op.nodeid().into(), op.name().as_ref(), reply);
+    }
+
+    fn symlink(&mut self, req: &Request<'_>, op: op::SymLink<'_>, reply: ReplyEntry) {
+        Filesystem::symlink(
+            self,
+            req,
+            op.nodeid().into(),
+            op.target().as_ref(),
+            op.link(),
+            op.security_ctx(),
+            reply,
+        );
+    }
+
+    fn rename(&mut self, req: &Request<'_>, op: op::Rename<'_>, reply: ReplyEmpty) {
+        Filesystem::rename(
+            self,
+            req,
+            op.src().dir.into(),
+            op.src().name.as_ref(),
+            op.dest().dir.into(),
+            op.dest().name.as_ref(),
+            0,
+            reply,
+        );
+    }
+
+    fn rename2(&mut self, req: &Request<'_>, op: op::Rename2<'_>, reply: ReplyEmpty) {
+        Filesystem::rename(
+            self,
+            req,
+            op.from().dir.into(),
+            op.from().name.as_ref(),
+            op.to().dir.into(),
+            op.to().name.as_ref(),
+            op.flags(),
+            reply,
+        );
+    }
+
+    fn link(&mut self, req: &Request<'_>, op: op::Link<'_>, reply: ReplyEntry) {
+        Filesystem::link(
+            self,
+            req,
+            op.inode_no().into(),
+            op.dest().dir.into(),
+            op.dest().name.as_ref(),
+            reply,
+        );
+    }
+
+    fn open(&mut self, req: &Request<'_>, op: op::Open<'_>, reply: ReplyOpen) {
+        Filesystem::open(
+            self,
+            req,
+            op.nodeid().into(),
+            op.flags().into(),
+            op.kill_suidgid(),
+            reply,
+        );
+    }
+
+    fn read(&mut self, req: &Request<'_>, op: op::Read<'_>, reply: ReplyData) {
+        Filesystem::read(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.offset(),
+            op.size(),
+            op.flags().into(),
+            op.lock_owner().map(|l| l.into()),
+            reply,
+        );
+    }
+
+    fn write(&mut self, req: &Request<'_>, op: op::Write<'_>, reply: ReplyWrite) {
+        Filesystem::write(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.offset(),
+            op.data(),
+            op.write_flags().into(),
+            op.flags().into(),
+            op.lock_owner().map(|l| l.into()),
+            op.kill_suidgid(),
+            reply,
+        );
+    }
+
+    fn flush(&mut self, req: &Request<'_>, op: op::Flush<'_>, reply: ReplyEmpty) {
+        Filesystem::flush(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.lock_owner().into(),
+            reply,
+        );
+    }
+
+    fn release(&mut self, req: &Request<'_>, op: op::Release<'_>, reply: ReplyEmpty) {
+        Filesystem::release(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.flags().into(),
+            op.lock_owner().map(|l| l.into()),
+            op.flush(),
+            reply,
+        );
+    }
+
+    fn fsync(&mut self, req: &Request<'_>, op: op::FSync<'_>, reply: ReplyEmpty) {
+        Filesystem::fsync(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.fdatasync(),
+            reply,
+        );
+    }
+
+    fn opendir(&mut self, req: &Request<'_>, op: op::OpenDir<'_>, reply: ReplyOpen) {
+        Filesystem::opendir(self, req, op.nodeid().into(), op.flags().into(), reply);
+    }
+
+    fn readdir(&mut self, req: &Request<'_>, op: op::ReadDir<'_>, reply: ReplyDirectory) {
+        Filesystem::readdir(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.offset(),
+            reply,
+        );
+    }
+
+    fn readdirplus(
+        &mut self,
+        req: &Request<'_>,
+        op: op::ReadDirPlus<'_>,
+        reply: ReplyDirectoryPlus,
+    ) {
+        Filesystem::readdirplus(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.offset(),
+            reply,
+        );
+    }
+
+    fn releasedir(&mut self, req: &Request<'_>, op: op::ReleaseDir<'_>, reply: ReplyEmpty) {
+        Filesystem::releasedir(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.flags().into(),
+            reply,
+        );
+    }
+
+    fn fsyncdir(&mut self, req: &Request<'_>, op: op::FSyncDir<'_>, reply: ReplyEmpty) {
+        Filesystem::fsyncdir(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.fdatasync(),
+            reply,
+        );
+    }
+
+    fn statfs(&mut self, req: &Request<'_>, op: op::StatFs<'_>, reply: ReplyStatfs) {
+        Filesystem::statfs(self, req, op.nodeid().into(), reply);
+    }
+
+    fn setxattr(&mut self, req: &Request<'_>, op: op::SetXAttr<'_>, reply: ReplyEmpty) {
+        Filesystem::setxattr(
+            self,
+            req,
+            op.nodeid().into(),
+            op.name(),
+            op.value(),
+            op.flags(),
+            op.setxattr_flags(),
+            op.position(),
+            reply,
+        );
+    }
+
+    fn getxattr(&mut self, req: &Request<'_>, op: op::GetXAttr<'_>, reply: ReplyXattr) {
+        Filesystem::getxattr(
+            self,
+            req,
+            op.nodeid().into(),
+            op.name(),
+            op.size_u32(),
+            reply,
+        );
+    }
+
+    fn listxattr(&mut self, req: &Request<'_>, op: op::ListXAttr<'_>, reply: ReplyXattr) {
+        Filesystem::listxattr(self, req, op.nodeid().into(), op.size(), reply);
+    }
+
+    fn removexattr(&mut self, req: &Request<'_>, op: op::RemoveXAttr<'_>, reply: ReplyEmpty) {
+        Filesystem::removexattr(self, req, op.nodeid().into(), op.name(), reply);
+    }
+
+    fn access(&mut self, req: &Request<'_>, op: op::Access<'_>, reply: ReplyEmpty) {
+        Filesystem::access(self, req, op.nodeid().into(), op.mask(), reply);
+    }
+
+    fn create(&mut self, req: &Request<'_>, op: op::Create<'_>, reply: ReplyCreate) {
+        Filesystem::create(
+            self,
+            req,
+            op.nodeid().into(),
+            op.name().as_ref(),
+            op.mode(),
+            op.umask(),
+            op.flags().into(),
+            op.kill_suidgid(),
+            op.security_ctx(),
+            reply,
+        );
+    }
+
+    fn tmpfile(&mut self, req: &Request<'_>, op: op::TmpFile<'_>, reply: ReplyCreate) {
+        Filesystem::tmpfile(
+            self,
+            req,
+            op.nodeid().into(),
+            op.mode(),
+            op.umask(),
+            op.flags().into(),
+            reply,
+        );
+    }
+
+    fn getlk(&mut self, req: &Request<'_>, op: op::GetLk<'_>, reply: ReplyLock) {
+        let lock = op.lock();
+        Filesystem::getlk(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.lock_owner().into(),
+            lock.range.0,
+            lock.range.1,
+            lock.typ,
+            lock.pid,
+            reply,
+        );
+    }
+
+    fn setlk(&mut self, req: &Request<'_>, op: op::SetLk<'_>, reply: ReplyEmpty) {
+        let lock = op.lock();
+        Filesystem::setlk(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.lock_owner().into(),
+            lock.range.0,
+            lock.range.1,
+            lock.typ,
+            lock.pid,
+            false,
+            reply,
+        );
+    }
+
+    fn setlkw(&mut self, req: &Request<'_>, op: op::SetLkW<'_>, reply: ReplyEmpty) {
+        let lock = op.lock();
+        Filesystem::setlk(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.lock_owner().into(),
+            lock.range.0,
+            lock.range.1,
+            lock.typ,
+            lock.pid,
+            true,
+            reply,
+        );
+    }
+
+    fn bmap(&mut self, req: &Request<'_>, op: op::BMap<'_>, reply: ReplyBmap) {
+        Filesystem::bmap(
+            self,
+            req,
+            op.nodeid().into(),
+            op.block_size(),
+            op.block(),
+            reply,
+        );
+    }
+
+    fn ioctl(&mut self, req: &Request<'_>, op: op::IoCtl<'_>, reply: ReplyIoctl) {
+        Filesystem::ioctl(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.flags(),
+            op.command(),
+            op.arg(),
+            op.in_data(),
+            op.out_size(),
+            reply,
+        );
+    }
+
+    fn fallocate(&mut self, req: &Request<'_>, op: op::FAllocate<'_>, reply: ReplyEmpty) {
+        Filesystem::fallocate(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.offset(),
+            op.len(),
+            op.mode(),
+            reply,
+        );
+    }
+
+    fn lseek(&mut self, req: &Request<'_>, op: op::Lseek<'_>, reply: ReplyLseek) {
+        Filesystem::lseek(
+            self,
+            req,
+            op.nodeid().into(),
+            op.file_handle().into(),
+            op.offset(),
+            op.whence(),
+            reply,
+        );
+    }
+
+    fn copy_file_range(&mut self, req: &Request<'_>, op: op::CopyFileRange<'_>, reply: ReplyWrite) {
+        let (i, o) = (op.src(), op.dest());
+        Filesystem::copy_file_range(
+            self,
+            req,
+            i.inode.into(),
+            i.file_handle.into(),
+            i.offset,
+            o.inode.into(),
+            o.file_handle.into(),
+            o.offset,
+            op.len(),
+            op.flags().try_into().unwrap(),
+            reply,
+        );
+    }
+
+    fn syncfs(&mut self, req: &Request<'_>, op: op::SyncFs<'_>, reply: ReplyEmpty) {
+        Filesystem::syncfs(self, req, op.nodeid().into(), reply);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn setvolname(&mut self, req: &Request<'_>, op: op::SetVolName<'_>, reply: ReplyEmpty) {
+        Filesystem::setvolname(self, req, op.name(), reply);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn exchange(&mut self, req: &Request<'_>, op: op::Exchange<'_>, reply: ReplyEmpty) {
+        Filesystem::exchange(
+            self,
+            req,
+            op.from().dir.into(),
+            op.from().name.as_ref(),
+            op.to().dir.into(),
+            op.to().name.as_ref(),
+            op.options(),
+            reply,
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    fn getxtimes(&mut self, req: &Request<'_>, op: op::GetXTimes<'_>, reply: ReplyXTimes) {
+        Filesystem::getxtimes(self, req, op.nodeid().into(), reply);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libc::{EPERM, O_APPEND, O_RDWR};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use zerocopy::FromBytes;
+
+    use crate::ll::fuse_abi::consts::*;
+    use crate::ll::fuse_abi::fuse_setattr_in;
+    use crate::ll::{FileHandle, INodeNo, LockOwner, OpenFlags, WriteFlags};
+    use crate::testing::Harness;
+    use crate::{Filesystem, ReplyAttr, ReplyEmpty, ReplyOpen, ReplyWrite, Request, TimeOrNow};
+
+    /// The arguments a `Filesystem` method was called with
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        SetAttr {
+            ino: u64,
+            mode: Option<u32>,
+            uid: Option<u32>,
+            gid: Option<u32>,
+            size: Option<u64>,
+            atime: Option<TimeOrNow>,
+            mtime: Option<TimeOrNow>,
+            fh: Option<u64>,
+        },
+        Open {
+            ino: u64,
+            flags: i32,
+            kill_suidgid: bool,
+        },
+        Write {
+            fh: u64,
+            offset: i64,
+            data: Vec<u8>,
+            write_flags: u32,
+            flags: i32,
+            lock_owner: Option<u64>,
+        },
+        Release {
+            fh: u64,
+            flags: i32,
+            lock_owner: Option<u64>,
+            flush: bool,
+        },
+    }
+
+    /// Records the calls of the blanket `FilesystemV2` implementation
+    #[derive(Default)]
+    struct Recorder(Vec<Call>);
+
+    impl Filesystem for Recorder {
+        fn setattr(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            mode: Option<u32>,
+            uid: Option<u32>,
+            gid: Option<u32>,
+            size: Option<u64>,
+            atime: Option<TimeOrNow>,
+            mtime: Option<TimeOrNow>,
+            _ctime: Option<SystemTime>,
+            fh: Option<u64>,
+            _crtime: Option<SystemTime>,
+            _chgtime: Option<SystemTime>,
+            _bkuptime: Option<SystemTime>,
+            _flags: Option<u32>,
+            _kill_suidgid: bool,
+            reply: ReplyAttr,
+        ) {
+            self.0.push(Call::SetAttr {
+                ino,
+                mode,
+                uid,
+                gid,
+                size,
+                atime,
+                mtime,
+                fh,
+            });
+            reply.error(EPERM);
+        }
+
+        fn open(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            flags: i32,
+            kill_suidgid: bool,
+            reply: ReplyOpen,
+        ) {
+            self.0.push(Call::Open {
+                ino,
+                flags,
+                kill_suidgid,
+            });
+            reply.opened(7, 0);
+        }
+
+        fn write(
+            &mut self,
+            _req: &Request<'_>,
+            _ino: u64,
+            fh: u64,
+            offset: i64,
+            data: &[u8],
+            write_flags: u32,
+            flags: i32,
+            lock_owner: Option<u64>,
+            _kill_suidgid: bool,
+            reply: ReplyWrite,
+        ) {
+            self.0.push(Call::Write {
+                fh,
+                offset,
+                data: data.to_vec(),
+                write_flags,
+                flags,
+                lock_owner,
+            });
+            reply.written(data.len() as u32);
+        }
+
+        fn release(
+            &mut self,
+            _req: &Request<'_>,
+            _ino: u64,
+            fh: u64,
+            flags: i32,
+            lock_owner: Option<u64>,
+            flush: bool,
+            reply: ReplyEmpty,
+        ) {
+            self.0.push(Call::Release {
+                fh,
+                flags,
+                lock_owner,
+                flush,
+            });
+            reply.ok();
+        }
+    }
+
+    #[test]
+    fn setattr_options() {
+        let mut harness = Harness::new(Recorder::default());
+        harness.init(0).unwrap();
+
+        let mut arg = fuse_setattr_in::new_zeroed();
+        arg.valid = FATTR_MODE | FATTR_SIZE | FATTR_FH | FATTR_MTIME | FATTR_MTIME_NOW;
+        arg.mode = 0o100600;
+        arg.size = 42;
+        arg.fh = 7;
+        // Ignored without FATTR_UID
+        arg.uid = 1000;
+        let setattr = harness.encoder().setattr(INodeNo(2), &arg);
+        harness.request(&setattr).unwrap();
+
+        let mut arg = fuse_setattr_in::new_zeroed();
+        arg.valid = FATTR_UID | FATTR_GID | FATTR_ATIME;
+        arg.uid = 1000;
+        arg.gid = 100;
+        arg.atime = 10;
+        let setattr = harness.encoder().setattr(INodeNo(2), &arg);
+        harness.request(&setattr).unwrap();
+
+        assert_eq!(
+            harness.filesystem().0,
+            [
+                Call::SetAttr {
+                    ino: 2,
+                    mode: Some(0o100600),
+                    uid: None,
+                    gid: None,
+                    size: Some(42),
+                    atime: None,
+                    mtime: Some(TimeOrNow::Now),
+                    fh: Some(7),
+                },
+                Call::SetAttr {
+                    ino: 2,
+                    mode: None,
+                    uid: Some(1000),
+                    gid: Some(100),
+                    size: None,
+                    atime: Some(TimeOrNow::SpecificTime(
+                        UNIX_EPOCH + Duration::from_secs(10)
+                    )),
+                    mtime: None,
+                    fh: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn open_write_release() {
+        let mut harness = Harness::new(Recorder::default());
+        harness.init(0).unwrap();
+        let flags = OpenFlags(O_RDWR | O_APPEND);
+
+        let open = harness
+            .encoder()
+            .open(INodeNo(2), flags, FUSE_OPEN_KILL_SUIDGID);
+        let reply = harness.request(&open).unwrap();
+        let fh = FileHandle(reply.decode().unwrap().open().unwrap().fh);
+        assert_eq!(fh, FileHandle(7));
+
+        let write = harness.encoder().write(
+            INodeNo(2),
+            fh,
+            3,
+            b"data",
+            WriteFlags(FUSE_WRITE_CACHE),
+            flags,
+            Some(LockOwner(9)),
+        );
+        let reply = harness.request(&write).unwrap();
+        assert_eq!(reply.decode().unwrap().write().unwrap().size, 4);
+
+        let release = harness.encoder().release(INodeNo(2), fh, flags, true, None);
+        harness.request(&release).unwrap();
+
+        assert_eq!(
+            harness.filesystem().0,
+            [
+                Call::Open {
+                    ino: 2,
+                    flags: O_RDWR | O_APPEND,
+                    kill_suidgid: true,
+                },
+                Call::Write {
+                    fh: 7,
+                    offset: 3,
+                    data: b"data".to_vec(),
+                    write_flags: FUSE_WRITE_CACHE | FUSE_WRITE_LOCKOWNER,
+                    flags: O_RDWR | O_APPEND,
+                    lock_owner: Some(9),
+                },
+                Call::Release {
+                    fh: 7,
+                    flags: O_RDWR | O_APPEND,
+                    lock_owner: None,
+                    flush: true,
+                },
+            ]
+        );
+    }
+}
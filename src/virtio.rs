@@ -0,0 +1,1109 @@
+//! virtio-fs over vhost-user
+//!
+//! virtio-fs carries the FUSE protocol between a guest kernel and the host. With vhost-user,
+//! the hypervisor (the frontend) hands the device over to a separate process on a Unix socket:
+//! it shares the guest memory and the virtqueues of the device, and an eventfd per queue to
+//! kick the backend and one to interrupt the guest. [`VirtioFsBackend`] is that backend. As a
+//! [`Transport`], it lets a [`Session`](crate::Session) serve a filesystem to the guest with
+//! the same dispatch code that serves a mount.
+//!
+//! Queue 0 is the high priority queue, which only carries `FUSE_FORGET`, `FUSE_BATCH_FORGET`
+//! and `FUSE_INTERRUPT`. Queue 1 is the only request queue. Each request is a chain of
+//! descriptors: the device-readable ones hold the request, the device-writable ones receive
+//! the reply.
+
+use libc::{c_int, c_void, EINTR, ENODEV, ENOENT, ENOSYS, EOPNOTSUPP};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, IoSlice, Read, Write};
+use std::mem::{self, size_of};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{fence, Ordering};
+use std::sync::{Arc, Mutex};
+
+use zerocopy::FromBytes;
+
+use crate::channel::Transport;
+use crate::ll::{self, fuse_abi as abi};
+use crate::reply::ReplySender;
+
+// vhost-user requests of the frontend
+const VHOST_USER_GET_FEATURES: u32 = 1;
+const VHOST_USER_SET_FEATURES: u32 = 2;
+const VHOST_USER_SET_OWNER: u32 = 3;
+const VHOST_USER_RESET_OWNER: u32 = 4;
+const VHOST_USER_SET_MEM_TABLE: u32 = 5;
+const VHOST_USER_SET_VRING_NUM: u32 = 8;
+const VHOST_USER_SET_VRING_ADDR: u32 = 9;
+const VHOST_USER_SET_VRING_BASE: u32 = 10;
+const VHOST_USER_GET_VRING_BASE: u32 = 11;
+const VHOST_USER_SET_VRING_KICK: u32 = 12;
+const VHOST_USER_SET_VRING_CALL: u32 = 13;
+const VHOST_USER_SET_VRING_ERR: u32 = 14;
+const VHOST_USER_GET_PROTOCOL_FEATURES: u32 = 15;
+const VHOST_USER_SET_PROTOCOL_FEATURES: u32 = 16;
+const VHOST_USER_GET_QUEUE_NUM: u32 = 17;
+const VHOST_USER_SET_VRING_ENABLE: u32 = 18;
+
+// Flags of the vhost-user message header
+const VHOST_USER_VERSION: u32 = 0x1;
+const VHOST_USER_REPLY: u32 = 0x4;
+const VHOST_USER_NEED_REPLY: u32 = 0x8;
+
+// Feature bits
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+const VHOST_USER_F_PROTOCOL_FEATURES: u64 = 1 << 30;
+const VHOST_USER_PROTOCOL_F_MQ: u64 = 1 << 0;
+const VHOST_USER_PROTOCOL_F_REPLY_ACK: u64 = 1 << 3;
+
+/// Virtio features the backend offers
+const FEATURES: u64 = VIRTIO_F_VERSION_1 | VHOST_USER_F_PROTOCOL_FEATURES;
+/// vhost-user protocol features the backend offers
+const PROTOCOL_FEATURES: u64 = VHOST_USER_PROTOCOL_F_MQ | VHOST_USER_PROTOCOL_F_REPLY_ACK;
+
+/// Queue index of `SET_VRING_KICK`, `SET_VRING_CALL` and `SET_VRING_ERR`
+const VHOST_USER_VRING_IDX_MASK: u64 = 0xff;
+/// Set by `SET_VRING_KICK`, `SET_VRING_CALL` and `SET_VRING_ERR` if no fd was sent
+const VHOST_USER_VRING_NOFD_MASK: u64 = 1 << 8;
+
+const VRING_DESC_F_NEXT: u16 = 1;
+const VRING_DESC_F_WRITE: u16 = 2;
+/// Size of a descriptor in the descriptor table
+const VRING_DESC_SIZE: u64 = 16;
+/// Size of an element of the used ring
+const VRING_USED_ELEM_SIZE: u64 = 8;
+/// Largest queue size of a split virtqueue
+const MAX_QUEUE_SIZE: u32 = 32768;
+
+/// The high priority queue and one request queue
+const NUM_QUEUES: usize = 2;
+/// Most memory regions a frontend may share in `SET_MEM_TABLE`
+const MAX_MEM_REGIONS: usize = 8;
+/// Size of the vhost-user message header
+const HEADER_SIZE: usize = 12;
+/// Largest payload of the handled vhost-user messages
+const MAX_PAYLOAD_SIZE: usize = 8 + MAX_MEM_REGIONS * 32;
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Receives data and the file descriptors sent along with it
+fn recv_with_fds(socket: &UnixStream, buf: &mut [u8], fds: &mut Vec<File>) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+    // Aligned space for the control messages
+    let mut control = [0u64; 16];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut c_void;
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+    let res = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const c_int;
+                let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                for i in 0..len / size_of::<c_int>() {
+                    fds.push(File::from_raw_fd(ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok(res as usize)
+}
+
+/// Guest memory shared by the frontend
+#[derive(Debug)]
+struct MemoryRegion {
+    /// Guest physical address of the region
+    guest_addr: u64,
+    /// Address of the region in the frontend's address space
+    user_addr: u64,
+    size: u64,
+    /// The mapping, which starts `offset` bytes before the region
+    mapping: *mut c_void,
+    offset: u64,
+}
+
+impl MemoryRegion {
+    fn map(
+        guest_addr: u64,
+        size: u64,
+        user_addr: u64,
+        offset: u64,
+        file: &File,
+    ) -> io::Result<Self> {
+        let len = size
+            .checked_add(offset)
+            .ok_or_else(|| invalid("Memory region too large"))?;
+        let mapping = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if mapping == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            guest_addr,
+            user_addr,
+            size,
+            mapping,
+            offset,
+        })
+    }
+
+    /// Pointer to `len` bytes at the given offset into the region
+    fn at(&self, offset: u64, len: u64) -> Option<*mut u8> {
+        if offset.checked_add(len)? > self.size {
+            return None;
+        }
+        Some(unsafe { (self.mapping as *mut u8).add((self.offset + offset) as usize) })
+    }
+}
+
+impl Drop for MemoryRegion {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.mapping, (self.size + self.offset) as usize);
+        }
+    }
+}
+
+/// A split virtqueue
+#[derive(Debug, Default)]
+struct Queue {
+    size: u16,
+    /// Addresses of the descriptor table and the rings in the frontend's address space
+    desc_addr: u64,
+    avail_addr: u64,
+    used_addr: u64,
+    /// Index of the next available descriptor chain to process
+    next_avail: u16,
+    /// Index of the next element of the used ring
+    next_used: u16,
+    /// Eventfd the frontend signals new descriptor chains with
+    kick: Option<File>,
+    /// Eventfd to interrupt the guest with after adding used descriptor chains
+    call: Option<File>,
+    enabled: bool,
+    /// Set after the guest put a malformed descriptor chain into the queue, which isn't
+    /// served anymore until the frontend sets it up again
+    broken: bool,
+}
+
+/// A buffer of a descriptor chain
+#[derive(Debug)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    writable: bool,
+}
+
+/// A request that the filesystem hasn't replied to yet
+#[derive(Debug)]
+struct Pending {
+    queue: usize,
+    head: u16,
+    /// Device-writable descriptors for the reply
+    reply: Vec<Descriptor>,
+}
+
+/// State of the device, shared by the transport and its senders
+#[derive(Debug)]
+struct Device {
+    regions: Vec<MemoryRegion>,
+    queues: Vec<Queue>,
+    features: u64,
+    protocol_features: u64,
+    /// Pending requests by their unique id
+    pending: HashMap<u64, Pending>,
+    /// Queue to look at first for the next request, so that no queue starves the others
+    next_queue: usize,
+}
+
+// The memory regions are only accessed with the device locked
+unsafe impl Send for Device {}
+
+impl Device {
+    fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            queues: (0..NUM_QUEUES).map(|_| Queue::default()).collect(),
+            features: 0,
+            protocol_features: 0,
+            pending: HashMap::new(),
+            next_queue: 0,
+        }
+    }
+
+    fn queue(&mut self, index: u64) -> io::Result<&mut Queue> {
+        self.queues
+            .get_mut(index as usize)
+            .ok_or_else(|| invalid("Invalid queue index"))
+    }
+
+    /// Pointer to `len` bytes of guest memory at the given guest physical address
+    fn guest(&self, addr: u64, len: u64) -> io::Result<*mut u8> {
+        self.regions
+            .iter()
+            .filter(|x| addr >= x.guest_addr)
+            .find_map(|x| x.at(addr - x.guest_addr, len))
+            .ok_or_else(|| invalid("Guest address out of bounds"))
+    }
+
+    /// Pointer to `len` bytes of guest memory at the given frontend address
+    fn user(&self, addr: u64, len: u64) -> io::Result<*mut u8> {
+        self.regions
+            .iter()
+            .filter(|x| addr >= x.user_addr)
+            .find_map(|x| x.at(addr - x.user_addr, len))
+            .ok_or_else(|| invalid("Frontend address out of bounds"))
+    }
+
+    fn read_u16(&self, addr: u64) -> io::Result<u16> {
+        let ptr = self.user(addr, 2)? as *const u16;
+        Ok(u16::from_le(unsafe { ptr::read_volatile(ptr) }))
+    }
+
+    fn write_u16(&self, addr: u64, value: u16) -> io::Result<()> {
+        let ptr = self.user(addr, 2)? as *mut u16;
+        unsafe { ptr::write_volatile(ptr, value.to_le()) };
+        Ok(())
+    }
+
+    /// Handles a message of the frontend and returns the payload of the reply, if it has one
+    fn handle(
+        &mut self,
+        request: u32,
+        payload: &[u8],
+        mut fds: Vec<File>,
+    ) -> io::Result<Option<Vec<u8>>> {
+        if payload.len() < min_payload_size(request) {
+            return Err(invalid("Message too short"));
+        }
+        match request {
+            VHOST_USER_GET_FEATURES => return Ok(Some(FEATURES.to_le_bytes().to_vec())),
+            VHOST_USER_SET_FEATURES => self.features = u64_at(payload, 0) & FEATURES,
+            VHOST_USER_SET_OWNER => {}
+            VHOST_USER_RESET_OWNER => {
+                self.queues.iter_mut().for_each(|x| *x = Queue::default());
+                self.pending.clear();
+                self.features = 0;
+            }
+            VHOST_USER_GET_PROTOCOL_FEATURES => {
+                return Ok(Some(PROTOCOL_FEATURES.to_le_bytes().to_vec()))
+            }
+            VHOST_USER_SET_PROTOCOL_FEATURES => {
+                self.protocol_features = u64_at(payload, 0) & PROTOCOL_FEATURES
+            }
+            VHOST_USER_GET_QUEUE_NUM => {
+                return Ok(Some((NUM_QUEUES as u64).to_le_bytes().to_vec()))
+            }
+            VHOST_USER_SET_MEM_TABLE => {
+                let count = u32_at(payload, 0) as usize;
+                if count > MAX_MEM_REGIONS || count != fds.len() || payload.len() < 8 + count * 32 {
+                    return Err(invalid("Invalid memory table"));
+                }
+                let mut regions = Vec::with_capacity(count);
+                for (i, file) in fds.iter().enumerate() {
+                    let region = &payload[8 + i * 32..];
+                    regions.push(MemoryRegion::map(
+                        u64_at(region, 0),
+                        u64_at(region, 8),
+                        u64_at(region, 16),
+                        u64_at(region, 24),
+                        file,
+                    )?);
+                }
+                self.regions = regions;
+            }
+            VHOST_USER_SET_VRING_NUM => {
+                let num = u32_at(payload, 4);
+                if !num.is_power_of_two() || num > MAX_QUEUE_SIZE {
+                    return Err(invalid("Invalid queue size"));
+                }
+                self.queue(u32_at(payload, 0).into())?.size = num as u16;
+            }
+            VHOST_USER_SET_VRING_ADDR => {
+                let queue = self.queue(u32_at(payload, 0).into())?;
+                queue.desc_addr = u64_at(payload, 8);
+                queue.used_addr = u64_at(payload, 16);
+                queue.avail_addr = u64_at(payload, 24);
+            }
+            VHOST_USER_SET_VRING_BASE => {
+                let queue = self.queue(u32_at(payload, 0).into())?;
+                queue.next_avail = u32_at(payload, 4) as u16;
+                queue.next_used = queue.next_avail;
+                queue.broken = false;
+            }
+            VHOST_USER_GET_VRING_BASE => {
+                let index = u32_at(payload, 0);
+                let queue = self.queue(index.into())?;
+                // Stops the queue
+                queue.kick = None;
+                queue.enabled = false;
+                let mut reply = index.to_le_bytes().to_vec();
+                reply.extend_from_slice(&u32::from(queue.next_avail).to_le_bytes());
+                return Ok(Some(reply));
+            }
+            VHOST_USER_SET_VRING_KICK | VHOST_USER_SET_VRING_CALL | VHOST_USER_SET_VRING_ERR => {
+                let arg = u64_at(payload, 0);
+                let file = if arg & VHOST_USER_VRING_NOFD_MASK == 0 {
+                    Some(fds.pop().ok_or_else(|| invalid("Missing eventfd"))?)
+                } else {
+                    None
+                };
+                let protocol = self.features & VHOST_USER_F_PROTOCOL_FEATURES != 0;
+                let queue = self.queue(arg & VHOST_USER_VRING_IDX_MASK)?;
+                match request {
+                    VHOST_USER_SET_VRING_KICK => {
+                        queue.kick = file;
+                        // Without protocol features, a queue starts once it has a kick fd
+                        if !protocol {
+                            queue.enabled = true;
+                        }
+                    }
+                    VHOST_USER_SET_VRING_CALL => queue.call = file,
+                    _ => {}
+                }
+            }
+            VHOST_USER_SET_VRING_ENABLE => {
+                let enable = u32_at(payload, 4) != 0;
+                self.queue(u32_at(payload, 0).into())?.enabled = enable;
+            }
+            _ => return Err(io::Error::from_raw_os_error(ENOSYS)),
+        }
+        Ok(None)
+    }
+
+    /// Takes the next available descriptor chain of a queue
+    fn pop(&mut self, index: usize) -> io::Result<Option<(u16, Vec<Descriptor>)>> {
+        let queue = &self.queues[index];
+        if !queue.enabled || queue.broken || queue.kick.is_none() || queue.size == 0 {
+            return Ok(None);
+        }
+        let (size, next_avail) = (queue.size, queue.next_avail);
+        let (desc_addr, avail_addr) = (queue.desc_addr, queue.avail_addr);
+        if self.read_u16(avail_addr + 2)? == next_avail {
+            return Ok(None);
+        }
+        // Read the chain only after seeing its index
+        fence(Ordering::Acquire);
+        let slot = u64::from(next_avail % size);
+        let head = self.read_u16(avail_addr + 4 + 2 * slot)?;
+        self.queues[index].next_avail = next_avail.wrapping_add(1);
+
+        let mut chain = Vec::new();
+        let mut next = head;
+        loop {
+            if next >= size || chain.len() >= usize::from(size) {
+                return Err(invalid("Invalid descriptor chain"));
+            }
+            let ptr = self.user(
+                desc_addr + VRING_DESC_SIZE * u64::from(next),
+                VRING_DESC_SIZE,
+            )?;
+            let mut desc = [0u8; VRING_DESC_SIZE as usize];
+            unsafe { ptr::copy_nonoverlapping(ptr, desc.as_mut_ptr(), desc.len()) };
+            let flags = u16::from_le_bytes([desc[12], desc[13]]);
+            chain.push(Descriptor {
+                addr: u64_at(&desc, 0),
+                len: u32_at(&desc, 8),
+                writable: flags & VRING_DESC_F_WRITE != 0,
+            });
+            if flags & VRING_DESC_F_NEXT == 0 {
+                break;
+            }
+            next = u16::from_le_bytes([desc[14], desc[15]]);
+        }
+        Ok(Some((head, chain)))
+    }
+
+    /// Returns a descriptor chain to the guest, with `len` bytes written to it
+    fn push(&mut self, index: usize, head: u16, len: u32) -> io::Result<()> {
+        let queue = &self.queues[index];
+        if queue.size == 0 {
+            return Ok(());
+        }
+        let (used_addr, next_used) = (queue.used_addr, queue.next_used);
+        let slot = u64::from(next_used % queue.size);
+        let ptr = self.user(
+            used_addr + 4 + VRING_USED_ELEM_SIZE * slot,
+            VRING_USED_ELEM_SIZE,
+        )?;
+        let mut elem = [0u8; VRING_USED_ELEM_SIZE as usize];
+        elem[..4].copy_from_slice(&u32::from(head).to_le_bytes());
+        elem[4..].copy_from_slice(&len.to_le_bytes());
+        unsafe { ptr::copy_nonoverlapping(elem.as_ptr(), ptr, elem.len()) };
+        // Publish the element before the index
+        fence(Ordering::Release);
+        let next_used = next_used.wrapping_add(1);
+        self.write_u16(used_addr + 2, next_used)?;
+        let queue = &mut self.queues[index];
+        queue.next_used = next_used;
+        if let Some(mut call) = queue.call.as_ref() {
+            call.write_all(&1u64.to_ne_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Copies the device-readable part of the next available request into the buffer. A
+    /// queue that the guest put a malformed descriptor chain into is marked broken, so that
+    /// the guest can't end the session.
+    fn next_request(&mut self, buf: &mut [u8]) -> Option<usize> {
+        for i in 0..NUM_QUEUES {
+            let index = (self.next_queue + i) % NUM_QUEUES;
+            match self.pop_request(index, buf) {
+                Ok(Some(len)) => {
+                    self.next_queue = (index + 1) % NUM_QUEUES;
+                    return Some(len);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("Stopping queue {}: {}", index, err);
+                    self.queues[index].broken = true;
+                }
+            }
+        }
+        None
+    }
+
+    /// Copies the next request of a queue into the buffer, dropping invalid requests before
+    /// it. Their kick was consumed already, so the queue is emptied before waiting again.
+    fn pop_request(&mut self, index: usize, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        'chains: while let Some((head, chain)) = self.pop(index)? {
+            let mut len = 0;
+            for desc in chain.iter().filter(|x| !x.writable) {
+                let size = desc.len as usize;
+                if len + size > buf.len() {
+                    warn!("Dropping a request that doesn't fit into the buffer");
+                    self.push(index, head, 0)?;
+                    continue 'chains;
+                }
+                let ptr = self.guest(desc.addr, desc.len.into())?;
+                unsafe { ptr::copy_nonoverlapping(ptr, buf[len..].as_mut_ptr(), size) };
+                len += size;
+            }
+            let header = match abi::fuse_in_header::read_from_prefix(&buf[..len]) {
+                Some(header) => header,
+                None => {
+                    warn!("Dropping a request of {} bytes", len);
+                    self.push(index, head, 0)?;
+                    continue;
+                }
+            };
+            if ll::expects_reply(header.opcode) {
+                let reply = chain.into_iter().filter(|x| x.writable).collect();
+                self.pending.insert(
+                    header.unique,
+                    Pending {
+                        queue: index,
+                        head,
+                        reply,
+                    },
+                );
+            } else {
+                self.push(index, head, 0)?;
+            }
+            return Ok(Some(len));
+        }
+        Ok(None)
+    }
+}
+
+/// Minimum payload size of a vhost-user request
+fn min_payload_size(request: u32) -> usize {
+    match request {
+        VHOST_USER_SET_FEATURES
+        | VHOST_USER_SET_PROTOCOL_FEATURES
+        | VHOST_USER_SET_MEM_TABLE
+        | VHOST_USER_SET_VRING_KICK
+        | VHOST_USER_SET_VRING_CALL
+        | VHOST_USER_SET_VRING_ERR
+        | VHOST_USER_SET_VRING_NUM
+        | VHOST_USER_SET_VRING_BASE
+        | VHOST_USER_GET_VRING_BASE
+        | VHOST_USER_SET_VRING_ENABLE => 8,
+        VHOST_USER_SET_VRING_ADDR => 40,
+        _ => 0,
+    }
+}
+
+/// A vhost-user backend of a virtio-fs device
+///
+/// It implements the vhost-user protocol on a connected Unix socket, typically to a
+/// hypervisor like QEMU started with a `vhost-user-fs-pci` device, and receives the FUSE
+/// requests of the guest from the virtqueues. The session ends when the frontend disconnects.
+#[derive(Debug)]
+pub struct VirtioFsBackend {
+    socket: UnixStream,
+    device: Arc<Mutex<Device>>,
+}
+
+impl VirtioFsBackend {
+    /// Create a backend on a socket connected to the frontend
+    pub fn new(socket: UnixStream) -> Self {
+        Self {
+            socket,
+            device: Arc::new(Mutex::new(Device::new())),
+        }
+    }
+
+    /// Listen on a Unix socket at the given path and create a backend for the first
+    /// frontend that connects to it
+    pub fn listen(path: &Path) -> io::Result<Self> {
+        info!("Waiting for a vhost-user frontend on {}", path.display());
+        let listener = UnixListener::bind(path)?;
+        let (socket, _) = listener.accept()?;
+        Ok(Self::new(socket))
+    }
+
+    /// Receives and handles a message of the frontend
+    fn handle_message(&self) -> io::Result<()> {
+        let mut header = [0u8; HEADER_SIZE];
+        let mut fds = Vec::new();
+        let len = recv_with_fds(&self.socket, &mut header, &mut fds)?;
+        if len == 0 {
+            info!("vhost-user frontend disconnected");
+            return Err(io::Error::from_raw_os_error(ENODEV));
+        }
+        (&self.socket).read_exact(&mut header[len..])?;
+        let (request, flags) = (u32_at(&header, 0), u32_at(&header, 4));
+        let size = u32_at(&header, 8) as usize;
+        if size > MAX_PAYLOAD_SIZE {
+            return Err(invalid("vhost-user message too large"));
+        }
+        let mut payload = vec![0; size];
+        (&self.socket).read_exact(&mut payload)?;
+
+        let mut device = self.device.lock().unwrap();
+        debug!("vhost-user request {}, {} bytes", request, size);
+        let result = device.handle(request, &payload, fds);
+        let ack = flags & VHOST_USER_NEED_REPLY != 0
+            && device.protocol_features & VHOST_USER_PROTOCOL_F_REPLY_ACK != 0;
+        drop(device);
+        let reply = match result {
+            Ok(Some(reply)) => reply,
+            Ok(None) if ack => 0u64.to_le_bytes().to_vec(),
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                warn!("Failed to handle vhost-user request {}: {}", request, err);
+                if !ack {
+                    return Ok(());
+                }
+                1u64.to_le_bytes().to_vec()
+            }
+        };
+        let mut message = Vec::with_capacity(HEADER_SIZE + reply.len());
+        message.extend_from_slice(&request.to_le_bytes());
+        message.extend_from_slice(&(VHOST_USER_VERSION | VHOST_USER_REPLY).to_le_bytes());
+        message.extend_from_slice(&(reply.len() as u32).to_le_bytes());
+        message.extend_from_slice(&reply);
+        (&self.socket).write_all(&message)
+    }
+}
+
+impl Transport for VirtioFsBackend {
+    type Sender = VirtioFsSender;
+
+    fn receive(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let kicks: Vec<RawFd> = {
+                let mut device = self.device.lock().unwrap();
+                if let Some(len) = device.next_request(buf) {
+                    return Ok(len);
+                }
+                device
+                    .queues
+                    .iter()
+                    .filter(|x| x.enabled && !x.broken)
+                    .filter_map(|x| x.kick.as_ref().map(|x| x.as_raw_fd()))
+                    .collect()
+            };
+            let mut fds: Vec<libc::pollfd> = std::iter::once(self.socket.as_raw_fd())
+                .chain(kicks)
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) } < 0 {
+                // The session loop retries after EINTR
+                return Err(io::Error::last_os_error());
+            }
+            for kick in fds[1..].iter().filter(|x| x.revents != 0) {
+                let mut value = [0u8; 8];
+                let res = unsafe { libc::read(kick.fd, value.as_mut_ptr() as *mut c_void, 8) };
+                if res < 0 && io::Error::last_os_error().raw_os_error() != Some(EINTR) {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            if fds[0].revents != 0 {
+                self.handle_message()?;
+            }
+        }
+    }
+
+    fn sender(&self) -> VirtioFsSender {
+        VirtioFsSender(self.device.clone())
+    }
+}
+
+/// Sender of replies to the guest of a virtio-fs device
+#[derive(Clone, Debug)]
+pub struct VirtioFsSender(Arc<Mutex<Device>>);
+
+impl ReplySender for VirtioFsSender {
+    fn send(&self, bufs: &[IoSlice<'_>]) -> io::Result<()> {
+        let mut message: Vec<u8> = bufs.iter().flat_map(|x| x.iter().copied()).collect();
+        let header = abi::fuse_out_header::read_from_prefix(&message[..])
+            .ok_or_else(|| invalid("Reply too short"))?;
+        if header.unique == 0 {
+            // Notifications need the notification queue
+            return Err(io::Error::from_raw_os_error(EOPNOTSUPP));
+        }
+        let mut device = self.0.lock().unwrap();
+        let pending = device
+            .pending
+            .remove(&header.unique)
+            .ok_or_else(|| io::Error::from_raw_os_error(ENOENT))?;
+        let capacity: usize = pending.reply.iter().map(|x| x.len as usize).sum();
+        if message.len() > capacity {
+            warn!(
+                "Reply to request {} doesn't fit into its buffers",
+                header.unique
+            );
+            message = ll::Response::new_error(ll::Errno::EIO)
+                .with_iovec(ll::RequestId(header.unique), |iov| {
+                    iov.iter().flat_map(|x| x.iter().copied()).collect()
+                });
+        }
+        let mut written = 0;
+        for desc in pending.reply.iter() {
+            let len = (desc.len as usize).min(message.len() - written);
+            if len == 0 {
+                break;
+            }
+            let ptr = device.guest(desc.addr, len as u64)?;
+            unsafe { ptr::copy_nonoverlapping(message[written..].as_ptr(), ptr, len) };
+            written += len;
+        }
+        device.push(pending.queue, pending.head, written as u32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libc::{c_int, c_void, EIO, ENOSYS};
+    use std::convert::TryFrom;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::mem;
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::os::unix::net::UnixStream;
+    use std::ptr;
+    use std::sync::atomic::{fence, Ordering};
+    use std::thread;
+
+    use super::*;
+    use crate::ll::fuse_abi::FUSE_KERNEL_VERSION;
+    use crate::ll::{AnyReply, INodeNo, ReplyError, RequestEncoder};
+    use crate::{Filesystem, Session};
+
+    /// Size of the guest memory
+    const MEM_SIZE: usize = 1 << 20;
+    /// Guest physical address of the guest memory
+    const GUEST_BASE: u64 = 0x1000_0000;
+    const QUEUE_SIZE: u16 = 8;
+
+    struct NullFs;
+
+    impl Filesystem for NullFs {}
+
+    /// A vhost-user frontend with one memory region, which lays out the queues and their
+    /// buffers at fixed offsets
+    struct Frontend {
+        socket: UnixStream,
+        memory: *mut u8,
+        kicks: Vec<File>,
+        calls: Vec<File>,
+        next_avail: [u16; NUM_QUEUES],
+        next_used: [u16; NUM_QUEUES],
+    }
+
+    fn eventfd() -> File {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        assert!(fd >= 0);
+        unsafe { File::from_raw_fd(fd) }
+    }
+
+    /// Offset of the descriptor table of a queue, followed by the available and used rings
+    fn queue_offset(queue: usize) -> usize {
+        queue * 0x10000
+    }
+
+    /// Offset of the request and reply buffers of a queue
+    fn data_offset(queue: usize) -> usize {
+        0x80000 + queue * 0x10000
+    }
+
+    impl Frontend {
+        fn new(socket: UnixStream) -> Self {
+            let file = tempfile::tempfile().unwrap();
+            file.set_len(MEM_SIZE as u64).unwrap();
+            let memory = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    MEM_SIZE,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            assert_ne!(memory, libc::MAP_FAILED);
+            let mut frontend = Self {
+                socket,
+                memory: memory as *mut u8,
+                kicks: (0..NUM_QUEUES).map(|_| eventfd()).collect(),
+                calls: (0..NUM_QUEUES).map(|_| eventfd()).collect(),
+                next_avail: [0; NUM_QUEUES],
+                next_used: [0; NUM_QUEUES],
+            };
+            frontend.handshake(file.as_raw_fd());
+            frontend
+        }
+
+        fn send(&self, request: u32, flags: u32, payload: &[u8], fds: &[RawFd]) {
+            let mut message = request.to_le_bytes().to_vec();
+            message.extend_from_slice(&(VHOST_USER_VERSION | flags).to_le_bytes());
+            message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            message.extend_from_slice(payload);
+            let mut iov = libc::iovec {
+                iov_base: message.as_mut_ptr() as *mut c_void,
+                iov_len: message.len(),
+            };
+            let mut control = [0u64; 16];
+            let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            if !fds.is_empty() {
+                let len = mem::size_of_val(fds);
+                msg.msg_control = control.as_mut_ptr() as *mut c_void;
+                msg.msg_controllen = unsafe { libc::CMSG_SPACE(len as u32) } as _;
+                unsafe {
+                    let cmsg = libc::CMSG_FIRSTHDR(&msg);
+                    (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                    (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                    (*cmsg).cmsg_len = libc::CMSG_LEN(len as u32) as _;
+                    ptr::copy_nonoverlapping(
+                        fds.as_ptr(),
+                        libc::CMSG_DATA(cmsg) as *mut c_int,
+                        fds.len(),
+                    );
+                }
+            }
+            let res = unsafe { libc::sendmsg(self.socket.as_raw_fd(), &msg, 0) };
+            assert_eq!(res, message.len() as isize);
+        }
+
+        fn reply(&self, request: u32) -> Vec<u8> {
+            let mut header = [0u8; HEADER_SIZE];
+            (&self.socket).read_exact(&mut header).unwrap();
+            assert_eq!(u32_at(&header, 0), request);
+            assert_eq!(u32_at(&header, 4), VHOST_USER_VERSION | VHOST_USER_REPLY);
+            let mut payload = vec![0; u32_at(&header, 8) as usize];
+            (&self.socket).read_exact(&mut payload).unwrap();
+            payload
+        }
+
+        fn get_u64(&self, request: u32) -> u64 {
+            self.send(request, 0, &[], &[]);
+            u64_at(&self.reply(request), 0)
+        }
+
+        fn vring(&self, request: u32, queue: usize, num: u32) {
+            let mut payload = (queue as u32).to_le_bytes().to_vec();
+            payload.extend_from_slice(&num.to_le_bytes());
+            self.send(request, 0, &payload, &[]);
+        }
+
+        fn handshake(&mut self, memory_fd: RawFd) {
+            let features = self.get_u64(VHOST_USER_GET_FEATURES);
+            assert_eq!(
+                features,
+                VIRTIO_F_VERSION_1 | VHOST_USER_F_PROTOCOL_FEATURES
+            );
+            self.send(VHOST_USER_SET_OWNER, 0, &[], &[]);
+            let protocol = self.get_u64(VHOST_USER_GET_PROTOCOL_FEATURES);
+            assert_ne!(protocol & VHOST_USER_PROTOCOL_F_REPLY_ACK, 0);
+            self.send(
+                VHOST_USER_SET_PROTOCOL_FEATURES,
+                0,
+                &VHOST_USER_PROTOCOL_F_REPLY_ACK.to_le_bytes(),
+                &[],
+            );
+            self.send(VHOST_USER_SET_FEATURES, 0, &features.to_le_bytes(), &[]);
+            assert_eq!(self.get_u64(VHOST_USER_GET_QUEUE_NUM), NUM_QUEUES as u64);
+
+            let mut table = 1u32.to_le_bytes().to_vec();
+            table.extend_from_slice(&[0; 4]);
+            for field in &[GUEST_BASE, MEM_SIZE as u64, self.memory as u64, 0] {
+                table.extend_from_slice(&field.to_le_bytes());
+            }
+            self.send(
+                VHOST_USER_SET_MEM_TABLE,
+                VHOST_USER_NEED_REPLY,
+                &table,
+                &[memory_fd],
+            );
+            assert_eq!(u64_at(&self.reply(VHOST_USER_SET_MEM_TABLE), 0), 0);
+
+            for queue in 0..NUM_QUEUES {
+                self.vring(VHOST_USER_SET_VRING_NUM, queue, QUEUE_SIZE.into());
+                self.vring(VHOST_USER_SET_VRING_BASE, queue, 0);
+                let base = self.memory as u64 + queue_offset(queue) as u64;
+                let mut addr = (queue as u32).to_le_bytes().to_vec();
+                addr.extend_from_slice(&[0; 4]);
+                for field in &[base, base + 0x2000, base + 0x1000, 0] {
+                    addr.extend_from_slice(&field.to_le_bytes());
+                }
+                self.send(VHOST_USER_SET_VRING_ADDR, 0, &addr, &[]);
+                let index = (queue as u64).to_le_bytes();
+                self.send(
+                    VHOST_USER_SET_VRING_KICK,
+                    0,
+                    &index,
+                    &[self.kicks[queue].as_raw_fd()],
+                );
+                self.send(
+                    VHOST_USER_SET_VRING_CALL,
+                    0,
+                    &index,
+                    &[self.calls[queue].as_raw_fd()],
+                );
+                self.vring(VHOST_USER_SET_VRING_ENABLE, queue, 1);
+            }
+        }
+
+        fn write(&self, offset: usize, data: &[u8]) {
+            assert!(offset + data.len() <= MEM_SIZE);
+            unsafe { ptr::copy_nonoverlapping(data.as_ptr(), self.memory.add(offset), data.len()) };
+        }
+
+        fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+            assert!(offset + len <= MEM_SIZE);
+            let mut data = vec![0; len];
+            unsafe { ptr::copy_nonoverlapping(self.memory.add(offset), data.as_mut_ptr(), len) };
+            data
+        }
+
+        /// Writes a descriptor to the table of a queue
+        fn descriptor(&self, queue: usize, index: u16, offset: usize, len: usize, flags: u16) {
+            let mut desc = (GUEST_BASE + offset as u64).to_le_bytes().to_vec();
+            desc.extend_from_slice(&(len as u32).to_le_bytes());
+            desc.extend_from_slice(&flags.to_le_bytes());
+            desc.extend_from_slice(&(index + 1).to_le_bytes());
+            let desc_table = queue_offset(queue);
+            self.write(
+                desc_table + usize::from(index) * VRING_DESC_SIZE as usize,
+                &desc,
+            );
+        }
+
+        /// Makes the chain starting at `head` available, without kicking the backend
+        fn publish(&mut self, queue: usize, head: u16) {
+            let avail = queue_offset(queue) + 0x1000;
+            let next_avail = self.next_avail[queue];
+            let slot = usize::from(next_avail % QUEUE_SIZE);
+            self.write(avail + 4 + 2 * slot, &head.to_le_bytes());
+            fence(Ordering::Release);
+            self.next_avail[queue] = next_avail.wrapping_add(1);
+            self.write(avail + 2, &self.next_avail[queue].to_le_bytes());
+        }
+
+        /// Puts a request into the descriptors `head..head + 4` of a queue, split into two
+        /// device-readable buffers, followed by two device-writable buffers of `reply_len`
+        /// bytes in total
+        fn push_request(&mut self, queue: usize, head: u16, request: &[u8], reply_len: usize) {
+            let data = data_offset(queue) + usize::from(head) * 0x1000;
+            let reply = data + 0x8000;
+            let split = request.len() / 2;
+            let buffers = [
+                (data, split, false),
+                (data + split, request.len() - split, false),
+                (reply, reply_len / 2, true),
+                (reply + reply_len / 2, reply_len - reply_len / 2, true),
+            ];
+            self.write(data, request);
+            for (i, (offset, len, writable)) in buffers.iter().enumerate() {
+                let mut flags = if *writable { VRING_DESC_F_WRITE } else { 0 };
+                if i + 1 < buffers.len() {
+                    flags |= VRING_DESC_F_NEXT;
+                }
+                self.descriptor(queue, head + i as u16, *offset, *len, flags);
+            }
+            self.publish(queue, head);
+        }
+
+        fn kick(&self, queue: usize) {
+            (&self.kicks[queue]).write_all(&1u64.to_ne_bytes()).unwrap();
+        }
+
+        /// Waits for the next used element of a queue, and returns its head and the reply
+        fn pop_used(&mut self, queue: usize) -> (u16, Vec<u8>) {
+            let used = queue_offset(queue) + 0x2000;
+            loop {
+                fence(Ordering::Acquire);
+                let used_idx = u16::from_le_bytes(self.read(used + 2, 2).try_into().unwrap());
+                if used_idx != self.next_used[queue] {
+                    break;
+                }
+                let mut fd = libc::pollfd {
+                    fd: self.calls[queue].as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                assert_eq!(unsafe { libc::poll(&mut fd, 1, 5000) }, 1, "No reply");
+                let mut value = [0u8; 8];
+                (&self.calls[queue]).read_exact(&mut value).unwrap();
+            }
+            let slot = usize::from(self.next_used[queue] % QUEUE_SIZE);
+            self.next_used[queue] = self.next_used[queue].wrapping_add(1);
+            let elem = self.read(used + 4 + 8 * slot, 8);
+            let head = u32_at(&elem, 0) as u16;
+            let data = data_offset(queue) + usize::from(head) * 0x1000;
+            (head, self.read(data + 0x8000, u32_at(&elem, 4) as usize))
+        }
+
+        /// Sends a request through a queue and returns the reply
+        fn request(&mut self, queue: usize, request: &[u8], reply_len: usize) -> Vec<u8> {
+            self.push_request(queue, 0, request, reply_len);
+            self.kick(queue);
+            let (head, reply) = self.pop_used(queue);
+            assert_eq!(head, 0);
+            assert_eq!(self.next_used[queue], self.next_avail[queue]);
+            reply
+        }
+    }
+
+    #[test]
+    fn serve_guest() {
+        let (frontend, backend) = UnixStream::pair().unwrap();
+        let session = thread::spawn(move || {
+            let backend = VirtioFsBackend::new(backend);
+            Session::with_transport(NullFs, backend).run().unwrap();
+        });
+        let mut frontend = Frontend::new(frontend);
+        let mut enc = RequestEncoder::new();
+
+        let init = enc.init(4096, 0);
+        let reply = frontend.request(1, init.as_bytes(), 4096);
+        let reply = AnyReply::try_from(&reply[..]).unwrap();
+        assert_eq!(reply.unique(), init.unique());
+        assert_eq!(reply.init().unwrap().major, FUSE_KERNEL_VERSION);
+
+        let statfs = enc.statfs(INodeNo(1));
+        let reply = frontend.request(1, statfs.as_bytes(), 4096);
+        let reply = AnyReply::try_from(&reply[..]).unwrap();
+        assert_eq!(reply.unique(), statfs.unique());
+        let st = reply.statfs().unwrap().st;
+        assert_eq!((st.bsize, st.namelen), (512, 255));
+
+        // A reply that doesn't fit into the buffers fails
+        let statfs = enc.statfs(INodeNo(1));
+        let reply = frontend.request(1, statfs.as_bytes(), 32);
+        match AnyReply::try_from(&reply[..]).unwrap().statfs() {
+            Err(ReplyError::Errno(err)) => assert_eq!(err.0.get(), EIO),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+
+        let getattr = enc.getattr(INodeNo(1), None);
+        let reply = frontend.request(1, getattr.as_bytes(), 4096);
+        match AnyReply::try_from(&reply[..]).unwrap().result() {
+            Err(err) => assert_eq!(err.0.get(), ENOSYS),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+
+        // Forget has no reply, but its buffers are returned right away
+        let forget = enc.forget(INodeNo(1), 1);
+        assert!(frontend.request(0, forget.as_bytes(), 16).is_empty());
+
+        frontend.vring(VHOST_USER_GET_VRING_BASE, 1, 0);
+        let state = frontend.reply(VHOST_USER_GET_VRING_BASE);
+        assert_eq!((u32_at(&state, 0), u32_at(&state, 4)), (1, 4));
+
+        drop(frontend);
+        session.join().unwrap();
+    }
+
+    #[test]
+    fn invalid_chains() {
+        let (frontend, backend) = UnixStream::pair().unwrap();
+        let session = thread::spawn(move || {
+            let backend = VirtioFsBackend::new(backend);
+            Session::with_transport(NullFs, backend).run().unwrap();
+        });
+        let mut frontend = Frontend::new(frontend);
+        let mut enc = RequestEncoder::new();
+        let init = enc.init(4096, 0);
+        frontend.request(1, init.as_bytes(), 4096);
+
+        // A request that is dropped doesn't hide the next one of the same kick
+        let statfs = enc.statfs(INodeNo(1));
+        frontend.push_request(1, 0, &[0; 8], 16);
+        frontend.push_request(1, 4, statfs.as_bytes(), 4096);
+        frontend.kick(1);
+        assert_eq!(frontend.pop_used(1), (0, Vec::new()));
+        let (head, reply) = frontend.pop_used(1);
+        assert_eq!(head, 4);
+        assert_eq!(
+            AnyReply::try_from(&reply[..]).unwrap().unique(),
+            statfs.unique()
+        );
+
+        // A chain that points outside of the descriptor table stops its queue only. The
+        // backend looks at it right after the forget, before handling the next message.
+        frontend.descriptor(1, QUEUE_SIZE - 1, data_offset(1), 64, VRING_DESC_F_NEXT);
+        frontend.publish(1, QUEUE_SIZE - 1);
+        let forget = enc.forget(INodeNo(1), 1);
+        assert!(frontend.request(0, forget.as_bytes(), 16).is_empty());
+        frontend.vring(VHOST_USER_GET_VRING_BASE, 1, 0);
+        let state = frontend.reply(VHOST_USER_GET_VRING_BASE);
+        assert_eq!((u32_at(&state, 0), u32_at(&state, 4)), (1, 4));
+        let forget = enc.forget(INodeNo(1), 1);
+        assert!(frontend.request(0, forget.as_bytes(), 16).is_empty());
+
+        drop(frontend);
+        session.join().unwrap();
+    }
+}